@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use nickel_lang_core::error::report::Aggregated;
+
+use crate::{
+    cli::GlobalOptions,
+    customize::CustomizeMode,
+    error::{CliResult, Error},
+    input::{InputOptions, Prepare},
+};
+
+#[derive(clap::Parser, Debug)]
+pub struct CheckCommand {
+    /// A Nickel file evaluating to a record of rules. Each field is a contract that the
+    /// configuration must satisfy.
+    #[arg(long, short)]
+    rules: PathBuf,
+
+    #[command(flatten)]
+    input: InputOptions<CustomizeMode>,
+}
+
+impl CheckCommand {
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        let mut program = self.input.prepare(&global)?;
+
+        let violations = match program.check_rules(&self.rules) {
+            Ok(violations) => violations,
+            Err(error) => return Err(Error::Program { program, error }),
+        };
+
+        let failed = !violations.is_empty();
+
+        // The same rule is often violated by many values, e.g. by each element of an array.
+        program.report(Aggregated(violations), global.error_format);
+
+        if failed {
+            Err(Error::CheckFailed)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -3,11 +3,19 @@
 use git_version::git_version;
 
 use crate::{
-    completions::GenCompletionsCommand, eval::EvalCommand, export::ExportCommand,
-    pprint_ast::PprintAstCommand, query::QueryCommand, typecheck::TypecheckCommand,
+    check::CheckCommand,
+    completions::{CompleteFieldCommand, GenCompletionsCommand},
+    diff::DiffCommand,
+    eval::EvalCommand,
+    export::ExportCommand,
+    lint::LintCommand,
+    pprint_ast::PprintAstCommand,
+    query::QueryCommand,
+    typecheck::TypecheckCommand,
+    validate::ValidateCommand,
 };
 
-use nickel_lang_core::error::report::ErrorFormat;
+use nickel_lang_core::error::report::{ErrorFormat, ReportChars, ReportStyle};
 
 #[cfg(feature = "repl")]
 use crate::repl::ReplCommand;
@@ -16,7 +24,13 @@ use crate::repl::ReplCommand;
 use crate::{doc::DocCommand, doctest::TestCommand};
 
 #[cfg(feature = "format")]
-use crate::format::FormatCommand;
+use crate::{format::FormatCommand, migrate::MigrateCommand};
+
+#[cfg(feature = "git-imports")]
+use crate::lock::LockCommand;
+
+#[cfg(feature = "packages")]
+use crate::package::PackageCommand;
 
 #[derive(clap::Parser, Debug)]
 /// The interpreter of the Nickel language.
@@ -49,17 +63,37 @@ pub struct GlobalOptions {
     #[arg(long, global = true, value_enum, default_value_t)]
     pub color: clap::ColorChoice,
 
-    /// Output error messages in a specific format. The schema of serialized errors reflects the
-    /// way they are currently rendered. This schema is not guaranteed to be stable yet.
+    /// Output error messages in a specific format. Serialized errors list the severity, the
+    /// message, the notes and the labels of each diagnostic, with the file name, the byte offsets
+    /// and the line and column of each label. This schema is not guaranteed to be stable yet.
     #[arg(long, global = true, value_enum, default_value_t)]
     pub error_format: ErrorFormat,
 
+    /// The characters used to draw the source snippets of error messages. Use `ascii` for
+    /// terminals or log collectors which don't support unicode
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub error_chars: ReportChars,
+
+    /// Wraps the messages and the notes of error messages to this width, in characters
+    #[arg(long, global = true, value_name = "COLUMNS")]
+    pub error_width: Option<usize>,
+
     #[cfg(feature = "metrics")]
     /// Print all recorded metrics at the very end of the program
     #[arg(long, global = true, default_value_t = false)]
     pub metrics: bool,
 }
 
+impl GlobalOptions {
+    /// The style of the errors reported as text.
+    pub fn report_style(&self) -> ReportStyle {
+        ReportStyle {
+            chars: self.error_chars,
+            width: self.error_width,
+        }
+    }
+}
+
 /// Available subcommands.
 #[derive(clap::Subcommand, Debug)]
 pub enum Command {
@@ -74,19 +108,42 @@ pub enum Command {
     Query(QueryCommand),
     /// Typechecks the program but does not run it
     Typecheck(TypecheckCommand),
+    /// Checks a data file (JSON, YAML or TOML) against a Nickel contract
+    Validate(ValidateCommand),
+    /// Checks Nickel files for style issues and suspicious constructs, without evaluating them
+    Lint(LintCommand),
+    /// Evaluates a Nickel program and checks the result against a set of policy rules
+    Check(CheckCommand),
+    /// Evaluates two Nickel programs and prints the paths of the values which differ
+    Diff(DiffCommand),
+    /// Pins the git imports of a Nickel program to commits, recorded in `nickel.lock`
+    #[cfg(feature = "git-imports")]
+    Lock(LockCommand),
+    /// Manages the package manifest, the dependencies and the lockfile of a Nickel package
+    #[cfg(feature = "packages")]
+    Package(PackageCommand),
     /// Starts a REPL session
     #[cfg(feature = "repl")]
     Repl(ReplCommand),
     /// Generates the documentation files for the a specified nickel file
     #[cfg(feature = "doc")]
     Doc(DocCommand),
-    /// Tests the documentation examples in the specified nickel file
+    /// Tests the documentation examples in the specified nickel file, and the fields of test files,
+    /// whose name ends with `.test.ncl`. Directories are searched recursively for test files
     #[cfg(feature = "doc")]
     Test(TestCommand),
     /// Formats Nickel files
     #[cfg(feature = "format")]
     Format(FormatCommand),
+    /// Rewrites Nickel files in place, for example to rename a field, preserving formatting and
+    /// comments
+    #[cfg(feature = "format")]
+    Migrate(MigrateCommand),
 
     /// Generate shell completion files
     GenCompletions(GenCompletionsCommand),
+    /// Prints the field paths of a Nickel file starting with a prefix. Used by the shell
+    /// completion scripts to complete the values of `--field`
+    #[command(hide = true)]
+    CompleteField(CompleteFieldCommand),
 }
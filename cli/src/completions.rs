@@ -1,3 +1,13 @@
+use std::path::PathBuf;
+
+use clap_complete::Shell;
+use nickel_lang_core::{
+    error::{Error as CoreError, IOError},
+    eval::cache::lazy::CBNCache,
+    pretty::ident_quoted,
+    program::{FieldPath, Program},
+};
+
 use crate::{
     cli::{GlobalOptions, Options},
     error::CliResult,
@@ -11,12 +21,165 @@ pub struct GenCompletionsCommand {
 
 impl GenCompletionsCommand {
     pub fn run(self, _: GlobalOptions) -> CliResult<()> {
+        let name = env!("CARGO_BIN_NAME");
+        let mut script = Vec::new();
+
         clap_complete::generate(
             self.shell,
             &mut <Options as clap::CommandFactory>::command(),
-            env!("CARGO_BIN_NAME"),
-            &mut std::io::stdout(),
+            name,
+            &mut script,
         );
+
+        // unwrap(): the generated scripts are made of the UTF-8 names and help of the arguments
+        let script = String::from_utf8(script).unwrap();
+        print!("{}", with_field_completion(self.shell, name, script));
         Ok(())
     }
 }
+
+/// Extend a completion script to complete the values of `--field` with the field paths of the
+/// last file of the command line, as listed by `nickel complete-field`.
+fn with_field_completion(shell: Shell, name: &str, script: String) -> String {
+    match shell {
+        Shell::Bash => format!(
+            r#"{script}
+_{name}_field_paths() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}" file word
+    if [[ "$prev" == "--field" ]]; then
+        for word in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+            [[ -f "$word" ]] && file="$word"
+        done
+        if [[ -n "$file" ]]; then
+            COMPREPLY=( $({name} complete-field "$file" "$cur" 2>/dev/null) )
+            compopt -o nospace
+            return 0
+        fi
+    fi
+    _{name} "$@"
+}}
+
+complete -F _{name}_field_paths -o bashdefault -o default {name}
+"#
+        ),
+        // The helper is defined before the main function, which is called at the end of the
+        // script when it's autoloaded from `fpath`.
+        Shell::Zsh => {
+            let helper = format!(
+                r#"
+_{name}_field_paths() {{
+    local file word
+    for word in "${{(@)words[2,CURRENT-1]}}"; do
+        [[ -f "$word" ]] && file="$word"
+    done
+    [[ -n "$file" ]] || return 1
+    local -a paths
+    paths=(${{(f)"$({name} complete-field "$file" "$PREFIX" 2>/dev/null)"}})
+    compadd -S '' -- $paths
+}}
+"#
+            );
+
+            let script = script.replace(
+                ":FIELD_PATH: '",
+                &format!(":FIELD_PATH:_{name}_field_paths'"),
+            );
+
+            match script.split_once('\n') {
+                Some((compdef, rest)) => format!("{compdef}\n{helper}{rest}"),
+                None => script,
+            }
+        }
+        Shell::Fish => format!(
+            r#"{script}
+function __{name}_field_paths
+    set -l file
+    for word in (commandline -opc)[2..-1]
+        test -f "$word"; and set file $word
+    end
+    test -n "$file"; and {name} complete-field $file (commandline -ct) 2>/dev/null
+end
+
+complete -c {name} -l field -f -a '(__{name}_field_paths)'
+"#
+        ),
+        _ => script,
+    }
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct CompleteFieldCommand {
+    /// The Nickel file whose fields are completed
+    file: PathBuf,
+
+    /// The field path typed so far, such as `foo.ba`
+    #[arg(default_value = "")]
+    prefix: String,
+}
+
+impl CompleteFieldCommand {
+    /// Print the field paths starting with the prefix, one per line. Only the fields of the record
+    /// containing the last field of the prefix are listed, so that completing `foo.ba` lists
+    /// `foo.bar` but not `foo.bar.baz`. Errors are silently ignored, since they would get in the
+    /// way of the shell.
+    pub fn run(self, _: GlobalOptions) -> CliResult<()> {
+        let parent = match last_dot(&self.prefix) {
+            Some(idx) => &self.prefix[..idx],
+            None => "",
+        };
+
+        if let Ok(paths) = self.field_paths(parent) {
+            for path in paths.into_iter().filter(|p| p.starts_with(&self.prefix)) {
+                println!("{path}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn field_paths(&self, parent: &str) -> Result<Vec<String>, CoreError> {
+        let mut program: Program<CBNCache> =
+            Program::new_from_file(&self.file, std::io::sink()).map_err(IOError::from)?;
+
+        if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
+            program.add_import_paths(nickel_path.split(':'));
+        }
+
+        let path = if parent.is_empty() {
+            FieldPath::new()
+        } else {
+            FieldPath::parse(program.cache_mut(), parent.to_owned())?
+        };
+
+        Ok(program
+            .field_names(&path)?
+            .iter()
+            .map(|id| {
+                if parent.is_empty() {
+                    ident_quoted(id)
+                } else {
+                    format!("{parent}.{}", ident_quoted(id))
+                }
+            })
+            .collect())
+    }
+}
+
+/// The index of the last dot of a field path which isn't inside a quoted field name.
+fn last_dot(path: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut last = None;
+
+    for (idx, c) in path.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '.' if !quoted => last = Some(idx),
+            _ => (),
+        }
+    }
+
+    last
+}
@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+
+use nickel_lang_core::{
+    diff::{diff, Change},
+    eval::cache::lazy::CBNCache,
+    program::Program,
+};
+use serde_json::Value;
+
+use crate::{
+    cli::GlobalOptions,
+    error::{CliResult, ResultErrorExt},
+};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
+pub enum DiffFormat {
+    /// One line per change, prefixed with `+` (added), `-` (removed) or `~` (changed)
+    #[default]
+    Text,
+    /// A JSON array of changes, each with a `kind`, a `path` and the `old` and `new` values
+    Json,
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct DiffCommand {
+    /// The old configuration
+    old: PathBuf,
+
+    /// The new configuration
+    new: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    format: DiffFormat,
+
+    /// Adds a directory to the list of paths to search for imports in.
+    #[arg(long, short = 'I')]
+    import_path: Vec<PathBuf>,
+}
+
+impl DiffCommand {
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        let old = self.eval(&self.old, &global)?;
+        let new = self.eval(&self.new, &global)?;
+        let changes = diff(&old, &new);
+
+        match self.format {
+            DiffFormat::Text => {
+                for change in changes {
+                    match change {
+                        Change::Added { path, new } => println!("+ {path}: {new}"),
+                        Change::Removed { path, old } => println!("- {path}: {old}"),
+                        Change::Changed { path, old, new } => println!("~ {path}: {old} -> {new}"),
+                    }
+                }
+            }
+            DiffFormat::Json => {
+                // unwrap(): serializing JSON values to a string can't fail
+                println!("{}", serde_json::to_string_pretty(&changes).unwrap());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Evaluate a configuration fully, as for an export, and convert it to JSON.
+    fn eval(&self, path: &Path, global: &GlobalOptions) -> CliResult<Value> {
+        let mut program: Program<CBNCache> = Program::new_from_file(path, std::io::stderr())?;
+
+        program.color_opt = global.color.into();
+        program.report_style = global.report_style();
+
+        program.add_import_paths(self.import_path.iter());
+
+        if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
+            program.add_import_paths(nickel_path.split(':'));
+        }
+
+        program
+            .eval_full_for_export()
+            .and_then(|term| Ok(Value::try_from(&term)?))
+            .report_with_program(program)
+    }
+}
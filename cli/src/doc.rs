@@ -22,6 +22,7 @@ pub enum DocFormat {
     Json,
     #[default]
     Markdown,
+    Html,
 }
 
 impl DocFormat {
@@ -29,6 +30,7 @@ impl DocFormat {
         match self {
             Self::Json => "json",
             Self::Markdown => "md",
+            Self::Html => "html",
         }
     }
 }
@@ -38,6 +40,7 @@ impl fmt::Display for DocFormat {
         match self {
             Self::Json => write!(f, "json"),
             Self::Markdown => write!(f, "markdown"),
+            Self::Html => write!(f, "html"),
         }
     }
 }
@@ -46,7 +49,8 @@ impl fmt::Display for DocFormat {
 pub struct DocCommand {
     /// The path of the generated documentation file. Default to
     /// `~/.nickel/doc/<input-file>.md` for input `<input-file>.ncl`, or to
-    /// `~/.nickel/doc/out.md` if the input is read from stdin.
+    /// `~/.nickel/doc/out.md` if the input is read from stdin. The extension depends on the
+    /// output format.
     #[arg(short, long)]
     pub output: Option<PathBuf>,
     /// Write documentation to stdout. Takes precedence over `output`
@@ -134,6 +138,7 @@ impl DocCommand {
         match self.format {
             DocFormat::Json => doc.write_json(&mut out),
             DocFormat::Markdown => doc.write_markdown(&mut out),
+            DocFormat::Html => doc.write_html(&mut out),
         }?;
 
         if let Some(out_path) = out_path {
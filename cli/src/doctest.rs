@@ -1,8 +1,14 @@
 //! The `nickel test` command.
 //!
-//! Extracts tests from docstrings and evaluates them, printing out any failures.
-
-use std::{collections::HashMap, io::Write as _, path::PathBuf, rc::Rc};
+//! Extracts tests from docstrings and evaluates them, printing out any failures. In test files,
+//! whose name ends with `.test.ncl`, the fields of the result are unit tests as well.
+
+use std::{
+    collections::HashMap,
+    io::Write as _,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use comrak::{arena_tree::NodeEdge, nodes::AstNode, Arena, ComrakOptions};
 use nickel_lang_core::{
@@ -14,8 +20,8 @@ use nickel_lang_core::{
     match_sharedterm, mk_app, mk_fun,
     program::Program,
     term::{
-        make, record::RecordData, LabeledType, RichTerm, Term, Traverse as _, TraverseOrder,
-        TypeAnnotation,
+        make, record::RecordData, LabeledType, RichTerm, RuntimeContract, Term, Traverse as _,
+        TraverseOrder, TypeAnnotation,
     },
     typ::{Type, TypeF},
     typecheck::TypecheckMode,
@@ -30,12 +36,39 @@ use crate::{
     input::{InputOptions, Prepare},
 };
 
+/// The suffix of the names of test files, whose fields are unit tests.
+const TEST_FILE_SUFFIX: &str = ".test.ncl";
+
 #[derive(clap::Parser, Debug)]
 pub struct TestCommand {
     #[command(flatten)]
     pub input: InputOptions<ExtractFieldOnly>,
 }
 
+fn is_test_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(TEST_FILE_SUFFIX))
+}
+
+/// Collect the test files of a directory and of its subdirectories, sorted by path.
+fn discover_test_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            discover_test_files(&path, files)?;
+        } else if is_test_file(&path) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
 /// The expected outcome of a test.
 #[derive(Debug)]
 enum Expected {
@@ -135,14 +168,26 @@ impl DocTest {
 }
 
 struct Error {
-    /// The record path to the field whose doctest triggered this error.
+    /// The record path to the field whose doctest triggered this error, or to the failing unit
+    /// test.
     path: Vec<LocIdent>,
     /// The field whose doctest triggered this error might have multiple tests in its
-    /// doc metadata. This is the index of the failing test.
-    idx: usize,
+    /// doc metadata. This is the index of the failing test. Unit tests don't have an index.
+    idx: Option<usize>,
     kind: ErrorKind,
 }
 
+/// The name of a test, as displayed to the user.
+fn test_name(path: &[LocIdent], idx: Option<usize>) -> String {
+    let path: Vec<_> = path.iter().map(|id| id.label()).collect();
+    let path = path.join(".");
+
+    match idx {
+        Some(idx) => format!("{path}/{idx}"),
+        None => path,
+    }
+}
+
 enum ErrorKind {
     /// A doctest was expected to succeed, but it failed.
     UnexpectedFailure { error: EvalError },
@@ -150,6 +195,65 @@ enum ErrorKind {
     UnexpectedSuccess { result: RichTerm },
     /// A doctest failed with an unexpected message.
     WrongTestFailure { message: String, expected: String },
+    /// A unit test evaluated to `false`.
+    False,
+}
+
+// Run the doctest stored in the field `val` of a record, and record its failure in `errors`.
+// `path` is the path to the record.
+fn run_doctest(
+    path: &mut Vec<LocIdent>,
+    prog: &mut Program<CacheImpl>,
+    errors: &mut Vec<Error>,
+    entry: &TestEntry,
+    val: &RichTerm,
+) {
+    path.push(entry.field_name);
+
+    print!("testing {}...", test_name(path, Some(entry.test_idx)));
+    let _ = std::io::stdout().flush();
+
+    // Undo the test's lazy wrapper.
+    let result = prog.eval_closure(Closure {
+        body: mk_app!(val.clone(), Term::Null),
+        env: Environment::new(),
+    });
+
+    let err = match result {
+        Ok(v) => {
+            if entry.expected_error.is_some() {
+                Some(ErrorKind::UnexpectedSuccess { result: v })
+            } else {
+                None
+            }
+        }
+        Err(e) => {
+            if let Some(expected) = &entry.expected_error {
+                let message = prog.report_as_str(e);
+                if !message.contains(expected) {
+                    Some(ErrorKind::WrongTestFailure {
+                        message,
+                        expected: expected.clone(),
+                    })
+                } else {
+                    None
+                }
+            } else {
+                Some(ErrorKind::UnexpectedFailure { error: e })
+            }
+        }
+    };
+    if let Some(err) = err {
+        println!("FAILED");
+        errors.push(Error {
+            kind: err,
+            path: path.clone(),
+            idx: Some(entry.test_idx),
+        });
+    } else {
+        println!("ok");
+    }
+    path.pop();
 }
 
 // Go through the record spine, running tests one-by-one.
@@ -172,53 +276,7 @@ fn run_tests(
                         continue;
                     };
 
-                    path.push(entry.field_name);
-                    let path_display: Vec<_> = path.iter().map(|id| id.label()).collect();
-
-                    print!("testing {}/{}...", path_display.join("."), entry.test_idx);
-                    let _ = std::io::stdout().flush();
-
-                    // Undo the test's lazy wrapper.
-                    let result = prog.eval_closure(Closure {
-                        body: mk_app!(val.clone(), Term::Null),
-                        env: Environment::new(),
-                    });
-
-                    let err = match result {
-                        Ok(v) => {
-                            if entry.expected_error.is_some() {
-                                Some(ErrorKind::UnexpectedSuccess { result: v })
-                            } else {
-                                None
-                            }
-                        }
-                        Err(e) => {
-                            if let Some(expected) = &entry.expected_error {
-                                let message = prog.report_as_str(e);
-                                if !message.contains(expected) {
-                                    Some(ErrorKind::WrongTestFailure {
-                                        message,
-                                        expected: expected.clone(),
-                                    })
-                                } else {
-                                    None
-                                }
-                            } else {
-                                Some(ErrorKind::UnexpectedFailure { error: e })
-                            }
-                        }
-                    };
-                    if let Some(err) = err {
-                        println!("FAILED");
-                        errors.push(Error {
-                            kind: err,
-                            path: path.clone(),
-                            idx: entry.test_idx,
-                        });
-                    } else {
-                        println!("ok");
-                    }
-                    path.pop();
+                    run_doctest(path, prog, errors, entry, val);
                 } else if let Some(val) = field.value.as_ref() {
                     path.push(*id);
                     run_tests(path, prog, errors, registry, val);
@@ -230,64 +288,196 @@ fn run_tests(
     }
 }
 
+// Go through the fields of an evaluated test file, running the unit tests one-by-one. A field
+// evaluating to a record is a group of tests, and its fields are tested recursively. A test fails
+// if its deep evaluation raises an error, such as a contract violation, or if it evaluates to
+// `false`.
+//
+// Contrary to [`run_tests`], the fields are evaluated one at a time, so that a failing test
+// doesn't prevent the other ones from running. The doctests inserted by [`doctest_transform`]
+// are run along the way.
+fn run_unit_tests(
+    path: &mut Vec<LocIdent>,
+    prog: &mut Program<CacheImpl>,
+    errors: &mut Vec<Error>,
+    registry: &TestRegistry,
+    rt: &RichTerm,
+) {
+    let (Term::Record(data) | Term::RecRecord(data, ..)) = rt.as_ref() else {
+        return;
+    };
+
+    for (id, field) in &data.fields {
+        let Some(value) = field.value.as_ref() else {
+            continue;
+        };
+
+        if let Some(entry) = registry.tests.get(&id.ident()) {
+            run_doctest(path, prog, errors, entry, value);
+            continue;
+        }
+
+        // The fields of an evaluated record are closurized, so they can be evaluated in an empty
+        // environment.
+        let value = RuntimeContract::apply_all(
+            value.clone(),
+            field.pending_contracts.iter().cloned(),
+            value.pos,
+        );
+
+        path.push(*id);
+
+        let result = match prog.eval_closure(Closure {
+            body: value.clone(),
+            env: Environment::new(),
+        }) {
+            Ok(evaled) if matches!(evaled.as_ref(), Term::Record(_)) => {
+                run_unit_tests(path, prog, errors, registry, &evaled);
+                path.pop();
+                continue;
+            }
+            Ok(_) => {
+                let deep_seq =
+                    make::static_access(RichTerm::from(Term::Var("std".into())), ["deep_seq"]);
+
+                prog.eval_closure(Closure {
+                    body: mk_app!(deep_seq, value.clone(), value),
+                    env: Environment::new(),
+                })
+            }
+            Err(error) => Err(error),
+        };
+
+        print!("testing {}...", test_name(path, None));
+        let _ = std::io::stdout().flush();
+
+        let err = match result {
+            Ok(result) if matches!(result.as_ref(), Term::Bool(false)) => Some(ErrorKind::False),
+            Ok(_) => None,
+            Err(error) => Some(ErrorKind::UnexpectedFailure { error }),
+        };
+
+        if let Some(err) = err {
+            println!("FAILED");
+            errors.push(Error {
+                kind: err,
+                path: path.clone(),
+                idx: None,
+            });
+        } else {
+            println!("ok");
+        }
+
+        path.pop();
+    }
+}
+
 impl TestCommand {
-    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
-        let mut program = self.input.prepare(&global)?;
+    pub fn run(mut self, global: GlobalOptions) -> CliResult<()> {
+        let num_errors = if self.input.files.iter().any(|path| path.is_dir()) {
+            let mut files = Vec::new();
+
+            for path in std::mem::take(&mut self.input.files) {
+                if path.is_dir() {
+                    discover_test_files(&path, &mut files)?;
+                } else {
+                    files.push(path);
+                }
+            }
+
+            let mut num_errors = 0;
+
+            for file in files {
+                println!("running {}", file.display());
+                self.input.files = vec![file];
+                num_errors += self.run_program(&global)?;
+            }
+
+            num_errors
+        } else {
+            self.run_program(&global)?
+        };
+
+        if num_errors > 0 {
+            eprintln!("{num_errors} failures");
+            Err(crate::error::Error::FailedTests)
+        } else {
+            Ok(())
+        }
+    }
 
-        let (spine, registry) = match self.prepare_tests(&mut program) {
-            Ok(x) => x,
+    /// Run the tests of the program made of the current inputs, print the failures, and return
+    /// their number.
+    fn run_program(&self, global: &GlobalOptions) -> CliResult<usize> {
+        let mut program = self.input.prepare(global)?;
+
+        let unit_tests =
+            !self.input.files.is_empty() && self.input.files.iter().all(|f| is_test_file(f));
+
+        let registry = match self.prepare_tests(&mut program) {
+            Ok(registry) => registry,
+            Err(error) => return Err(crate::error::Error::Program { program, error }),
+        };
+
+        // The record spine evaluates every field, so that a failing unit test would abort the
+        // whole run. Test files are thus evaluated field by field instead.
+        let result = if unit_tests {
+            program.eval()
+        } else {
+            program.eval_closurized_record_spine()
+        };
+
+        let rt = match result {
+            Ok(rt) => rt,
             Err(error) => return Err(crate::error::Error::Program { program, error }),
         };
 
         let mut path = Vec::new();
         let mut errors = Vec::new();
-        run_tests(&mut path, &mut program, &mut errors, &registry, &spine);
+
+        if unit_tests {
+            run_unit_tests(&mut path, &mut program, &mut errors, &registry, &rt);
+        } else {
+            run_tests(&mut path, &mut program, &mut errors, &registry, &rt);
+        }
 
         let num_errors = errors.len();
         for e in errors {
-            let path_display: Vec<_> = e.path.iter().map(|id| id.label()).collect();
-            let path_display = path_display.join(".");
+            let name = test_name(&e.path, e.idx);
             match e.kind {
                 ErrorKind::UnexpectedSuccess { result } => {
                     println!(
-                        "test {}/{} succeeded (evaluated to {result}), but it should have failed",
-                        path_display, e.idx
+                        "test {name} succeeded (evaluated to {result}), but it should have failed"
                     );
                 }
                 ErrorKind::WrongTestFailure { message, expected } => {
                     println!(
-                        "test {}/{} failed, but the error didn't contain \"{expected}\". Actual error:\n{}",
-                        path_display, e.idx, message,
+                        "test {name} failed, but the error didn't contain \"{expected}\". Actual error:\n{message}",
                     );
                 }
                 ErrorKind::UnexpectedFailure { error } => {
-                    println!("test {}/{} failed", path_display, e.idx);
+                    println!("test {name} failed");
                     program.report_to_stdout(
                         error,
                         nickel_lang_core::error::report::ErrorFormat::Text,
                     );
                 }
+                ErrorKind::False => {
+                    println!("test {name} failed: it evaluated to false");
+                }
             }
         }
 
-        if num_errors > 0 {
-            eprintln!("{num_errors} failures");
-            Err(crate::error::Error::FailedTests)
-        } else {
-            Ok(())
-        }
+        Ok(num_errors)
     }
 
-    fn prepare_tests(
-        self,
-        program: &mut Program<CacheImpl>,
-    ) -> Result<(RichTerm, TestRegistry), CoreError> {
+    fn prepare_tests(&self, program: &mut Program<CacheImpl>) -> Result<TestRegistry, CoreError> {
         let mut registry = TestRegistry::default();
         program.typecheck(TypecheckMode::Walk)?;
         program
             .custom_transform(|cache, rt| doctest_transform(cache, &mut registry, rt))
             .map_err(|e| e.unwrap_error("transforming doctest"))?;
-        Ok((program.eval_closurized_record_spine()?, registry))
+        Ok(registry)
     }
 }
 
@@ -2,11 +2,11 @@
 
 use nickel_lang_core::{
     error::{
-        report::{ColorOpt, ErrorFormat},
-        Diagnostic, IntoDiagnostics, ParseError,
+        report::{ColorOpt, ErrorFormat, ReportStyle},
+        Diagnostic, Diagnostics, EvalError, IntoDiagnostics, ParseError,
     },
     eval::cache::lazy::CBNCache,
-    files::{FileId, Files},
+    files::Files,
     program::{FieldOverride, FieldPath, Program},
 };
 
@@ -48,6 +48,11 @@ pub enum Error {
     Format {
         error: crate::format::FormatError,
     },
+    #[cfg(feature = "format")]
+    Codemod {
+        path: Option<std::path::PathBuf>,
+        error: nickel_lang_core::codemod::CodemodError,
+    },
     /// An invalid invocation of the CLI that couldn't be caught by the simple parsing provided by
     /// clap.
     CliUsage {
@@ -61,10 +66,26 @@ pub enum Error {
     /// Upon receiving this error, the caller should simply exit without proceeding with evaluation.
     CustomizeInfoPrinted,
     FailedTests,
+    /// The lint configuration file is invalid.
+    LintConfig {
+        path: std::path::PathBuf,
+        error: nickel_lang_core::lint::LintConfigError,
+    },
+    /// Linting reported errors, which have already been printed.
+    LintFailed,
+    /// The configuration violates some rules, which have already been printed.
+    CheckFailed,
+    /// `nickel format --check` found unformatted files, which have already been printed.
+    #[cfg(feature = "format")]
+    FormatCheckFailed,
+    #[cfg(feature = "packages")]
+    Package {
+        error: nickel_lang_core::package::PackageError,
+    },
 }
 
 impl IntoDiagnostics for CliUsageError {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
         fn mk_unknown_diags<FileId>(
             data: UnknownFieldData,
             method: &str,
@@ -98,27 +119,28 @@ impl IntoDiagnostics for CliUsageError {
         }
 
         match self {
-            CliUsageError::UnknownFieldOverride(data) => mk_unknown_diags(data, "override"),
-            CliUsageError::UnknownFieldAssignment(data) => mk_unknown_diags(data, "assignment"),
+            CliUsageError::UnknownFieldOverride(data) => mk_unknown_diags(data, "override").into(),
+            CliUsageError::UnknownFieldAssignment(data) => {
+                mk_unknown_diags(data, "assignment").into()
+            }
             CliUsageError::CantAssignNonInput {
                 ovd: FieldOverride { path, value, .. },
-            } => {
-                vec![Diagnostic::error()
-                    .with_message(format!("invalid assignment: `{path}` isn't an input"))
-                    .with_notes(vec![
-                        format!(
-                            "`{path}` already has a value and thus can't be assigned \
+            } => vec![Diagnostic::error()
+                .with_message(format!("invalid assignment: `{path}` isn't an input"))
+                .with_notes(vec![
+                    format!(
+                        "`{path}` already has a value and thus can't be assigned \
                             without `--override`."
-                        ),
-                        format!(
-                            "If you really want to override this field, please use \
+                    ),
+                    format!(
+                        "If you really want to override this field, please use \
                             `--override '{path}={value}'` instead."
-                        ),
-                    ])]
-            }
+                    ),
+                ])]
+            .into(),
             CliUsageError::AssignmentParseError { error } => {
                 let mut diags = IntoDiagnostics::into_diagnostics(error, files);
-                diags.push(
+                diags.diagnostics.push(
                     Diagnostic::note()
                         .with_message("when parsing a field assignment on the command line")
                         .with_notes(vec![
@@ -133,7 +155,7 @@ impl IntoDiagnostics for CliUsageError {
             }
             CliUsageError::FieldPathParseError { error } => {
                 let mut diags = IntoDiagnostics::into_diagnostics(error, files);
-                diags.push(
+                diags.diagnostics.push(
                     Diagnostic::note()
                         .with_message("when parsing a field path on the command line")
                         .with_notes(vec![
@@ -162,7 +184,7 @@ pub enum Warning {
 }
 
 impl IntoDiagnostics for Warning {
-    fn into_diagnostics(self, _files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, _files: &Files) -> Diagnostics {
         vec![Diagnostic::warning()
             .with_message("empty query path")
             .with_notes(vec![
@@ -176,6 +198,7 @@ impl IntoDiagnostics for Warning {
             \n`nickel query config.ncl --field module.input"
                     .into(),
             ])]
+        .into()
     }
 }
 
@@ -194,6 +217,20 @@ impl From<crate::format::FormatError> for Error {
     }
 }
 
+#[cfg(feature = "format")]
+impl From<nickel_lang_core::codemod::CodemodError> for Error {
+    fn from(error: nickel_lang_core::codemod::CodemodError) -> Self {
+        Error::Codemod { path: None, error }
+    }
+}
+
+#[cfg(feature = "packages")]
+impl From<nickel_lang_core::package::PackageError> for Error {
+    fn from(error: nickel_lang_core::package::PackageError) -> Self {
+        Error::Package { error }
+    }
+}
+
 #[cfg(feature = "repl")]
 impl From<nickel_lang_core::repl::InitError> for Error {
     fn from(error: nickel_lang_core::repl::InitError) -> Self {
@@ -201,6 +238,13 @@ impl From<nickel_lang_core::repl::InitError> for Error {
     }
 }
 
+/// Report the warnings emitted so far by the evaluation of `program` on the standard error
+/// stream.
+pub fn report_warnings(program: &mut Program<CBNCache>, format: ErrorFormat) {
+    // Denied warnings abort the evaluation, so the remaining ones are never denied.
+    program.report_warnings(format, false);
+}
+
 pub trait ResultErrorExt<T> {
     fn report_with_program(self, program: Program<CBNCache>) -> CliResult<T>;
 }
@@ -213,7 +257,7 @@ impl<T> ResultErrorExt<T> for Result<T, nickel_lang_core::error::Error> {
 
 impl Error {
     /// Report this error on the standard error stream.
-    pub fn report(self, format: ErrorFormat, color: ColorOpt) {
+    pub fn report(self, format: ErrorFormat, color: ColorOpt, style: ReportStyle) {
         // Report a standalone error which doesn't actually refer to any source code.
         let report_standalone = |main_label: &str, msg: Option<String>| {
             use nickel_lang_core::{
@@ -221,19 +265,33 @@ impl Error {
                 error::report::report as core_report,
             };
 
-            let mut dummy_cache = Cache::new(ErrorTolerance::Tolerant);
+            let dummy_cache = Cache::new(ErrorTolerance::Tolerant);
             let diagnostic = Diagnostic::error()
                 .with_message(main_label)
                 .with_notes(msg.into_iter().collect());
 
-            core_report(&mut dummy_cache, diagnostic, format, color);
+            core_report(&dummy_cache, diagnostic, format, color, style);
         };
 
         // We try to fit every error in a diagnostic. This makes sure all errors are rendered using
         // the same format set (potentitally by default) by the `--error-format` flag. This also
         // makes error styling more consistent.
         match self {
-            Error::Program { mut program, error } => program.report(error, format),
+            Error::Program { mut program, error } => {
+                let is_blame = matches!(
+                    error,
+                    nickel_lang_core::error::Error::EvalError(EvalError::BlameError { .. })
+                );
+                program.report(error, format);
+
+                if is_blame {
+                    let trace = program.take_contract_trace();
+
+                    if !trace.is_empty() {
+                        program.report(trace, format);
+                    }
+                }
+            }
             Error::Io { error } => {
                 report_standalone("IO error", Some(error.to_string()));
             }
@@ -251,8 +309,26 @@ impl Error {
             }
             #[cfg(feature = "format")]
             Error::Format { error } => report_standalone("format error", Some(error.to_string())),
+            #[cfg(feature = "format")]
+            Error::Codemod { path, error } => report_standalone(
+                "migration error",
+                Some(match path {
+                    Some(path) => format!("{}: {error}", path.display()),
+                    None => error.to_string(),
+                }),
+            ),
             Error::CliUsage { error, mut program } => program.report(error, format),
             Error::FailedTests => report_standalone("tests failed", None),
+            Error::LintConfig { path, error } => report_standalone(
+                "invalid lint configuration",
+                Some(format!("{}: {error}", path.display())),
+            ),
+            Error::LintFailed => report_standalone("linting failed", None),
+            Error::CheckFailed => report_standalone("rules check failed", None),
+            #[cfg(feature = "format")]
+            Error::FormatCheckFailed => report_standalone("some files aren't formatted", None),
+            #[cfg(feature = "packages")]
+            Error::Package { error } => report_standalone("package error", Some(error.to_string())),
             Error::CustomizeInfoPrinted => {
                 // Nothing to do, the caller should simply exit.
             }
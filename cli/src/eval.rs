@@ -1,23 +1,42 @@
+use nickel_lang_core::{error::Error, eval::cache::lazy::CBNCache, program::Program};
+
 use crate::{
     cli::GlobalOptions,
     customize::CustomizeMode,
-    error::{CliResult, ResultErrorExt},
+    error::{report_warnings, CliResult, ResultErrorExt},
     input::{InputOptions, Prepare},
+    watch::watch,
 };
 
 #[derive(clap::Parser, Debug)]
 pub struct EvalCommand {
+    /// Evaluates the program again each time one of the input files or of the files they import
+    /// changes, until interrupted
+    #[arg(long, requires = "files")]
+    pub watch: bool,
+
     #[command(flatten)]
     pub input: InputOptions<CustomizeMode>,
 }
 
 impl EvalCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        if self.watch {
+            return watch(&self.input, &self.input.files, &global, |program| {
+                Self::eval(program, &global)
+            });
+        }
+
         let mut program = self.input.prepare(&global)?;
+        let result = Self::eval(&mut program, &global);
+
+        result.report_with_program(program)
+    }
+
+    fn eval(program: &mut Program<CBNCache>, global: &GlobalOptions) -> Result<(), Error> {
+        let result = program.eval_full();
+        report_warnings(program, global.error_format);
 
-        program
-            .eval_full()
-            .map(|t| println!("{t}"))
-            .report_with_program(program)
+        result.map(|t| println!("{t}"))
     }
 }
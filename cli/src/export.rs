@@ -1,17 +1,25 @@
-use std::{fs, io::Write, path::PathBuf};
+use std::{
+    fs,
+    io::{self, Write},
+    num::NonZeroUsize,
+    path::PathBuf,
+};
 
 use nickel_lang_core::{
-    error::{Error, IOError},
+    error::{Error, ExportError, ExportErrorData, IOError},
     eval::cache::lazy::CBNCache,
     program::Program,
-    serialize::{self, ExportFormat},
+    provenance,
+    serialize::{self, ExportFormat, JsonLayout, KeyOrder},
+    term::{record::RecordData, RichTerm, Term},
 };
 
 use crate::{
     cli::GlobalOptions,
     customize::CustomizeMode,
-    error::{CliResult, ResultErrorExt},
+    error::{report_warnings, CliResult, ResultErrorExt},
     input::{InputOptions, Prepare},
+    watch::watch,
 };
 
 #[derive(clap::Parser, Debug)]
@@ -23,35 +31,219 @@ pub struct ExportCommand {
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
+    /// Export JSON on a single line, as expected by log pipelines which process one document per
+    /// line
+    #[arg(long, conflicts_with_all = ["indent", "parallel"])]
+    pub compact: bool,
+
+    /// The number of spaces for each level of indentation of JSON exports
+    #[arg(long, value_name = "SPACES", conflicts_with = "parallel")]
+    pub indent: Option<usize>,
+
+    /// The order of the fields of the exported records. Both orders only depend on the program,
+    /// so that the output is byte-stable across runs and machines
+    #[arg(long, value_enum, default_value_t)]
+    pub key_order: KeyOrder,
+
+    /// Render the top-level fields of the result on the given number of threads. Only worth it
+    /// for very large exports, and only supported for JSON and YAML
+    #[arg(long, value_name = "THREADS")]
+    pub parallel: Option<NonZeroUsize>,
+
+    /// Write the result while evaluating it, instead of evaluating it fully first. This lowers
+    /// the memory usage of very large exports, but an error might be reported after a part of the
+    /// output has been written. Only supported for JSON
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "parallel",
+            "redact_secrets",
+            "secrets_output",
+            "provenance_output",
+            "provenance_comments",
+        ]
+    )]
+    pub stream: bool,
+
+    /// Replace the values of the fields marked as `secret` with a placeholder
+    #[arg(long)]
+    pub redact_secrets: bool,
+
+    /// Write the values of the fields marked as `secret` to this file instead of the main output,
+    /// where they are replaced with a placeholder. Implies `--redact-secrets`
+    #[arg(long, value_name = "FILE")]
+    pub secrets_output: Option<PathBuf>,
+
+    /// Write the location of the definition of each exported value to this file, as a tree with
+    /// the same shape as the output, in the same format
+    #[arg(long, value_name = "FILE")]
+    pub provenance_output: Option<PathBuf>,
+
+    /// Follow each exported value with a comment giving the location of its definition. Only
+    /// supported for YAML
+    #[arg(long, conflicts_with = "parallel")]
+    pub provenance_comments: bool,
+
+    /// After exporting, report the record fields and the let bindings which have never been
+    /// evaluated
+    #[arg(long)]
+    pub report_unused: bool,
+
+    /// Exports the program again each time one of the input files or of the files they import
+    /// changes, until interrupted
+    #[arg(long, requires = "files")]
+    pub watch: bool,
+
     #[command(flatten)]
     pub input: InputOptions<CustomizeMode>,
 }
 
 impl ExportCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        if self.watch {
+            return watch(&self.input, &self.input.files, &global, |program| {
+                self.export_program(program, &global)
+            });
+        }
+
         let mut program = self.input.prepare(&global)?;
+        let result = self.export_program(&mut program, &global);
 
-        self.export(&mut program).report_with_program(program)
+        result.report_with_program(program)
     }
 
-    fn export(self, program: &mut Program<CBNCache>) -> Result<(), Error> {
-        let rt = program.eval_full_for_export()?;
+    fn export_program(
+        &self,
+        program: &mut Program<CBNCache>,
+        global: &GlobalOptions,
+    ) -> Result<(), Error> {
+        let report_unused = self.report_unused;
+
+        program.set_track_demand(report_unused);
+        let result = self.export(program);
+        report_warnings(program, global.error_format);
+
+        if report_unused && result.is_ok() {
+            for unused in program.unused() {
+                program.report(unused, global.error_format);
+            }
+        }
+
+        result
+    }
+
+    fn export(&self, program: &mut Program<CBNCache>) -> Result<(), Error> {
+        if self.provenance_comments && self.format != ExportFormat::Yaml {
+            return Err(ExportError::from(ExportErrorData::Other(
+                "provenance comments are only supported for YAML exports".to_owned(),
+            ))
+            .into());
+        }
+
+        if (self.compact || self.indent.is_some()) && self.format != ExportFormat::Json {
+            return Err(ExportError::from(ExportErrorData::Other(
+                "`--compact` and `--indent` are only supported for JSON exports".to_owned(),
+            ))
+            .into());
+        }
 
         // We only add a trailing newline for JSON exports. Both YAML and TOML
         // exporters already append a trailing newline by default.
         let trailing_newline = self.format == ExportFormat::Json;
 
+        if self.stream {
+            if self.format != ExportFormat::Json {
+                return Err(ExportError::from(ExportErrorData::Other(
+                    "`--stream` is only supported for JSON exports".to_owned(),
+                ))
+                .into());
+            }
+
+            let layout = self.json_layout();
+
+            if let Some(file) = &self.output {
+                let mut file = io::BufWriter::new(fs::File::create(file).map_err(IOError::from)?);
+                program.stream_export_json(&mut file, layout, self.key_order)?;
+                writeln!(file).map_err(IOError::from)?;
+                file.flush().map_err(IOError::from)?;
+            } else {
+                let mut stdout = io::stdout().lock();
+                program.stream_export_json(&mut stdout, layout, self.key_order)?;
+                writeln!(stdout).map_err(IOError::from)?;
+            }
+
+            return Ok(());
+        }
+
+        let rt = program.eval_full_for_export()?;
+
         serialize::validate(self.format, &rt)?;
 
-        if let Some(file) = self.output {
+        let rt = if self.redact_secrets || self.secrets_output.is_some() {
+            let (public, secrets) = serialize::split_secrets(&rt);
+
+            if let Some(file) = &self.secrets_output {
+                let mut file = fs::File::create(file).map_err(IOError::from)?;
+                // An empty record is exported if there aren't any secret, so that the consumers
+                // of the secrets file can always rely on its presence.
+                let secrets = secrets.unwrap_or_else(|| Term::Record(RecordData::empty()).into());
+                self.to_writer(&mut file, &secrets)?;
+
+                if trailing_newline {
+                    writeln!(file).map_err(IOError::from)?;
+                }
+            }
+
+            public
+        } else {
+            rt
+        };
+
+        if let Some(file) = &self.provenance_output {
+            let tree = provenance::provenance_tree(&rt, program.cache_mut().files())?;
+            let mut file = fs::File::create(file).map_err(IOError::from)?;
+            self.to_writer(&mut file, &tree)?;
+
+            if trailing_newline {
+                writeln!(file).map_err(IOError::from)?;
+            }
+        }
+
+        let annotated = if self.provenance_comments {
+            Some(provenance::to_yaml_with_provenance(
+                &rt,
+                program.cache_mut().files(),
+                self.key_order,
+            )?)
+        } else {
+            None
+        };
+
+        let write = |writer: &mut (dyn Write + Send)| -> Result<(), Error> {
+            match (&annotated, self.parallel) {
+                (Some(yaml), _) => writer.write_all(yaml.as_bytes()).map_err(IOError::from)?,
+                (None, Some(threads)) => serialize::to_writer_parallel(
+                    writer,
+                    self.format,
+                    &rt,
+                    threads,
+                    self.key_order,
+                )?,
+                (None, None) => self.to_writer(writer, &rt)?,
+            }
+
+            Ok(())
+        };
+
+        if let Some(file) = &self.output {
             let mut file = fs::File::create(file).map_err(IOError::from)?;
-            serialize::to_writer(&mut file, self.format, &rt)?;
+            write(&mut file)?;
 
             if trailing_newline {
                 writeln!(file).map_err(IOError::from)?;
             }
         } else {
-            serialize::to_writer(std::io::stdout(), self.format, &rt)?;
+            write(&mut std::io::stdout())?;
 
             if trailing_newline {
                 println!();
@@ -60,4 +252,23 @@ impl ExportCommand {
 
         Ok(())
     }
+
+    /// Serialize a term in the selected format and order of fields, with the selected layout for
+    /// JSON.
+    fn to_writer(&self, writer: impl Write, rt: &RichTerm) -> Result<(), ExportError> {
+        if self.format == ExportFormat::Json {
+            serialize::to_writer_json(writer, rt, self.json_layout(), self.key_order)
+        } else {
+            serialize::to_writer_with_key_order(writer, self.format, rt, self.key_order)
+        }
+    }
+
+    /// The layout of JSON exports selected by `--compact` and `--indent`.
+    fn json_layout(&self) -> JsonLayout {
+        match (self.compact, self.indent) {
+            (true, _) => JsonLayout::Compact,
+            (false, Some(indent)) => JsonLayout::Pretty { indent },
+            (false, None) => JsonLayout::default(),
+        }
+    }
 }
@@ -77,6 +77,11 @@ impl Write for Output {
 
 #[derive(clap::Parser, Debug)]
 pub struct FormatCommand {
+    /// Don't write the formatted files, but fail if any of them isn't already formatted. The
+    /// paths of the unformatted files are printed to the standard error.
+    #[arg(long)]
+    check: bool,
+
     #[command(flatten)]
     input: InputOptions<NoCustomizeMode>,
 }
@@ -89,6 +94,10 @@ impl FormatCommand {
             output.persist();
             Ok(())
         }
+        if self.check {
+            return self.check();
+        }
+
         if self.input.files.is_empty() {
             return format(stdin(), Output::Stdout);
         }
@@ -98,4 +107,39 @@ impl FormatCommand {
         }
         Ok(())
     }
+
+    /// Check that the inputs are formatted, without writing anything.
+    fn check(self) -> CliResult<()> {
+        /// Return `true` if the content of `input` is formatted.
+        fn is_formatted(mut input: impl Read) -> CliResult<bool> {
+            let mut source = Vec::new();
+            input.read_to_end(&mut source)?;
+
+            let mut formatted = Vec::new();
+            nickel_lang_core::format::format(source.as_slice(), &mut formatted)
+                .map_err(FormatError::FormatError)?;
+
+            Ok(source == formatted)
+        }
+
+        let mut unformatted = false;
+
+        if self.input.files.is_empty() && !is_formatted(stdin())? {
+            eprintln!("<stdin> isn't formatted");
+            unformatted = true;
+        }
+
+        for file in self.input.files.iter() {
+            if !is_formatted(BufReader::new(File::open(file)?))? {
+                eprintln!("{} isn't formatted", file.display());
+                unformatted = true;
+            }
+        }
+
+        if unformatted {
+            Err(crate::error::Error::FormatCheckFailed)
+        } else {
+            Ok(())
+        }
+    }
 }
@@ -1,6 +1,10 @@
 use std::path::PathBuf;
 
-use nickel_lang_core::{eval::cache::lazy::CBNCache, program::Program};
+#[cfg(feature = "git-imports")]
+use nickel_lang_core::git_import::{GitImportConfig, Lockfile, LOCKFILE_NAME};
+#[cfg(feature = "http-imports")]
+use nickel_lang_core::http_import::HttpImportConfig;
+use nickel_lang_core::{eval::cache::lazy::CBNCache, policy::CapabilityPolicy, program::Program};
 
 use crate::{cli::GlobalOptions, customize::Customize, error::CliResult};
 
@@ -24,6 +28,31 @@ pub struct InputOptions<Customize: clap::Args> {
     #[arg(long, short = 'I', global = true)]
     pub import_path: Vec<PathBuf>,
 
+    /// Turns warnings, such as accesses to deprecated fields, into errors
+    #[arg(long, global = true)]
+    pub deny_warnings: bool,
+
+    /// Rejects the constructs which could make the result differ across runs or machines, such
+    /// as calls to non-deterministic native functions or absolute imports. The
+    /// `NICKEL_IMPORT_PATH` environment variable is ignored in this mode.
+    #[arg(long, global = true)]
+    pub reproducible: bool,
+
+    /// Evaluates the program in a sandbox, where imports and native functions are denied. The
+    /// files given on the command line are still read
+    #[arg(long, global = true)]
+    pub sandbox: bool,
+
+    /// Records every contract application and label polarity flip during evaluation, and reports
+    /// this trace along with blame errors, in the format of `--error-format`
+    #[arg(long, global = true)]
+    pub trace_contracts: bool,
+
+    /// Only imports remote files from the local cache, without downloading them
+    #[cfg(feature = "http-imports")]
+    #[arg(long, global = true)]
+    pub offline: bool,
+
     #[command(flatten)]
     pub customize_mode: Customize,
 }
@@ -41,11 +70,38 @@ impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
         }?;
 
         program.color_opt = global.color.into();
+        program.report_style = global.report_style();
+        program.set_deny_warnings(self.deny_warnings);
+        program.set_reproducible(self.reproducible);
+        program.set_contract_trace(self.trace_contracts);
+
+        if self.sandbox {
+            program.set_policy(CapabilityPolicy::sandboxed());
+        }
 
         program.add_import_paths(self.import_path.iter());
 
-        if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
-            program.add_import_paths(nickel_path.split(':'));
+        #[cfg(feature = "packages")]
+        if let Some(packages) = crate::package::packages()? {
+            program.set_packages(packages);
+        }
+
+        #[cfg(feature = "git-imports")]
+        program.set_git_imports(Some(git_import_config(Lockfile::load(LOCKFILE_NAME)?)));
+
+        #[cfg(feature = "http-imports")]
+        if let Some(dirs) = directories::BaseDirs::new() {
+            program.set_http_imports(Some(
+                HttpImportConfig::new(dirs.cache_dir().join("nickel").join("http"))
+                    .offline(self.offline),
+            ));
+        }
+
+        // The environment of the machine mustn't influence the result in reproducible mode.
+        if !self.reproducible {
+            if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
+                program.add_import_paths(nickel_path.split(':'));
+            }
         }
 
         #[cfg(debug_assertions)]
@@ -56,3 +112,19 @@ impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
         self.customize_mode.customize(program)
     }
 }
+
+/// The directory storing the checkouts of git repositories, in the cache directory of the user.
+#[cfg(feature = "git-imports")]
+pub fn git_cache_dir() -> PathBuf {
+    let cache_dir = directories::BaseDirs::new()
+        .map(|dirs| dirs.cache_dir().to_owned())
+        .unwrap_or_else(std::env::temp_dir);
+
+    cache_dir.join("nickel").join("git")
+}
+
+/// The configuration of git imports, with the checkouts stored in [git_cache_dir].
+#[cfg(feature = "git-imports")]
+pub fn git_import_config(lockfile: Lockfile) -> GitImportConfig {
+    GitImportConfig::new(git_cache_dir(), lockfile)
+}
@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+
+use nickel_lang_core::{
+    eval::cache::lazy::CBNCache,
+    lint::{LintConfig, LintLevel, Linter, CONFIG_FILE_NAME},
+    program::Program,
+};
+
+use crate::{
+    cli::GlobalOptions,
+    error::{CliResult, Error},
+};
+
+#[derive(clap::Parser, Debug)]
+pub struct LintCommand {
+    /// The Nickel files to lint.
+    #[arg(required_unless_present = "list")]
+    files: Vec<PathBuf>,
+
+    /// The lint configuration file. Defaults to the first `nickel-lint.toml` file found in the
+    /// current directory or in one of its ancestors.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Fail if any lint reports a warning, and not only on errors.
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// Print the available lint rules with their level, and exit.
+    #[arg(long)]
+    list: bool,
+}
+
+impl LintCommand {
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        let mut linter = Linter::new();
+
+        if let Some(path) = self.config_path()? {
+            let config = std::fs::read_to_string(&path)?;
+            LintConfig::from_toml(&config)
+                .and_then(|config| linter.configure(config))
+                .map_err(|error| Error::LintConfig { path, error })?;
+        }
+
+        if self.list {
+            for rule in linter.rules() {
+                println!(
+                    "{} ({}): {}",
+                    rule.name(),
+                    linter.level(rule),
+                    rule.description()
+                );
+            }
+
+            return Ok(());
+        }
+
+        let mut failed = false;
+
+        for file in &self.files {
+            let mut program: Program<CBNCache> = Program::new_from_file(file, std::io::stderr())?;
+            program.color_opt = global.color.into();
+            program.report_style = global.report_style();
+
+            let diagnostics = match program.lint(&linter) {
+                Ok(diagnostics) => diagnostics,
+                Err(error) => return Err(Error::Program { program, error }),
+            };
+
+            for diagnostic in diagnostics {
+                failed |= diagnostic.level == LintLevel::Deny || self.deny_warnings;
+                program.report(diagnostic, global.error_format);
+            }
+        }
+
+        if failed {
+            Err(Error::LintFailed)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn config_path(&self) -> CliResult<Option<PathBuf>> {
+        if let Some(path) = &self.config {
+            return Ok(Some(path.clone()));
+        }
+
+        let current_dir = std::env::current_dir()?;
+
+        Ok(current_dir
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|path| Path::is_file(path)))
+    }
+}
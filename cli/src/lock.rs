@@ -0,0 +1,44 @@
+use nickel_lang_core::git_import::{Lockfile, LOCKFILE_NAME};
+
+use crate::{
+    cli::GlobalOptions,
+    customize::NoCustomizeMode,
+    error::{CliResult, Error},
+    input::{git_import_config, InputOptions, Prepare},
+};
+
+#[derive(clap::Parser, Debug)]
+pub struct LockCommand {
+    #[command(flatten)]
+    inputs: InputOptions<NoCustomizeMode>,
+
+    /// Resolves the references of all the git imports again, instead of only pinning the ones
+    /// which aren't in the lockfile yet
+    #[arg(long)]
+    update: bool,
+}
+
+impl LockCommand {
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        let lockfile = if self.update {
+            Lockfile::default()
+        } else {
+            Lockfile::load(LOCKFILE_NAME)?
+        };
+
+        let mut program = self.inputs.prepare(&global)?;
+        program.set_git_imports(Some(git_import_config(lockfile).update(true)));
+
+        if let Err(error) = program.resolve_imports() {
+            return Err(Error::Program { program, error });
+        }
+
+        program
+            .git_imports()
+            .expect("git imports have been enabled")
+            .lockfile
+            .save(LOCKFILE_NAME)?;
+
+        Ok(())
+    }
+}
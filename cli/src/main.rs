@@ -6,21 +6,32 @@ mod doc;
 mod doctest;
 #[cfg(feature = "format")]
 mod format;
+#[cfg(feature = "git-imports")]
+mod lock;
 #[cfg(feature = "metrics")]
 mod metrics;
+#[cfg(feature = "format")]
+mod migrate;
+#[cfg(feature = "packages")]
+mod package;
 #[cfg(feature = "repl")]
 mod repl;
 
+mod check;
 mod cli;
 mod completions;
 mod customize;
+mod diff;
 mod error;
 mod eval;
 mod export;
 mod input;
+mod lint;
 mod pprint_ast;
 mod query;
 mod typecheck;
+mod validate;
+mod watch;
 
 use std::process::ExitCode;
 
@@ -34,6 +45,7 @@ fn main() -> ExitCode {
 
     let error_format = opts.global.error_format;
     let color = opts.global.color;
+    let report_style = opts.global.report_style();
     #[cfg(feature = "metrics")]
     let report_metrics = opts.global.metrics;
 
@@ -43,7 +55,16 @@ fn main() -> ExitCode {
         Command::Export(export) => export.run(opts.global),
         Command::Query(query) => query.run(opts.global),
         Command::Typecheck(typecheck) => typecheck.run(opts.global),
+        Command::Validate(validate) => validate.run(opts.global),
+        Command::Lint(lint) => lint.run(opts.global),
+        Command::Check(check) => check.run(opts.global),
+        Command::Diff(diff) => diff.run(opts.global),
+        #[cfg(feature = "git-imports")]
+        Command::Lock(lock) => lock.run(opts.global),
+        #[cfg(feature = "packages")]
+        Command::Package(package) => package.run(opts.global),
         Command::GenCompletions(completions) => completions.run(opts.global),
+        Command::CompleteField(complete) => complete.run(opts.global),
 
         #[cfg(feature = "repl")]
         Command::Repl(repl) => repl.run(opts.global),
@@ -55,6 +76,8 @@ fn main() -> ExitCode {
 
         #[cfg(feature = "format")]
         Command::Format(format) => format.run(opts.global),
+        #[cfg(feature = "format")]
+        Command::Migrate(migrate) => migrate.run(opts.global),
     };
 
     #[cfg(feature = "metrics")]
@@ -67,7 +90,7 @@ fn main() -> ExitCode {
         // user's point of view.
         Ok(()) | Err(error::Error::CustomizeInfoPrinted) => ExitCode::SUCCESS,
         Err(error) => {
-            error.report(error_format, color.into());
+            error.report(error_format, color.into(), report_style);
             ExitCode::FAILURE
         }
     }
@@ -0,0 +1,114 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use nickel_lang_core::codemod::{self, Rewrite};
+
+use crate::{
+    cli::GlobalOptions,
+    error::{CliResult, Error},
+};
+
+#[derive(clap::Parser, Debug)]
+pub struct MigrateCommand {
+    #[command(subcommand)]
+    migration: Migration,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Migration {
+    /// Renames a field, both in record literals and in field accesses
+    RenameField {
+        /// The current name of the field
+        old: String,
+        /// The new name of the field
+        new: String,
+        #[command(flatten)]
+        target: Target,
+    },
+    /// Replaces the nodes matching a tree-sitter query with a template
+    Rewrite {
+        /// A tree-sitter query capturing the node to replace as `@match`
+        #[arg(long)]
+        pattern: String,
+        /// The replacement, where `$name` stands for the text of the node captured as `@name`
+        #[arg(long)]
+        template: String,
+        #[command(flatten)]
+        target: Target,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct Target {
+    /// Files to rewrite. Directories are searched recursively for `.ncl` files.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Print the files which would be modified, but don't modify them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl MigrateCommand {
+    pub fn run(self, _global: GlobalOptions) -> CliResult<()> {
+        let (rewrite, target) = match self.migration {
+            Migration::RenameField { old, new, target } => {
+                (Rewrite::rename_field(&old, &new), target)
+            }
+            Migration::Rewrite {
+                pattern,
+                template,
+                target,
+            } => (Rewrite::new(&pattern, &template)?, target),
+        };
+
+        let mut files = Vec::new();
+        for path in &target.paths {
+            collect_files(path, &mut files)?;
+        }
+
+        for file in files {
+            let source = fs::read_to_string(&file)?;
+            let rewritten =
+                codemod::apply(&source, std::slice::from_ref(&rewrite)).map_err(|error| {
+                    Error::Codemod {
+                        path: Some(file.clone()),
+                        error,
+                    }
+                })?;
+
+            if rewritten != source {
+                if target.dry_run {
+                    println!("{}", file.display());
+                } else {
+                    fs::write(&file, rewritten)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Collect `path` if it's a file, or the `.ncl` files under `path` if it's a directory.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> CliResult<()> {
+    if !path.is_dir() {
+        files.push(path.to_owned());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "ncl") {
+            collect_files(&entry, files)?;
+        }
+    }
+
+    Ok(())
+}
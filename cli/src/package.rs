@@ -0,0 +1,142 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+use nickel_lang_core::package::{
+    Dependency, Lockfile, Manifest, PackageError, Resolution, Resolver, VersionReq, LOCKFILE_NAME,
+    MANIFEST_NAME, VENDOR_DIR,
+};
+
+use crate::{cli::GlobalOptions, error::CliResult, input::git_cache_dir};
+
+#[derive(clap::Parser, Debug)]
+pub struct PackageCommand {
+    #[command(subcommand)]
+    command: PackageSubcommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum PackageSubcommand {
+    /// Creates a package manifest in the current directory
+    Init {
+        /// The name of the package. Defaults to the name of the current directory
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Adds a dependency to the package manifest, and pins it in the lockfile
+    Add(AddCommand),
+    /// Resolves all the dependencies again, ignoring the versions pinned by the lockfile
+    Update,
+    /// Copies the dependencies into the `vendor` directory, which is then used instead of the
+    /// local cache
+    Vendor,
+}
+
+#[derive(clap::Parser, Debug)]
+struct AddCommand {
+    /// The name of the dependency
+    name: String,
+
+    /// The directory of a local package, relative to the current directory
+    #[arg(long, required_unless_present = "git", conflicts_with = "git")]
+    path: Option<PathBuf>,
+
+    /// The URL of the git repository of the package
+    #[arg(long)]
+    git: Option<String>,
+
+    /// The versions accepted, matched against the tags of the repository
+    #[arg(long, requires = "git")]
+    version: Option<VersionReq>,
+
+    /// The branch, tag or commit to use when there is no version requirement
+    #[arg(long = "ref", requires = "git", conflicts_with = "version")]
+    reference: Option<String>,
+}
+
+/// Resolve the dependencies of the package in the current directory, and save the lockfile.
+fn resolve(manifest: &Manifest, lockfile: Lockfile) -> CliResult<Resolution> {
+    let resolution =
+        Resolver::new(git_cache_dir(), lockfile).resolve(&env::current_dir()?, manifest)?;
+    resolution.lockfile.save(LOCKFILE_NAME)?;
+
+    Ok(resolution)
+}
+
+/// The root directories of the dependencies of the package in the current directory, if there is
+/// one. The dependencies are taken from the `vendor` directory if it exists, and otherwise from
+/// the lockfile, which must pin all of them.
+pub fn packages() -> CliResult<Option<BTreeMap<String, PathBuf>>> {
+    if !Path::new(MANIFEST_NAME).exists() {
+        return Ok(None);
+    }
+
+    let lockfile = Lockfile::load(LOCKFILE_NAME)?;
+
+    if Path::new(VENDOR_DIR).is_dir() {
+        return Ok(Some(
+            lockfile.vendored(env::current_dir()?.join(VENDOR_DIR)),
+        ));
+    }
+
+    let manifest = Manifest::load(MANIFEST_NAME)?;
+    let resolution = Resolver::new(git_cache_dir(), lockfile)
+        .frozen(true)
+        .resolve(&env::current_dir()?, &manifest)?;
+
+    Ok(Some(resolution.roots))
+}
+
+impl PackageCommand {
+    pub fn run(self, _global: GlobalOptions) -> CliResult<()> {
+        match self.command {
+            PackageSubcommand::Init { name } => {
+                if Path::new(MANIFEST_NAME).exists() {
+                    return Err(PackageError::File {
+                        path: MANIFEST_NAME.into(),
+                        msg: "the manifest already exists".to_owned(),
+                    }
+                    .into());
+                }
+
+                let name = match name {
+                    Some(name) => name,
+                    None => env::current_dir()?
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| "package".to_owned()),
+                };
+
+                Manifest::new(name).save(MANIFEST_NAME)?;
+            }
+            PackageSubcommand::Add(add) => {
+                let mut manifest = Manifest::load(MANIFEST_NAME)?;
+                let dependency = match (add.path, add.git) {
+                    (Some(path), _) => Dependency::Path { path },
+                    (None, Some(git)) => Dependency::Git {
+                        git,
+                        version: add.version,
+                        reference: add.reference,
+                    },
+                    (None, None) => unreachable!("clap requires either --path or --git"),
+                };
+
+                manifest.dependencies.insert(add.name, dependency);
+                // The manifest is only updated once the new dependency has been resolved.
+                resolve(&manifest, Lockfile::load(LOCKFILE_NAME)?)?;
+                manifest.save(MANIFEST_NAME)?;
+            }
+            PackageSubcommand::Update => {
+                resolve(&Manifest::load(MANIFEST_NAME)?, Lockfile::default())?;
+            }
+            PackageSubcommand::Vendor => {
+                let manifest = Manifest::load(MANIFEST_NAME)?;
+                resolve(&manifest, Lockfile::load(LOCKFILE_NAME)?)?.vendor(VENDOR_DIR)?;
+            }
+        }
+
+        Ok(())
+    }
+}
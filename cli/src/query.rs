@@ -47,6 +47,11 @@ pub struct QueryCommand {
     #[arg(short, long, conflicts_with_all(["doc", "contract", "typ", "default", "value"]))]
     pub output: Option<PathBuf>,
 
+    /// Export the metadata of every field under the selected field, as a tree, instead of the
+    /// metadata of the selected field only. The values of the fields aren't evaluated.
+    #[arg(long, conflicts_with_all(["doc", "contract", "typ", "default", "value"]))]
+    pub tree: bool,
+
     #[command(flatten)]
     pub inputs: InputOptions<ExtractFieldOnly>,
 }
@@ -164,6 +169,17 @@ impl QueryCommand {
 
         use MetadataExportFormat::*;
         match self.format {
+            Markdown if self.tree => {
+                eprintln!(
+                    "Output query result as a tree in markdown format is currently not supported."
+                )
+            }
+            format @ (Json | Toml | Yaml) if self.tree => {
+                let _ = &program
+                    .metadata()
+                    .map(|tree| self.export(tree, format))
+                    .report_with_program(program)?;
+            }
             Markdown => {
                 if self.output.is_some() {
                     eprintln!("Output query result in markdown format to a file is currently not supported.")
@@ -7,6 +7,8 @@ use crate::{cli::GlobalOptions, error::CliResult};
 
 #[derive(clap::Parser, Debug)]
 pub struct ReplCommand {
+    /// The file where the history of the inputs is saved. It's updated after each input and
+    /// defaults to `~/.nickel_history`
     #[arg(long)]
     pub history_file: Option<PathBuf>,
 }
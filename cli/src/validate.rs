@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use nickel_lang_core::{eval::cache::lazy::CBNCache, program::Program};
+
+use crate::{
+    cli::GlobalOptions,
+    error::{CliResult, ResultErrorExt},
+};
+
+#[derive(clap::Parser, Debug)]
+pub struct ValidateCommand {
+    /// The data file to validate. Its format is determined by its extension (`.json`, `.yaml`,
+    /// `.yml`, `.toml` or `.ncl`).
+    data: PathBuf,
+
+    /// A Nickel file evaluating to the contract to apply, such as a record contract.
+    #[arg(long, short)]
+    contract: PathBuf,
+
+    /// Adds a directory to the list of paths to search for imports in.
+    #[arg(long, short = 'I')]
+    import_path: Vec<PathBuf>,
+}
+
+impl ValidateCommand {
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        let mut program: Program<CBNCache> =
+            Program::new_validate(&self.data, &self.contract, std::io::stderr());
+
+        program.color_opt = global.color.into();
+        program.report_style = global.report_style();
+
+        program.add_import_paths(self.import_path.iter());
+
+        if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
+            program.add_import_paths(nickel_path.split(':'));
+        }
+
+        program
+            .eval_full_for_export()
+            .map(|_| ())
+            .report_with_program(program)
+    }
+}
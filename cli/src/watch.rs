@@ -0,0 +1,111 @@
+//! The `--watch` mode of the evaluation commands.
+//!
+//! The program is evaluated again each time one of the files it reads changes. There's no file
+//! watcher: the modification times of the files are polled, which is cheap for the handful of
+//! files making a configuration, and behaves the same on every platform.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use nickel_lang_core::{error::Error as CoreError, eval::cache::lazy::CBNCache, program::Program};
+
+use crate::{
+    cli::GlobalOptions,
+    error::{CliResult, Error},
+    input::Prepare,
+};
+
+/// The interval between two checks of the modification times.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Run `op` on the program prepared from `input`, then run it again on a fresh program each time
+/// one of the files read by the program changes, until the process is interrupted. Errors are
+/// reported as they happen, without stopping the watch.
+pub fn watch(
+    input: &impl Prepare,
+    inputs: &[PathBuf],
+    global: &GlobalOptions,
+    mut op: impl FnMut(&mut Program<CBNCache>) -> Result<(), CoreError>,
+) -> CliResult<()> {
+    let mut files = inputs.to_vec();
+
+    loop {
+        // The snapshot is taken before the program is prepared, so that the changes made while
+        // it's evaluated aren't missed.
+        let snapshot = Snapshot::take(&files);
+
+        // The input files are always watched, so that a program which doesn't even parse is run
+        // again once it has been fixed.
+        files = inputs.to_vec();
+
+        match input.prepare(global) {
+            Ok(mut program) => {
+                let result = op(&mut program);
+                files.extend(program.source_files());
+
+                if let Err(error) = result {
+                    report(Error::Program { program, error }, global);
+                }
+            }
+            Err(Error::CustomizeInfoPrinted) => return Err(Error::CustomizeInfoPrinted),
+            Err(error) => report(error, global),
+        }
+
+        files.sort();
+        files.dedup();
+
+        eprintln!("watching {} files for changes...", files.len());
+        while !snapshot.changed(&files) {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+fn report(error: Error, global: &GlobalOptions) {
+    error.report(
+        global.error_format,
+        global.color.into(),
+        global.report_style(),
+    );
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+/// The modification times of the watched files at some point in time.
+struct Snapshot {
+    taken: SystemTime,
+    modified: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl Snapshot {
+    fn take(files: &[PathBuf]) -> Self {
+        Snapshot {
+            taken: SystemTime::now(),
+            modified: files
+                .iter()
+                .map(|path| (path.clone(), modified(path)))
+                .collect(),
+        }
+    }
+
+    /// Return `true` if the modification time of one of the files has changed since the snapshot,
+    /// or if one of them has been created or deleted. The files which weren't known when the
+    /// snapshot was taken, such as the ones imported for the first time, have changed if they
+    /// have been modified since then.
+    fn changed(&self, files: &[PathBuf]) -> bool {
+        files.iter().any(|path| {
+            let current = modified(path);
+
+            match self.modified.get(path) {
+                Some(before) => current != *before,
+                None => current.is_none_or(|current| current >= self.taken),
+            }
+        })
+    }
+}
@@ -73,3 +73,83 @@ fn automatic_color_on_non_tty() {
         );
     }
 }
+
+#[cfg(feature = "format")]
+#[test]
+fn format_check() {
+    let check = |source: &[u8]| {
+        let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+        let mut nickel = Command::new(nickel_bin)
+            .args(["format", "--check"])
+            .stdin(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Nickel should be runnable");
+        let mut stdin = nickel
+            .stdin
+            .take()
+            .expect("couldn't retrieve stdin handle to Nickel");
+        stdin
+            .write_all(source)
+            .expect("writing into Nickel stdin should work");
+        drop(stdin);
+
+        nickel
+            .wait()
+            .expect("Nickel should exit successfully")
+            .success()
+    };
+
+    assert!(check(b"{ foo = 1 }\n"));
+    assert!(!check(b"{foo=1}"));
+}
+
+#[test]
+fn diff_configurations() {
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let old = dir.path().join("old.ncl");
+    let new = dir.path().join("new.ncl");
+    std::fs::write(&old, "{ port = 80, hosts = [\"a\", \"b\"] }").unwrap();
+    std::fs::write(&new, "{ port = 40 + 40 + 1, hosts = [\"a\"], tls = true }").unwrap();
+
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let output = Command::new(nickel_bin)
+        .arg("diff")
+        .args([&old, &new])
+        .output()
+        .expect("Nickel should be runnable");
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).expect("The result of Nickel should be valid utf8"),
+        "- hosts[1]: \"b\"\n~ port: 80 -> 81\n+ tls: true\n"
+    );
+}
+
+#[test]
+fn complete_field_paths() {
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let file = dir.path().join("config.ncl");
+    std::fs::write(
+        &file,
+        r#"{ server = { port = 80, path = "/" }, "my field" = 1, other = std.fail_with "lazy" }"#,
+    )
+    .unwrap();
+
+    let complete = |prefix: &str| {
+        let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+        let output = Command::new(nickel_bin)
+            .arg("complete-field")
+            .arg(&file)
+            .arg(prefix)
+            .output()
+            .expect("Nickel should be runnable");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).expect("The result of Nickel should be valid utf8")
+    };
+
+    assert_eq!(complete(""), "\"my field\"\nother\nserver\n");
+    assert_eq!(complete("se"), "server\n");
+    assert_eq!(complete("server.p"), "server.path\nserver.port\n");
+    assert_eq!(complete("missing.p"), "");
+}
@@ -0,0 +1,76 @@
+//! Benchmarks on a set of representative configuration programs, covering each phase of the
+//! pipeline: parsing, typechecking, evaluation, merging and serialization. This suite is meant to
+//! be the common yardstick for performance work: see `scripts/compare-benches.sh` to compare two
+//! revisions.
+use criterion::{criterion_group, criterion_main, Criterion};
+use nickel_lang_utils::{
+    bench::{criterion_config, EvalMode},
+    ncl_bench_group,
+    test_program::parse,
+};
+
+use std::path::PathBuf;
+
+/// The programs of the suite, relative to the `benches` directory.
+const PROGRAMS: &[&str] = &[
+    "configs/services.ncl",
+    "configs/layers.ncl",
+    "configs/ci.ncl",
+    "mantis/deploy.ncl",
+    "mantis/schemas/nomad/types.ncl",
+];
+
+pub fn parse_configs(c: &mut Criterion) {
+    for program in PROGRAMS {
+        let path = PathBuf::from_iter([env!("CARGO_MANIFEST_DIR"), "benches", program]);
+        let source = std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("Failed to read {path:?}: {err}"));
+
+        c.bench_function(&format!("parse {program}"), |b| {
+            b.iter(|| parse(&source).unwrap())
+        });
+    }
+}
+
+criterion_group!(
+    name = parsing;
+    config = criterion_config();
+    targets = parse_configs
+);
+
+ncl_bench_group! {
+    name = evaluation;
+    config = criterion_config();
+    {
+        name = "typecheck ci",
+        path = "configs/ci",
+        eval_mode = EvalMode::TypeCheck,
+    }, {
+        name = "eval ci",
+        path = "configs/ci",
+        args = (20),
+        eval_mode = EvalMode::DeepSeq,
+    }, {
+        name = "eval services",
+        path = "configs/services",
+        args = (50),
+        eval_mode = EvalMode::DeepSeq,
+    }, {
+        name = "merge layers",
+        path = "configs/layers",
+        args = (200),
+        eval_mode = EvalMode::DeepSeq,
+    }, {
+        name = "serialize services json",
+        path = "configs/services",
+        subtest = "json",
+        args = (50),
+    }, {
+        name = "serialize services yaml",
+        path = "configs/services",
+        subtest = "yaml",
+        args = (50),
+    }
+}
+
+criterion_main!(parsing, evaluation);
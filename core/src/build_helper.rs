@@ -0,0 +1,176 @@
+//! Validate and embed Nickel configurations at compile time.
+//!
+//! A build script calls [export_config] on a Nickel file. The file is parsed, typechecked and
+//! fully evaluated, and the result is exported as JSON to the `OUT_DIR` of the crate being built.
+//! Any error fails the build, with the usual Nickel error report printed by Cargo. The crate then
+//! embeds the exported JSON with [include_config](crate::include_config):
+//!
+//! ```no_run
+//! // build.rs
+//! nickel_lang_core::build_helper::export_config("config/app.ncl");
+//! ```
+//!
+//! ```ignore
+//! // src/main.rs
+//! const APP_CONFIG: &str = nickel_lang_core::include_config!("app");
+//! ```
+//!
+//! The exported JSON is named after the stem of the Nickel file. The build script is re-run
+//! whenever the file or one of its imports changes.
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cache::ImportResolver,
+    error::{report::ColorOpt, Error},
+    eval::cache::CacheImpl,
+    program::Program,
+    serialize::{self, ExportFormat},
+    typecheck::TypecheckMode,
+};
+
+/// The subdirectory of `OUT_DIR` where configurations are exported.
+pub const OUT_SUBDIR: &str = "nickel";
+
+/// Evaluate the Nickel file at `path` and export it as JSON to `$OUT_DIR/nickel/<stem>.json`,
+/// where `<stem>` is the file name of `path` without its extension. Emit the corresponding
+/// `cargo:rerun-if-changed` directives for the file and its imports.
+///
+/// This function is meant to be called from a build script.
+///
+/// # Panics
+///
+/// Panics with the error report, thus failing the build, if the configuration can't be parsed,
+/// typechecked, evaluated or exported, or if `OUT_DIR` isn't set.
+pub fn export_config(path: impl AsRef<Path>) {
+    let out_dir = std::env::var_os("OUT_DIR")
+        .expect("nickel: `OUT_DIR` isn't set. `export_config` must be called from a build script");
+
+    match try_export_config(path.as_ref(), Path::new(&out_dir)) {
+        Ok(exported) => {
+            for dep in exported.dependencies {
+                println!("cargo:rerun-if-changed={}", dep.display());
+            }
+        }
+        Err(report) => panic!("\n{report}"),
+    }
+}
+
+/// The result of a successful export by [try_export_config].
+#[derive(Clone, Debug)]
+pub struct ExportedConfig {
+    /// The path of the exported JSON file.
+    pub output: PathBuf,
+    /// The paths of the source files the configuration depends on, including the main file.
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// Evaluate the Nickel file at `path` and export it as JSON to `<out_dir>/nickel/<stem>.json`.
+/// Contrary to [export_config], this function doesn't print anything and returns the error
+/// report on failure.
+pub fn try_export_config(path: &Path, out_dir: &Path) -> Result<ExportedConfig, String> {
+    let stem = path
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| format!("nickel: invalid configuration path `{}`", path.display()))?;
+
+    let mut program: Program<CacheImpl> = Program::new_from_file(path, io::sink())
+        .map_err(|err| format!("nickel: couldn't read `{}`: {err}", path.display()))?;
+    program.color_opt = ColorOpt::NEVER;
+
+    let json = eval_json(&mut program).map_err(|err| program.report_as_str(err))?;
+
+    let output_dir = out_dir.join(OUT_SUBDIR);
+    let output = output_dir.join(format!("{stem}.json"));
+    fs::create_dir_all(&output_dir)
+        .and_then(|()| fs::write(&output, json))
+        .map_err(|err| format!("nickel: couldn't write `{}`: {err}", output.display()))?;
+
+    let cache = &*program.cache_mut();
+    let mut dependencies: Vec<PathBuf> = cache
+        .terms()
+        .keys()
+        .filter_map(|file_id| cache.get_path(*file_id))
+        .map(PathBuf::from)
+        .collect();
+    dependencies.sort();
+    dependencies.dedup();
+
+    Ok(ExportedConfig {
+        output,
+        dependencies,
+    })
+}
+
+fn eval_json(program: &mut Program<CacheImpl>) -> Result<String, Error> {
+    program.typecheck(TypecheckMode::Walk)?;
+    let rt = program.eval_full_for_export()?;
+    serialize::validate(ExportFormat::Json, &rt)?;
+    Ok(serialize::to_string(ExportFormat::Json, &rt)?)
+}
+
+/// Embed the JSON exported by [export_config](crate::build_helper::export_config) from a build
+/// script, as a `&'static str`. The argument is the stem of the Nickel file, e.g. `"app"` for
+/// `config/app.ncl`.
+#[macro_export]
+macro_rules! include_config {
+    ($name:literal) => {
+        include_str!(concat!(env!("OUT_DIR"), "/nickel/", $name, ".json"))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("nickel-build-helper-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn export_with_imports() {
+        let dir = temp_dir("imports");
+        fs::write(dir.join("port.ncl"), "8000 + 80").unwrap();
+        fs::write(
+            dir.join("app.ncl"),
+            "{ server = { port | Number = import \"port.ncl\" } }",
+        )
+        .unwrap();
+
+        let exported = try_export_config(&dir.join("app.ncl"), &dir).unwrap();
+        assert_eq!(exported.output, dir.join(OUT_SUBDIR).join("app.json"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&exported.output).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!({ "server": { "port": 8080 } }));
+
+        assert!(exported
+            .dependencies
+            .iter()
+            .any(|dep| dep.ends_with("port.ncl")));
+        assert!(exported
+            .dependencies
+            .iter()
+            .any(|dep| dep.ends_with("app.ncl")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn export_error() {
+        let dir = temp_dir("error");
+        fs::write(dir.join("bad.ncl"), "{ port : String = 1 }").unwrap();
+
+        let report = try_export_config(&dir.join("bad.ncl"), &dir).unwrap_err();
+        assert!(report.contains("error"));
+        assert!(!dir.join(OUT_SUBDIR).join("bad.json").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -182,6 +182,11 @@ impl<'ast> FromMainline<'ast, term::record::FieldMetadata> for record::FieldMeta
             opt: metadata.opt,
             not_exported: metadata.not_exported,
             priority: metadata.priority.clone(),
+            deprecated: metadata
+                .deprecated
+                .as_ref()
+                .map(|msg| rc::Rc::from(msg.as_str())),
+            secret: metadata.secret,
         }
     }
 }
@@ -525,7 +530,8 @@ impl From<&term::UnaryOp> for PrimOp {
             | term::UnaryOp::RecDefault
             | term::UnaryOp::RecForce
             | term::UnaryOp::PatternBranch
-            | term::UnaryOp::ContractPostprocessResult) => {
+            | term::UnaryOp::ContractPostprocessResult
+            | term::UnaryOp::NativeCall(_)) => {
                 panic!("didn't expect {op} at the parsing stage")
             }
         }
@@ -805,6 +811,8 @@ impl<'ast> FromAst<record::FieldMetadata<'ast>> for term::record::FieldMetadata
             opt: metadata.opt,
             not_exported: metadata.not_exported,
             priority: metadata.priority.clone(),
+            deprecated: metadata.deprecated.as_ref().map(|msg| String::from(&**msg)),
+            secret: metadata.secret,
         }
     }
 }
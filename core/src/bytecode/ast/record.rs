@@ -34,6 +34,10 @@ pub struct FieldMetadata<'ast> {
     pub not_exported: bool,
     /// The merge priority.
     pub priority: MergePriority,
+    /// The deprecation message, if the field is deprecated.
+    pub deprecated: Option<Rc<str>>,
+    /// If the value of the field is sensitive.
+    pub secret: bool,
 }
 
 impl<'ast> FieldMetadata<'ast> {
@@ -47,6 +51,8 @@ impl<'ast> FieldMetadata<'ast> {
             && !self.opt
             && !self.not_exported
             && matches!(self.priority, MergePriority::Neutral)
+            && self.deprecated.is_none()
+            && !self.secret
     }
 }
 
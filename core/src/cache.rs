@@ -5,28 +5,40 @@ use crate::error::{Error, ImportError, ParseError, ParseErrors, TypecheckError};
 use crate::eval::cache::Cache as EvalCache;
 use crate::eval::Closure;
 use crate::files::{FileId, Files};
+#[cfg(feature = "git-imports")]
+use crate::git_import::{self, GitImport, GitImportConfig};
+#[cfg(feature = "http-imports")]
+use crate::http_import::{self, HttpImportConfig, RemoteImport};
+use crate::identifier::{Ident, LocIdent};
 use crate::metrics::measure_runtime;
+use crate::native::{self, NativeFunction};
 #[cfg(feature = "nix-experimental")]
 use crate::nix_ffi;
+#[cfg(feature = "packages")]
+use crate::package;
 use crate::parser::{lexer::Lexer, ErrorTolerantParser};
-use crate::position::TermPos;
+#[cfg(any(feature = "http-imports", feature = "git-imports"))]
+use crate::policy::Capability;
+use crate::policy::CapabilityPolicy;
+use crate::position::{RawSpan, TermPos};
 use crate::program::FieldPath;
 use crate::stdlib::{self as nickel_stdlib, StdlibModule};
 use crate::term::record::{Field, RecordData};
 use crate::term::{RichTerm, SharedTerm, Term};
 use crate::transform::import_resolution;
-use crate::typ::UnboundTypeVariableError;
-use crate::typecheck::{self, type_check, TypecheckMode, Wildcards};
+use crate::typ::{RecordRowF, RecordRows, RecordRowsF, Type, TypeF, UnboundTypeVariableError};
+use crate::typecheck::{self, mk_uniftype, type_check, TypecheckMode, UnifType, Wildcards};
 use crate::{eval, parser, transform};
 
 use io::Read;
-use serde::Deserialize;
 use std::collections::hash_map;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
+#[cfg(feature = "fs-imports")]
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::result::Result;
 use std::time::SystemTime;
 use void::Void;
@@ -47,6 +59,13 @@ pub enum InputFormat {
 impl InputFormat {
     /// Returns an [InputFormat] based on the file extension of a path.
     pub fn from_path(path: &Path) -> Option<InputFormat> {
+        // The fragment of an URL, such as the hash of a remote import, isn't part of the
+        // extension.
+        let path = path
+            .to_str()
+            .and_then(|path| path.split_once('#'))
+            .map_or(path, |(path, _)| Path::new(path));
+
         match path.extension().and_then(OsStr::to_str) {
             Some("ncl") => Some(InputFormat::Nickel),
             Some("json") => Some(InputFormat::Json),
@@ -125,6 +144,25 @@ pub struct Cache {
     /// Whether processing should try to continue even in case of errors. Needed by the NLS.
     error_tolerance: ErrorTolerance,
     import_paths: Vec<PathBuf>,
+    /// The native functions registered by the embedder, exposed in the initial environment.
+    natives: BTreeMap<Ident, NativeFunction>,
+    /// The values bound by the embedder in the initial environment.
+    bindings: BTreeMap<Ident, RichTerm>,
+    /// If set, imports whose resolution depends on the machine are rejected.
+    reproducible: bool,
+    /// The capability policy, deciding which files can be imported.
+    policy: CapabilityPolicy,
+    /// The loader serving imports instead of the filesystem, if any.
+    loader: Option<Loader>,
+    /// The configuration of remote imports, or `None` if they are disabled.
+    #[cfg(feature = "http-imports")]
+    http_imports: Option<HttpImportConfig>,
+    /// The configuration of git imports, or `None` if they are disabled.
+    #[cfg(feature = "git-imports")]
+    git_imports: Option<GitImportConfig>,
+    /// The root directories of the packages which can be imported, indexed by name.
+    #[cfg(feature = "packages")]
+    packages: BTreeMap<String, PathBuf>,
 
     #[cfg(debug_assertions)]
     /// Skip loading the stdlib, used for debugging purpose
@@ -374,12 +412,55 @@ impl Cache {
             rev_imports: HashMap::new(),
             error_tolerance,
             import_paths: Vec::new(),
+            natives: BTreeMap::new(),
+            bindings: BTreeMap::new(),
+            reproducible: false,
+            policy: CapabilityPolicy::default(),
+            loader: None,
+            #[cfg(feature = "http-imports")]
+            http_imports: None,
+            #[cfg(feature = "git-imports")]
+            git_imports: None,
+            #[cfg(feature = "packages")]
+            packages: BTreeMap::new(),
 
             #[cfg(debug_assertions)]
             skip_stdlib: false,
         }
     }
 
+    /// Serve imports from `loader` instead of the filesystem. See [ImportLoader].
+    pub fn set_import_loader(&mut self, loader: impl ImportLoader + 'static) {
+        self.loader = Some(Loader(Rc::new(loader)));
+    }
+
+    /// Enable remote imports with the given configuration, or disable them if `config` is `None`.
+    /// See [crate::http_import].
+    #[cfg(feature = "http-imports")]
+    pub fn set_http_imports(&mut self, config: Option<HttpImportConfig>) {
+        self.http_imports = config;
+    }
+
+    /// Enable git imports with the given configuration, or disable them if `config` is `None`.
+    /// See [crate::git_import].
+    #[cfg(feature = "git-imports")]
+    pub fn set_git_imports(&mut self, config: Option<GitImportConfig>) {
+        self.git_imports = config;
+    }
+
+    /// The configuration of git imports, including the commits pinned so far.
+    #[cfg(feature = "git-imports")]
+    pub fn git_imports(&self) -> Option<&GitImportConfig> {
+        self.git_imports.as_ref()
+    }
+
+    /// Set the root directories of the packages which can be imported, indexed by name. See
+    /// [crate::package].
+    #[cfg(feature = "packages")]
+    pub fn set_packages(&mut self, packages: BTreeMap<String, PathBuf>) {
+        self.packages = packages;
+    }
+
     pub fn add_import_paths<P>(&mut self, paths: impl Iterator<Item = P>)
     where
         PathBuf: From<P>,
@@ -387,6 +468,325 @@ impl Cache {
         self.import_paths.extend(paths.map(PathBuf::from));
     }
 
+    /// Reject the imports whose resolution depends on the machine, that is imports of absolute
+    /// paths. See [crate::program::Program::set_reproducible].
+    pub fn set_reproducible(&mut self, reproducible: bool) {
+        self.reproducible = reproducible;
+    }
+
+    /// Find the source imported as `path` by `parent`, and add it to the cache if it isn't there
+    /// yet. Return its identity together with its id.
+    fn locate(
+        &mut self,
+        path: &OsStr,
+        format: InputFormat,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(CacheOp<FileId>, PathBuf), ImportError> {
+        #[cfg(feature = "http-imports")]
+        {
+            if http_import::is_remote(path) {
+                return self.locate_remote(path, format, parent, pos);
+            }
+
+            if parent
+                .and_then(|p| self.get_path(p))
+                .is_some_and(http_import::is_remote)
+            {
+                return Err(ImportError::IOError(
+                    path.to_string_lossy().into_owned(),
+                    "a remote file can only import other remote files".to_owned(),
+                    *pos,
+                ));
+            }
+        }
+
+        #[cfg(feature = "git-imports")]
+        if git_import::is_git(path) {
+            return self.locate_git(path, format, parent, pos);
+        }
+
+        #[cfg(feature = "packages")]
+        if package::is_package(path) {
+            return self.locate_package(path, format, parent, pos);
+        }
+
+        match self.loader.clone() {
+            Some(Loader(loader)) => self.locate_with_loader(&*loader, path, format, parent, pos),
+            None => self.locate_in_filesystem(path, format, parent, pos),
+        }
+    }
+
+    /// Find the file imported as `path` by `parent` in the filesystem, looking in the directory of
+    /// the parent first and then in the import paths. Return the normalized path of the file
+    /// together with its id.
+    fn locate_in_filesystem(
+        &mut self,
+        path: &OsStr,
+        format: InputFormat,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(CacheOp<FileId>, PathBuf), ImportError> {
+        // `parent` is the file that did the import. We first look in its containing directory.
+        let parent_path = parent.and_then(|p| self.get_path(p)).map(PathBuf::from);
+
+        // The capability policy applies to the imports written in Nickel sources. The imports
+        // generated for the inputs of a program, which don't have a path, are chosen by the
+        // embedder and aren't checked.
+        let checked = parent_path.is_some();
+
+        let mut parent_path = parent_path.unwrap_or_default();
+        parent_path.pop();
+
+        let possible_parents: Vec<PathBuf> = std::iter::once(parent_path)
+            .chain(self.import_paths.iter().cloned())
+            .collect();
+        // The first denial, reported if the import can't be found in an allowed directory.
+        let mut first_denial = None;
+
+        // Try to import from all possibilities, taking the first one that succeeds.
+        let found = possible_parents.iter().find_map(|parent| {
+            let mut path_buf = parent.clone();
+            path_buf.push(path);
+
+            if checked {
+                if let Err(reason) = self.policy.check_import(&path_buf) {
+                    first_denial.get_or_insert(reason);
+                    return None;
+                }
+            }
+
+            let id_op = if is_dir(&path_buf) {
+                self.get_or_add_directory(&path_buf)
+            } else {
+                self.get_or_add_file(&path_buf, format)
+            };
+
+            id_op.ok().map(|x| (x, path_buf))
+        });
+
+        match (found, first_denial) {
+            (Some(found), _) => Ok(found),
+            (None, Some(reason)) => Err(ImportError::CapabilityDenied(
+                path.to_string_lossy().into_owned(),
+                reason,
+                *pos,
+            )),
+            (None, None) => {
+                let parents = possible_parents
+                    .iter()
+                    .map(|p| p.to_string_lossy())
+                    .collect::<Vec<_>>();
+                Err(ImportError::IOError(
+                    path.to_string_lossy().into_owned(),
+                    format!("could not find import (looked in [{}])", parents.join(", ")),
+                    *pos,
+                ))
+            }
+        }
+    }
+
+    /// Find the source imported as `path` by `parent` with an [ImportLoader]. Return the identity
+    /// of the source together with its id.
+    fn locate_with_loader(
+        &mut self,
+        loader: &dyn ImportLoader,
+        path: &OsStr,
+        format: InputFormat,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(CacheOp<FileId>, PathBuf), ImportError> {
+        let parent_id = parent.and_then(|p| self.get_path(p)).map(PathBuf::from);
+
+        // The inputs of a program are added to the cache by the embedder beforehand, and aren't
+        // served by the loader.
+        if parent_id.is_none() {
+            let source_path = SourcePath::Path(PathBuf::from(path), format);
+
+            if let Some(entry) = self.file_ids.get(&source_path) {
+                return Ok((CacheOp::Cached(entry.id), PathBuf::from(path)));
+            }
+        }
+
+        let id = loader
+            .resolve(Path::new(path), parent_id.as_deref())
+            .ok_or_else(|| {
+                ImportError::IOError(
+                    path.to_string_lossy().into_owned(),
+                    "could not find import".to_owned(),
+                    *pos,
+                )
+            })?;
+
+        if parent_id.is_some() {
+            self.policy.check_import(&id).map_err(|reason| {
+                ImportError::CapabilityDenied(path.to_string_lossy().into_owned(), reason, *pos)
+            })?;
+        }
+
+        let source_path = SourcePath::Path(id.clone(), format);
+
+        if let Some(entry) = self.file_ids.get(&source_path) {
+            return Ok((CacheOp::Cached(entry.id), id));
+        }
+
+        let contents = loader.load(&id).map_err(|err| {
+            ImportError::IOError(path.to_string_lossy().into_owned(), err.to_string(), *pos)
+        })?;
+
+        Ok((CacheOp::Done(self.add_string(source_path, contents)), id))
+    }
+
+    /// Fetch the remote file at the URL `path`, unless it's already in the cache. See
+    /// [crate::http_import].
+    #[cfg(feature = "http-imports")]
+    fn locate_remote(
+        &mut self,
+        path: &OsStr,
+        format: InputFormat,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(CacheOp<FileId>, PathBuf), ImportError> {
+        let url = path.to_string_lossy();
+        let error = |msg: String| ImportError::IOError(url.clone().into_owned(), msg, *pos);
+
+        let Some(config) = &self.http_imports else {
+            return Err(error("remote imports are disabled".to_owned()));
+        };
+
+        if parent.is_some() {
+            self.policy
+                .check_capability(&Capability::Network)
+                .map_err(|reason| {
+                    ImportError::CapabilityDenied(url.clone().into_owned(), reason, *pos)
+                })?;
+        }
+
+        let import = RemoteImport::parse(&url).map_err(error)?;
+        let source_path = SourcePath::Path(PathBuf::from(path), format);
+
+        if let Some(entry) = self.file_ids.get(&source_path) {
+            return Ok((CacheOp::Cached(entry.id), PathBuf::from(path)));
+        }
+
+        let contents = config.fetch(&import).map_err(error)?;
+
+        Ok((
+            CacheOp::Done(self.add_string(source_path, contents)),
+            PathBuf::from(path),
+        ))
+    }
+
+    /// Check out the repository of the git import `path` at the pinned commit if needed, and add
+    /// the imported file to the cache. See [crate::git_import].
+    #[cfg(feature = "git-imports")]
+    fn locate_git(
+        &mut self,
+        path: &OsStr,
+        format: InputFormat,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(CacheOp<FileId>, PathBuf), ImportError> {
+        let url = path.to_string_lossy();
+        let error = |msg: String| ImportError::IOError(url.clone().into_owned(), msg, *pos);
+
+        if parent.is_some() {
+            self.policy
+                .check_capability(&Capability::Network)
+                .map_err(|reason| {
+                    ImportError::CapabilityDenied(url.clone().into_owned(), reason, *pos)
+                })?;
+        }
+
+        let import = GitImport::parse(&url).map_err(error)?;
+        let Some(config) = &mut self.git_imports else {
+            return Err(error("git imports are disabled".to_owned()));
+        };
+        let local_path = config.fetch(&import).map_err(error)?;
+
+        // The file is then read from the checkout: the policy applies as for the other files of
+        // the filesystem.
+        if parent.is_some() {
+            self.policy.check_import(&local_path).map_err(|reason| {
+                ImportError::CapabilityDenied(url.clone().into_owned(), reason, *pos)
+            })?;
+        }
+
+        self.get_or_add_file(&local_path, format)
+            .map(|id_op| (id_op, local_path))
+            .map_err(|err| error(err.to_string()))
+    }
+
+    /// Find the file imported as `path` in one of the packages, and add it to the cache. The
+    /// capability policy applies as for the other files of the filesystem. See [crate::package].
+    #[cfg(feature = "packages")]
+    fn locate_package(
+        &mut self,
+        path: &OsStr,
+        format: InputFormat,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(CacheOp<FileId>, PathBuf), ImportError> {
+        let import = path.to_string_lossy();
+        let error = |msg: String| ImportError::IOError(import.clone().into_owned(), msg, *pos);
+
+        let (name, file) = package::parse_import(&import);
+        let Some(root) = self.packages.get(name) else {
+            return Err(error(format!("unknown package `{name}`")));
+        };
+        let local_path = root.join(file);
+
+        if parent.is_some() {
+            self.policy.check_import(&local_path).map_err(|reason| {
+                ImportError::CapabilityDenied(import.clone().into_owned(), reason, *pos)
+            })?;
+        }
+
+        self.get_or_add_file(&local_path, format)
+            .map(|id_op| (id_op, local_path))
+            .map_err(|err| error(err.to_string()))
+    }
+
+    /// Set the capability policy checked when resolving imports. See
+    /// [crate::program::Program::set_policy].
+    pub fn set_policy(&mut self, policy: CapabilityPolicy) {
+        self.policy = policy;
+    }
+
+    /// Register a native function, exposed as a field of the `native` record of the initial
+    /// environment. A function registered under the same name as a previous one replaces it.
+    ///
+    /// Native functions must be registered before the standard library is prepared, that is before
+    /// the first evaluation or typechecking.
+    pub fn register_native(&mut self, fun: NativeFunction) {
+        self.natives.insert(fun.name(), fun);
+    }
+
+    /// Bind `id` to `value` in the initial environment. A binding with the same name as a
+    /// previous one replaces it.
+    ///
+    /// As for native functions, bindings must be added before the standard library is prepared.
+    pub fn add_binding(&mut self, id: Ident, value: RichTerm) {
+        self.bindings.insert(id, value);
+    }
+
+    /// The `native` record exposing the registered native functions, or `None` if no native
+    /// function has been registered.
+    fn natives_record(&self) -> Option<RichTerm> {
+        if self.natives.is_empty() {
+            return None;
+        }
+
+        let fields = self
+            .natives
+            .values()
+            .map(|fun| (LocIdent::from(fun.name()), fun.to_term()));
+
+        Some(RichTerm::from(Term::Record(RecordData::with_field_values(
+            fields,
+        ))))
+    }
+
     /// Same as [Self::add_file], but assume that the path is already normalized, and take the
     /// timestamp as a parameter.
     fn add_file_(
@@ -395,7 +795,7 @@ impl Cache {
         format: InputFormat,
         timestamp: SystemTime,
     ) -> io::Result<FileId> {
-        let contents = std::fs::read_to_string(&path)?;
+        let contents = read_to_string(&path)?;
         let file_id = self.files.add(&path, contents);
         self.file_paths
             .insert(file_id, SourcePath::Path(path.clone(), format));
@@ -442,6 +842,41 @@ impl Cache {
         }
     }
 
+    /// Try to retrieve the id of the record imported for a directory from the cache. If it was not
+    /// in cache, or if the directory has changed since, generate it and add it as a new entry. See
+    /// [directory_record].
+    fn get_or_add_directory(&mut self, path: &Path) -> io::Result<CacheOp<FileId>> {
+        let normalized = normalize_path(path)?;
+        let timestamp = match self.id_or_new_timestamp_of(&normalized, InputFormat::Nickel)? {
+            SourceState::UpToDate(id) => return Ok(CacheOp::Cached(id)),
+            SourceState::Stale(timestamp) => timestamp,
+        };
+
+        let file_id = self.files.add(&normalized, String::new());
+        let term = directory_record(&normalized, file_id)?;
+        let source_path = SourcePath::Path(normalized.clone(), InputFormat::Nickel);
+
+        self.file_paths.insert(file_id, source_path.clone());
+        self.file_ids.insert(
+            source_path,
+            NameIdEntry {
+                id: file_id,
+                source: SourceKind::Filesystem(timestamp),
+            },
+        );
+        // The record is already a term, which doesn't need to be parsed.
+        self.terms.insert(
+            file_id,
+            TermEntry {
+                term,
+                state: EntryState::Parsed,
+                parse_errs: ParseErrors::default(),
+            },
+        );
+
+        Ok(CacheOp::Done(file_id))
+    }
+
     /// Load a source and add it to the name-id table.
     ///
     /// Do not check if a source with the same name already exists: if it is the
@@ -512,7 +947,7 @@ impl Cache {
         &mut self,
         file_id: FileId,
         format: InputFormat,
-    ) -> Result<CacheOp<ParseErrors>, ParseError> {
+    ) -> Result<CacheOp<ParseErrors>, ParseErrors> {
         if let Some(TermEntry { parse_errs, .. }) = self.terms.get(&file_id) {
             Ok(CacheOp::Cached(parse_errs.clone()))
         } else {
@@ -532,6 +967,10 @@ impl Cache {
     /// Parse a source and populate the corresponding entry in the cache, or do
     /// nothing if the entry has already been parsed. Support multiple formats.
     /// This function is error tolerant if `self.error_tolerant` is `true`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(file = ?self.files.name(file_id), ?format))
+    )]
     pub fn parse(
         &mut self,
         file_id: FileId,
@@ -540,7 +979,7 @@ impl Cache {
         let result = self.parse_lax(file_id, format);
 
         match self.error_tolerance {
-            ErrorTolerance::Tolerant => result.map_err(|err| err.into()),
+            ErrorTolerance::Tolerant => result,
             ErrorTolerance::Strict => match result? {
                 CacheOp::Done(e) | CacheOp::Cached(e) if !e.no_errors() => Err(e),
                 CacheOp::Done(_) => Ok(CacheOp::Done(ParseErrors::none())),
@@ -550,7 +989,7 @@ impl Cache {
     }
 
     /// Parse a source without querying nor populating the cache.
-    pub fn parse_nocache(&self, file_id: FileId) -> Result<(RichTerm, ParseErrors), ParseError> {
+    pub fn parse_nocache(&self, file_id: FileId) -> Result<(RichTerm, ParseErrors), ParseErrors> {
         self.parse_nocache_multi(file_id, InputFormat::default())
     }
 
@@ -559,7 +998,7 @@ impl Cache {
         &self,
         file_id: FileId,
         format: InputFormat,
-    ) -> Result<(RichTerm, ParseErrors), ParseError> {
+    ) -> Result<(RichTerm, ParseErrors), ParseErrors> {
         let attach_pos = |t: RichTerm| -> RichTerm {
             let pos: TermPos = self.files.source_span(file_id).into();
             t.with_pos(pos)
@@ -571,14 +1010,23 @@ impl Cache {
             InputFormat::Nickel => {
                 let (t, parse_errs) = measure_runtime!(
                     "runtime:parse:nickel",
-                    parser::grammar::TermParser::new().parse_tolerant(file_id, Lexer::new(buf))?
+                    parser::grammar::TermParser::new().parse_recover(file_id, Lexer::new(buf))?
                 );
 
                 Ok((t, parse_errs))
             }
-            InputFormat::Json => serde_json::from_str(self.files.source(file_id))
-                .map(|t| (attach_pos(t), ParseErrors::default()))
-                .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files)),
+            InputFormat::Json => {
+                crate::serialize::json_deser::from_str(self.files.source(file_id), file_id)
+                    .map(|t| (attach_pos(t), ParseErrors::default()))
+                    .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files).into())
+            }
+            #[cfg(feature = "yaml-positions")]
+            InputFormat::Yaml => {
+                crate::serialize::yaml_deser::from_str(self.files.source(file_id), file_id)
+                    .map(|t| (attach_pos(t), ParseErrors::default()))
+                    .map_err(|err| ParseError::from_yaml(err, file_id).into())
+            }
+            #[cfg(not(feature = "yaml-positions"))]
             InputFormat::Yaml => {
                 // YAML files can contain multiple documents. If there is only
                 // one we transparently deserialize it. If there are multiple,
@@ -586,18 +1034,13 @@ impl Cache {
                 let de = serde_yaml::Deserializer::from_str(self.files.source(file_id));
                 let mut terms = de
                     .map(|de| {
-                        RichTerm::deserialize(de)
+                        serde::Deserialize::deserialize(de)
                             .map(attach_pos)
                             .map_err(|err| (ParseError::from_serde_yaml(err, file_id)))
                     })
                     .collect::<Result<Vec<_>, _>>()?;
 
-                if terms.is_empty() {
-                    unreachable!(
-                        "serde always produces at least one document, \
-                        the empty string turns into `null`"
-                    )
-                } else if terms.len() == 1 {
+                if terms.len() == 1 {
                     Ok((
                         terms.pop().expect("we just checked the length"),
                         ParseErrors::default(),
@@ -614,7 +1057,7 @@ impl Cache {
             InputFormat::Toml => {
                 crate::serialize::toml_deser::from_str(self.files.source(file_id), file_id)
                     .map(|t| (attach_pos(t), ParseErrors::default()))
-                    .map_err(|err| (ParseError::from_toml(err, file_id)))
+                    .map_err(|err| ParseError::from_toml(err, file_id).into())
             }
             #[cfg(feature = "nix-experimental")]
             InputFormat::Nix => {
@@ -622,7 +1065,7 @@ impl Cache {
                     .map_err(|e| ParseError::from_nix(e.what(), file_id))?;
                 serde_json::from_str(&json)
                     .map(|t| (attach_pos(t), ParseErrors::default()))
-                    .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files))
+                    .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files).into())
             }
             InputFormat::Text => Ok((
                 attach_pos(Term::Str(self.files.source(file_id).into()).into()),
@@ -634,6 +1077,10 @@ impl Cache {
     /// Typecheck an entry of the cache and update its state accordingly, or do nothing if the
     /// entry has already been typechecked. Require that the corresponding source has been parsed.
     /// If the source contains imports, recursively typecheck on the imports too.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(file = ?self.files.name(file_id)))
+    )]
     pub fn typecheck(
         &mut self,
         file_id: FileId,
@@ -673,6 +1120,10 @@ impl Cache {
     /// or do nothing if the entry has already been transformed. Require that the corresponding
     /// source has been parsed.
     /// If the source contains imports, recursively perform transformations on the imports too.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(file = ?self.files.name(file_id)))
+    )]
     pub fn transform(
         &mut self,
         file_id: FileId,
@@ -909,6 +1360,10 @@ impl Cache {
     /// It only accumulates errors if the cache is in error tolerant mode, otherwise it returns an
     /// `Err(..)` containing  a `CacheError`.
     #[allow(clippy::type_complexity)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(file = ?self.files.name(file_id)))
+    )]
     pub fn resolve_imports(
         &mut self,
         file_id: FileId,
@@ -1074,6 +1529,14 @@ impl Cache {
         self.files.name(file_id)
     }
 
+    /// Retrieve the format of a source given an id. Return `None` for sources which don't come
+    /// from a file, such as the standard library or the REPL inputs.
+    pub fn input_format(&self, file_id: FileId) -> Option<InputFormat> {
+        self.file_paths
+            .get(&file_id)
+            .and_then(InputFormat::from_source_path)
+    }
+
     /// Retrieve the id of a source given a name.
     ///
     /// Note that files added via [Self::add_file] are indexed by their full normalized path (cf
@@ -1170,6 +1633,46 @@ impl Cache {
         ret
     }
 
+    /// Invalidate the files which have changed on the filesystem since they were loaded, among
+    /// the given files and the files they transitively import, together with the files depending
+    /// on them (see [Self::invalidate_cache]). The unchanged files keep their cached terms, and
+    /// changed imports are loaded again the next time they are resolved.
+    ///
+    /// Returns the ids of the files which have changed.
+    pub fn invalidate_stale(&mut self, roots: impl IntoIterator<Item = FileId>) -> Vec<FileId> {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<FileId> = roots.into_iter().collect();
+        let mut stale = Vec::new();
+
+        while let Some(file_id) = stack.pop() {
+            if visited.insert(file_id) {
+                stack.extend(self.get_imports(file_id));
+
+                if self.is_stale(file_id) {
+                    stale.push(file_id);
+                }
+            }
+        }
+
+        for file_id in &stale {
+            self.invalidate_cache(*file_id);
+        }
+
+        stale
+    }
+
+    /// Whether a file loaded from the filesystem has been modified or removed since. Sources which
+    /// don't come from the filesystem are never stale.
+    fn is_stale(&self, file_id: FileId) -> bool {
+        match self.file_paths.get(&file_id) {
+            Some(SourcePath::Path(path, format)) => !matches!(
+                self.id_or_new_timestamp_of(path, *format),
+                Ok(SourceState::UpToDate(id)) if id == file_id
+            ),
+            _ => false,
+        }
+    }
+
     /// Retrieve the state of an entry. Return `None` if the entry is not in the term cache,
     /// meaning that the content of the source has been loaded but has not been parsed yet.
     pub fn entry_state(&self, file_id: FileId) -> Option<EntryState> {
@@ -1240,6 +1743,7 @@ impl Cache {
     }
 
     /// Load and parse the standard library in the cache.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub fn load_stdlib(&mut self) -> Result<CacheOp<()>, Error> {
         let mut ret = CacheOp::Cached(());
 
@@ -1340,7 +1844,33 @@ impl Cache {
                 )
             })
             .collect();
-        Ok(typecheck::mk_initial_ctxt(&stdlib_terms_vec).unwrap())
+        let mut ctxt = typecheck::mk_initial_ctxt(&stdlib_terms_vec).unwrap();
+
+        if !self.natives.is_empty() {
+            let rows =
+                self.natives
+                    .values()
+                    .rev()
+                    .fold(RecordRows(RecordRowsF::Empty), |tail, fun| {
+                        RecordRows(RecordRowsF::Extend {
+                            row: RecordRowF {
+                                id: fun.name().into(),
+                                typ: Box::new(fun.typ()),
+                            },
+                            tail: Box::new(tail),
+                        })
+                    });
+            let native_type = UnifType::from_type(Type::from(TypeF::Record(rows)), &ctxt.term_env);
+
+            ctxt.type_env
+                .insert(Ident::new(native::NATIVE_RECORD), native_type);
+        }
+
+        for id in self.bindings.keys() {
+            ctxt.type_env.insert(*id, mk_uniftype::dynamic());
+        }
+
+        Ok(ctxt)
     }
 
     /// Generate the initial evaluation environment from the list of `file_ids` corresponding to the
@@ -1381,6 +1911,26 @@ impl Cache {
             }
         });
 
+        if let Some(natives) = self.natives_record() {
+            eval::env_add(
+                eval_cache,
+                &mut eval_env,
+                native::NATIVE_RECORD.into(),
+                natives,
+                eval::Environment::new(),
+            );
+        }
+
+        for (id, value) in &self.bindings {
+            eval::env_add(
+                eval_cache,
+                &mut eval_env,
+                (*id).into(),
+                value.clone(),
+                eval::Environment::new(),
+            );
+        }
+
         Ok(eval_env)
     }
 }
@@ -1422,7 +1972,81 @@ pub trait ImportResolver {
     fn get_path(&self, file_id: FileId) -> Option<&OsStr>;
 }
 
+/// A source of imports, used by the cache instead of the filesystem. Embedders can implement it to
+/// serve imports from memory, a database, an archive, etc. See [Cache::set_import_loader] and
+/// [crate::program::Program::set_import_loader].
+///
+/// Sources are identified by a path, which doesn't need to exist on the filesystem. The identity
+/// is the key of the source in the cache, so that a source imported several times is only loaded
+/// and parsed once, and it's the name of the source shown in error messages. Nested imports are
+/// resolved relatively to the identity of the importing source.
+///
+/// The capability policy still applies: with a loader, imports require the
+/// [Capability::Filesystem](crate::policy::Capability::Filesystem) capability, and the import
+/// roots restrict the identities which can be imported. The import paths are ignored.
+pub trait ImportLoader {
+    /// Return the identity of the source imported as `path` by the source identified by `parent`,
+    /// or `None` if there is no such source. `parent` is `None` for the inputs of a program.
+    fn resolve(&self, path: &Path, parent: Option<&Path>) -> Option<PathBuf>;
+
+    /// Return the content of the source identified by `id`.
+    fn load(&self, id: &Path) -> io::Result<String>;
+}
+
+/// An [ImportLoader] serving sources stored in memory, indexed by path. Paths are resolved
+/// relatively to the directory of the importing source, as on the filesystem.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryLoader {
+    sources: HashMap<PathBuf, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a source, replacing any source with the same path.
+    pub fn add(&mut self, path: impl Into<PathBuf>, source: impl Into<String>) {
+        self.sources
+            .insert(normalize_abs_path(&path.into()), source.into());
+    }
+}
+
+impl ImportLoader for MemoryLoader {
+    fn resolve(&self, path: &Path, parent: Option<&Path>) -> Option<PathBuf> {
+        let mut id = parent
+            .and_then(Path::parent)
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        id.push(path);
+        let id = normalize_abs_path(&id);
+
+        self.sources.contains_key(&id).then_some(id)
+    }
+
+    fn load(&self, id: &Path) -> io::Result<String> {
+        self.sources
+            .get(id)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+/// A shared [ImportLoader], stored in the cache.
+#[derive(Clone)]
+struct Loader(Rc<dyn ImportLoader>);
+
+impl std::fmt::Debug for Loader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Loader")
+    }
+}
+
 impl ImportResolver for Cache {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(?path, ?format))
+    )]
     fn resolve(
         &mut self,
         path: &OsStr,
@@ -1430,44 +2054,30 @@ impl ImportResolver for Cache {
         parent: Option<FileId>,
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError> {
-        // `parent` is the file that did the import. We first look in its containing directory.
-        let mut parent_path = parent
-            .and_then(|p| self.get_path(p))
-            .map(PathBuf::from)
-            .unwrap_or_default();
-        parent_path.pop();
-
-        let possible_parents: Vec<PathBuf> = std::iter::once(parent_path)
-            .chain(self.import_paths.iter().cloned())
-            .collect();
+        if self.reproducible && Path::new(path).is_absolute() {
+            return Err(ImportError::NonReproducible(
+                path.to_string_lossy().into_owned(),
+                "absolute paths depend on the layout of the machine; \
+                import the file relatively to the importing file instead"
+                    .to_owned(),
+                *pos,
+            ));
+        }
 
-        // Try to import from all possibilities, taking the first one that succeeds.
-        let (id_op, path_buf) = possible_parents
-            .iter()
-            .find_map(|parent| {
-                let mut path_buf = parent.clone();
-                path_buf.push(path);
-                self.get_or_add_file(&path_buf, format)
-                    .ok()
-                    .map(|x| (x, path_buf))
-            })
-            .ok_or_else(|| {
-                let parents = possible_parents
-                    .iter()
-                    .map(|p| p.to_string_lossy())
-                    .collect::<Vec<_>>();
-                ImportError::IOError(
-                    path.to_string_lossy().into_owned(),
-                    format!("could not find import (looked in [{}])", parents.join(", ")),
-                    *pos,
-                )
-            })?;
+        let (id_op, path_buf) = self.locate(path, format, parent, pos)?;
 
         let (result, file_id) = match id_op {
             CacheOp::Cached(id) => (ResolvedTerm::FromCache, id),
             CacheOp::Done(id) => (ResolvedTerm::FromFile { path: path_buf }, id),
         };
 
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            file = ?self.files.name(file_id),
+            cached = matches!(result, ResolvedTerm::FromCache),
+            "import resolved"
+        );
+
         if let Some(parent) = parent {
             self.imports.entry(parent).or_default().insert(file_id);
             self.rev_imports.entry(file_id).or_default().insert(parent);
@@ -1501,7 +2111,7 @@ impl ImportResolver for Cache {
 pub fn normalize_path(path: impl Into<PathBuf>) -> std::io::Result<PathBuf> {
     let mut path = path.into();
     if path.is_relative() {
-        path = std::env::current_dir()?.join(path);
+        path = current_dir()?.join(path);
     }
     Ok(normalize_abs_path(&path))
 }
@@ -1546,10 +2156,131 @@ fn normalize_abs_path(path: &Path) -> PathBuf {
 }
 
 /// Return the timestamp of a file. Return `None` if an IO error occurred.
+#[cfg(feature = "fs-imports")]
 pub fn timestamp(path: impl AsRef<OsStr>) -> io::Result<SystemTime> {
     fs::metadata(path.as_ref())?.modified()
 }
 
+/// Return the timestamp of a file. Always fails, as filesystem access is disabled.
+#[cfg(not(feature = "fs-imports"))]
+pub fn timestamp(_path: impl AsRef<OsStr>) -> io::Result<SystemTime> {
+    Err(fs_disabled())
+}
+
+/// Generate the record imported for the directory `dir`. Each file with a known extension (see
+/// [InputFormat::from_path]) is imported as a field named after the file without its extension,
+/// and each subdirectory is imported as a field named after it, recursively. Hidden files and
+/// files with other extensions are ignored.
+fn directory_record(dir: &Path, file_id: FileId) -> io::Result<RichTerm> {
+    // The imports of the record are relative to the directory containing the importing file,
+    // which for the record is the parent of `dir`.
+    let dir_name = dir.file_name().map(PathBuf::from).unwrap_or_default();
+    // The importing file is found from the position of the record when resolving its imports.
+    let pos = TermPos::Original(RawSpan {
+        src_id: file_id,
+        start: 0.into(),
+        end: 0.into(),
+    });
+    let mut fields = BTreeMap::new();
+
+    for path in read_dir(dir)? {
+        let Some(file_name) = path.file_name().and_then(OsStr::to_str) else {
+            continue;
+        };
+
+        if file_name.starts_with('.') {
+            continue;
+        }
+
+        let (name, format) = if is_dir(&path) {
+            (file_name, InputFormat::Nickel)
+        } else {
+            match (
+                path.file_stem().and_then(OsStr::to_str),
+                InputFormat::from_path(&path),
+            ) {
+                (Some(stem), Some(format)) => (stem, format),
+                _ => continue,
+            }
+        };
+
+        let import = Term::Import {
+            path: dir_name.join(file_name).into_os_string(),
+            format,
+        };
+
+        if fields.insert(name.to_owned(), import).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "several entries of the directory `{}` are imported as `{name}`",
+                    dir.display()
+                ),
+            ));
+        }
+    }
+
+    let record = RecordData::with_field_values(
+        fields
+            .into_iter()
+            .map(|(name, import)| (LocIdent::new(name), RichTerm::new(import, pos))),
+    );
+
+    Ok(RichTerm::new(Term::Record(record), pos))
+}
+
+#[cfg(feature = "fs-imports")]
+fn is_dir(path: &Path) -> bool {
+    path.is_dir()
+}
+
+#[cfg(not(feature = "fs-imports"))]
+fn is_dir(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(feature = "fs-imports")]
+fn read_dir(path: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect()
+}
+
+#[cfg(not(feature = "fs-imports"))]
+fn read_dir(_path: &Path) -> io::Result<Vec<PathBuf>> {
+    Err(fs_disabled())
+}
+
+#[cfg(feature = "fs-imports")]
+fn read_to_string(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path)
+}
+
+#[cfg(not(feature = "fs-imports"))]
+fn read_to_string(_path: &Path) -> io::Result<String> {
+    Err(fs_disabled())
+}
+
+#[cfg(feature = "fs-imports")]
+fn current_dir() -> io::Result<PathBuf> {
+    std::env::current_dir()
+}
+
+/// Without a filesystem, relative paths are resolved from the root, so that sources added from
+/// memory under a relative name can still import each other.
+#[cfg(not(feature = "fs-imports"))]
+fn current_dir() -> io::Result<PathBuf> {
+    Ok(PathBuf::from("/"))
+}
+
+#[cfg(not(feature = "fs-imports"))]
+fn fs_disabled() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "filesystem access is disabled (the `fs-imports` feature is off)",
+    )
+}
+
 /// Provide mockup import resolvers for testing purpose.
 pub mod resolvers {
     use super::*;
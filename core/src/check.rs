@@ -0,0 +1,89 @@
+//! Policy rules checked against evaluated configurations.
+//!
+//! A rules file is a Nickel record where each field is a rule: a contract which any configuration
+//! checked against the file must satisfy, for example that every container sets resource limits.
+//! The documentation of the field describes the rule, and is used as the message of a violation
+//! if the contract doesn't provide one:
+//!
+//! ```nickel
+//! {
+//!   containers_have_limits
+//!     | doc "Every container must set resource limits"
+//!     = {
+//!       containers | Array { limits | { cpu | String, memory | String }, .. },
+//!       ..
+//!     },
+//! }
+//! ```
+//!
+//! [Program::check_rules](crate::program::Program::check_rules) evaluates the configuration fully,
+//! as for an export, and applies each rule to the result. A contract failure is reported as a
+//! [Violation], which locates both the rule and the offending value. Since the configuration is
+//! fully evaluated, the position of the offending value is where it has been defined, possibly
+//! in another file than the main one (see [crate::provenance]).
+use crate::{
+    error::{Diagnostic, Diagnostics, IntoDiagnostics, Label},
+    files::Files,
+    identifier::LocIdent,
+    position::{RawSpan, TermPos},
+    provenance::Provenance,
+};
+
+/// A rule violated by a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// The name of the rule, which is the name of the field defining it.
+    pub rule: String,
+    /// The definition of the rule.
+    pub rule_span: Option<RawSpan>,
+    /// The error message of the contract, or the documentation of the rule.
+    pub message: Option<String>,
+    /// The additional notes of the contract.
+    pub notes: Vec<String>,
+    /// The field holding the offending value, if the contract failed on a field.
+    pub field: Option<LocIdent>,
+    /// The position of the offending value.
+    pub value_pos: TermPos,
+    /// The provenance of the offending value.
+    pub provenance: Option<Provenance>,
+}
+
+impl IntoDiagnostics for Violation {
+    fn into_diagnostics(self, _files: &Files) -> Diagnostics {
+        let mut labels = Vec::new();
+
+        if let Some(span) = self.value_pos.into_opt() {
+            let label = Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize());
+            labels.push(match &self.field {
+                Some(field) => label.with_message(format!("offending value of field `{field}`")),
+                None => label.with_message("offending value"),
+            });
+        }
+
+        if let Some(span) = self.rule_span {
+            labels.push(
+                Label::secondary(span.src_id, span.start.to_usize()..span.end.to_usize())
+                    .with_message("rule defined here"),
+            );
+        }
+
+        let mut notes = self.notes;
+
+        if let Some(message) = self.message {
+            notes.insert(0, message);
+        }
+
+        if let Some(provenance) = self.provenance {
+            notes.push(format!("the offending value is defined at {provenance}"));
+        }
+
+        vec![Diagnostic::error()
+            .with_message(format!(
+                "the configuration violates the rule `{}`",
+                self.rule
+            ))
+            .with_labels(labels)
+            .with_notes(notes)]
+        .into()
+    }
+}
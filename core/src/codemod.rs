@@ -0,0 +1,283 @@
+//! Source-to-source rewriting of Nickel code, for migrations such as renaming a field across a
+//! code base.
+//!
+//! Rewrites operate on the [concrete syntax tree](crate::cst) produced by the tree-sitter grammar
+//! of Nickel, the one used by the formatter. Contrary to the AST, this tree keeps track of the
+//! exact text of each node: a rewrite only replaces the text of the nodes it matches, and the rest
+//! of the source, including comments and formatting, is left untouched.
+//!
+//! A [Rewrite] is made of a pattern, which is a [tree-sitter
+//! query](https://tree-sitter.github.io/tree-sitter/using-parsers#query-syntax), and a template.
+//! The node captured as `@match` by the pattern is replaced by the template, where `$name` stands
+//! for the text of the node captured as `@name` (and `$$` for a literal `$`). For example, the
+//! following rewrite turns `config.enabled` into `(config.enabled == "true")`:
+//!
+//! ```
+//! # use nickel_lang_core::codemod::{apply, Rewrite};
+//! let rewrite = Rewrite::new(
+//!     r#"((record_operation_chain t: (_) @record id: (ident) @field) @match
+//!         (#eq? @field "enabled"))"#,
+//!     r#"($record.enabled == "true")"#,
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(
+//!     apply("if config.enabled then 1 else 0", &[rewrite]).unwrap(),
+//!     r#"if (config.enabled == "true") then 1 else 0"#
+//! );
+//! ```
+use std::{fmt, ops::Range};
+
+use tree_sitter::{Query, QueryCursor};
+
+use crate::cst::SyntaxTree;
+
+/// The name of the capture designating the node to replace.
+pub const MATCH_CAPTURE: &str = "match";
+
+/// An error occurring when building or applying a rewrite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodemodError {
+    /// The pattern isn't a valid tree-sitter query.
+    InvalidPattern(String),
+    /// The pattern doesn't capture any node as `@match`.
+    MissingMatchCapture,
+    /// The template refers to a capture which isn't defined by the pattern.
+    UnknownCapture(String),
+    /// The source to rewrite isn't valid Nickel.
+    Parse,
+}
+
+impl fmt::Display for CodemodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodemodError::InvalidPattern(msg) => write!(f, "invalid rewrite pattern: {msg}"),
+            CodemodError::MissingMatchCapture => write!(
+                f,
+                "the rewrite pattern must capture the node to replace as `@{MATCH_CAPTURE}`"
+            ),
+            CodemodError::UnknownCapture(name) => write!(
+                f,
+                "the rewrite template refers to `${name}`, but the pattern doesn't capture `@{name}`"
+            ),
+            CodemodError::Parse => write!(f, "the source to rewrite contains syntax errors"),
+        }
+    }
+}
+
+impl std::error::Error for CodemodError {}
+
+#[derive(Debug, Clone)]
+enum TemplatePart {
+    Text(String),
+    Capture(u32),
+}
+
+/// A rewrite rule. See the [module documentation](self).
+#[derive(Debug)]
+pub struct Rewrite {
+    query: Query,
+    match_index: u32,
+    template: Vec<TemplatePart>,
+}
+
+impl Rewrite {
+    /// Build a rewrite replacing the node captured as `@match` by `pattern` with `template`.
+    pub fn new(pattern: &str, template: &str) -> Result<Self, CodemodError> {
+        let query = Query::new(&tree_sitter_nickel::language(), pattern)
+            .map_err(|err| CodemodError::InvalidPattern(err.to_string()))?;
+        let match_index = query
+            .capture_index_for_name(MATCH_CAPTURE)
+            .ok_or(CodemodError::MissingMatchCapture)?;
+
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                text.push(c);
+                continue;
+            }
+
+            if chars.next_if_eq(&'$').is_some() {
+                text.push('$');
+                continue;
+            }
+
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+            }
+
+            let index = query
+                .capture_index_for_name(&name)
+                .ok_or(CodemodError::UnknownCapture(name))?;
+
+            if !text.is_empty() {
+                parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+            }
+            parts.push(TemplatePart::Capture(index));
+        }
+
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+
+        Ok(Rewrite {
+            query,
+            match_index,
+            template: parts,
+        })
+    }
+
+    /// Rename every field named `old` to `new`, both in record literals and in field accesses
+    /// such as `config.old`. Fields are renamed regardless of the record they belong to, and
+    /// fields bound by destructuring patterns are left untouched. `new` is quoted if it isn't a
+    /// valid identifier.
+    pub fn rename_field(old: &str, new: &str) -> Self {
+        let old = old.replace('\\', "\\\\").replace('"', "\\\"");
+        let pattern = format!(
+            r#"((field_path_elem (ident) @match) (#eq? @match "{old}"))
+               ((record_operation_chain id: (ident) @match) (#eq? @match "{old}"))"#
+        );
+        let query = Query::new(&tree_sitter_nickel::language(), &pattern)
+            .expect("the field renaming pattern should be a valid query");
+        let match_index = query
+            .capture_index_for_name(MATCH_CAPTURE)
+            .expect("the field renaming pattern should capture `@match`");
+
+        Rewrite {
+            query,
+            match_index,
+            template: vec![TemplatePart::Text(quote_ident(new))],
+        }
+    }
+}
+
+/// Quote a field name if it isn't a valid identifier.
+fn quote_ident(name: &str) -> String {
+    let mut chars = name.chars().skip_while(|c| *c == '_');
+    let is_ident = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '\''));
+
+    if is_ident {
+        name.to_owned()
+    } else {
+        format!("{name:?}")
+    }
+}
+
+/// Apply rewrites to a Nickel source. All the rewrites are matched against the original source:
+/// if the nodes matched by several rewrites overlap, only the first one in the source is
+/// replaced.
+pub fn apply(source: &str, rewrites: &[Rewrite]) -> Result<String, CodemodError> {
+    let tree = SyntaxTree::parse(source);
+
+    if tree.has_errors() {
+        return Err(CodemodError::Parse);
+    }
+
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    let mut cursor = QueryCursor::new();
+
+    for rewrite in rewrites {
+        for m in cursor.matches(&rewrite.query, tree.root().node, source.as_bytes()) {
+            let capture = |index: u32| {
+                m.captures
+                    .iter()
+                    .find(|capture| capture.index == index)
+                    .map(|capture| capture.node.byte_range())
+            };
+
+            let Some(range) = capture(rewrite.match_index) else {
+                continue;
+            };
+
+            let replacement = rewrite
+                .template
+                .iter()
+                .map(|part| match part {
+                    TemplatePart::Text(text) => text.as_str(),
+                    TemplatePart::Capture(index) => capture(*index)
+                        .map(|range| &source[range])
+                        .unwrap_or_default(),
+                })
+                .collect();
+
+            edits.push((range, replacement));
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| (range.start, range.end));
+
+    let mut result = String::with_capacity(source.len());
+    let mut offset = 0;
+
+    for (range, replacement) in edits {
+        if range.start < offset {
+            continue;
+        }
+
+        result.push_str(&source[offset..range.start]);
+        result.push_str(&replacement);
+        offset = range.end;
+    }
+
+    result.push_str(&source[offset..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rename_field() {
+        let source = r#"
+let config = {
+  # The port to listen on
+  port | Number = 80,
+  server.port = 8080,
+} in
+config.port + config.server.port
+"#;
+
+        assert_eq!(
+            apply(source, &[Rewrite::rename_field("port", "listen-port")]).unwrap(),
+            r#"
+let config = {
+  # The port to listen on
+  listen-port | Number = 80,
+  server.listen-port = 8080,
+} in
+config.listen-port + config.server.listen-port
+"#
+        );
+
+        assert_eq!(
+            apply("{ foo = 1 }", &[Rewrite::rename_field("foo", "foo bar")]).unwrap(),
+            r#"{ "foo bar" = 1 }"#
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Rewrite::new("(ident) @id", "x").unwrap_err(),
+            CodemodError::MissingMatchCapture
+        );
+        assert_eq!(
+            Rewrite::new("(ident) @match", "$other").unwrap_err(),
+            CodemodError::UnknownCapture("other".into())
+        );
+        assert!(matches!(
+            Rewrite::new("(not_a_node) @match", "x").unwrap_err(),
+            CodemodError::InvalidPattern(_)
+        ));
+        assert_eq!(
+            apply("{ foo = }", &[Rewrite::rename_field("foo", "bar")]).unwrap_err(),
+            CodemodError::Parse
+        );
+    }
+}
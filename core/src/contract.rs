@@ -0,0 +1,133 @@
+//! Nickel contracts describing Rust types.
+//!
+//! A type implementing [NickelContract] provides the Nickel source of a contract accepting the
+//! values which deserialize to this type, as done by [crate::deserialize::from_term]. Checking a
+//! configuration against this contract before deserializing it reports mismatches as blame
+//! errors pointing to the configuration, instead of deserialization errors.
+//!
+//! The trait is implemented for the usual types of the standard library, and can be derived for
+//! structs and enums with the `derive` feature. The derive macro follows the `serde` attributes
+//! which change the expected shape of the data: `rename`, `rename_all`, `default`, `skip` and
+//! `deny_unknown_fields`. Doc comments of fields are turned into `doc` metadata.
+//!
+//! ```ignore
+//! #[derive(serde::Deserialize, NickelContract)]
+//! struct Server {
+//!     /// The host to listen on.
+//!     host: String,
+//!     port: Option<u16>,
+//! }
+//!
+//! let server: Server = program.eval_deserialize_checked()?;
+//! ```
+//!
+//! For `Server`, [NickelContract::contract] returns, on a single line:
+//!
+//! ```nickel
+//! {
+//!   "host" | doc "The host to listen on." | (String),
+//!   "port" | optional | (std.contract.custom (fun label value =>
+//!     if value == null then 'Ok value else std.contract.check (std.number.Nat) label value)),
+//!   ..
+//! }
+//! ```
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    rc::Rc,
+    sync::Arc,
+};
+
+#[cfg(feature = "derive")]
+pub use nickel_lang_derive::NickelContract;
+
+/// A Rust type with a corresponding Nickel contract.
+pub trait NickelContract {
+    /// The Nickel source of a contract accepting the values which deserialize to this type. The
+    /// source is a single expression referring only to the standard library.
+    fn contract() -> String;
+}
+
+macro_rules! impl_contract {
+    ($contract:literal: $($t:ty),+) => {
+        $(
+            impl NickelContract for $t {
+                fn contract() -> String {
+                    String::from($contract)
+                }
+            }
+        )+
+    };
+}
+
+impl_contract!("Bool": bool);
+impl_contract!("String": String, char);
+impl_contract!("Number": f32, f64);
+impl_contract!("std.number.Integer": i8, i16, i32, i64, i128, isize);
+impl_contract!("std.number.Nat": u8, u16, u32, u64, u128, usize);
+
+// `std.contract.Equal null` isn't used for `null`: on a record, its check is delayed, so that
+// `std.contract.any_of` would pick it for any record, and then blame.
+impl NickelContract for () {
+    fn contract() -> String {
+        String::from(
+            "std.contract.custom (fun _label value => \
+            if value == null then 'Ok value else 'Error { message = \"expected null\" })",
+        )
+    }
+}
+
+impl<T: NickelContract> NickelContract for Option<T> {
+    fn contract() -> String {
+        format!(
+            "std.contract.custom (fun label value => \
+            if value == null then 'Ok value else std.contract.check ({}) label value)",
+            T::contract()
+        )
+    }
+}
+
+macro_rules! impl_contract_wrapper {
+    ($($wrapper:ident),+) => {
+        $(
+            impl<T: NickelContract + ?Sized> NickelContract for $wrapper<T> {
+                fn contract() -> String {
+                    T::contract()
+                }
+            }
+        )+
+    };
+}
+
+impl_contract_wrapper!(Box, Rc, Arc);
+
+macro_rules! impl_contract_array {
+    ($($array:ident),+) => {
+        $(
+            impl<T: NickelContract> NickelContract for $array<T> {
+                fn contract() -> String {
+                    format!("Array ({})", T::contract())
+                }
+            }
+        )+
+    };
+}
+
+impl_contract_array!(Vec, VecDeque, BTreeSet);
+
+impl<T: NickelContract, S> NickelContract for HashSet<T, S> {
+    fn contract() -> String {
+        format!("Array ({})", T::contract())
+    }
+}
+
+impl<T: NickelContract> NickelContract for BTreeMap<String, T> {
+    fn contract() -> String {
+        format!("{{ _ | ({}) }}", T::contract())
+    }
+}
+
+impl<T: NickelContract, S> NickelContract for HashMap<String, T, S> {
+    fn contract() -> String {
+        format!("{{ _ | ({}) }}", T::contract())
+    }
+}
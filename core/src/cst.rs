@@ -0,0 +1,365 @@
+//! A lossless concrete syntax tree (CST) of Nickel sources.
+//!
+//! The AST produced by [crate::parser] is tailored for evaluation: comments, whitespace and the
+//! exact spelling of the source are lost. Tools which edit code, such as the formatter, the
+//! [codemods](crate::codemod) or the language server, need to preserve them. The CST is built by
+//! the tree-sitter grammar of Nickel, alongside the AST, and keeps track of the text and the
+//! position of every token, including comments.
+//!
+//! The tree is lossless: each byte of the source is covered by exactly one [Token], and
+//! concatenating the text of the tokens returned by [SyntaxTree::tokens] gives back the source.
+//!
+//! ```
+//! # use nickel_lang_core::cst::SyntaxTree;
+//! let source = "{\n  # The port to listen on\n  port = 80,\n}";
+//! let tree = SyntaxTree::parse(source);
+//!
+//! let text: String = tree.tokens().iter().map(|token| token.text).collect();
+//! assert_eq!(text, source);
+//!
+//! let port = tree.node_at(source.find("port =").unwrap()).unwrap();
+//! let field = port.ancestors().find(|node| node.kind() == "record_field").unwrap();
+//! let comments: Vec<_> = field.leading_comments().iter().map(|c| c.text()).collect();
+//! assert_eq!(comments, ["# The port to listen on"]);
+//! ```
+use std::ops::Range;
+
+use tree_sitter::{Node, Parser, Tree};
+
+/// The concrete syntax tree of a Nickel source.
+#[derive(Clone, Debug)]
+pub struct SyntaxTree {
+    source: String,
+    tree: Tree,
+}
+
+/// A node of a [SyntaxTree]. Nodes are either named, such as `record_field` or `ident`, or
+/// anonymous, such as keywords and punctuation. The kinds of the nodes are the ones of the
+/// tree-sitter grammar of Nickel.
+#[derive(Clone, Copy, Debug)]
+pub struct SyntaxNode<'a> {
+    pub(crate) node: Node<'a>,
+    source: &'a str,
+}
+
+/// The kind of a [Token].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Spaces, tabs and line breaks.
+    Whitespace,
+    /// A comment, from the `#` to the end of the line, excluding the line break.
+    Comment,
+    /// Any other token, such as a keyword, an identifier or a literal, with the kind of the
+    /// corresponding leaf of the tree.
+    Syntax(&'static str),
+}
+
+/// A token of a [SyntaxTree], which is either a leaf of the tree or trivia.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Range<usize>,
+}
+
+impl Token<'_> {
+    /// Return `true` if the token is a comment or whitespace, which doesn't appear in the AST.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+impl SyntaxTree {
+    /// Parse a Nickel source. Parsing always succeeds: syntax errors are represented as error
+    /// nodes in the tree, which can be detected with [Self::has_errors].
+    pub fn parse(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_nickel::language())
+            .expect("the Nickel grammar should be compatible with tree-sitter");
+        // unwrap(): parsing only fails if there's no language, or if it's cancelled or timed out
+        let tree = parser.parse(&source, None).unwrap();
+
+        SyntaxTree { source, tree }
+    }
+
+    /// The parsed source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The root node of the tree, which spans the whole source.
+    pub fn root(&self) -> SyntaxNode<'_> {
+        SyntaxNode {
+            node: self.tree.root_node(),
+            source: &self.source,
+        }
+    }
+
+    /// Return `true` if the source contains syntax errors.
+    pub fn has_errors(&self) -> bool {
+        self.tree.root_node().has_error()
+    }
+
+    /// The spans of the syntax errors of the source. A missing token, such as an unclosed
+    /// delimiter, is reported as an empty span.
+    pub fn errors(&self) -> Vec<Range<usize>> {
+        fn collect(node: Node<'_>, acc: &mut Vec<Range<usize>>) {
+            if node.is_error() || node.is_missing() {
+                acc.push(node.byte_range());
+            } else if node.has_error() {
+                let mut cursor = node.walk();
+                for child in node.children(&mut cursor) {
+                    collect(child, acc);
+                }
+            }
+        }
+
+        let mut errors = Vec::new();
+        collect(self.tree.root_node(), &mut errors);
+        errors
+    }
+
+    /// The smallest named node containing the byte at `offset`, if any.
+    pub fn node_at(&self, offset: usize) -> Option<SyntaxNode<'_>> {
+        self.tree
+            .root_node()
+            .named_descendant_for_byte_range(offset, offset)
+            .map(|node| SyntaxNode {
+                node,
+                source: &self.source,
+            })
+    }
+
+    /// The tokens of the source, in order. The spans of the tokens are contiguous and cover the
+    /// whole source.
+    pub fn tokens(&self) -> Vec<Token<'_>> {
+        let mut tokens = Tokens {
+            source: &self.source,
+            offset: 0,
+            tokens: Vec::new(),
+        };
+
+        tokens.push_node(self.tree.root_node());
+        tokens.push_gap(self.source.len(), TokenKind::Whitespace);
+        tokens.tokens
+    }
+}
+
+impl<'a> SyntaxNode<'a> {
+    /// The kind of the node in the tree-sitter grammar of Nickel, such as `record_field`.
+    pub fn kind(&self) -> &'static str {
+        self.node.kind()
+    }
+
+    /// Return `true` if the node is named, that is if it isn't a keyword or punctuation.
+    pub fn is_named(&self) -> bool {
+        self.node.is_named()
+    }
+
+    /// Return `true` if the node is a comment.
+    pub fn is_comment(&self) -> bool {
+        self.kind() == "comment"
+    }
+
+    /// Return `true` if the node is a syntax error, or a missing token inserted by the parser to
+    /// recover from an error.
+    pub fn is_error(&self) -> bool {
+        self.node.is_error() || self.node.is_missing()
+    }
+
+    /// The span of the node in the source, in bytes.
+    pub fn span(&self) -> Range<usize> {
+        self.node.byte_range()
+    }
+
+    /// The text of the node, including the comments and whitespace between its children.
+    pub fn text(&self) -> &'a str {
+        &self.source[self.span()]
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        self.node.parent().map(|node| self.with_node(node))
+    }
+
+    /// The ancestors of the node, from its parent to the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = Self> {
+        std::iter::successors(self.parent(), Self::parent)
+    }
+
+    /// The children of the node, including anonymous nodes and comments.
+    pub fn children(&self) -> Vec<Self> {
+        let mut cursor = self.node.walk();
+        self.node
+            .children(&mut cursor)
+            .map(|node| self.with_node(node))
+            .collect()
+    }
+
+    /// The child of the node stored in the given field of the grammar, such as the `path` of a
+    /// `record_field`.
+    pub fn child_by_field(&self, field: &str) -> Option<Self> {
+        self.node
+            .child_by_field_name(field)
+            .map(|node| self.with_node(node))
+    }
+
+    /// The comments directly preceding the node, in order: the previous siblings of the node
+    /// which are comments, up to the first sibling which isn't.
+    pub fn leading_comments(&self) -> Vec<Self> {
+        let mut comments: Vec<_> =
+            std::iter::successors(self.node.prev_sibling(), Node::prev_sibling)
+                .take_while(|node| node.kind() == "comment")
+                .map(|node| self.with_node(node))
+                .collect();
+        comments.reverse();
+        comments
+    }
+
+    fn with_node(&self, node: Node<'a>) -> Self {
+        SyntaxNode {
+            node,
+            source: self.source,
+        }
+    }
+}
+
+/// The state of the conversion of a tree to a list of tokens.
+struct Tokens<'a> {
+    source: &'a str,
+    /// The end of the last token.
+    offset: usize,
+    tokens: Vec<Token<'a>>,
+}
+
+impl<'a> Tokens<'a> {
+    fn push_node(&mut self, node: Node<'_>) {
+        if node.child_count() == 0 {
+            let span = node.byte_range();
+
+            // Missing nodes are inserted by the parser and don't correspond to any text.
+            if span.is_empty() {
+                return;
+            }
+
+            self.push_gap(span.start, TokenKind::Syntax(node.kind()));
+
+            let kind = if node.kind() == "comment" {
+                TokenKind::Comment
+            } else {
+                TokenKind::Syntax(node.kind())
+            };
+
+            self.push(kind, span);
+        } else {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                self.push_node(child);
+            }
+
+            self.push_gap(node.end_byte(), TokenKind::Syntax(node.kind()));
+        }
+    }
+
+    /// Push the text between the last token and `end`, which doesn't belong to any leaf of the
+    /// tree. It's whitespace, unless the grammar has a token which isn't a leaf, in which case
+    /// it's attributed to `kind`.
+    fn push_gap(&mut self, end: usize, kind: TokenKind) {
+        if self.offset >= end {
+            return;
+        }
+
+        let span = self.offset..end;
+        let kind = if self.source[span.clone()].trim().is_empty() {
+            TokenKind::Whitespace
+        } else {
+            kind
+        };
+
+        self.push(kind, span);
+    }
+
+    fn push(&mut self, kind: TokenKind, span: Range<usize>) {
+        self.offset = span.end;
+        self.tokens.push(Token {
+            kind,
+            text: &self.source[span.clone()],
+            span,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossless() {
+        let source = r#"
+# A configuration
+let config = {
+  # The port to listen on
+  port | Number = 80, # inline
+  name = "server %{"a" ++ "b"}",
+} in
+config.port
+"#;
+
+        let tree = SyntaxTree::parse(source);
+        assert!(!tree.has_errors());
+
+        let tokens = tree.tokens();
+        let text: String = tokens.iter().map(|token| token.text).collect();
+        assert_eq!(text, source);
+
+        let comments: Vec<_> = tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::Comment)
+            .map(|token| token.text)
+            .collect();
+        assert_eq!(
+            comments,
+            ["# A configuration", "# The port to listen on", "# inline"]
+        );
+        assert!(tokens
+            .iter()
+            .any(|token| token.kind == TokenKind::Syntax("let")));
+    }
+
+    #[test]
+    fn navigation() {
+        let source = "{\n  # First\n  # Second\n  foo = 1,\n  bar = foo,\n}";
+        let tree = SyntaxTree::parse(source);
+
+        let foo = tree.node_at(source.find("foo").unwrap()).unwrap();
+        assert_eq!(foo.text(), "foo");
+
+        let field = foo
+            .ancestors()
+            .find(|node| node.kind() == "record_field")
+            .unwrap();
+        assert_eq!(field.text(), "foo = 1");
+        let comments: Vec<_> = field.leading_comments().iter().map(|c| c.text()).collect();
+        assert_eq!(comments, ["# First", "# Second"]);
+
+        let bar = tree.node_at(source.find("bar").unwrap()).unwrap();
+        let bar_field = bar
+            .ancestors()
+            .find(|node| node.kind() == "record_field")
+            .unwrap();
+        assert!(bar_field.leading_comments().is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let source = "{ foo = }";
+        let tree = SyntaxTree::parse(source);
+
+        assert!(tree.has_errors());
+        assert!(!tree.errors().is_empty());
+
+        let text: String = tree.tokens().iter().map(|token| token.text).collect();
+        assert_eq!(text, source);
+    }
+}
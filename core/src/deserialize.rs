@@ -8,7 +8,11 @@ use serde::de::{
     VariantAccess, Visitor,
 };
 
+use serde::de::DeserializeOwned;
+
+use crate::error::{ExportError, ExportErrorData};
 use crate::identifier::LocIdent;
+use crate::serialize::{NickelPointer, NickelPointerElem};
 use crate::term::array::Array;
 use crate::term::record::Field;
 use crate::term::{IndexMap, RichTerm, Term};
@@ -42,6 +46,58 @@ pub enum RustDeserializationError {
     Other(String),
 }
 
+/// Deserialize a fully evaluated term to a Rust value. Contrary to `T::deserialize(rt)`, a failure
+/// is reported as an [ExportError] located at the offending value of `rt`, which can then be
+/// reported as any other Nickel error.
+pub fn from_term<T: DeserializeOwned>(rt: &RichTerm) -> Result<T, ExportError> {
+    serde_path_to_error::deserialize(rt.clone()).map_err(|err| {
+        let (path, term) = locate(rt, err.path());
+
+        ExportError {
+            path,
+            data: ExportErrorData::Deserialization {
+                message: err.into_inner().to_string(),
+                term,
+            },
+        }
+    })
+}
+
+/// Follow the path of a deserialization error inside the deserialized term, as far as it goes.
+/// Return the path followed and the value found there.
+fn locate(rt: &RichTerm, path: &serde_path_to_error::Path) -> (NickelPointer, RichTerm) {
+    use serde_path_to_error::Segment;
+
+    let mut pointer = NickelPointer::new();
+    let mut current = rt;
+
+    for segment in path.iter() {
+        let next = match (segment, current.as_ref()) {
+            (Segment::Map { key }, Term::Record(record))
+            | (Segment::Enum { variant: key }, Term::Record(record)) => record
+                .fields
+                .iter()
+                .find(|(id, _)| id.label() == key)
+                .and_then(|(id, field)| {
+                    Some((NickelPointerElem::Field(id.ident()), field.value.as_ref()?))
+                }),
+            (Segment::Seq { index }, Term::Array(array, _)) => array
+                .get(*index)
+                .map(|elt| (NickelPointerElem::Index(*index), elt)),
+            _ => None,
+        };
+
+        let Some((elem, next)) = next else {
+            break;
+        };
+
+        pointer.0.push(elem);
+        current = next;
+    }
+
+    (pointer, current.clone())
+}
+
 impl<'de> serde::Deserializer<'de> for RichTerm {
     type Error = RustDeserializationError;
 
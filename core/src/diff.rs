@@ -0,0 +1,198 @@
+//! Structural differences between evaluated configurations.
+//!
+//! Two configurations are compared once they have been fully evaluated and converted to JSON
+//! values (see [crate::serialize]), so that only the exported data matters: two programs written
+//! differently but producing the same result have no difference. Records are compared field by
+//! field, and arrays element by element, so that a change deep inside a configuration is reported
+//! at the path of the changed value rather than as a change of the whole configuration.
+//!
+//! ```
+//! # use nickel_lang_core::diff::diff;
+//! # use serde_json::json;
+//! let changes = diff(
+//!     &json!({ "port": 80, "hosts": ["a", "b"] }),
+//!     &json!({ "port": 8080, "hosts": ["a"], "tls": true }),
+//! );
+//! let paths: Vec<_> = changes.iter().map(|change| change.path().to_string()).collect();
+//! assert_eq!(paths, ["hosts[1]", "port", "tls"]);
+//! ```
+use std::{collections::BTreeSet, fmt};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::pretty::ident_quoted;
+
+/// An element of the path to a value: the name of a field, or the index of an array element.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum PathElem {
+    Field(String),
+    Index(usize),
+}
+
+/// The path to a value from the root of a configuration. The path of the root is empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Path(pub Vec<PathElem>);
+
+impl Path {
+    fn with(&self, elem: PathElem) -> Self {
+        let mut path = self.clone();
+        path.0.push(elem);
+        path
+    }
+}
+
+/// Print the path as a Nickel field path, such as `servers[0].port`, quoting the fields which
+/// aren't valid identifiers. The root is printed as `<root>`.
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "<root>");
+        }
+
+        for (i, elem) in self.0.iter().enumerate() {
+            match elem {
+                PathElem::Field(name) => {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    write!(f, "{}", ident_quoted(&name.as_str().into()))?;
+                }
+                PathElem::Index(idx) => write!(f, "[{idx}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A difference between two configurations.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Change {
+    /// A field or an array element only present in the new configuration.
+    Added { path: Path, new: Value },
+    /// A field or an array element only present in the old configuration.
+    Removed { path: Path, old: Value },
+    /// A value which is different in the two configurations. Records and arrays are only reported
+    /// as changed if the other value isn't a record, respectively an array.
+    Changed { path: Path, old: Value, new: Value },
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Compare two configurations, converted to JSON values. The changes are sorted by path, the
+/// fields of records being in alphabetical order.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at(&Path::default(), old, new, &mut changes);
+    changes
+}
+
+fn diff_at(path: &Path, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+            for name in names {
+                let path = path.with(PathElem::Field(name.clone()));
+
+                match (old.get(name), new.get(name)) {
+                    (Some(old), Some(new)) => diff_at(&path, old, new, changes),
+                    (Some(old), None) => changes.push(Change::Removed {
+                        path,
+                        old: old.clone(),
+                    }),
+                    (None, Some(new)) => changes.push(Change::Added {
+                        path,
+                        new: new.clone(),
+                    }),
+                    (None, None) => unreachable!("the name comes from one of the records"),
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for idx in 0..old.len().max(new.len()) {
+                let path = path.with(PathElem::Index(idx));
+
+                match (old.get(idx), new.get(idx)) {
+                    (Some(old), Some(new)) => diff_at(&path, old, new, changes),
+                    (Some(old), None) => changes.push(Change::Removed {
+                        path,
+                        old: old.clone(),
+                    }),
+                    (None, Some(new)) => changes.push(Change::Added {
+                        path,
+                        new: new.clone(),
+                    }),
+                    (None, None) => unreachable!("the index is smaller than one of the lengths"),
+                }
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn nested_changes() {
+        let old = json!({
+            "name": "app",
+            "servers": [{ "port": 80 }, { "port": 81 }],
+            "tags": { "env": "prod" },
+        });
+        let new = json!({
+            "name": "app",
+            "servers": [{ "port": 8080 }],
+            "tags": "prod",
+            "my field": null,
+        });
+
+        let changes: Vec<_> = diff(&old, &new)
+            .iter()
+            .map(|change| change.path().to_string())
+            .collect();
+        assert_eq!(
+            changes,
+            ["\"my field\"", "servers[0].port", "servers[1]", "tags"]
+        );
+
+        assert!(diff(&old, &old).is_empty());
+        assert_eq!(
+            diff(&json!(1), &json!(2)),
+            [Change::Changed {
+                path: Path::default(),
+                old: json!(1),
+                new: json!(2),
+            }]
+        );
+    }
+
+    #[test]
+    fn serialization() {
+        let changes = diff(&json!({ "a": [1] }), &json!({ "a": [1, 2] }));
+
+        assert_eq!(
+            serde_json::to_value(changes).unwrap(),
+            json!([{ "kind": "added", "path": ["a", 1], "new": 2 }])
+        );
+    }
+}
@@ -1,10 +1,7 @@
 //! An environment for storing variables with scopes.
-use std::cell::RefCell;
 use std::collections::{hash_map, HashMap};
 use std::hash::Hash;
 use std::iter::FromIterator;
-use std::marker::PhantomData;
-use std::ptr::NonNull;
 use std::rc::Rc;
 
 use crate::metrics::{increment, sample};
@@ -18,43 +15,32 @@ use crate::metrics::{increment, sample};
 /// For the generic parameters, `K` is the type for the environment
 /// keys, and `V` are their value.
 ///
-/// The linked list is composed of the current layer and the previous layers.
-/// The current layer is stored as an `Rc<Hashmap>`. It is inserted in the previous layers
-/// by cloning.
-/// The insertion is made by trying to get the current layer as mutable using
-/// [`Rc::get_mut`]. If it can, it means it is the only owner of this layer
-/// in the environment, allowing it to mutate it. If it cannot, it means that
-/// the current has been cloned and inserted in the previous environment already,
-/// so it can safely be reset as a new hashmap.
-/// The previous layers are set in order from the most recent one to the oldest.
+/// The linked list is composed of the current layer and the previous layers, the latter being
+/// set in order from the most recent one to the oldest.
+///
+/// Layers are copy-on-write. Cloning an environment doesn't allocate anything: the clone simply
+/// shares both the current layer and the previous layers with the original. A private layer is
+/// only materialized when an environment is actually extended (by [`Environment::insert`] or
+/// [`Extend::extend`]): if the current layer is shared, that is if [`Rc::get_mut`] fails, it is
+/// frozen and pushed on top of the previous layers, and a fresh current layer is created. If the
+/// current layer isn't shared, it's updated in place.
+///
+/// This matters because the interpreter clones the environment each time it allocates a thunk,
+/// but most thunks never extend their environment.
 #[derive(Debug, PartialEq)]
 pub struct Environment<K: Hash + Eq, V: PartialEq> {
     current: Rc<HashMap<K, V>>,
-    previous: RefCell<Option<Rc<Environment<K, V>>>>,
+    previous: Option<Rc<Environment<K, V>>>,
 }
 
 impl<K: Hash + Eq, V: PartialEq> Clone for Environment<K, V> {
-    /// Clone has to create a new environment, while ensuring that previous
-    /// defined layers are accessible but not modifiable anymore.
-    /// For that, it checks if the current Environment has already be cloned,
-    /// and if it wasn't, it sets the `current` has the new head of `previous`.
-    /// Then a clone is an empty `current` and the clone of `self.previous`.
+    /// Cloning an environment is cheap: the layers are shared with the original environment and
+    /// only reference counts are updated. See [Environment].
     fn clone(&self) -> Self {
         increment!("Environment::clone");
-        if !self.current.is_empty() && !self.was_cloned() {
-            sample!(
-                "Environment.curr_layer_size_at_clone",
-                self.current.len() as f64
-            );
-            self.previous.replace_with(|old| {
-                Some(Rc::new(Environment {
-                    current: self.current.clone(),
-                    previous: RefCell::new(old.clone()),
-                }))
-            });
-        }
+
         Self {
-            current: Rc::new(HashMap::new()),
+            current: self.current.clone(),
             previous: self.previous.clone(),
         }
     }
@@ -64,7 +50,7 @@ impl<K: Hash + Eq, V: PartialEq> Default for Environment<K, V> {
     fn default() -> Self {
         Self {
             current: Rc::new(HashMap::new()),
-            previous: RefCell::new(None),
+            previous: None,
         }
     }
 }
@@ -78,10 +64,7 @@ impl<K: Hash + Eq, V: PartialEq> Environment<K, V> {
     /// Inserts a key-value pair into the Environment.
     pub fn insert(&mut self, key: K, value: V) {
         increment!("Environment::insert");
-        if self.was_cloned() {
-            self.current = Rc::new(HashMap::new());
-        }
-        Rc::get_mut(&mut self.current).unwrap().insert(key, value);
+        self.current_mut().insert(key, value);
     }
 
     /// Tries to find the value of a key in the Environment.
@@ -101,20 +84,7 @@ impl<K: Hash + Eq, V: PartialEq> Environment<K, V> {
     /// Creates an iterator that visits all layers from the most recent one to the oldest.
     /// The element iterator type is `Rc<HashMap<K, V>>`.
     pub fn iter_layers(&self) -> EnvLayerIter<'_, K, V> {
-        EnvLayerIter {
-            env: if !self.was_cloned() {
-                Some(NonNull::from(self))
-            } else {
-                // if was cloned, current is the same as first of previous (that cannot be empty
-                // then)
-                self.previous
-                    .borrow()
-                    .as_ref()
-                    // SAFETY: created from Rc, so cannot be null
-                    .map(|prev| unsafe { NonNull::new_unchecked(Rc::as_ptr(prev) as *mut _) })
-            },
-            _marker: PhantomData,
-        }
+        EnvLayerIter { env: Some(self) }
     }
 
     /// Creates an iterator that visits all elements from the Environment, from the oldest layer to
@@ -122,13 +92,9 @@ impl<K: Hash + Eq, V: PartialEq> Environment<K, V> {
     /// hashmap would have the same values as the Environment. The element iterator type is `(&'env
     /// K, &'env V)`, with `'env` being the lifetime of the Environment.
     pub fn iter_elems(&self) -> EnvElemIter<'_, K, V> {
-        let mut env: Vec<NonNull<HashMap<K, V>>> = self
-            .iter_layers()
-            // SAFETY: Rc::as_ptr never returnes null
-            .map(|hmap| unsafe { NonNull::new_unchecked(Rc::as_ptr(hmap) as *mut _) })
-            .collect();
-        // SAFETY: by design, env cannot be empty, and coming from an Rc, it is well aligned and initialized
-        let current_map = unsafe { env.pop().unwrap().as_ref() }.iter();
+        let mut env: Vec<&HashMap<K, V>> = self.iter_layers().map(|hmap| hmap.as_ref()).collect();
+        // unwrap(): there's always at least one layer, the current one
+        let current_map = env.pop().unwrap().iter();
         EnvElemIter { env, current_map }
     }
 
@@ -146,16 +112,42 @@ impl<K: Hash + Eq, V: PartialEq> Environment<K, V> {
         }
     }
 
-    /// Checks if `current` has been cloned. If it has, it is present both in current and in
-    /// previous, making it Rc strong count bigger than 1.
-    fn was_cloned(&self) -> bool {
-        Rc::strong_count(&self.current) > 1
+    /// Returns a mutable reference to the current layer, materializing a new private layer first
+    /// if the current one is shared with other environments.
+    fn current_mut(&mut self) -> &mut HashMap<K, V> {
+        if Rc::get_mut(&mut self.current).is_none() {
+            self.materialize_layer();
+        }
+
+        // unwrap(): either the layer wasn't shared, or `materialize_layer` just allocated a fresh
+        // one that we own exclusively.
+        Rc::get_mut(&mut self.current).unwrap()
+    }
+
+    /// Freezes the current layer by pushing it on top of the previous layers (unless it's empty,
+    /// in which case it's just dropped), and starts a new, empty, private current layer.
+    fn materialize_layer(&mut self) {
+        increment!("Environment::materialize_layer");
+
+        let frozen = std::mem::replace(&mut self.current, Rc::new(HashMap::new()));
+
+        if !frozen.is_empty() {
+            sample!(
+                "Environment.curr_layer_size_at_materialize",
+                frozen.len() as f64
+            );
+
+            self.previous = Some(Rc::new(Environment {
+                current: frozen,
+                previous: self.previous.take(),
+            }));
+        }
     }
 
     /// Checks quickly if two environments are obviously equal (when their components are
     /// physically equal as pointers or obviously equal such as being both empty).
     pub(crate) fn ptr_eq(this: &Self, that: &Self) -> bool {
-        let prev_layers_eq = match (&*this.previous.borrow(), &*that.previous.borrow()) {
+        let prev_layers_eq = match (&this.previous, &that.previous) {
             (Some(ptr_this), Some(ptr_that)) => Rc::ptr_eq(ptr_this, ptr_that),
             (None, None) => true,
             _ => false,
@@ -172,19 +164,14 @@ impl<K: Hash + Eq, V: PartialEq> FromIterator<(K, V)> for Environment<K, V> {
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
         Self {
             current: Rc::new(HashMap::from_iter(iter)),
-            previous: RefCell::new(None),
+            previous: None,
         }
     }
 }
 
 impl<K: Hash + Eq, V: PartialEq> Extend<(K, V)> for Environment<K, V> {
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
-        // if can mut. borrow current, then we just extend, otherwise it means
-        // it was cloned, and we recreate a new map from iter for current
-        match Rc::get_mut(&mut self.current) {
-            Some(current) => current.extend(iter),
-            None => self.current = Rc::new(HashMap::from_iter(iter)),
-        }
+        self.current_mut().extend(iter)
     }
 }
 
@@ -195,25 +182,16 @@ impl<K: Hash + Eq, V: PartialEq> Extend<(K, V)> for Environment<K, V> {
 /// [`iter_layers`]: Environment::iter_layers
 ///
 pub struct EnvLayerIter<'a, K: 'a + Hash + Eq, V: 'a + PartialEq> {
-    env: Option<NonNull<Environment<K, V>>>,
-    _marker: PhantomData<&'a Environment<K, V>>,
+    env: Option<&'a Environment<K, V>>,
 }
 
 impl<'a, K: 'a + Hash + Eq, V: 'a + PartialEq> Iterator for EnvLayerIter<'a, K, V> {
     type Item = &'a Rc<HashMap<K, V>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // SAFETY: NonNull being in an option, we know it cannot be null and can be dereferenceable
-        self.env.map(|env| unsafe {
-            let res = &env.as_ref().current;
-            self.env = env
-                .as_ref()
-                .previous
-                .borrow()
-                .as_ref()
-                // SAFETY: can safely create NonNull from Rc
-                .map(|prev| NonNull::new_unchecked(Rc::as_ptr(prev) as *mut _));
-            res
+        self.env.map(|env| {
+            self.env = env.previous.as_deref();
+            &env.current
         })
     }
 }
@@ -224,7 +202,7 @@ impl<'a, K: 'a + Hash + Eq, V: 'a + PartialEq> Iterator for EnvLayerIter<'a, K,
 /// Created by the [`Environment::iter_elems`] method.
 ///
 pub struct EnvElemIter<'a, K: 'a + Hash + Eq, V: 'a + PartialEq> {
-    env: Vec<NonNull<HashMap<K, V>>>,
+    env: Vec<&'a HashMap<K, V>>,
     current_map: std::collections::hash_map::Iter<'a, K, V>,
 }
 
@@ -235,7 +213,7 @@ impl<'a, K: 'a + Hash + Eq, V: 'a + PartialEq> Iterator for EnvElemIter<'a, K, V
         loop {
             match self.current_map.next() {
                 Some(res) => return Some(res),
-                None => self.current_map = unsafe { self.env.pop()?.as_ref() }.iter(),
+                None => self.current_map = self.env.pop()?.iter(),
             }
         }
     }
@@ -266,7 +244,7 @@ mod tests {
 
     impl<K: Hash + Eq, V: PartialEq> Environment<K, V> {
         pub fn depth(&self) -> usize {
-            1 + self.previous.borrow().as_ref().map_or(0, |p| p.depth())
+            1 + self.previous.as_ref().map_or(0, |p| p.depth())
         }
     }
 
@@ -303,15 +281,22 @@ mod tests {
         let mut env_base = Environment::<u8, char>::new();
         assert_eq!(env_base.depth(), 1);
 
+        // Cloning doesn't create any new layer
         let mut env2 = env_base.clone();
         assert_eq!(env_base.depth(), 1);
-        assert_eq!(env_base.depth(), 1);
+        assert_eq!(env2.depth(), 1);
 
+        // The shared current layer is empty, so it's just replaced with a private one
         env2.insert(1, 'a');
         let env3 = env2.clone();
         assert_eq!(env_base.depth(), 1);
+        assert_eq!(env2.depth(), 1);
+        assert_eq!(env3.depth(), 1);
+
+        // The current layer of `env2` is shared with `env3`: inserting materializes a new layer
+        env2.insert(2, 'b');
         assert_eq!(env2.depth(), 2);
-        assert_eq!(env3.depth(), 2);
+        assert_eq!(env3.depth(), 1);
 
         let env4 = env_base.clone();
         assert_eq!(env_base.depth(), 1);
@@ -320,15 +305,34 @@ mod tests {
         env_base.insert(1, 'z');
         assert_eq!(env_base.depth(), 1);
         assert_eq!(env2.depth(), 2);
-        assert_eq!(env3.depth(), 2);
+        assert_eq!(env3.depth(), 1);
         assert_eq!(env4.depth(), 1);
 
         let env5 = env_base.clone();
+        assert_eq!(env_base.depth(), 1);
+        assert_eq!(env5.depth(), 1);
+
+        env_base.insert(2, 'y');
         assert_eq!(env_base.depth(), 2);
         assert_eq!(env2.depth(), 2);
-        assert_eq!(env3.depth(), 2);
+        assert_eq!(env3.depth(), 1);
         assert_eq!(env4.depth(), 1);
-        assert_eq!(env5.depth(), 2);
+        assert_eq!(env5.depth(), 1);
+        assert_eq!(env5.get(&2), None);
+    }
+
+    #[test]
+    fn test_unshared_layer_updated_in_place() {
+        let mut env_base = Environment::<u8, char>::new();
+        env_base.insert(1, 'a');
+
+        // Once the clone is dropped, the current layer isn't shared anymore and can be updated in
+        // place.
+        drop(env_base.clone());
+        env_base.insert(2, 'b');
+        assert_eq!(env_base.depth(), 1);
+        assert_eq!(env_base.get(&1), Some(&'a'));
+        assert_eq!(env_base.get(&2), Some(&'b'));
     }
 
     #[test]
@@ -339,11 +343,11 @@ mod tests {
         assert_eq!(env_base.iter_layers().count(), 1);
         assert_eq!(env_base.iter_layers().next().unwrap().get(&1), Some(&'a'));
         assert_eq!(env_base.iter_layers().nth(1), None);
-        let _ = env_base.clone();
+        let _env_clone1 = env_base.clone();
         assert_eq!(env_base.iter_layers().count(), 1);
         env_base.insert(2, 'b');
         assert_eq!(env_base.iter_layers().count(), 2);
-        let _ = env_base.clone();
+        let _env_clone2 = env_base.clone();
         let mut iter = env_base.iter_layers();
         let map1 = iter.next().unwrap();
         assert_eq!(map1.get(&2), Some(&'b'));
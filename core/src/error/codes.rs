@@ -0,0 +1,379 @@
+//! Stable codes identifying the kinds of errors and warnings.
+//!
+//! Each variant of the error types of this module, as well as each warning, has a code such as
+//! `E0001`, which is attached to its main diagnostic and shown by the error messages. Codes never
+//! change once assigned, so that tools can filter or suppress errors by code, and users can search
+//! for them. Errors wrapping another error, such
+//! as [super::EvalError::ParseError], use the code of the wrapped error.
+//!
+//! Codes are grouped by error type: `E00xx` for evaluation errors, `E01xx` for typechecking
+//! errors, `E02xx` for parse errors, `E03xx` for import errors, `E04xx` for export errors,
+//! `E05xx` for access errors, `E06xx` for REPL errors, `E07xx` for IO errors and `Wxxxx` for
+//! warnings.
+use super::*;
+
+macro_rules! error_codes {
+    ($($variant:ident = $code:literal, $summary:literal;)*) => {
+        /// The code of a kind of error or warning. See the [module documentation](self).
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum ErrorCode {
+            $(
+                #[doc = $summary]
+                $variant,
+            )*
+        }
+
+        impl ErrorCode {
+            /// All the error codes, in the order of their codes.
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$variant),*];
+
+            /// The code, such as `E0001`.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$variant => $code,)*
+                }
+            }
+
+            /// A short description of the kind of error.
+            pub fn summary(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$variant => $summary,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    Blame = "E0001", "A contract was broken.";
+    MissingFieldDef = "E0002", "A field required by a contract has no definition.";
+    DynamicTypeError = "E0003", "A value has an unexpected type at run time.";
+    UnaryPrimopTypeError = "E0004", "The argument of a primitive operation has an unexpected type.";
+    NAryPrimopTypeError = "E0005", "An argument of a primitive operation has an unexpected type.";
+    NotAFunction = "E0006", "A value which isn't a function was applied.";
+    FieldMissing = "E0007", "A record field was accessed but doesn't exist.";
+    NotEnoughArgs = "E0008", "A primitive operation was applied to too few arguments.";
+    MergeIncompatibleArgs = "E0009", "Two values which can't be merged were merged.";
+    UnboundIdentifier = "E0010", "A variable is used but isn't defined.";
+    InfiniteRecursion = "E0011", "A value depends on itself.";
+    DeserializationError = "E0012", "A string couldn't be deserialized.";
+    IllegalPolymorphicTailAccess = "E0013", "A polymorphic record tail was accessed.";
+    IncomparableValues = "E0014", "Two values which can't be compared were compared.";
+    NonExhaustiveEnumMatch = "E0015", "No branch of an enum match matches the value.";
+    NonExhaustiveMatch = "E0016", "No branch of a match expression matches the value.";
+    FailedDestructuring = "E0017", "A value doesn't match a destructuring pattern.";
+    QueryNonRecord = "E0018", "A query goes through a value which isn't a record.";
+    InternalError = "E0019", "An unexpected internal error of the interpreter.";
+    OtherEvalError = "E0020", "A generic evaluation error.";
+    Cancelled = "E0021", "The evaluation was cancelled.";
+    NonReproducible = "E0022", "A machine-dependent construct was used in reproducible mode.";
+    CapabilityDenied = "E0023", "An action was denied by the capability policy.";
+    ResourceExhausted = "E0024", "The evaluation exceeded a resource limit.";
+    StackOverflow = "E0025", "The evaluation stack exceeded its maximal depth.";
+    TypecheckUnboundIdentifier = "E0101", "A variable is used but isn't defined.";
+    MissingRow = "E0102", "A record or enum type lacks an expected row.";
+    MissingDynTail = "E0103", "A record type lacks an expected `Dyn` tail.";
+    ExtraRow = "E0104", "A record or enum type has an unexpected row.";
+    ExtraDynTail = "E0105", "A record type has an unexpected `Dyn` tail.";
+    ForallParametricityViolation = "E0106", "A polymorphic type variable escapes its scope.";
+    UnboundTypeVariable = "E0107", "A type variable is used but isn't bound.";
+    TypeMismatch = "E0108", "Two types don't match.";
+    RecordRowMismatch = "E0109", "The types of a record field don't match.";
+    EnumRowMismatch = "E0110", "The types of an enum variant don't match.";
+    RecordRowConflict = "E0111", "A record row conflicts with a constraint.";
+    EnumRowConflict = "E0112", "An enum row conflicts with a constraint.";
+    ArrowTypeMismatch = "E0113", "Two function types don't match.";
+    ContractTypeInTermPosition = "E0114", "A contract type is used as an expression.";
+    VarLevelMismatch = "E0115", "A type variable is unified outside of its scope.";
+    OrPatternVarsMismatch = "E0116", "The alternatives of an or-pattern bind different variables.";
+    UnexpectedEof = "E0201", "The input ended unexpectedly.";
+    UnexpectedToken = "E0202", "The parser encountered an unexpected token.";
+    ExtraToken = "E0203", "The input continues after a complete expression.";
+    UnmatchedCloseBrace = "E0204", "A closing brace has no matching opening brace.";
+    InvalidEscapeSequence = "E0205", "A string contains an invalid escape sequence.";
+    InvalidAsciiEscapeCode = "E0206", "A string contains an invalid ASCII escape code.";
+    StringDelimiterMismatch = "E0207", "The delimiters of a string don't match.";
+    ExternalFormatError = "E0208", "A JSON, YAML or TOML input couldn't be parsed.";
+    UnboundTypeVariables = "E0209", "Type variables are used but aren't bound.";
+    InvalidRecordType = "E0210", "A record literal can't be interpreted as a record type.";
+    RecursiveLetPattern = "E0211", "A recursive let-binding uses a pattern.";
+    PatternInLetBlock = "E0212", "A let block uses a pattern.";
+    TypeVariableKindMismatch = "E0213", "A type variable is used with different kinds.";
+    TypedFieldWithoutDefinition = "E0214", "A field has a type annotation but no definition.";
+    InterpolationInStaticPath = "E0215", "A static field path contains string interpolation.";
+    DuplicateIdentInRecordPattern = "E0216", "A record pattern binds the same variable twice.";
+    DuplicateIdentInLetBlock = "E0217", "A let block binds the same variable twice.";
+    DisabledFeature = "E0218", "A disabled feature of the language is used.";
+    InvalidContract = "E0219", "A term which can't be a contract is used as a contract.";
+    InvalidImportFormat = "E0220", "An import specifies an unknown format.";
+    ImportIo = "E0301", "An imported file couldn't be read.";
+    ImportParse = "E0302", "An imported file couldn't be parsed.";
+    ImportNonReproducible = "E0303", "An absolute import is used in reproducible mode.";
+    ImportCapabilityDenied = "E0304", "An import was denied by the capability policy.";
+    UnsupportedNull = "E0401", "`null` can't be exported to the target format.";
+    NotAString = "E0402", "A value exported as raw text isn't a string.";
+    NonSerializable = "E0403", "A value can't be serialized.";
+    NoDocumentation = "E0404", "No documentation was found.";
+    NumberOutOfRange = "E0405", "A number can't be represented in the target format.";
+    OtherExportError = "E0406", "A generic export error.";
+    NotARecord = "E0407", "A value exported to a format requiring a record isn't one.";
+    UnsupportedNesting = "E0408", "A nested value can't be exported to the target format.";
+    Deserialization = "E0409", "An exported value can't be deserialized to a Rust type.";
+    AccessTypeMismatch = "E0501", "A value accessed from Rust has an unexpected type.";
+    AccessMissingField = "E0502", "A field accessed from Rust doesn't exist.";
+    AccessInvalidNumber = "E0503", "A number accessed from Rust can't be converted.";
+    ReplUnknownCommand = "E0601", "An unknown REPL command was used.";
+    ReplMissingArg = "E0602", "A REPL command lacks an argument.";
+    Io = "E0701", "An input or output operation failed.";
+    DeprecatedField = "W0001", "A deprecated field is used.";
+}
+
+impl ErrorCode {
+    /// Return the error code whose code is `code`, such as `E0001`, if any.
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|error_code| error_code.as_str() == code)
+    }
+
+    /// Attach the code to the first diagnostic of `diagnostics`, which is the main one, unless it
+    /// already has a code.
+    pub(crate) fn attach(
+        self,
+        mut diagnostics: Vec<Diagnostic<DiagnosticFileId>>,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
+        if let Some(diagnostic) = diagnostics.first_mut() {
+            diagnostic
+                .code
+                .get_or_insert_with(|| self.as_str().to_owned());
+        }
+
+        diagnostics
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Error {
+    /// The code of the error, or `None` for an empty list of parse errors.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Error::EvalError(error) => Some(error.code()),
+            Error::TypecheckError(error) => Some(error.code()),
+            Error::ParseErrors(errors) => errors.errors.first().map(ParseError::code),
+            Error::ImportError(error) => Some(error.code()),
+            Error::ExportError(error) => Some(error.code()),
+            Error::IOError(_) => Some(ErrorCode::Io),
+            Error::ReplError(error) => Some(error.code()),
+        }
+    }
+}
+
+impl EvalError {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            EvalError::ParseError(error) => error.code(),
+            EvalError::SerializationError(error) => error.code(),
+            EvalError::DeniedWarning(warning) => warning.code(),
+            EvalError::BlameError { .. } => ErrorCode::Blame,
+            EvalError::MissingFieldDef { .. } => ErrorCode::MissingFieldDef,
+            EvalError::TypeError { .. } => ErrorCode::DynamicTypeError,
+            EvalError::UnaryPrimopTypeError { .. } => ErrorCode::UnaryPrimopTypeError,
+            EvalError::NAryPrimopTypeError { .. } => ErrorCode::NAryPrimopTypeError,
+            EvalError::NotAFunc { .. } => ErrorCode::NotAFunction,
+            EvalError::FieldMissing { .. } => ErrorCode::FieldMissing,
+            EvalError::NotEnoughArgs { .. } => ErrorCode::NotEnoughArgs,
+            EvalError::MergeIncompatibleArgs { .. } => ErrorCode::MergeIncompatibleArgs,
+            EvalError::UnboundIdentifier { .. } => ErrorCode::UnboundIdentifier,
+            EvalError::InfiniteRecursion { .. } => ErrorCode::InfiniteRecursion,
+            EvalError::DeserializationError { .. } => ErrorCode::DeserializationError,
+            EvalError::IllegalPolymorphicTailAccess { .. } => {
+                ErrorCode::IllegalPolymorphicTailAccess
+            }
+            EvalError::IncomparableValues { .. } => ErrorCode::IncomparableValues,
+            EvalError::NonExhaustiveEnumMatch { .. } => ErrorCode::NonExhaustiveEnumMatch,
+            EvalError::NonExhaustiveMatch { .. } => ErrorCode::NonExhaustiveMatch,
+            EvalError::FailedDestructuring { .. } => ErrorCode::FailedDestructuring,
+            EvalError::QueryNonRecord { .. } => ErrorCode::QueryNonRecord,
+            EvalError::InternalError { .. } => ErrorCode::InternalError,
+            EvalError::Other { .. } => ErrorCode::OtherEvalError,
+            EvalError::Cancelled { .. } => ErrorCode::Cancelled,
+            EvalError::NonReproducible { .. } => ErrorCode::NonReproducible,
+            EvalError::CapabilityDenied { .. } => ErrorCode::CapabilityDenied,
+            EvalError::ResourceExhausted { .. } => ErrorCode::ResourceExhausted,
+            EvalError::StackOverflow { .. } => ErrorCode::StackOverflow,
+        }
+    }
+}
+
+impl Warning {
+    /// The code of the warning.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Warning::EvalWarning(warning) => warning.code(),
+        }
+    }
+}
+
+impl EvalWarning {
+    /// The code of the warning.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            EvalWarning::DeprecatedField { .. } => ErrorCode::DeprecatedField,
+        }
+    }
+}
+
+impl TypecheckError {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            TypecheckError::UnboundIdentifier { .. } => ErrorCode::TypecheckUnboundIdentifier,
+            TypecheckError::MissingRow { .. } => ErrorCode::MissingRow,
+            TypecheckError::MissingDynTail { .. } => ErrorCode::MissingDynTail,
+            TypecheckError::ExtraRow { .. } => ErrorCode::ExtraRow,
+            TypecheckError::ExtraDynTail { .. } => ErrorCode::ExtraDynTail,
+            TypecheckError::ForallParametricityViolation { .. } => {
+                ErrorCode::ForallParametricityViolation
+            }
+            TypecheckError::UnboundTypeVariable { .. } => ErrorCode::UnboundTypeVariable,
+            TypecheckError::TypeMismatch { .. } => ErrorCode::TypeMismatch,
+            TypecheckError::RecordRowMismatch { .. } => ErrorCode::RecordRowMismatch,
+            TypecheckError::EnumRowMismatch { .. } => ErrorCode::EnumRowMismatch,
+            TypecheckError::RecordRowConflict { .. } => ErrorCode::RecordRowConflict,
+            TypecheckError::EnumRowConflict { .. } => ErrorCode::EnumRowConflict,
+            TypecheckError::ArrowTypeMismatch { .. } => ErrorCode::ArrowTypeMismatch,
+            TypecheckError::CtrTypeInTermPos { .. } => ErrorCode::ContractTypeInTermPosition,
+            TypecheckError::VarLevelMismatch { .. } => ErrorCode::VarLevelMismatch,
+            TypecheckError::OrPatternVarsMismatch { .. } => ErrorCode::OrPatternVarsMismatch,
+        }
+    }
+}
+
+impl ParseError {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ParseError::UnexpectedEOF { .. } => ErrorCode::UnexpectedEof,
+            ParseError::UnexpectedToken { .. } => ErrorCode::UnexpectedToken,
+            ParseError::ExtraToken { .. } => ErrorCode::ExtraToken,
+            ParseError::UnmatchedCloseBrace { .. } => ErrorCode::UnmatchedCloseBrace,
+            ParseError::InvalidEscapeSequence { .. } => ErrorCode::InvalidEscapeSequence,
+            ParseError::InvalidAsciiEscapeCode { .. } => ErrorCode::InvalidAsciiEscapeCode,
+            ParseError::StringDelimiterMismatch { .. } => ErrorCode::StringDelimiterMismatch,
+            ParseError::ExternalFormatError { .. } => ErrorCode::ExternalFormatError,
+            ParseError::UnboundTypeVariables { .. } => ErrorCode::UnboundTypeVariables,
+            ParseError::InvalidRecordType { .. } => ErrorCode::InvalidRecordType,
+            ParseError::RecursiveLetPattern { .. } => ErrorCode::RecursiveLetPattern,
+            ParseError::PatternInLetBlock { .. } => ErrorCode::PatternInLetBlock,
+            ParseError::TypeVariableKindMismatch { .. } => ErrorCode::TypeVariableKindMismatch,
+            ParseError::TypedFieldWithoutDefinition { .. } => {
+                ErrorCode::TypedFieldWithoutDefinition
+            }
+            ParseError::InterpolationInStaticPath { .. } => ErrorCode::InterpolationInStaticPath,
+            ParseError::DuplicateIdentInRecordPattern { .. } => {
+                ErrorCode::DuplicateIdentInRecordPattern
+            }
+            ParseError::DuplicateIdentInLetBlock { .. } => ErrorCode::DuplicateIdentInLetBlock,
+            ParseError::DisabledFeature { .. } => ErrorCode::DisabledFeature,
+            ParseError::InvalidContract { .. } => ErrorCode::InvalidContract,
+            ParseError::InvalidImportFormat { .. } => ErrorCode::InvalidImportFormat,
+        }
+    }
+}
+
+impl ImportError {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ImportError::IOError { .. } => ErrorCode::ImportIo,
+            ImportError::ParseErrors(errors, _) => errors
+                .errors
+                .first()
+                .map_or(ErrorCode::ImportParse, ParseError::code),
+            ImportError::NonReproducible { .. } => ErrorCode::ImportNonReproducible,
+            ImportError::CapabilityDenied { .. } => ErrorCode::ImportCapabilityDenied,
+        }
+    }
+}
+
+impl ExportError {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        self.data.code()
+    }
+}
+
+impl ExportErrorData {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ExportErrorData::UnsupportedNull { .. } => ErrorCode::UnsupportedNull,
+            ExportErrorData::NotAString { .. } => ErrorCode::NotAString,
+            ExportErrorData::NotARecord { .. } => ErrorCode::NotARecord,
+            ExportErrorData::UnsupportedNesting { .. } => ErrorCode::UnsupportedNesting,
+            ExportErrorData::Deserialization { .. } => ErrorCode::Deserialization,
+            ExportErrorData::NonSerializable { .. } => ErrorCode::NonSerializable,
+            ExportErrorData::NoDocumentation { .. } => ErrorCode::NoDocumentation,
+            ExportErrorData::NumberOutOfRange { .. } => ErrorCode::NumberOutOfRange,
+            ExportErrorData::Other { .. } => ErrorCode::OtherExportError,
+        }
+    }
+}
+
+impl AccessError {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        self.data.code()
+    }
+}
+
+impl AccessErrorData {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AccessErrorData::TypeMismatch { .. } => ErrorCode::AccessTypeMismatch,
+            AccessErrorData::MissingField { .. } => ErrorCode::AccessMissingField,
+            AccessErrorData::InvalidNumber { .. } => ErrorCode::AccessInvalidNumber,
+        }
+    }
+}
+
+impl ReplError {
+    /// The code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ReplError::UnknownCommand { .. } => ErrorCode::ReplUnknownCommand,
+            ReplError::MissingArg { .. } => ErrorCode::ReplMissingArg,
+            ReplError::InvalidQueryPath(error) => error.code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn codes_are_unique() {
+        let codes: HashSet<_> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+    }
+
+    #[test]
+    fn from_code_roundtrip() {
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_code(code.as_str()), Some(*code));
+        }
+
+        assert_eq!(ErrorCode::from_code("E9999"), None);
+    }
+}
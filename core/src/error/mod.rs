@@ -5,16 +5,18 @@
 use codespan::ByteIndex;
 pub use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle};
 
-use codespan_reporting::files::Files as _;
+use codespan_reporting::diagnostic::Severity;
+use codespan_reporting::files::{self as codespan_files, Files as _};
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream, WriteColor};
 use lalrpop_util::ErrorRecovery;
 use malachite::num::conversion::traits::ToSci;
+use std::fmt;
 
 use crate::{
     cache::Cache,
-    eval::callstack::CallStack,
+    eval::{callstack::CallStack, limits::Resource},
     files::{FileId, Files},
-    identifier::LocIdent,
+    identifier::{Ident, LocIdent},
     label::{
         self,
         ty_path::{self, PathSpan},
@@ -27,15 +29,19 @@ use crate::{
         utils::mk_span,
     },
     position::{RawSpan, TermPos},
+    pretty::to_string_redacted,
     repl,
     serialize::{ExportFormat, NickelPointer},
-    term::{pattern::Pattern, record::FieldMetadata, Number, RichTerm, Term},
+    term::{pattern::Pattern, record::FieldMetadata, Number, RecordOpKind, RichTerm, Term},
     typ::{EnumRow, RecordRow, Type, TypeF, VarKindDiscriminant},
 };
 
+pub mod codes;
 pub mod report;
 pub mod suggest;
 
+use codes::ErrorCode;
+
 /// A general error occurring during either parsing or evaluation.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
@@ -64,7 +70,7 @@ pub enum EvalError {
     /// A field required by a record contract is missing a definition.
     MissingFieldDef {
         id: LocIdent,
-        metadata: FieldMetadata,
+        metadata: Box<FieldMetadata>,
         pos_record: TermPos,
         pos_access: TermPos,
     },
@@ -129,7 +135,14 @@ pub enum EvalError {
         merge_label: MergeLabel,
     },
     /// An unbound identifier was referenced.
-    UnboundIdentifier(LocIdent, TermPos),
+    UnboundIdentifier {
+        /// The unbound identifier.
+        id: LocIdent,
+        /// The position of the reference.
+        pos: TermPos,
+        /// The identifiers in scope at the reference, used to suggest similar identifiers.
+        in_scope: suggest::InScope,
+    },
     /// An element in the evaluation Cache was entered during its own update.
     InfiniteRecursion(CallStack, TermPos),
     /// A serialization error occurred during a call to the builtin `serialize`.
@@ -189,6 +202,87 @@ pub enum EvalError {
     InternalError(String, TermPos),
     /// Errors occurring rarely enough to not deserve a dedicated variant.
     Other(String, TermPos),
+    /// The evaluation has been cancelled through a
+    /// [CancellationToken](crate::eval::interrupt::CancellationToken). The position is the one of
+    /// the term being evaluated at that point.
+    Cancelled(TermPos),
+    /// A warning has been emitted while warnings are denied. See
+    /// [VirtualMachine::set_deny_warnings](crate::eval::VirtualMachine::set_deny_warnings).
+    DeniedWarning(EvalWarning),
+    /// A construct whose result may differ across runs or machines has been used in reproducible
+    /// mode. See [VirtualMachine::set_reproducible](crate::eval::VirtualMachine::set_reproducible).
+    NonReproducible {
+        /// A description of the rejected construct.
+        construct: String,
+        /// Why the construct isn't reproducible.
+        reason: String,
+        pos: TermPos,
+    },
+    /// An action has been denied by the capability policy. See
+    /// [Program::set_policy](crate::program::Program::set_policy).
+    CapabilityDenied {
+        /// A description of the denied action.
+        action: String,
+        /// Why the action has been denied.
+        reason: String,
+        pos: TermPos,
+    },
+    /// A resource limit has been exceeded. See
+    /// [VirtualMachine::set_resource_limits](crate::eval::VirtualMachine::set_resource_limits).
+    ResourceExhausted {
+        resource: Resource,
+        /// The position of the term being evaluated when the limit was exceeded.
+        pos: TermPos,
+        call_stack: CallStack,
+    },
+    /// The evaluation stack exceeded its maximal depth, typically because of a deep or infinite
+    /// recursion. See [ResourceLimits::max_depth](crate::eval::limits::ResourceLimits::max_depth).
+    StackOverflow {
+        max_depth: usize,
+        /// The position of the term being evaluated when the limit was exceeded.
+        pos: TermPos,
+        call_stack: CallStack,
+    },
+}
+
+/// A non-fatal issue detected during evaluation. Warnings are collected by the virtual machine
+/// (see [VirtualMachine::take_warnings](crate::eval::VirtualMachine::take_warnings)).
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalWarning {
+    /// A field marked as `deprecated` has been accessed, defined by a merge, or exported.
+    DeprecatedField {
+        /// The field, with the position of its declaration.
+        id: LocIdent,
+        /// The deprecation message.
+        message: String,
+        /// The position of the access or of the definition merged with the declaration, or
+        /// [TermPos::None] if the field has been exported.
+        pos_access: TermPos,
+    },
+}
+
+/// A non-fatal issue, reported without aborting the current operation. This is the counterpart
+/// of [Error] for warnings: warnings are collected by a [Program](crate::program::Program) (see
+/// [Program::take_warnings](crate::program::Program::take_warnings)) and reported as diagnostics
+/// with the warning severity. When warnings are denied, a warning is turned into the
+/// corresponding error (see [Error::from]).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    EvalWarning(EvalWarning),
+}
+
+impl From<EvalWarning> for Warning {
+    fn from(warning: EvalWarning) -> Warning {
+        Warning::EvalWarning(warning)
+    }
+}
+
+impl From<Warning> for Error {
+    fn from(warning: Warning) -> Error {
+        match warning {
+            Warning::EvalWarning(warning) => Error::EvalError(EvalError::DeniedWarning(warning)),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -222,7 +316,12 @@ pub const UNKNOWN_SOURCE_NAME: &str = "<unknown> (generated by evaluation)";
 #[derive(Debug, PartialEq, Clone)]
 pub enum TypecheckError {
     /// An unbound identifier was referenced.
-    UnboundIdentifier { id: LocIdent, pos: TermPos },
+    UnboundIdentifier {
+        id: LocIdent,
+        pos: TermPos,
+        /// The identifiers in scope at the reference, used to suggest similar identifiers.
+        in_scope: suggest::InScope,
+    },
     /// A specific row was expected to be in the type of an expression, but was not.
     MissingRow {
         id: LocIdent,
@@ -459,10 +558,10 @@ impl From<Vec<ParseError>> for ParseErrors {
 }
 
 impl IntoDiagnostics for ParseErrors {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
         self.errors
             .into_iter()
-            .flat_map(|e| e.into_diagnostics(files))
+            .map(|e| e.into_diagnostics(files))
             .collect()
     }
 }
@@ -590,6 +689,20 @@ pub enum ImportError {
         /* error */ ParseErrors,
         /* import position */ TermPos,
     ),
+    /// An import has been rejected in reproducible mode. See
+    /// [Program::set_reproducible](crate::program::Program::set_reproducible).
+    NonReproducible(
+        /* imported file */ String,
+        /* reason */ String,
+        /* import position */ TermPos,
+    ),
+    /// An import has been denied by the capability policy. See
+    /// [Program::set_policy](crate::program::Program::set_policy).
+    CapabilityDenied(
+        /* imported file */ String,
+        /* reason */ String,
+        /* import position */ TermPos,
+    ),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -608,6 +721,11 @@ pub enum ExportErrorData {
     UnsupportedNull(ExportFormat, RichTerm),
     /// Tried exporting something else than a `String` to raw format.
     NotAString(RichTerm),
+    /// Tried exporting something else than a record to a format which requires one at the top
+    /// level.
+    NotARecord(ExportFormat, RichTerm),
+    /// Encountered a nested record or array for a format that doesn't support them.
+    UnsupportedNesting(ExportFormat, RichTerm),
     /// A term contains constructs that cannot be serialized.
     NonSerializable(RichTerm),
     /// No exportable documentation was found when requested.
@@ -617,6 +735,12 @@ pub enum ExportErrorData {
         term: RichTerm,
         value: Number,
     },
+    /// A value can't be deserialized to the expected Rust type. See
+    /// [crate::deserialize::from_term].
+    Deserialization {
+        message: String,
+        term: RichTerm,
+    },
     Other(String),
 }
 
@@ -629,6 +753,67 @@ impl From<ExportErrorData> for ExportError {
     }
 }
 
+/// An error occurring when extracting a Rust value from an evaluated term. See
+/// [crate::term::access].
+#[derive(Debug, PartialEq, Clone)]
+pub struct AccessError {
+    /// The path to the value where the error occurred, from the term the access started from.
+    pub path: NickelPointer,
+    /// The cause of the error.
+    pub data: AccessErrorData,
+}
+
+/// The cause of an [AccessError].
+#[derive(Debug, PartialEq, Clone)]
+pub enum AccessErrorData {
+    /// The value doesn't have the expected type, or isn't evaluated.
+    TypeMismatch {
+        expected: &'static str,
+        value: RichTerm,
+    },
+    /// The record doesn't have the field, or the field doesn't have a value.
+    MissingField { field: Ident, record: RichTerm },
+    /// The number can't be represented by the expected Rust type.
+    InvalidNumber {
+        expected: &'static str,
+        value: RichTerm,
+    },
+}
+
+impl From<AccessErrorData> for AccessError {
+    fn from(data: AccessErrorData) -> AccessError {
+        AccessError {
+            path: NickelPointer::new(),
+            data,
+        }
+    }
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.data {
+            AccessErrorData::TypeMismatch { expected, value } => write!(
+                f,
+                "expected a {expected}, got {}",
+                value
+                    .as_ref()
+                    .type_of()
+                    .unwrap_or_else(|| String::from("<unevaluated>"))
+            )?,
+            AccessErrorData::MissingField { field, .. } => write!(f, "missing field `{field}`")?,
+            AccessErrorData::InvalidNumber { expected, value } => {
+                write!(f, "the number {value} can't be represented as a {expected}")?
+            }
+        }
+
+        if !self.path.0.is_empty() {
+            write!(f, " (in `{}`)", self.path)?;
+        }
+
+        Ok(())
+    }
+}
+
 /// A general I/O error, occurring when reading a source file or writing an export.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IOError(pub String);
@@ -714,6 +899,616 @@ impl From<ReplError> for Error {
     }
 }
 
+/// The phase of the execution an [Error] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    Parse,
+    Import,
+    Typecheck,
+    Eval,
+    Export,
+    IO,
+    Repl,
+}
+
+impl ErrorKind {
+    /// A stable, lowercase name for this kind of error, suitable for logs and metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Parse => "parse",
+            ErrorKind::Import => "import",
+            ErrorKind::Typecheck => "typecheck",
+            ErrorKind::Eval => "eval",
+            ErrorKind::Export => "export",
+            ErrorKind::IO => "io",
+            ErrorKind::Repl => "repl",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::EvalError(_) => ErrorKind::Eval,
+            Error::TypecheckError(_) => ErrorKind::Typecheck,
+            Error::ParseErrors(_) => ErrorKind::Parse,
+            Error::ImportError(_) => ErrorKind::Import,
+            Error::ExportError(_) => ErrorKind::Export,
+            Error::IOError(_) => ErrorKind::IO,
+            Error::ReplError(_) => ErrorKind::Repl,
+        }
+    }
+
+    /// The main location of the error, if any. Use [Files::name] and [Files::location] to turn it
+    /// into a file name and a line number.
+    pub fn span(&self) -> Option<RawSpan> {
+        match self {
+            Error::EvalError(err) => err.span(),
+            Error::TypecheckError(err) => err.span(),
+            Error::ParseErrors(errs) => errs.errors.first().and_then(ParseError::span),
+            Error::ImportError(err) => err.span(),
+            Error::ExportError(_) | Error::IOError(_) => None,
+            Error::ReplError(ReplError::InvalidQueryPath(err)) => err.span(),
+            Error::ReplError(_) => None,
+        }
+    }
+
+    /// The file the error comes from, if any.
+    pub fn file_id(&self) -> Option<FileId> {
+        match self {
+            Error::ParseErrors(errs) => errs.errors.first().and_then(ParseError::file_id),
+            _ => self.span().map(|span| span.src_id),
+        }
+    }
+}
+
+impl EvalError {
+    /// The main location of the error, if any.
+    pub fn span(&self) -> Option<RawSpan> {
+        match self {
+            EvalError::BlameError { label, .. }
+            | EvalError::IllegalPolymorphicTailAccess { label, .. } => {
+                label.arg_pos.into_opt().or(Some(label.span))
+            }
+            EvalError::MissingFieldDef {
+                metadata,
+                pos_access,
+                ..
+            } => pos_access.into_opt().or_else(|| metadata.annotation_span()),
+            EvalError::TypeError(_, _, pos, _)
+            | EvalError::UnaryPrimopTypeError { arg_pos: pos, .. }
+            | EvalError::NAryPrimopTypeError { arg_pos: pos, .. }
+            | EvalError::NotAFunc(_, _, pos)
+            | EvalError::FieldMissing { pos_op: pos, .. }
+            | EvalError::NotEnoughArgs(_, _, pos)
+            | EvalError::UnboundIdentifier { pos, .. }
+            | EvalError::InfiniteRecursion(_, pos)
+            | EvalError::DeserializationError(_, _, pos)
+            | EvalError::IncomparableValues { eq_pos: pos, .. }
+            | EvalError::NonExhaustiveEnumMatch { pos, .. }
+            | EvalError::NonExhaustiveMatch { pos, .. }
+            | EvalError::QueryNonRecord { pos, .. }
+            | EvalError::InternalError(_, pos)
+            | EvalError::Other(_, pos)
+            | EvalError::Cancelled(pos)
+            | EvalError::NonReproducible { pos, .. }
+            | EvalError::CapabilityDenied { pos, .. }
+            | EvalError::ResourceExhausted { pos, .. }
+            | EvalError::StackOverflow { pos, .. } => pos.into_opt(),
+            EvalError::DeniedWarning(warning) => warning.span(),
+            EvalError::MergeIncompatibleArgs { merge_label, .. } => Some(merge_label.span),
+            EvalError::FailedDestructuring { pattern, .. } => pattern.pos.into_opt(),
+            EvalError::ParseError(err) => err.span(),
+            EvalError::SerializationError(_) => None,
+        }
+    }
+}
+
+impl EvalWarning {
+    /// The main location of the warning, if any.
+    pub fn span(&self) -> Option<RawSpan> {
+        match self {
+            EvalWarning::DeprecatedField { id, pos_access, .. } => {
+                pos_access.into_opt().or(id.pos.into_opt())
+            }
+        }
+    }
+}
+
+impl Warning {
+    /// The main location of the warning, if any.
+    pub fn span(&self) -> Option<RawSpan> {
+        match self {
+            Warning::EvalWarning(warning) => warning.span(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::EvalWarning(warning) => write!(f, "{warning}"),
+        }
+    }
+}
+
+impl fmt::Display for EvalWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalWarning::DeprecatedField { id, message, .. } => {
+                write!(f, "field `{id}` is deprecated: {message}")
+            }
+        }
+    }
+}
+
+impl TypecheckError {
+    /// The main location of the error, if any.
+    pub fn span(&self) -> Option<RawSpan> {
+        match self {
+            TypecheckError::UnboundIdentifier { pos, .. }
+            | TypecheckError::MissingRow { pos, .. }
+            | TypecheckError::MissingDynTail { pos, .. }
+            | TypecheckError::ExtraRow { pos, .. }
+            | TypecheckError::ExtraDynTail { pos, .. }
+            | TypecheckError::ForallParametricityViolation { pos, .. }
+            | TypecheckError::TypeMismatch { pos, .. }
+            | TypecheckError::RecordRowMismatch { pos, .. }
+            | TypecheckError::EnumRowMismatch { pos, .. }
+            | TypecheckError::RecordRowConflict { pos, .. }
+            | TypecheckError::EnumRowConflict { pos, .. }
+            | TypecheckError::ArrowTypeMismatch { pos, .. }
+            | TypecheckError::CtrTypeInTermPos { pos, .. }
+            | TypecheckError::VarLevelMismatch { pos, .. }
+            | TypecheckError::OrPatternVarsMismatch { pos, .. } => pos.into_opt(),
+            TypecheckError::UnboundTypeVariable(id) => id.pos.into_opt(),
+        }
+    }
+}
+
+impl ImportError {
+    /// The location of the import, if any.
+    pub fn span(&self) -> Option<RawSpan> {
+        match self {
+            ImportError::IOError(_, _, pos)
+            | ImportError::ParseErrors(_, pos)
+            | ImportError::NonReproducible(_, _, pos)
+            | ImportError::CapabilityDenied(_, _, pos) => pos.into_opt(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EvalError(err) => write!(f, "{err}"),
+            Error::TypecheckError(err) => write!(f, "{err}"),
+            Error::ParseErrors(errs) => write!(f, "{errs}"),
+            Error::ImportError(err) => write!(f, "{err}"),
+            Error::ExportError(err) => write!(f, "{err}"),
+            Error::IOError(err) => write!(f, "{err}"),
+            Error::ReplError(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let type_of = |rt: &RichTerm| {
+            rt.as_ref()
+                .type_of()
+                .unwrap_or_else(|| String::from("<unevaluated>"))
+        };
+
+        match self {
+            EvalError::BlameError { label, .. } => {
+                match label.field_name {
+                    Some(id) => write!(f, "contract broken by the value of `{id}`")?,
+                    None => write!(f, "contract broken by a value")?,
+                }
+
+                match label.current_diagnostic().and_then(|d| d.message.as_ref()) {
+                    Some(msg) => write!(f, ": {msg}"),
+                    None => Ok(()),
+                }
+            }
+            EvalError::MissingFieldDef { id, .. } => write!(f, "missing definition for `{id}`"),
+            EvalError::TypeError(expected, msg, _, value) => write!(
+                f,
+                "dynamic type error: expected {expected}, got {}: {msg}",
+                type_of(value)
+            ),
+            EvalError::UnaryPrimopTypeError {
+                primop,
+                expected,
+                arg_evaluated,
+                ..
+            } => write!(
+                f,
+                "dynamic type error: {primop} expects its argument to be a {expected}, got {}",
+                type_of(arg_evaluated)
+            ),
+            EvalError::NAryPrimopTypeError {
+                primop,
+                expected,
+                arg_number,
+                arg_evaluated,
+                ..
+            } => write!(
+                f,
+                "dynamic type error: {primop} expects its {} argument to be a {expected}, got {}",
+                cardinal(*arg_number),
+                type_of(arg_evaluated)
+            ),
+            EvalError::ParseError(err) => write!(f, "{err}"),
+            EvalError::NotAFunc(value, _, _) => {
+                write!(
+                    f,
+                    "not a function: applied a value of type {}",
+                    type_of(value)
+                )
+            }
+            EvalError::FieldMissing { id, operator, .. } => {
+                write!(f, "missing field `{id}`, required by {operator}")
+            }
+            EvalError::NotEnoughArgs(count, op, _) => {
+                write!(f, "not enough arguments: {op} expects {count} arguments")
+            }
+            EvalError::MergeIncompatibleArgs {
+                left_arg,
+                right_arg,
+                ..
+            } => write!(
+                f,
+                "non mergeable terms: can't merge a value of type {} with a value of type {}",
+                type_of(left_arg),
+                type_of(right_arg)
+            ),
+            EvalError::UnboundIdentifier { id, .. } => write!(f, "unbound identifier `{id}`"),
+            EvalError::InfiniteRecursion(_, _) => write!(f, "infinite recursion"),
+            EvalError::SerializationError(err) => write!(f, "{err}"),
+            EvalError::DeserializationError(format, msg, _) => {
+                write!(f, "{format} parse error: {msg}")
+            }
+            EvalError::IllegalPolymorphicTailAccess { action, .. } => {
+                write!(f, "contract broken by a value: {}", action.message())
+            }
+            EvalError::IncomparableValues { left, right, .. } => write!(
+                f,
+                "cannot compare values for equality: a {} can't be meaningfully compared with a {}",
+                type_of(left),
+                type_of(right)
+            ),
+            EvalError::NonExhaustiveEnumMatch { .. } | EvalError::NonExhaustiveMatch { .. } => {
+                write!(f, "unmatched pattern: a value doesn't match any branch")
+            }
+            EvalError::FailedDestructuring { .. } => write!(f, "destructuring failed"),
+            EvalError::QueryNonRecord { id, value, .. } => write!(
+                f,
+                "tried to query field `{id}`, but the expression has type {}",
+                type_of(value)
+            ),
+            EvalError::InternalError(msg, _) => write!(f, "internal error: {msg}"),
+            EvalError::Other(msg, _) => f.write_str(msg),
+            EvalError::Cancelled(_) => write!(f, "evaluation cancelled"),
+            EvalError::DeniedWarning(warning) => write!(f, "{warning}"),
+            EvalError::NonReproducible {
+                construct, reason, ..
+            } => write!(f, "non-reproducible {construct}: {reason}"),
+            EvalError::CapabilityDenied { action, reason, .. } => {
+                write!(f, "{action} denied: {reason}")
+            }
+            EvalError::ResourceExhausted { resource, .. } => {
+                write!(f, "evaluation exceeded {resource}")
+            }
+            EvalError::StackOverflow { max_depth, .. } => {
+                write!(
+                    f,
+                    "stack overflow: evaluation exceeded the depth of {max_depth}"
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for TypecheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypecheckError::UnboundIdentifier { id, .. } => {
+                write!(f, "unbound identifier `{id}`")
+            }
+            TypecheckError::MissingRow {
+                id,
+                expected,
+                inferred,
+                ..
+            } => write!(
+                f,
+                "type error: missing row `{id}`: expected {expected}, found {inferred}"
+            ),
+            TypecheckError::MissingDynTail {
+                expected, inferred, ..
+            } => write!(
+                f,
+                "type error: missing dynamic tail `; Dyn`: expected {expected}, found {inferred}"
+            ),
+            TypecheckError::ExtraRow {
+                id,
+                expected,
+                inferred,
+                ..
+            } => write!(
+                f,
+                "type error: extra row `{id}`: expected {expected}, found {inferred}"
+            ),
+            TypecheckError::ExtraDynTail {
+                expected, inferred, ..
+            } => write!(
+                f,
+                "type error: extra dynamic tail `; Dyn`: expected {expected}, found {inferred}"
+            ),
+            TypecheckError::ForallParametricityViolation { violating_type, .. } => write!(
+                f,
+                "values of type `{violating_type}` are not guaranteed to be compatible \
+                with polymorphic record or enum tails"
+            ),
+            TypecheckError::UnboundTypeVariable(id) => write!(f, "unbound type variable `{id}`"),
+            TypecheckError::TypeMismatch {
+                expected, inferred, ..
+            }
+            | TypecheckError::RecordRowMismatch {
+                expected, inferred, ..
+            }
+            | TypecheckError::EnumRowMismatch {
+                expected, inferred, ..
+            }
+            | TypecheckError::ArrowTypeMismatch {
+                expected, inferred, ..
+            } => write!(
+                f,
+                "incompatible types: expected {expected}, found {inferred}"
+            ),
+            TypecheckError::RecordRowConflict { row, .. } => {
+                write!(f, "multiple record row declarations: `{row}`")
+            }
+            TypecheckError::EnumRowConflict { row, .. } => {
+                write!(f, "multiple enum row declarations: `{row}`")
+            }
+            TypecheckError::CtrTypeInTermPos { .. } => write!(
+                f,
+                "types containing user-defined contracts cannot be converted into contracts"
+            ),
+            TypecheckError::VarLevelMismatch { type_var, .. } => write!(
+                f,
+                "invalid polymorphic generalization: the type variable `{type_var}` escapes its scope"
+            ),
+            TypecheckError::OrPatternVarsMismatch { var, .. } => write!(
+                f,
+                "`{var}` doesn't appear in all the branches of an or-pattern"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, err) in self.errors.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{err}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedEOF(_, _) => write!(f, "unexpected end of file"),
+            ParseError::UnexpectedToken(_, _) => write!(f, "unexpected token"),
+            ParseError::ExtraToken(_) => write!(f, "superfluous unexpected token"),
+            ParseError::UnmatchedCloseBrace(_) => write!(f, "unmatched closing brace '}}'"),
+            ParseError::InvalidEscapeSequence(_) => write!(f, "invalid escape sequence"),
+            ParseError::InvalidAsciiEscapeCode(_) => write!(f, "invalid ascii escape code"),
+            ParseError::StringDelimiterMismatch { .. } => {
+                write!(f, "string closing delimiter has too many `%`")
+            }
+            ParseError::ExternalFormatError(format, msg, _) => {
+                write!(f, "{format} parse error: {msg}")
+            }
+            ParseError::UnboundTypeVariables(idents) => write!(
+                f,
+                "unbound type variable(s): {}",
+                idents
+                    .iter()
+                    .map(|id| format!("`{id}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ParseError::InvalidRecordType { .. } => write!(f, "invalid record literal"),
+            ParseError::RecursiveLetPattern(_) => {
+                write!(f, "recursive destructuring is not supported")
+            }
+            ParseError::PatternInLetBlock(_) => write!(
+                f,
+                "destructuring patterns are not currently permitted in let blocks"
+            ),
+            ParseError::TypeVariableKindMismatch { ty_var, .. } => write!(
+                f,
+                "the type variable `{ty_var}` is used in conflicting ways"
+            ),
+            ParseError::TypedFieldWithoutDefinition { .. } => {
+                write!(f, "statically typed field without a definition")
+            }
+            ParseError::InterpolationInStaticPath { .. } => {
+                write!(f, "string interpolation is forbidden within a query")
+            }
+            ParseError::DuplicateIdentInRecordPattern { ident, .. } => {
+                write!(f, "duplicated binding `{ident}` in record pattern")
+            }
+            ParseError::DuplicateIdentInLetBlock { ident, .. } => {
+                write!(f, "duplicated binding `{ident}` in let block")
+            }
+            ParseError::DisabledFeature { feature, .. } => write!(
+                f,
+                "interpreter compiled without required features: {feature}"
+            ),
+            ParseError::InvalidContract(_) => write!(f, "invalid contract expression"),
+            ParseError::InvalidImportFormat { .. } => write!(f, "unknown import format tag"),
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::IOError(path, error, _) => write!(f, "import of {path} failed: {error}"),
+            ImportError::ParseErrors(errs, _) => write!(f, "parse error in import: {errs}"),
+            ImportError::NonReproducible(path, reason, _) => {
+                write!(f, "non-reproducible import of {path}: {reason}")
+            }
+            ImportError::CapabilityDenied(path, reason, _) => {
+                write!(f, "import of {path} denied: {reason}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.data {
+            ExportErrorData::UnsupportedNull(format, _) => {
+                write!(f, "{format} format doesn't support null values")?
+            }
+            ExportErrorData::NotAString(rt) => write!(
+                f,
+                "raw export expects a String value, but got {}",
+                rt.as_ref()
+                    .type_of()
+                    .unwrap_or_else(|| String::from("<unevaluated>"))
+            )?,
+            ExportErrorData::NotARecord(format, rt) => write!(
+                f,
+                "{format} export expects a Record value, but got {}",
+                rt.as_ref()
+                    .type_of()
+                    .unwrap_or_else(|| String::from("<unevaluated>"))
+            )?,
+            ExportErrorData::UnsupportedNesting(format, _) => write!(
+                f,
+                "{format} format doesn't support nested records or arrays"
+            )?,
+            ExportErrorData::NonSerializable(_) => write!(f, "non serializable term")?,
+            ExportErrorData::NoDocumentation(_) => write!(f, "no documentation found")?,
+            ExportErrorData::NumberOutOfRange { value, .. } => write!(
+                f,
+                "the number {} is too large (in absolute value) to be serialized",
+                value.to_sci()
+            )?,
+            ExportErrorData::Deserialization { message, .. } => {
+                write!(f, "deserialization failed: {message}")?
+            }
+            ExportErrorData::Other(msg) => write!(f, "serialization failed: {msg}")?,
+        }
+
+        if !self.path.0.is_empty() {
+            write!(f, " (in `{}`)", self.path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for IOError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for ReplError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplError::UnknownCommand(cmd) => write!(f, "unknown command `{cmd}`"),
+            ReplError::MissingArg { cmd, msg_opt } => {
+                write!(f, "{cmd}: missing argument")?;
+
+                match msg_opt {
+                    Some(msg) => write!(f, " ({msg})"),
+                    None => Ok(()),
+                }
+            }
+            ReplError::InvalidQueryPath(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+impl std::error::Error for EvalError {}
+impl std::error::Error for TypecheckError {}
+impl std::error::Error for ParseErrors {}
+impl std::error::Error for ParseError {}
+impl std::error::Error for ImportError {}
+impl std::error::Error for ExportError {}
+impl std::error::Error for AccessError {}
+impl std::error::Error for IOError {}
+impl std::error::Error for ReplError {}
+
+impl ParseError {
+    /// The main location of the error, if any.
+    pub fn span(&self) -> Option<RawSpan> {
+        match self {
+            ParseError::UnexpectedEOF(_, _) => None,
+            ParseError::UnboundTypeVariables(idents) => {
+                idents.first().and_then(|id| id.pos.into_opt())
+            }
+            ParseError::DuplicateIdentInRecordPattern { ident, .. }
+            | ParseError::DuplicateIdentInLetBlock { ident, .. } => ident.pos.into_opt(),
+            ParseError::UnexpectedToken(span, _)
+            | ParseError::ExtraToken(span)
+            | ParseError::UnmatchedCloseBrace(span)
+            | ParseError::InvalidEscapeSequence(span)
+            | ParseError::InvalidAsciiEscapeCode(span)
+            | ParseError::StringDelimiterMismatch {
+                closing_delimiter: span,
+                ..
+            }
+            | ParseError::InvalidRecordType {
+                record_span: span, ..
+            }
+            | ParseError::RecursiveLetPattern(span)
+            | ParseError::PatternInLetBlock(span)
+            | ParseError::TypeVariableKindMismatch { span, .. }
+            | ParseError::TypedFieldWithoutDefinition {
+                field_span: span, ..
+            }
+            | ParseError::InterpolationInStaticPath {
+                path_elem_span: span,
+                ..
+            }
+            | ParseError::DisabledFeature { span, .. }
+            | ParseError::InvalidContract(span)
+            | ParseError::InvalidImportFormat { span } => Some(*span),
+            ParseError::ExternalFormatError(_, _, span_opt) => *span_opt,
+        }
+    }
+
+    /// The file the error comes from, if any.
+    pub fn file_id(&self) -> Option<FileId> {
+        match self {
+            ParseError::UnexpectedEOF(file_id, _) => Some(*file_id),
+            _ => self.span().map(|span| span.src_id),
+        }
+    }
+}
+
 impl ParseError {
     pub fn from_lalrpop<T>(
         error: lalrpop_util::ParseError<usize, T, InternalParseError>,
@@ -857,31 +1652,301 @@ impl ParseError {
         )
     }
 
-    pub fn from_toml(error: toml_edit::TomlError, file_id: FileId) -> Self {
-        use codespan::{ByteIndex, ByteOffset};
+    #[cfg(feature = "yaml-positions")]
+    pub fn from_yaml(error: crate::serialize::yaml_deser::YamlError, file_id: FileId) -> Self {
+        use codespan::{ByteIndex, ByteOffset};
+
+        let start = error.offset.map(|offset| ByteIndex::from(offset as u32));
+        ParseError::ExternalFormatError(
+            String::from("yaml"),
+            error.message,
+            start.map(|start| RawSpan {
+                src_id: file_id,
+                start,
+                end: start + ByteOffset::from(1),
+            }),
+        )
+    }
+
+    pub fn from_toml(error: toml_edit::TomlError, file_id: FileId) -> Self {
+        use codespan::{ByteIndex, ByteOffset};
+
+        let span = error.span();
+        ParseError::ExternalFormatError(
+            String::from("toml"),
+            error.to_string(),
+            span.map(|span| RawSpan {
+                src_id: file_id,
+                start: ByteIndex::from(span.start as u32),
+                end: ByteIndex(span.end as u32) + ByteOffset::from(1),
+            }),
+        )
+    }
+
+    #[cfg(feature = "nix-experimental")]
+    pub fn from_nix(error: &str, _file_id: FileId) -> Self {
+        // Span is shown in the nix error message
+        ParseError::ExternalFormatError(String::from("nix"), error.to_string(), None)
+    }
+}
+
+pub const INTERNAL_ERROR_MSG: &str =
+    "This error should not happen. This is likely a bug in the Nickel interpreter. Please consider \
+ reporting it at https://github.com/tweag/nickel/issues with the above error message.";
+
+/// The note attached to the errors raised in reproducible mode.
+const REPRODUCIBLE_MODE_NOTE: &str =
+    "This construct is rejected because the evaluation runs in reproducible mode.";
+
+/// The file id of the labels of diagnostics. A label either points to a file of the program, or to
+/// a synthetic snippet generated when building the diagnostic (see [label_alt]).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DiagnosticFileId {
+    /// A file of the program.
+    File(FileId),
+    /// A snippet, given by its index in [Diagnostics::snippets].
+    Snippet(usize),
+}
+
+impl From<FileId> for DiagnosticFileId {
+    fn from(file_id: FileId) -> Self {
+        DiagnosticFileId::File(file_id)
+    }
+}
+
+/// A list of diagnostics, together with the synthetic snippets their labels may refer to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    pub diagnostics: Vec<Diagnostic<DiagnosticFileId>>,
+    pub snippets: Vec<String>,
+}
+
+impl Diagnostics {
+    /// Append the diagnostics of `other` together with their snippets. The labels of `other`
+    /// referring to a snippet are renumbered accordingly.
+    pub fn append(&mut self, other: Diagnostics) {
+        let offset = self.snippets.len();
+
+        self.snippets.extend(other.snippets);
+        self.diagnostics
+            .extend(other.diagnostics.into_iter().map(|mut diagnostic| {
+                for label in diagnostic.labels.iter_mut() {
+                    if let DiagnosticFileId::Snippet(index) = &mut label.file_id {
+                        *index += offset;
+                    }
+                }
+
+                diagnostic
+            }));
+    }
+
+    /// Return the file database to render the diagnostics with, made of the files of the program
+    /// the diagnostics have been built from and of the snippets.
+    pub fn files<'a>(&'a self, files: &'a Files) -> DiagnosticFiles<'a> {
+        DiagnosticFiles::new(files, &self.snippets)
+    }
+}
+
+// Diagnostics built outside of this module only refer to files.
+impl From<Vec<Diagnostic<FileId>>> for Diagnostics {
+    fn from(diagnostics: Vec<Diagnostic<FileId>>) -> Self {
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| Diagnostic {
+                severity: diagnostic.severity,
+                code: diagnostic.code,
+                message: diagnostic.message,
+                labels: diagnostic
+                    .labels
+                    .into_iter()
+                    .map(|label| Label {
+                        style: label.style,
+                        file_id: label.file_id.into(),
+                        range: label.range,
+                        message: label.message,
+                    })
+                    .collect(),
+                notes: diagnostic.notes,
+            })
+            .collect();
+
+        Diagnostics {
+            diagnostics,
+            snippets: Vec::new(),
+        }
+    }
+}
+
+impl FromIterator<Diagnostics> for Diagnostics {
+    fn from_iter<I: IntoIterator<Item = Diagnostics>>(iter: I) -> Self {
+        let mut result = Diagnostics::default();
+
+        for diagnostics in iter {
+            result.append(diagnostics);
+        }
+
+        result
+    }
+}
+
+/// The file database of [Diagnostics]: the files of the program, extended with the snippets of
+/// the diagnostics, which are named [UNKNOWN_SOURCE_NAME].
+#[derive(Copy, Clone, Debug)]
+pub struct DiagnosticFiles<'a> {
+    files: &'a Files,
+    snippets: &'a [String],
+}
+
+impl<'a> DiagnosticFiles<'a> {
+    pub fn new(files: &'a Files, snippets: &'a [String]) -> Self {
+        DiagnosticFiles { files, snippets }
+    }
+
+    /// Return the name of a file or a snippet.
+    pub fn name(&self, file_id: DiagnosticFileId) -> String {
+        match file_id {
+            DiagnosticFileId::File(file_id) => self.files.name(file_id).to_string_lossy().into(),
+            DiagnosticFileId::Snippet(_) => UNKNOWN_SOURCE_NAME.to_owned(),
+        }
+    }
+
+    /// Return the content of a file or a snippet.
+    ///
+    /// Panics if `file_id` is invalid.
+    pub fn source(&self, file_id: DiagnosticFileId) -> &'a str {
+        match file_id {
+            DiagnosticFileId::File(file_id) => self.files.source(file_id),
+            DiagnosticFileId::Snippet(index) => &self.snippets[index],
+        }
+    }
+
+    fn snippet(&self, index: usize) -> Result<&'a str, codespan_files::Error> {
+        self.snippets
+            .get(index)
+            .map(String::as_str)
+            .ok_or(codespan_files::Error::FileMissing)
+    }
+}
+
+impl<'a, 'files: 'a> codespan_files::Files<'a> for DiagnosticFiles<'files> {
+    type FileId = DiagnosticFileId;
+    type Name = String;
+    type Source = &'a str;
+
+    fn name(&'a self, id: DiagnosticFileId) -> Result<String, codespan_files::Error> {
+        match id {
+            DiagnosticFileId::File(id) => codespan_files::Files::name(self.files, id),
+            DiagnosticFileId::Snippet(index) => {
+                self.snippet(index).map(|_| UNKNOWN_SOURCE_NAME.to_owned())
+            }
+        }
+    }
+
+    fn source(&'a self, id: DiagnosticFileId) -> Result<&'a str, codespan_files::Error> {
+        match id {
+            DiagnosticFileId::File(id) => codespan_files::Files::source(self.files, id),
+            DiagnosticFileId::Snippet(index) => self.snippet(index),
+        }
+    }
+
+    fn line_index(
+        &'a self,
+        id: DiagnosticFileId,
+        byte_index: usize,
+    ) -> Result<usize, codespan_files::Error> {
+        match id {
+            DiagnosticFileId::File(id) => {
+                codespan_files::Files::line_index(self.files, id, byte_index)
+            }
+            DiagnosticFileId::Snippet(index) => {
+                Ok(codespan_files::line_starts(self.snippet(index)?)
+                    .take_while(|start| *start <= byte_index)
+                    .count()
+                    - 1)
+            }
+        }
+    }
+
+    fn line_range(
+        &'a self,
+        id: DiagnosticFileId,
+        line_index: usize,
+    ) -> Result<std::ops::Range<usize>, codespan_files::Error> {
+        match id {
+            DiagnosticFileId::File(id) => {
+                codespan_files::Files::line_range(self.files, id, line_index)
+            }
+            DiagnosticFileId::Snippet(index) => {
+                let snippet = self.snippet(index)?;
+                let starts: Vec<_> = codespan_files::line_starts(snippet).collect();
+                // As for codespan's `SimpleFile`, the line following the last one starts at the
+                // end of the source.
+                let line_start = |line_index: usize| match starts.get(line_index) {
+                    Some(start) => Ok(*start),
+                    None if line_index == starts.len() => Ok(snippet.len()),
+                    None => Err(codespan_files::Error::LineTooLarge {
+                        given: line_index,
+                        max: starts.len() - 1,
+                    }),
+                };
+
+                Ok(line_start(line_index)?..line_start(line_index + 1)?)
+            }
+        }
+    }
+}
+
+/// The state of the construction of diagnostics: the files of the program, which are only read,
+/// and the snippets generated so far (see [label_alt]).
+struct DiagnosticsBuilder<'a> {
+    files: &'a Files,
+    snippets: Vec<String>,
+}
+
+impl<'a> DiagnosticsBuilder<'a> {
+    /// Build a list of diagnostics with `build`, which can generate snippets through the builder.
+    fn build(
+        files: &'a Files,
+        build: impl FnOnce(&mut Self) -> Vec<Diagnostic<DiagnosticFileId>>,
+    ) -> Diagnostics {
+        let mut builder = DiagnosticsBuilder {
+            files,
+            snippets: Vec::new(),
+        };
+        let diagnostics = build(&mut builder);
 
-        let span = error.span();
-        ParseError::ExternalFormatError(
-            String::from("toml"),
-            error.to_string(),
-            span.map(|span| RawSpan {
-                src_id: file_id,
-                start: ByteIndex::from(span.start as u32),
-                end: ByteIndex(span.end as u32) + ByteOffset::from(1),
-            }),
-        )
+        Diagnostics {
+            diagnostics,
+            snippets: builder.snippets,
+        }
     }
 
-    #[cfg(feature = "nix-experimental")]
-    pub fn from_nix(error: &str, _file_id: FileId) -> Self {
-        // Span is shown in the nix error message
-        ParseError::ExternalFormatError(String::from("nix"), error.to_string(), None)
+    /// Add a synthetic snippet and return its file id.
+    fn add_snippet(&mut self, snippet: String) -> DiagnosticFileId {
+        self.snippets.push(snippet);
+        DiagnosticFileId::Snippet(self.snippets.len() - 1)
+    }
+
+    /// Build the diagnostics of a nested error, taking over their snippets.
+    fn nested(&mut self, error: impl IntoDiagnostics) -> Vec<Diagnostic<DiagnosticFileId>> {
+        let mut diagnostics = Diagnostics {
+            diagnostics: Vec::new(),
+            snippets: std::mem::take(&mut self.snippets),
+        };
+        diagnostics.append(error.into_diagnostics(self.files));
+        self.snippets = diagnostics.snippets;
+
+        diagnostics.diagnostics
     }
 }
 
-pub const INTERNAL_ERROR_MSG: &str =
-    "This error should not happen. This is likely a bug in the Nickel interpreter. Please consider \
- reporting it at https://github.com/tweag/nickel/issues with the above error message.";
+impl std::ops::Deref for DiagnosticsBuilder<'_> {
+    type Target = Files;
+
+    fn deref(&self) -> &Files {
+        self.files
+    }
+}
 
 /// A trait for converting an error to a diagnostic.
 pub trait IntoDiagnostics {
@@ -889,10 +1954,9 @@ pub trait IntoDiagnostics {
     ///
     /// # Arguments
     ///
-    /// - `files`: this is a mutable reference to allow insertion of temporary snippets. Note that
-    ///   `Files` is cheaply clonable and copy-on-write, so you can easily get a mutable `Files` from
-    ///    a non-mutable one, but bear in mind that the returned diagnostics may contains file ids that
-    ///    refer to your mutated files.
+    /// - `files`: the files of the program, which are only read. The diagnostics can also refer to
+    ///   synthetic snippets, which are returned together with them (see [label_alt]). Use
+    ///   [Diagnostics::files] to render them.
     ///
     /// # Return
     ///
@@ -900,33 +1964,39 @@ pub trait IntoDiagnostics {
     /// ordered requires to sidestep a limitation of codespan. The current solution is to generate
     /// one diagnostic per callstack element. See issue
     /// [#285](https://github.com/brendanzab/codespan/issues/285).
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>>;
+    fn into_diagnostics(self, files: &Files) -> Diagnostics;
 }
 
 // Allow the use of a single `Diagnostic` directly as an error that can be reported by Nickel.
 impl IntoDiagnostics for Diagnostic<FileId> {
-    fn into_diagnostics(self, _files: &mut Files) -> Vec<Diagnostic<FileId>> {
-        vec![self]
+    fn into_diagnostics(self, _files: &Files) -> Diagnostics {
+        vec![self].into()
     }
 }
 
 // Helpers for the creation of codespan `Label`s
 
 /// Create a primary label from a span.
-fn primary(span: &RawSpan) -> Label<FileId> {
-    Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize())
+fn primary(span: &RawSpan) -> Label<DiagnosticFileId> {
+    Label::primary(
+        span.src_id.into(),
+        span.start.to_usize()..span.end.to_usize(),
+    )
 }
 
 /// Create a secondary label from a span.
-fn secondary(span: &RawSpan) -> Label<FileId> {
-    Label::secondary(span.src_id, span.start.to_usize()..span.end.to_usize())
+fn secondary(span: &RawSpan) -> Label<DiagnosticFileId> {
+    Label::secondary(
+        span.src_id.into(),
+        span.start.to_usize()..span.end.to_usize(),
+    )
 }
 
 /// Create a label from an optional span, or fallback to annotating the alternative snippet
 /// `alt_term` if the span is `None`.
 ///
-/// When `span_opt` is `None`, the code snippet `alt_term` is added to `files` under a special name
-/// and is referred to instead.
+/// When `span_opt` is `None`, the code snippet `alt_term` is added to the snippets of the
+/// diagnostics under construction and is referred to instead. It is shown under a special name.
 ///
 /// This is useful because during evaluation, some terms are the results of computations. They
 /// correspond to nothing in the original source, and thus have a position set to `None`(e.g. the
@@ -947,9 +2017,9 @@ fn secondary(span: &RawSpan) -> Label<FileId> {
 ///     ^^^^^^^ some annotation
 /// ```
 ///
-/// The reason for the mutable reference to `files` is that codespan do no let you annotate
-/// something that is not in `files`: you can't provide a raw snippet, you need to provide a
-/// `FileId` referring to a file. This leaves the following possibilities:
+/// The reason for the snippets is that codespan do no let you annotate something that is not in
+/// its file database: you can't provide a raw snippet, you need to provide a `FileId` referring to
+/// a file. This leaves the following possibilities:
 ///
 /// 1. Do nothing: just elude annotations which refer to the term
 /// 2. Print the term and the annotation as a note together with the diagnostic. Notes are
@@ -957,9 +2027,9 @@ fn secondary(span: &RawSpan) -> Label<FileId> {
 ///     - pretty formatting of annotations for such snippets
 ///     - style consistency: the style of the error now depends on the term being from the source or
 ///       a byproduct of evaluation
-/// 3. Add the term to files, take 1: pass a reference to files so that the code building the
-///    diagnostic can itself add arbitrary snippets if necessary, and get back their `FileId`. This
-///    is what is done here.
+/// 3. Add the term to files, take 1: pass a mutable reference to files so that the code building
+///    the diagnostic can itself add arbitrary snippets if necessary, and get back their `FileId`.
+///    This pollutes the file database of the program with snippets, or forces to copy it.
 /// 4. Add the term to files, take 2: make a wrapper around the `Files` and `FileId` structures of
 ///    codespan which handle source mapping. `FileId` could be something like
 ///    `Either<codespan::FileId, CustomId = u32>` so that `to_diagnostic` could construct and use
@@ -968,21 +2038,25 @@ fn secondary(span: &RawSpan) -> Label<FileId> {
 ///    `LoneCode = String or (Id, String)>` so we don't have to return the additional list of
 ///    snippets. This adds some boilerplate, that we wanted to avoid, but this stays on the
 ///    reasonable side of being an alternative.
+///
+/// The first variant of 4. is what is done here: the file id of labels is a [DiagnosticFileId],
+/// and the snippets are returned together with the diagnostics in [Diagnostics]. As codespan
+/// requires file ids to be `Copy`, snippets can't be stored in the file ids themselves.
 fn label_alt(
     span_opt: Option<RawSpan>,
     alt_term: String,
     style: LabelStyle,
-    files: &mut Files,
-) -> Label<FileId> {
+    files: &mut DiagnosticsBuilder,
+) -> Label<DiagnosticFileId> {
     match span_opt {
         Some(span) => Label::new(
             style,
-            span.src_id,
+            span.src_id.into(),
             span.start.to_usize()..span.end.to_usize(),
         ),
         None => {
             let range = 0..alt_term.len();
-            Label::new(style, files.add(UNKNOWN_SOURCE_NAME, alt_term), range)
+            Label::new(style, files.add_snippet(alt_term), range)
         }
     }
 }
@@ -991,7 +2065,11 @@ fn label_alt(
 /// snippet `alt_term` if the span is `None`.
 ///
 /// See [`label_alt`].
-fn primary_alt(span_opt: Option<RawSpan>, alt_term: String, files: &mut Files) -> Label<FileId> {
+fn primary_alt(
+    span_opt: Option<RawSpan>,
+    alt_term: String,
+    files: &mut DiagnosticsBuilder,
+) -> Label<DiagnosticFileId> {
     label_alt(span_opt, alt_term, LabelStyle::Primary, files)
 }
 
@@ -999,15 +2077,19 @@ fn primary_alt(span_opt: Option<RawSpan>, alt_term: String, files: &mut Files) -
 /// term if its span is `None`.
 ///
 /// See [`label_alt`].
-fn primary_term(term: &RichTerm, files: &mut Files) -> Label<FileId> {
-    primary_alt(term.pos.into_opt(), term.to_string(), files)
+fn primary_term(term: &RichTerm, files: &mut DiagnosticsBuilder) -> Label<DiagnosticFileId> {
+    primary_alt(term.pos.into_opt(), to_string_redacted(term), files)
 }
 
 /// Create a secondary label from an optional span, or fallback to annotating the alternative
 /// snippet `alt_term` if the span is `None`.
 ///
 /// See [`label_alt`].
-fn secondary_alt(span_opt: TermPos, alt_term: String, files: &mut Files) -> Label<FileId> {
+fn secondary_alt(
+    span_opt: TermPos,
+    alt_term: String,
+    files: &mut DiagnosticsBuilder,
+) -> Label<DiagnosticFileId> {
     label_alt(span_opt.into_opt(), alt_term, LabelStyle::Secondary, files)
 }
 
@@ -1015,8 +2097,8 @@ fn secondary_alt(span_opt: TermPos, alt_term: String, files: &mut Files) -> Labe
 /// this term if its span is `None`.
 ///
 /// See [`label_alt`].
-fn secondary_term(term: &RichTerm, files: &mut Files) -> Label<FileId> {
-    secondary_alt(term.pos, term.to_string(), files)
+fn secondary_term(term: &RichTerm, files: &mut DiagnosticsBuilder) -> Label<DiagnosticFileId> {
+    secondary_alt(term.pos, to_string_redacted(term), files)
 }
 
 fn cardinal(number: usize) -> String {
@@ -1033,12 +2115,12 @@ fn cardinal(number: usize) -> String {
 }
 
 impl IntoDiagnostics for Error {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
         match self {
             Error::ParseErrors(errs) => errs
                 .errors
                 .into_iter()
-                .flat_map(|e| e.into_diagnostics(files))
+                .map(|e| e.into_diagnostics(files))
                 .collect(),
             Error::TypecheckError(err) => err.into_diagnostics(files),
             Error::EvalError(err) => err.into_diagnostics(files),
@@ -1050,8 +2132,71 @@ impl IntoDiagnostics for Error {
     }
 }
 
+impl IntoDiagnostics for Warning {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        match self {
+            Warning::EvalWarning(warning) => warning.into_diagnostics(files),
+        }
+    }
+}
+
+impl IntoDiagnostics for EvalWarning {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = self.code();
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl EvalWarning {
+    fn diagnostics_without_code(
+        self,
+        _files: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
+        match self {
+            EvalWarning::DeprecatedField {
+                id,
+                message,
+                pos_access,
+            } => {
+                let mut labels = vec![];
+
+                if let Some(span) = pos_access.into_opt() {
+                    labels.push(primary(&span).with_message("used here"));
+                }
+
+                if let Some(span) = id.pos.into_opt() {
+                    if labels.is_empty() {
+                        labels.push(primary(&span).with_message("exported here"));
+                    } else {
+                        labels.push(secondary(&span).with_message("declared deprecated here"));
+                    }
+                }
+
+                vec![Diagnostic::warning()
+                    .with_message(format!("field `{id}` is deprecated"))
+                    .with_labels(labels)
+                    .with_notes(vec![message])]
+            }
+        }
+    }
+}
+
 impl IntoDiagnostics for EvalError {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = self.code();
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl EvalError {
+    fn diagnostics_without_code(
+        self,
+        files: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
         match self {
             EvalError::BlameError {
                 evaluated_arg,
@@ -1085,12 +2230,8 @@ impl IntoDiagnostics for EvalError {
                 // Is it really useful to include the label if we show the position of the ident?
                 // We have to see in practice if it can be the case that `id.pos` is
                 // `TermPos::None`, but the label is defined.
-                if let Some(label) = metadata
-                    .annotation
-                    .first()
-                    .map(|labeled_ty| labeled_ty.label.clone())
-                {
-                    diags.push(blame_error::contract_bind_loc(&label));
+                if let Some(span) = metadata.annotation_span() {
+                    diags.push(blame_error::contract_bind_loc(&span));
                 }
 
                 diags
@@ -1130,14 +2271,22 @@ impl IntoDiagnostics for EvalError {
                     .with_labels(labels)
                     .with_notes(vec![msg])]
             }
-            EvalError::ParseError(parse_error) => parse_error.into_diagnostics(files),
+            EvalError::ParseError(parse_error) => files.nested(parse_error),
             EvalError::NotAFunc(t, arg, pos_opt) => vec![Diagnostic::error()
                 .with_message("not a function")
                 .with_labels(vec![
                     primary_term(&t, files)
                         .with_message("this term is applied, but it is not a function"),
-                    secondary_alt(pos_opt, format!("({}) ({})", t, arg), files)
-                        .with_message("applied here"),
+                    secondary_alt(
+                        pos_opt,
+                        format!(
+                            "({}) ({})",
+                            to_string_redacted(&t),
+                            to_string_redacted(&arg)
+                        ),
+                        files,
+                    )
+                    .with_message("applied here"),
                 ])],
             EvalError::FieldMissing {
                 id: name,
@@ -1152,8 +2301,11 @@ impl IntoDiagnostics for EvalError {
 
                 if let Some(span) = pos_op.into_opt() {
                     labels.push(
-                        Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize())
-                            .with_message(format!("this requires the field `{field}` to exist")),
+                        Label::primary(
+                            span.src_id.into(),
+                            span.start.to_usize()..span.end.to_usize(),
+                        )
+                        .with_message(format!("this requires the field `{field}` to exist")),
                     );
                 } else {
                     notes.push(format!(
@@ -1182,8 +2334,11 @@ impl IntoDiagnostics for EvalError {
 
                 if let Some(span) = span_opt.into_opt() {
                     labels.push(
-                        Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize())
-                            .with_message(msg),
+                        Label::primary(
+                            span.src_id.into(),
+                            span.start.to_usize()..span.end.to_usize(),
+                        )
+                        .with_message(msg),
                     );
                 } else {
                     notes.push(msg);
@@ -1303,14 +2458,16 @@ impl IntoDiagnostics for EvalError {
                     .with_labels(labels)
                     .with_notes(notes)]
             }
-            EvalError::UnboundIdentifier(ident, span_opt) => vec![Diagnostic::error()
-                .with_message(format!("unbound identifier `{ident}`"))
-                .with_labels(vec![primary_alt(
-                    span_opt.into_opt(),
-                    ident.to_string(),
-                    files,
-                )
-                .with_message("this identifier is unbound")])],
+            EvalError::UnboundIdentifier { id, pos, in_scope } => {
+                let mut notes = Vec::new();
+                suggest::add_suggestion(&mut notes, &in_scope.identifiers(), &id);
+
+                vec![Diagnostic::error()
+                    .with_message(format!("unbound identifier `{id}`"))
+                    .with_labels(vec![primary_alt(pos.into_opt(), id.to_string(), files)
+                        .with_message("this identifier is unbound")])
+                    .with_notes(notes)]
+            }
             EvalError::InfiniteRecursion(_call_stack, span_opt) => {
                 let labels = span_opt
                     .as_opt_ref()
@@ -1329,6 +2486,113 @@ impl IntoDiagnostics for EvalError {
 
                 vec![Diagnostic::error().with_message(msg).with_labels(labels)]
             }
+            EvalError::Cancelled(span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![secondary(span).with_message("while evaluating this")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message("evaluation cancelled")
+                    .with_labels(labels)]
+            }
+            EvalError::DeniedWarning(warning) => files
+                .nested(warning)
+                .into_iter()
+                .map(|diag| Diagnostic {
+                    severity: Severity::Error,
+                    ..diag
+                })
+                .collect(),
+            EvalError::NonReproducible {
+                construct,
+                reason,
+                pos,
+            } => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("used here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("non-reproducible {construct}"))
+                    .with_labels(labels)
+                    .with_notes(vec![reason, REPRODUCIBLE_MODE_NOTE.to_owned()])]
+            }
+            EvalError::CapabilityDenied {
+                action,
+                reason,
+                pos,
+            } => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("denied here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("{action} denied"))
+                    .with_labels(labels)
+                    .with_notes(vec![reason])]
+            }
+            EvalError::ResourceExhausted {
+                resource,
+                pos,
+                call_stack,
+            } => {
+                use blame_error::ExtendWithCallStack;
+
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![secondary(span).with_message("while evaluating this")])
+                    .unwrap_or_default();
+
+                let mut diagnostics = vec![Diagnostic::error()
+                    .with_message(format!("evaluation exceeded {resource}"))
+                    .with_labels(labels)];
+                diagnostics.extend_with_call_stack(files, &call_stack);
+                diagnostics
+            }
+            EvalError::StackOverflow {
+                max_depth,
+                pos,
+                call_stack,
+            } => {
+                // A deep recursion may leave a very long call stack: only the innermost calls are
+                // shown.
+                const MAX_FRAMES: usize = 10;
+
+                let (calls, curr_call) = call_stack.group_by_calls(files);
+                let mut labels: Vec<_> = pos
+                    .as_opt_ref()
+                    .map(|span| primary(span).with_message("while evaluating this"))
+                    .into_iter()
+                    .collect();
+                labels.extend(curr_call.iter().chain(calls.iter()).take(MAX_FRAMES).map(
+                    |cdescr| {
+                        let name = cdescr
+                            .head
+                            .map(|ident| ident.to_string())
+                            .unwrap_or_else(|| String::from("<func>"));
+                        secondary(&cdescr.span).with_message(format!("calling {name}"))
+                    },
+                ));
+
+                let total = calls.len() + usize::from(curr_call.is_some());
+                let mut notes = vec![format!(
+                    "The evaluation stack exceeded the maximal depth of {max_depth}, which is \
+                    usually caused by a deep or infinite recursion."
+                )];
+                if total > MAX_FRAMES {
+                    notes.push(format!(
+                        "Only the {MAX_FRAMES} innermost calls out of {total} are shown."
+                    ));
+                }
+
+                vec![Diagnostic::error()
+                    .with_message("stack overflow")
+                    .with_labels(labels)
+                    .with_notes(notes)]
+            }
             EvalError::InternalError(msg, span_opt) => {
                 let labels = span_opt
                     .as_opt_ref()
@@ -1340,7 +2604,7 @@ impl IntoDiagnostics for EvalError {
                     .with_labels(labels)
                     .with_notes(vec![String::from(INTERNAL_ERROR_MSG)])]
             }
-            EvalError::SerializationError(err) => err.into_diagnostics(files),
+            EvalError::SerializationError(err) => files.nested(err),
             EvalError::DeserializationError(format, msg, span_opt) => {
                 let labels = span_opt
                     .as_opt_ref()
@@ -1474,7 +2738,7 @@ impl IntoDiagnostics for EvalError {
                 arg_pos,
                 arg_evaluated,
             )
-            .into_diagnostics(files),
+            .diagnostics_without_code(files),
             EvalError::NAryPrimopTypeError {
                 primop,
                 expected,
@@ -1490,7 +2754,7 @@ impl IntoDiagnostics for EvalError {
                 arg_pos,
                 arg_evaluated,
             )
-            .into_diagnostics(files),
+            .diagnostics_without_code(files),
             EvalError::QueryNonRecord { pos, id, value } => {
                 let label = format!(
                     "tried to query field `{}`, but the expression has type {}",
@@ -1520,19 +2784,19 @@ mod blame_error {
     use codespan_reporting::diagnostic::{Diagnostic, Label};
 
     use crate::{
-        eval::callstack::CallStack,
+        eval::callstack::{CallDescr, CallStack},
         files::{FileId, Files},
         label::{
             self,
             ty_path::{self, PathSpan},
             Polarity,
         },
-        position::TermPos,
+        position::{RawSpan, TermPos},
         term::RichTerm,
         typ::Type,
     };
 
-    use super::{primary, secondary, secondary_term};
+    use super::{primary, secondary, secondary_term, DiagnosticFileId, DiagnosticsBuilder};
 
     /// Returns a title to be used by blame errors based on the `path` and `polarity`
     /// of the label.
@@ -1562,9 +2826,9 @@ mod blame_error {
     pub fn build_diagnostic_labels(
         evaluated_arg: Option<RichTerm>,
         blame_label: &label::Label,
-        path_label: Label<FileId>,
-        files: &mut Files,
-    ) -> Vec<Label<FileId>> {
+        path_label: Label<DiagnosticFileId>,
+        files: &mut DiagnosticsBuilder,
+    ) -> Vec<Label<DiagnosticFileId>> {
         let mut labels = vec![path_label];
 
         if let Some(ref arg_pos) = blame_label.arg_pos.into_opt() {
@@ -1635,123 +2899,157 @@ mod blame_error {
         fn extend_with_call_stack(&mut self, files: &Files, call_stack: &CallStack);
     }
 
-    impl ExtendWithCallStack for Vec<Diagnostic<FileId>> {
+    impl ExtendWithCallStack for Vec<Diagnostic<DiagnosticFileId>> {
+        /// Add a note showing the chain of function calls leading to the error: the call being
+        /// evaluated when the error occurred, if any, as the primary label, and the enclosing
+        /// calls, from the innermost to the outermost, as numbered secondary labels.
         fn extend_with_call_stack(&mut self, files: &Files, call_stack: &CallStack) {
-            let (calls, curr_call) = call_stack.group_by_calls(files);
-            let diag_curr_call = curr_call.map(|cdescr| {
-                let name = cdescr
+            fn call_name(cdescr: &CallDescr) -> String {
+                cdescr
                     .head
                     .map(|ident| ident.to_string())
-                    .unwrap_or_else(|| String::from("<func>"));
-                Diagnostic::note().with_labels(vec![
-                    primary(&cdescr.span).with_message(format!("While calling to {name}"))
-                ])
-            });
-            let diags =
-                calls.into_iter().enumerate().map(|(i, cdescr)| {
-                    let name = cdescr
-                        .head
-                        .map(|ident| ident.to_string())
-                        .unwrap_or_else(|| String::from("<func>"));
-                    Diagnostic::note().with_labels(vec![secondary(&cdescr.span)
-                        .with_message(format!("({}) calling {}", i + 1, name))])
-                });
+                    .unwrap_or_else(|| String::from("<func>"))
+            }
+
+            let (calls, curr_call) = call_stack.group_by_calls(files);
+
+            let labels: Vec<_> = curr_call
+                .map(|cdescr| {
+                    primary(&cdescr.span)
+                        .with_message(format!("while calling {}", call_name(&cdescr)))
+                })
+                .into_iter()
+                .chain(calls.iter().enumerate().map(|(i, cdescr)| {
+                    secondary(&cdescr.span).with_message(format!(
+                        "({}) calling {}",
+                        i + 1,
+                        call_name(cdescr)
+                    ))
+                }))
+                .collect();
 
-            self.extend(diag_curr_call);
-            self.extend(diags);
+            if !labels.is_empty() {
+                self.push(
+                    Diagnostic::note()
+                        .with_message("call chain leading to the error")
+                        .with_labels(labels),
+                );
+            }
         }
     }
 
     /// Calls [`crate::label::ty_path::span`], but if the call returns `None` (the position of the
-    /// subtype isn't defined), [path_span] pretty-prints the type inside a new source, parses it,
+    /// subtype isn't defined), [path_span] pretty-prints the type inside a new snippet, parses it,
     /// and calls `ty_path::span`. This new type is guaranteed to have all of its positions set,
     /// providing a definite `PathSpan`. This is similar to the behavior of [`super::primary_alt`].
-    pub fn path_span(files: &mut Files, path: &[ty_path::Elem], ty: &Type) -> PathSpan {
+    ///
+    /// Return the path span together with the file id its span refers to, which is the snippet in
+    /// the second case.
+    pub fn path_span(
+        files: &mut DiagnosticsBuilder,
+        path: &[ty_path::Elem],
+        ty: &Type,
+    ) -> (PathSpan, DiagnosticFileId) {
         use crate::parser::{grammar::FixedTypeParser, lexer::Lexer, ErrorTolerantParser};
 
-        ty_path::span(path.iter().peekable(), ty)
-            .or_else(|| {
-                let type_pprinted = format!("{ty}");
-                let file_id = files.add(super::UNKNOWN_SOURCE_NAME, type_pprinted.clone());
-
-                let ty_with_pos = FixedTypeParser::new()
-                    .parse_strict(file_id, Lexer::new(&type_pprinted))
-                    .unwrap();
+        if let Some(path_span) = ty_path::span(path.iter().peekable(), ty) {
+            let file_id = path_span.span.src_id.into();
+            return (path_span, file_id);
+        }
 
-                ty_path::span(path.iter().peekable(), &ty_with_pos)
-            })
-            .expect(
-                "path_span: we pretty-printed and parsed again the type of a label, \
-                so it must have all of its position defined, but `ty_path::span` returned `None`",
-            )
+        let type_pprinted = format!("{ty}");
+        // The snippet isn't a file of the program: the file id of the positions of the parsed type
+        // is irrelevant, as they are reported relatively to the snippet.
+        let ty_with_pos = FixedTypeParser::new()
+            .parse_strict(FileId::DETACHED, Lexer::new(&type_pprinted))
+            .unwrap();
+        let path_span = ty_path::span(path.iter().peekable(), &ty_with_pos).expect(
+            "path_span: we pretty-printed and parsed again the type of a label, \
+            so it must have all of its position defined, but `ty_path::span` returned `None`",
+        );
+
+        (path_span, files.add_snippet(type_pprinted))
     }
 
     /// Generate a codespan label that describes the [type path][crate::label::ty_path::Path] of a
     /// (Nickel) label.
-    pub fn report_ty_path(files: &mut Files, l: &label::Label) -> Label<FileId> {
-        let PathSpan {
-            span,
-            last,
-            last_arrow_elem,
-        } = path_span(files, &l.path, &l.typ);
+    pub fn report_ty_path(
+        files: &mut DiagnosticsBuilder,
+        l: &label::Label,
+    ) -> Label<DiagnosticFileId> {
+        let (
+            PathSpan {
+                span,
+                last,
+                last_arrow_elem,
+                depth,
+            },
+            file_id,
+        ) = path_span(files, &l.path, &l.typ);
+
+        // The argument containing the failing subcontract, such as `the 2nd argument`, when the
+        // subcontract is part of the domain of an arrow.
+        let arg = ty_path::arg_position(&l.path[..depth])
+            .map(|position| format!("the {} argument", super::cardinal(position)))
+            .unwrap_or_else(|| String::from("an argument"));
 
         let msg = match (last, last_arrow_elem) {
             // The type path doesn't contain any arrow, and the failing subcontract is the
             // contract for the elements of an array
-            (Some(ty_path::Elem::Array), None) => "expected array element type",
+            (Some(ty_path::Elem::Array), None) => "expected array element type".to_owned(),
             // The type path doesn't contain any arrow, and the failing subcontract is the contract
             // for the fields of a dictionary
-            (Some(ty_path::Elem::Dict), None) => "expected dictionary field type",
+            (Some(ty_path::Elem::Dict), None) => "expected dictionary field type".to_owned(),
             // The type path doesn't contain any arrow, and the failing subcontract is the contract
             // for the field of a record
-            (Some(ty_path::Elem::Field(_)), None) => "expected field type",
+            (Some(ty_path::Elem::Field(_)), None) => "expected field type".to_owned(),
             // The original contract contains an arrow, and the path is only composed of codomains.
             // Then polarity is necessarily true and the cause of the blame is the return value of
             // the function
             (Some(_), Some(ty_path::Elem::Codomain)) if ty_path::has_no_dom(&l.path) => {
-                "expected return type"
+                "expected return type".to_owned()
             }
             // The original contract contains an arrow, the subcontract is the domain of an
             // arrow, and the polarity is positive. The function is to be blamed for calling an
             // argument on a value of the wrong type.
             (Some(_), Some(ty_path::Elem::Domain)) if l.polarity == Polarity::Positive => {
-                "expected type of an argument of an inner call"
+                format!("expected type of {arg} of an inner call")
             }
             // The original contract contains an arrow, the subcontract is the codomain of an
             // arrow, and the polarity is positive. The function is to be blamed for calling a
             // higher-order function argument on a function which returns a value of the wrong
             // type.
             (Some(_), Some(ty_path::Elem::Codomain)) if l.polarity == Polarity::Positive => {
-                "expected return type of a sub-function passed as an argument of an inner call"
+                format!("expected return type of a sub-function passed as {arg} of an inner call")
             }
             // The original contract contains an arrow, the subcontract is the domain of an arrow,
             // and the polarity is negative. The caller is to be blamed for providing an argument
             // of the wrong type.
             (Some(_), Some(ty_path::Elem::Domain)) => {
-                "expected type of the argument provided by the caller"
+                format!("expected type of {arg} provided by the caller")
             }
             // The original contract contains an arrow, the subcontract is the codomain of an
             // arrow, and the polarity is negative. The caller is to be blamed for providing a
             // higher-order function argument which returns a value of the wrong type.
             (Some(_), Some(ty_path::Elem::Codomain)) => {
-                "expected return type of a function provided by the caller"
+                format!("expected return type of a function provided by the caller as {arg}")
             }
             // If there is a last arrow element, then there must be last element
             (None, Some(_)) => panic!(
                 "blame error reporting: inconsistent path analysis, last_elem\
 is None but last_arrow_elem is Some"
             ),
-            _ => "expected type",
+            _ => "expected type".to_owned(),
         };
 
-        secondary(&span).with_message(msg.to_owned())
+        Label::secondary(file_id, span.start.to_usize()..span.end.to_usize()).with_message(msg)
     }
 
     /// Return a note diagnostic showing where a contract was bound.
-    pub fn contract_bind_loc(l: &label::Label) -> Diagnostic<FileId> {
+    pub fn contract_bind_loc(span: &RawSpan) -> Diagnostic<DiagnosticFileId> {
         Diagnostic::note().with_labels(vec![Label::primary(
-            l.span.src_id,
-            l.span.start.to_usize()..l.span.end.to_usize(),
+            span.src_id.into(),
+            span.start.to_usize()..span.end.to_usize(),
         )
         .with_message("bound here")])
     }
@@ -1765,12 +3063,12 @@ is None but last_arrow_elem is Some"
     /// leading "contract broken by .." and the custom contract diagnostic message in tail
     /// position.
     pub fn blame_diagnostics(
-        files: &mut Files,
+        files: &mut DiagnosticsBuilder,
         mut label: label::Label,
         evaluated_arg: Option<RichTerm>,
         call_stack: &CallStack,
         msg_addendum: &str,
-    ) -> Vec<Diagnostic<FileId>> {
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
         use std::fmt::Write;
 
         let mut diagnostics = Vec::new();
@@ -1804,11 +3102,22 @@ is None but last_arrow_elem is Some"
             write!(&mut msg, "{new_msg_block}{}", &super::escape(contract_msg)).unwrap();
         }
 
-        let contract_notes = head_contract_diagnostic
+        let mut contract_notes = head_contract_diagnostic
             .map(|diag| diag.notes)
             .unwrap_or_default();
         let path_label = report_ty_path(files, &label);
 
+        // The value of a secret field must not end up in logs.
+        let evaluated_arg = if label.secret {
+            contract_notes.push(match label.field_name {
+                Some(ident) => format!("The value of `{ident}` is secret and has been redacted."),
+                None => "The value is secret and has been redacted.".to_owned(),
+            });
+            None
+        } else {
+            evaluated_arg
+        };
+
         let labels = build_diagnostic_labels(evaluated_arg, &label, path_label, files);
 
         // If there are notes in the head contract diagnostic, we build the first
@@ -1849,7 +3158,19 @@ is None but last_arrow_elem is Some"
 }
 
 impl IntoDiagnostics for ParseError {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = self.code();
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl ParseError {
+    fn diagnostics_without_code(
+        self,
+        files: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
         let diagnostic = match self {
             ParseError::UnexpectedEOF(file_id, _expected) => {
                 let end = files.source_span(file_id).end;
@@ -2068,8 +3389,20 @@ impl IntoDiagnostics for ParseError {
 }
 
 impl IntoDiagnostics for TypecheckError {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
-        fn mk_expr_label(span_opt: &TermPos) -> Vec<Label<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = self.code();
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl TypecheckError {
+    fn diagnostics_without_code(
+        self,
+        files: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
+        fn mk_expr_label(span_opt: &TermPos) -> Vec<Label<DiagnosticFileId>> {
             span_opt
                 .as_opt_ref()
                 .map(|span| vec![primary(span).with_message("this expression")])
@@ -2085,10 +3418,10 @@ impl IntoDiagnostics for TypecheckError {
         }
 
         match self {
-            TypecheckError::UnboundIdentifier { id, pos } =>
+            TypecheckError::UnboundIdentifier { id, pos, in_scope } =>
             // Use the same diagnostic as `EvalError::UnboundIdentifier` for consistency.
             {
-                EvalError::UnboundIdentifier(id, pos).into_diagnostics(files)
+                EvalError::UnboundIdentifier { id, pos, in_scope }.diagnostics_without_code(files)
             }
             TypecheckError::MissingRow {
                 id,
@@ -2268,10 +3601,16 @@ impl IntoDiagnostics for TypecheckError {
                 // We generate a diagnostic for the underlying error, but append a prefix to the
                 // error message to make it clear that this is not a separate error but a more
                 // precise description of why the unification of a row failed.
-                diags.extend((*err).into_diagnostics(files).into_iter().map(|mut diag| {
-                    diag.message = format!("while typing field `{}`: {}", field, diag.message);
-                    diag
-                }));
+                diags.extend(
+                    (*err)
+                        .diagnostics_without_code(files)
+                        .into_iter()
+                        .map(|mut diag| {
+                            diag.message =
+                                format!("while typing field `{}`: {}", field, diag.message);
+                            diag
+                        }),
+                );
                 diags
             }
             TypecheckError::EnumRowMismatch {
@@ -2322,10 +3661,13 @@ impl IntoDiagnostics for TypecheckError {
                 // the error message to make it clear that this is not a separate error but a more
                 // precise description of why the unification of a row failed.
                 if let Some(err) = cause {
-                    diags.extend((*err).into_diagnostics(files).into_iter().map(|mut diag| {
-                        diag.message = format!("while typing enum row `{id}`: {}", diag.message);
-                        diag
-                    }));
+                    diags.extend((*err).diagnostics_without_code(files).into_iter().map(
+                        |mut diag| {
+                            diag.message =
+                                format!("while typing enum row `{id}`: {}", diag.message);
+                            diag
+                        },
+                    ));
                 }
 
                 diags
@@ -2409,17 +3751,30 @@ impl IntoDiagnostics for TypecheckError {
                 cause,
                 pos,
             } => {
-                let PathSpan {
-                    span: expd_span, ..
-                } = blame_error::path_span(files, &type_path, &expected);
-                let PathSpan {
-                    span: actual_span, ..
-                } = blame_error::path_span(files, &type_path, &inferred);
+                let (
+                    PathSpan {
+                        span: expd_span, ..
+                    },
+                    expd_file_id,
+                ) = blame_error::path_span(files, &type_path, &expected);
+                let (
+                    PathSpan {
+                        span: actual_span, ..
+                    },
+                    actual_file_id,
+                ) = blame_error::path_span(files, &type_path, &inferred);
 
                 let mut labels = vec![
-                    secondary(&expd_span).with_message("this part of the expected type"),
-                    secondary(&actual_span)
-                        .with_message("does not match this part of the inferred type"),
+                    Label::secondary(
+                        expd_file_id,
+                        expd_span.start.to_usize()..expd_span.end.to_usize(),
+                    )
+                    .with_message("this part of the expected type"),
+                    Label::secondary(
+                        actual_file_id,
+                        actual_span.start.to_usize()..actual_span.end.to_usize(),
+                    )
+                    .with_message("does not match this part of the inferred type"),
                 ];
                 labels.extend(mk_expr_label(&pos));
 
@@ -2440,11 +3795,13 @@ impl IntoDiagnostics for TypecheckError {
                     // information, so we just ignore it.
                     TypecheckError::TypeMismatch { .. } => (),
                     err => {
-                        diags.extend(err.into_diagnostics(files).into_iter().map(|mut diag| {
-                            diag.message =
-                                format!("while matching function types: {}", diag.message);
-                            diag
-                        }));
+                        diags.extend(err.diagnostics_without_code(files).into_iter().map(
+                            |mut diag| {
+                                diag.message =
+                                    format!("while matching function types: {}", diag.message);
+                                diag
+                            },
+                        ));
                     }
                 }
 
@@ -2535,7 +3892,19 @@ impl IntoDiagnostics for TypecheckError {
 }
 
 impl IntoDiagnostics for ImportError {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = self.code();
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl ImportError {
+    fn diagnostics_without_code(
+        self,
+        files: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
         match self {
             ImportError::IOError(path, error, span_opt) => {
                 let labels = span_opt
@@ -2548,10 +3917,10 @@ impl IntoDiagnostics for ImportError {
                     .with_labels(labels)]
             }
             ImportError::ParseErrors(error, span_opt) => {
-                let mut diagnostic: Vec<Diagnostic<FileId>> = error
+                let mut diagnostic: Vec<Diagnostic<DiagnosticFileId>> = error
                     .errors
                     .into_iter()
-                    .flat_map(|e| e.into_diagnostics(files))
+                    .flat_map(|e| files.nested(e))
                     .collect();
 
                 if let Some(span) = span_opt.as_opt_ref() {
@@ -2562,12 +3931,46 @@ impl IntoDiagnostics for ImportError {
 
                 diagnostic
             }
+            ImportError::NonReproducible(path, reason, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("non-reproducible import of {path}"))
+                    .with_labels(labels)
+                    .with_notes(vec![reason, REPRODUCIBLE_MODE_NOTE.to_owned()])]
+            }
+            ImportError::CapabilityDenied(path, reason, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("import of {path} denied"))
+                    .with_labels(labels)
+                    .with_notes(vec![reason])]
+            }
         }
     }
 }
 
 impl IntoDiagnostics for ExportError {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = self.code();
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl ExportError {
+    fn diagnostics_without_code(
+        self,
+        files: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
         let mut notes = if !self.path.0.is_empty() {
             vec![format!("When exporting field `{}`", self.path)]
         } else {
@@ -2584,6 +3987,53 @@ impl IntoDiagnostics for ExportError {
                 ))
                 .with_labels(vec![primary_term(&rt, files)])
                 .with_notes(notes)],
+            ExportErrorData::NotARecord(format, rt) => {
+                notes.push(match format {
+                    ExportFormat::Xml => "An XML document is exported from a record with a \
+                        single field, which is the root element."
+                        .to_owned(),
+                    ExportFormat::Dotenv | ExportFormat::Shell => format!(
+                        "A {format} file is exported from a record whose fields are the \
+                        variables."
+                    ),
+                    ExportFormat::Ini => "An INI file is exported from a record whose fields \
+                        are the sections and the global keys."
+                        .to_owned(),
+                    ExportFormat::Properties => "A properties file is exported from a record, \
+                        whose nested fields are flattened into dotted keys."
+                        .to_owned(),
+                    ExportFormat::Hcl => "An HCL file is exported from a record whose fields \
+                        are the top-level blocks and attributes."
+                        .to_owned(),
+                    _ => format!(
+                        "{format} documents are tables, which correspond to records: wrap the \
+                        value in a record to export it."
+                    ),
+                });
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "{format} export expects a Record value, but got {}",
+                        rt.as_ref()
+                            .type_of()
+                            .unwrap_or_else(|| String::from("<unevaluated>"))
+                    ))
+                    .with_labels(vec![primary_term(&rt, files)])
+                    .with_notes(notes)]
+            }
+            ExportErrorData::UnsupportedNesting(format, rt) => {
+                notes.push(format!(
+                    "The values of a {format} file are strings: only strings, numbers, booleans \
+                    and enum tags can be exported."
+                ));
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "{format} format doesn't support nested records or arrays"
+                    ))
+                    .with_labels(vec![primary_term(&rt, files)])
+                    .with_notes(notes)]
+            }
             ExportErrorData::UnsupportedNull(format, rt) => vec![Diagnostic::error()
                 .with_message(format!("{format} format doesn't support null values"))
                 .with_labels(vec![primary_term(&rt, files)])
@@ -2630,6 +4080,10 @@ impl IntoDiagnostics for ExportError {
                     .with_labels(vec![primary_term(&term, files)])
                     .with_notes(notes)]
             }
+            ExportErrorData::Deserialization { message, term } => vec![Diagnostic::error()
+                .with_message(format!("deserialization failed: {message}"))
+                .with_labels(vec![primary_term(&term, files)])
+                .with_notes(notes)],
             ExportErrorData::Other(msg) => {
                 notes.push(msg);
 
@@ -2641,8 +4095,61 @@ impl IntoDiagnostics for ExportError {
     }
 }
 
+impl IntoDiagnostics for AccessError {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = self.code();
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl AccessError {
+    fn diagnostics_without_code(
+        self,
+        files: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
+        let mut notes = if !self.path.0.is_empty() {
+            vec![format!("When accessing `{}`", self.path)]
+        } else {
+            vec![]
+        };
+        let message = self.to_string();
+
+        let labels = match self.data {
+            AccessErrorData::TypeMismatch { value, .. }
+            | AccessErrorData::InvalidNumber { value, .. } => vec![primary_term(&value, files)],
+            AccessErrorData::MissingField { field, record } => {
+                if let Term::Record(data) = record.as_ref() {
+                    let field_names = data.field_names(RecordOpKind::IgnoreEmptyOpt);
+                    suggest::add_suggestion(&mut notes, &field_names, &field.label());
+                }
+
+                vec![primary_term(&record, files).with_message("in this record")]
+            }
+        };
+
+        vec![Diagnostic::error()
+            .with_message(message)
+            .with_labels(labels)
+            .with_notes(notes)]
+    }
+}
+
 impl IntoDiagnostics for IOError {
-    fn into_diagnostics(self, _fil: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = ErrorCode::Io;
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl IOError {
+    fn diagnostics_without_code(
+        self,
+        _fil: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
         match self {
             IOError(msg) => vec![Diagnostic::error().with_message(msg)],
         }
@@ -2650,14 +4157,26 @@ impl IntoDiagnostics for IOError {
 }
 
 impl IntoDiagnostics for ReplError {
-    fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let code = self.code();
+        DiagnosticsBuilder::build(files, |files| {
+            code.attach(self.diagnostics_without_code(files))
+        })
+    }
+}
+
+impl ReplError {
+    fn diagnostics_without_code(
+        self,
+        files: &mut DiagnosticsBuilder,
+    ) -> Vec<Diagnostic<DiagnosticFileId>> {
         match self {
             ReplError::UnknownCommand(s) => vec![Diagnostic::error()
                 .with_message(format!("unknown command `{s}`"))
                 .with_notes(vec![String::from(
                     "type `:?` or `:help` for a list of available commands.",
                 )])],
-            ReplError::InvalidQueryPath(err) => err.into_diagnostics(files),
+            ReplError::InvalidQueryPath(err) => files.nested(err),
             ReplError::MissingArg { cmd, msg_opt } => {
                 let mut notes = msg_opt
                     .as_ref()
@@ -4,17 +4,229 @@ use super::*;
 /// Serializable wrapper type to export diagnostics with a top-level attribute.
 #[derive(serde::Serialize)]
 pub struct DiagnosticsWrapper {
-    pub diagnostics: Vec<Diagnostic<FileId>>,
+    pub diagnostics: Vec<SerializableDiagnostic>,
 }
 
-impl From<Vec<Diagnostic<FileId>>> for DiagnosticsWrapper {
-    fn from(diagnostics: Vec<Diagnostic<FileId>>) -> Self {
-        Self { diagnostics }
+impl DiagnosticsWrapper {
+    /// Convert diagnostics to their serializable form, resolving the file ids and the byte
+    /// offsets of their labels using `files`.
+    pub fn new(diagnostics: Vec<Diagnostic<DiagnosticFileId>>, files: &DiagnosticFiles) -> Self {
+        Self {
+            diagnostics: diagnostics
+                .into_iter()
+                .map(|diagnostic| SerializableDiagnostic::new(diagnostic, files))
+                .collect(),
+        }
+    }
+}
+
+/// A diagnostic in a self-contained form, which can be consumed by tools without access to the
+/// file database of the interpreter.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct SerializableDiagnostic {
+    /// One of `bug`, `error`, `warning`, `note` or `help`.
+    pub severity: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    pub labels: Vec<SerializableLabel>,
+    pub notes: Vec<String>,
+}
+
+impl SerializableDiagnostic {
+    pub fn new(diagnostic: Diagnostic<DiagnosticFileId>, files: &DiagnosticFiles) -> Self {
+        let severity = match diagnostic.severity {
+            Severity::Bug => "bug",
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        };
+
+        SerializableDiagnostic {
+            severity,
+            code: diagnostic.code,
+            message: diagnostic.message,
+            labels: diagnostic
+                .labels
+                .into_iter()
+                .map(|label| SerializableLabel::new(label, files))
+                .collect(),
+            notes: diagnostic.notes,
+        }
+    }
+}
+
+/// A label of a [SerializableDiagnostic], pointing to a span of a source.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct SerializableLabel {
+    /// Either `primary` or `secondary`.
+    pub style: &'static str,
+    pub message: String,
+    /// The name of the file of the span.
+    pub file: String,
+    /// The start of the span, as a byte offset.
+    pub start_offset: usize,
+    /// The end of the span, as a byte offset (exclusive).
+    pub end_offset: usize,
+    /// The start of the span, as a position in the file.
+    pub start: SerializablePosition,
+    /// The end of the span, as a position in the file.
+    pub end: SerializablePosition,
+}
+
+impl SerializableLabel {
+    pub fn new(label: Label<DiagnosticFileId>, files: &DiagnosticFiles) -> Self {
+        let style = match label.style {
+            LabelStyle::Primary => "primary",
+            LabelStyle::Secondary => "secondary",
+        };
+
+        SerializableLabel {
+            style,
+            message: label.message,
+            file: files.name(label.file_id),
+            start_offset: label.range.start,
+            end_offset: label.range.end,
+            start: SerializablePosition::new(files, label.file_id, label.range.start),
+            end: SerializablePosition::new(files, label.file_id, label.range.end),
+        }
+    }
+}
+
+/// A position in a file. Lines and columns start at 1, and columns are counted in characters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct SerializablePosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SerializablePosition {
+    fn new(files: &DiagnosticFiles, file_id: DiagnosticFileId, offset: usize) -> Self {
+        let source = files.source(file_id);
+        // The end of a span can be the end of the file, which `Files::location` rejects. We
+        // compute the position by hand, which also handles offsets out of the source gracefully.
+        let before = source.get(..offset.min(source.len())).unwrap_or(source);
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+        SerializablePosition {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// The maximal number of locations shown by an aggregated diagnostic. See [Aggregated].
+pub const AGGREGATED_SAMPLES: usize = 3;
+
+/// A list of errors reported together, where identical errors are collapsed into one diagnostic.
+///
+/// Errors are identical when their main diagnostics have the same severity, code, message and
+/// secondary labels, which typically point to the contract which failed. Only their primary
+/// labels, which point to the offending values, differ. The first error of a group is reported
+/// with the primary labels of the first [AGGREGATED_SAMPLES] errors of the group and a note giving
+/// the total number of occurrences. Exact duplicates are only reported once. Groups are reported
+/// in the order of their first error.
+pub struct Aggregated<E>(pub Vec<E>);
+
+struct Group {
+    /// The diagnostics of the first error of the group.
+    diagnostics: Vec<Diagnostic<DiagnosticFileId>>,
+    /// The primary labels of each occurrence.
+    occurrences: Vec<Vec<Label<DiagnosticFileId>>>,
+}
+
+fn primary_labels(diagnostic: &Diagnostic<DiagnosticFileId>) -> Vec<Label<DiagnosticFileId>> {
+    diagnostic
+        .labels
+        .iter()
+        .filter(|label| label.style == LabelStyle::Primary)
+        .cloned()
+        .collect()
+}
+
+fn same_group(d1: &Diagnostic<DiagnosticFileId>, d2: &Diagnostic<DiagnosticFileId>) -> bool {
+    let secondary_labels = |d: &Diagnostic<DiagnosticFileId>| -> Vec<Label<DiagnosticFileId>> {
+        d.labels
+            .iter()
+            .filter(|label| label.style == LabelStyle::Secondary)
+            .cloned()
+            .collect()
+    };
+
+    d1.severity == d2.severity
+        && d1.code == d2.code
+        && d1.message == d2.message
+        && secondary_labels(d1) == secondary_labels(d2)
+}
+
+impl<E: IntoDiagnostics> IntoDiagnostics for Aggregated<E> {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        DiagnosticsBuilder::build(files, |files| {
+            let mut groups: Vec<Group> = Vec::new();
+
+            for error in self.0 {
+                let diagnostics = files.nested(error);
+
+                let Some(main) = diagnostics.first() else {
+                    continue;
+                };
+
+                let primary = primary_labels(main);
+
+                match groups
+                    .iter_mut()
+                    .find(|group| same_group(&group.diagnostics[0], main))
+                {
+                    Some(group) if !group.occurrences.contains(&primary) => {
+                        group.occurrences.push(primary)
+                    }
+                    Some(_) => (),
+                    None => groups.push(Group {
+                        diagnostics,
+                        occurrences: vec![primary],
+                    }),
+                }
+            }
+
+            groups
+                .into_iter()
+                .flat_map(|group| {
+                    let mut diagnostics = group.diagnostics;
+                    let count = group.occurrences.len();
+
+                    if count > 1 {
+                        let main = &mut diagnostics[0];
+                        main.labels
+                            .retain(|label| label.style == LabelStyle::Secondary);
+                        main.labels.extend(
+                            group
+                                .occurrences
+                                .into_iter()
+                                .take(AGGREGATED_SAMPLES)
+                                .flatten(),
+                        );
+
+                        main.notes.push(if count > AGGREGATED_SAMPLES {
+                            format!(
+                                "this error occurred {count} times, only the first \
+                            {AGGREGATED_SAMPLES} locations are shown"
+                            )
+                        } else {
+                            format!("this error occurred {count} times")
+                        });
+                    }
+
+                    diagnostics
+                })
+                .collect()
+        })
     }
 }
 
 /// Available export formats for error diagnostics.
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 pub enum ErrorFormat {
     #[default]
     Text,
@@ -24,36 +236,163 @@ pub enum ErrorFormat {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub struct ColorOpt(pub(crate) clap::ColorChoice);
+pub(crate) enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+/// Whether to use colors when reporting errors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColorOpt(pub(crate) ColorMode);
 
 impl ColorOpt {
+    /// Use colors if the output is a terminal.
+    pub const AUTO: ColorOpt = ColorOpt(ColorMode::Auto);
+    pub const ALWAYS: ColorOpt = ColorOpt(ColorMode::Always);
+    pub const NEVER: ColorOpt = ColorOpt(ColorMode::Never);
+
     fn for_terminal(self, is_terminal: bool) -> ColorChoice {
         match self.0 {
-            clap::ColorChoice::Auto => {
+            ColorMode::Auto => {
                 if is_terminal {
                     ColorChoice::Auto
                 } else {
                     ColorChoice::Never
                 }
             }
-            clap::ColorChoice::Always => ColorChoice::Always,
-            clap::ColorChoice::Never => ColorChoice::Never,
+            ColorMode::Always => ColorChoice::Always,
+            ColorMode::Never => ColorChoice::Never,
         }
     }
 }
 
+#[cfg(feature = "clap")]
 impl From<clap::ColorChoice> for ColorOpt {
     fn from(color_choice: clap::ColorChoice) -> Self {
-        Self(color_choice)
+        match color_choice {
+            clap::ColorChoice::Auto => ColorOpt::AUTO,
+            clap::ColorChoice::Always => ColorOpt::ALWAYS,
+            clap::ColorChoice::Never => ColorOpt::NEVER,
+        }
     }
 }
 
 impl Default for ColorOpt {
     fn default() -> Self {
-        Self(clap::ColorChoice::Auto)
+        ColorOpt::AUTO
     }
 }
 
+/// The characters used to draw the source snippets of text diagnostics.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ReportChars {
+    /// Box-drawing characters.
+    #[default]
+    Unicode,
+    /// ASCII characters only, for terminals or log collectors which don't support unicode.
+    Ascii,
+}
+
+/// The style of text diagnostics. Colors are configured separately by [ColorOpt]. The style is
+/// ignored by the structured [ErrorFormat]s.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReportStyle {
+    pub chars: ReportChars,
+    /// The width of the output, in characters. The messages and the notes of diagnostics are
+    /// wrapped at word boundaries to fit in this width, as far as possible. Source snippets are
+    /// never wrapped. `None` disables wrapping.
+    pub width: Option<usize>,
+}
+
+impl ReportStyle {
+    fn config(&self) -> codespan_reporting::term::Config {
+        use codespan_reporting::term::{Chars, Config};
+
+        Config {
+            chars: match self.chars {
+                ReportChars::Unicode => Chars::box_drawing(),
+                ReportChars::Ascii => Chars::ascii(),
+            },
+            ..Config::default()
+        }
+    }
+
+    /// Wrap the message and the notes of a diagnostic to fit in the width of the style.
+    fn wrap(&self, mut diagnostic: Diagnostic<DiagnosticFileId>) -> Diagnostic<DiagnosticFileId> {
+        let Some(width) = self.width else {
+            return diagnostic;
+        };
+
+        // The message is preceded by the severity and the code, as in `error[E001]: `.
+        let severity_len = match diagnostic.severity {
+            Severity::Bug => "bug".len(),
+            Severity::Error => "error".len(),
+            Severity::Warning => "warning".len(),
+            Severity::Note => "note".len(),
+            Severity::Help => "help".len(),
+        };
+        let prefix_len =
+            severity_len + diagnostic.code.as_ref().map_or(0, |code| code.len() + 2) + 2;
+
+        diagnostic.message = wrap(
+            &diagnostic.message,
+            width.saturating_sub(prefix_len),
+            &" ".repeat(prefix_len),
+        );
+        // Continuation lines of notes are indented by codespan.
+        diagnostic.notes = diagnostic
+            .notes
+            .iter()
+            .map(|note| wrap(note, width.saturating_sub(NOTE_PREFIX_LEN), ""))
+            .collect();
+
+        diagnostic
+    }
+}
+
+/// An estimate of the width of the gutter and the bullet preceding the notes of a diagnostic, as
+/// in `   = `.
+const NOTE_PREFIX_LEN: usize = 6;
+
+/// Wrap `text` at word boundaries so that its lines fit in `width` characters, as far as possible:
+/// a word longer than `width` is put on its own line. Existing line breaks and the indentation of
+/// existing lines are preserved, and the lines introduced by wrapping are indented with `indent`.
+fn wrap(text: &str, width: usize, indent: &str) -> String {
+    let mut lines = Vec::new();
+
+    for line in text.lines() {
+        let content = line.trim_start();
+        let mut current = line[..line.len() - content.len()].to_owned();
+        let mut current_len = current.chars().count();
+        let mut has_word = false;
+
+        for word in content.split(' ').filter(|word| !word.is_empty()) {
+            let word_len = word.chars().count();
+
+            if has_word && current_len + 1 + word_len > width {
+                lines.push(std::mem::replace(&mut current, indent.to_owned()));
+                current_len = indent.chars().count();
+                has_word = false;
+            }
+
+            if has_word {
+                current.push(' ');
+                current_len += 1;
+            }
+
+            current.push_str(word);
+            current_len += word_len;
+            has_word = true;
+        }
+
+        lines.push(current);
+    }
+
+    lines.join("\n")
+}
+
 /// Pretty-print an error on stderr.
 ///
 /// # Arguments
@@ -61,18 +400,20 @@ impl Default for ColorOpt {
 /// - `cache` is the file cache used during the evaluation, which is required by the reporting
 ///   infrastructure to point at specific locations and print snippets when needed.
 pub fn report<E: IntoDiagnostics>(
-    cache: &mut Cache,
+    cache: &Cache,
     error: E,
     format: ErrorFormat,
     color_opt: ColorOpt,
+    style: ReportStyle,
 ) {
     use std::io::{stderr, IsTerminal};
 
-    report_with(
+    report_with_style(
         &mut StandardStream::stderr(color_opt.for_terminal(stderr().is_terminal())).lock(),
-        &mut cache.files().clone(),
+        cache.files(),
         error,
         format,
+        style,
     )
 }
 
@@ -83,48 +424,235 @@ pub fn report<E: IntoDiagnostics>(
 /// - `cache` is the file cache used during the evaluation, which is required by the reporting
 ///   infrastructure to point at specific locations and print snippets when needed.
 pub fn report_to_stdout<E: IntoDiagnostics>(
-    cache: &mut Cache,
+    cache: &Cache,
     error: E,
     format: ErrorFormat,
     color_opt: ColorOpt,
+    style: ReportStyle,
 ) {
     use std::io::{stdout, IsTerminal};
 
-    report_with(
+    report_with_style(
         &mut StandardStream::stdout(color_opt.for_terminal(stdout().is_terminal())).lock(),
-        &mut cache.files().clone(),
+        cache.files(),
         error,
         format,
+        style,
     )
 }
 
-/// Report an error on `stderr`, provided a file database and a list of stdlib file ids.
+/// Report an error on `writer`, provided a file database.
 pub fn report_with<E: IntoDiagnostics>(
     writer: &mut dyn WriteColor,
-    files: &mut Files,
+    files: &Files,
     error: E,
     format: ErrorFormat,
 ) {
-    let config = codespan_reporting::term::Config::default();
-    let diagnostics = error.into_diagnostics(files);
-    let stderr = std::io::stderr();
+    report_with_style(writer, files, error, format, ReportStyle::default())
+}
+
+/// Report an error on `writer`, provided a file database and the style of text diagnostics.
+pub fn report_with_style<E: IntoDiagnostics>(
+    writer: &mut dyn WriteColor,
+    files: &Files,
+    error: E,
+    format: ErrorFormat,
+    style: ReportStyle,
+) {
+    let config = style.config();
+    let Diagnostics {
+        diagnostics,
+        snippets,
+    } = error.into_diagnostics(files);
+    let files = DiagnosticFiles::new(files, &snippets);
 
     let result = match format {
-        ErrorFormat::Text => diagnostics.iter().try_for_each(|d| {
-            codespan_reporting::term::emit(writer, &config, files, d).map_err(|err| err.to_string())
+        ErrorFormat::Text => diagnostics.into_iter().try_for_each(|d| {
+            codespan_reporting::term::emit(writer, &config, &files, &style.wrap(d))
+                .map_err(|err| err.to_string())
         }),
-        ErrorFormat::Json => serde_json::to_writer(stderr, &DiagnosticsWrapper::from(diagnostics))
-            .map(|_| eprintln!())
-            .map_err(|err| err.to_string()),
-        ErrorFormat::Yaml => serde_yaml::to_writer(stderr, &DiagnosticsWrapper::from(diagnostics))
-            .map_err(|err| err.to_string()),
-        ErrorFormat::Toml => toml::to_string(&DiagnosticsWrapper::from(diagnostics))
-            .map(|repr| eprint!("{}", repr))
-            .map_err(|err| err.to_string()),
+        ErrorFormat::Json => {
+            serde_json::to_writer(&mut *writer, &DiagnosticsWrapper::new(diagnostics, &files))
+                .map_err(|err| err.to_string())
+                .and_then(|_| writeln!(writer).map_err(|err| err.to_string()))
+        }
+        ErrorFormat::Yaml => {
+            serde_yaml::to_writer(&mut *writer, &DiagnosticsWrapper::new(diagnostics, &files))
+                .map_err(|err| err.to_string())
+        }
+        ErrorFormat::Toml => toml::to_string(&DiagnosticsWrapper::new(diagnostics, &files))
+            .map_err(|err| err.to_string())
+            .and_then(|repr| write!(writer, "{repr}").map_err(|err| err.to_string())),
     };
 
     match result {
         Ok(()) => (),
-        Err(err) => panic!("error::report_with(): could not print an error on stderr: {err}"),
+        Err(err) => panic!("error::report_with_style(): could not print an error: {err}"),
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codespan_reporting::term::termcolor::NoColor;
+
+    #[test]
+    fn aggregated_diagnostics() {
+        let mut files = Files::new();
+        let file_id = files.add("main.ncl", "[1, 2, 3, 4, 5] | Array String");
+
+        let blame = |start: usize| {
+            Diagnostic::error()
+                .with_message("contract broken by a value")
+                .with_labels(vec![
+                    Label::primary(file_id, start..start + 1),
+                    Label::secondary(file_id, 24..30).with_message("expected type"),
+                ])
+        };
+        let diagnostics = Aggregated(vec![
+            blame(1),
+            Diagnostic::error().with_message("other error"),
+            blame(4),
+            blame(1),
+            blame(7),
+            blame(10),
+            blame(13),
+        ])
+        .into_diagnostics(&files)
+        .diagnostics;
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[1],
+            Diagnostic::error().with_message("other error")
+        );
+
+        let primary_starts: Vec<_> = diagnostics[0]
+            .labels
+            .iter()
+            .filter(|label| label.style == LabelStyle::Primary)
+            .map(|label| label.range.start)
+            .collect();
+        assert_eq!(primary_starts, [1, 4, 7]);
+        assert_eq!(
+            diagnostics[0].notes,
+            ["this error occurred 5 times, only the first 3 locations are shown"]
+        );
+    }
+
+    #[test]
+    fn snippets() {
+        use crate::term::make as mk_term;
+
+        let files = Files::new();
+        let not_a_func = |value: i64, arg: i64| {
+            EvalError::NotAFunc(
+                mk_term::integer(value),
+                mk_term::integer(arg),
+                TermPos::None,
+            )
+        };
+
+        let diagnostics =
+            Aggregated(vec![not_a_func(1, 2), not_a_func(3, 4)]).into_diagnostics(&files);
+
+        assert_eq!(diagnostics.snippets, ["1", "(1) (2)", "3", "(3) (4)"]);
+
+        let file_ids: Vec<_> = diagnostics.diagnostics[1]
+            .labels
+            .iter()
+            .map(|label| label.file_id)
+            .collect();
+        assert_eq!(
+            file_ids,
+            [DiagnosticFileId::Snippet(2), DiagnosticFileId::Snippet(3)]
+        );
+
+        let mut buffer = Vec::new();
+        report_with(
+            &mut NoColor::new(&mut buffer),
+            &files,
+            Aggregated(vec![not_a_func(1, 2), not_a_func(3, 4)]),
+            ErrorFormat::Text,
+        );
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.contains(UNKNOWN_SOURCE_NAME));
+        assert!(output.contains("(3) (4)"));
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(
+            wrap("contract broken by the value of `port`", 16, "  "),
+            "contract broken\n  by the value\n  of `port`"
+        );
+        assert_eq!(
+            wrap("first line\n    indented second line", 12, ""),
+            "first line\n    indented\nsecond line"
+        );
+        assert_eq!(wrap("a_very_long_word b", 4, ""), "a_very_long_word\nb");
+    }
+
+    #[test]
+    fn ascii_style() {
+        let mut files = Files::new();
+        let file_id = files.add("main.ncl", "1 + \"a\"");
+
+        let diagnostic = Diagnostic::error()
+            .with_message("dynamic type error")
+            .with_labels(vec![Label::primary(file_id, 4..7)]);
+
+        let mut buffer = Vec::new();
+        report_with_style(
+            &mut NoColor::new(&mut buffer),
+            &files,
+            diagnostic,
+            ErrorFormat::Text,
+            ReportStyle {
+                chars: ReportChars::Ascii,
+                width: None,
+            },
+        );
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.is_ascii());
+        assert!(output.contains("main.ncl:1:5"));
+    }
+
+    #[test]
+    fn serializable_diagnostics() {
+        let mut files = Files::new();
+        let file_id = files.add("main.ncl", "let x = 1 in\n  x + \"a\"");
+
+        let diagnostic = Diagnostic::error()
+            .with_code("E001")
+            .with_message("dynamic type error")
+            .with_labels(vec![
+                Label::primary(file_id, 19..22).with_message("this expression"),
+                Label::secondary(file_id, 4..5),
+            ])
+            .with_notes(vec!["expected a number".to_owned()]);
+
+        let mut buffer = Vec::new();
+        report_with(
+            &mut NoColor::new(&mut buffer),
+            &files,
+            diagnostic,
+            ErrorFormat::Json,
+        );
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            concat!(
+                r#"{"diagnostics":[{"severity":"error","code":"E001","message":"dynamic type error","#,
+                r#""labels":[{"style":"primary","message":"this expression","file":"main.ncl","#,
+                r#""start_offset":19,"end_offset":22,"start":{"line":2,"column":7},"#,
+                r#""end":{"line":2,"column":10}},{"style":"secondary","message":"","#,
+                r#""file":"main.ncl","start_offset":4,"end_offset":5,"start":{"line":1,"column":5},"#,
+                r#""end":{"line":1,"column":6}}],"notes":["expected a number"]}]}"#,
+                "\n"
+            )
+        );
+    }
+}
@@ -5,8 +5,15 @@
 //! The current implementation uses the normalized Damereau-Levenshtein edit distance to find the
 //! closest match.
 
+use std::{fmt, rc::Rc};
+
 use strsim::normalized_damerau_levenshtein;
 
+use crate::{
+    environment::Environment,
+    identifier::{Ident, LocIdent},
+};
+
 /// The minimum similarity between the user's input and an existing symbol for the symbol to be
 /// considered a serious candidate. The current threshold is rather low, because short words with
 /// edit distance 1 such as `bar` and `bare` might have a similarity which isn't that high.
@@ -60,3 +67,58 @@ where
         notes.push(format!("Did you mean `{best_match}`?"));
     }
 }
+
+/// Return the identifiers bound in the given environments, to be used as candidates when
+/// suggesting a replacement for an unbound identifier. Generated identifiers, which can't be
+/// written by the user, are excluded.
+pub fn identifiers_in_scope<'env, V>(
+    envs: impl IntoIterator<Item = &'env Environment<Ident, V>>,
+) -> Vec<LocIdent>
+where
+    V: PartialEq + 'static,
+{
+    let mut idents: Vec<LocIdent> = envs
+        .into_iter()
+        .flat_map(|env| env.iter_elems().map(|(id, _)| LocIdent::from(*id)))
+        .filter(|id| !id.is_generated())
+        .collect();
+
+    idents.sort();
+    idents.dedup();
+    idents
+}
+
+/// The identifiers in scope where an unbound identifier is referenced, used as candidates for a
+/// suggestion. Listing them means going through whole environments, including the standard
+/// library, so it's only done when the error is reported: errors which are caught and discarded
+/// don't pay for it.
+#[derive(Clone, Default)]
+pub struct InScope(Option<Rc<dyn Fn() -> Vec<LocIdent>>>);
+
+impl InScope {
+    /// Capture the environments in scope. Environments are persistent, so cloning them is cheap.
+    pub fn new<V>(envs: impl IntoIterator<Item = Environment<Ident, V>>) -> Self
+    where
+        V: PartialEq + 'static,
+    {
+        let envs: Vec<_> = envs.into_iter().collect();
+        InScope(Some(Rc::new(move || identifiers_in_scope(&envs))))
+    }
+
+    /// Return the identifiers in scope. See [identifiers_in_scope].
+    pub fn identifiers(&self) -> Vec<LocIdent> {
+        self.0.as_ref().map(|list| list()).unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for InScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InScope { .. }")
+    }
+}
+
+impl PartialEq for InScope {
+    fn eq(&self, other: &Self) -> bool {
+        self.identifiers() == other.identifiers()
+    }
+}
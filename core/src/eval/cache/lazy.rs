@@ -504,6 +504,24 @@ impl Thunk {
         self.data.borrow().closure().clone()
     }
 
+    /// Call `f` on each closure stored in this thunk: the closure of a standard thunk, or both the
+    /// original expression and the cached value, if any, of a revertible thunk.
+    ///
+    /// As opposed to [Self::borrow], this method never panics on a revertible thunk whose cached
+    /// value hasn't been built yet.
+    pub(crate) fn for_each_closure(&self, mut f: impl FnMut(&Closure)) {
+        match &self.data.borrow().inner {
+            InnerThunkData::Standard(closure) => f(closure),
+            InnerThunkData::Revertible { orig, cached, .. } => {
+                f(orig);
+
+                if let Some(cached) = cached {
+                    f(cached);
+                }
+            }
+        }
+    }
+
     /// Consume the thunk and return an owned closure. Avoid cloning if this thunk is the only
     /// reference to the inner closure.
     pub fn into_closure(self) -> Closure {
@@ -686,9 +704,16 @@ impl ThunkUpdateFrame {
     }
 }
 
-/// Placeholder [Cache] for the call-by-need evaluation strategy.
+/// The approximate size of a thunk, including the reference counts of the [Rc].
+const THUNK_SIZE: usize =
+    2 * std::mem::size_of::<usize>() + std::mem::size_of::<RefCell<ThunkData>>();
+
+/// [Cache] for the call-by-need evaluation strategy. Thunks are reference-counted and don't live in
+/// a central store, so the cache only accounts for the thunks allocated.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct CBNCache {}
+pub struct CBNCache {
+    allocated_bytes: usize,
+}
 
 impl Cache for CBNCache {
     type UpdateIndex = ThunkUpdateFrame;
@@ -719,6 +744,8 @@ impl Cache for CBNCache {
     }
 
     fn add(&mut self, clos: Closure, bty: BindingType) -> CacheIndex {
+        self.allocated_bytes += THUNK_SIZE;
+
         match bty {
             BindingType::Normal => Thunk::new(clos),
             BindingType::Revertible(deps) => Thunk::new_rev(clos, deps),
@@ -738,7 +765,7 @@ impl Cache for CBNCache {
     }
 
     fn new() -> Self {
-        CBNCache {}
+        CBNCache { allocated_bytes: 0 }
     }
 
     fn reset_index_state(&mut self, idx: &mut Self::UpdateIndex) {
@@ -750,6 +777,7 @@ impl Cache for CBNCache {
         idx: &CacheIndex,
         mut f: F,
     ) -> CacheIndex {
+        self.allocated_bytes += THUNK_SIZE;
         idx.map(|v| f(self, v))
     }
 
@@ -770,6 +798,7 @@ impl Cache for CBNCache {
     }
 
     fn revert(&mut self, idx: &CacheIndex) -> CacheIndex {
+        self.allocated_bytes += THUNK_SIZE;
         idx.revert()
     }
 
@@ -779,4 +808,8 @@ impl Cache for CBNCache {
     ) -> Result<Self::UpdateIndex, BlackholedError> {
         idx.mk_update_frame()
     }
+
+    fn allocated_bytes(&self) -> usize {
+        self.allocated_bytes
+    }
 }
@@ -90,4 +90,8 @@ pub trait Cache: Clone {
         &mut self,
         idx: &mut CacheIndex,
     ) -> Result<Self::UpdateIndex, BlackholedError>;
+
+    /// The approximate number of bytes allocated for the elements of the cache since its creation.
+    /// Elements which have been freed since are still accounted for.
+    fn allocated_bytes(&self) -> usize;
 }
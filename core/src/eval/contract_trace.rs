@@ -0,0 +1,174 @@
+//! Tracing of contract applications.
+//!
+//! When contract tracing is enabled (see [VirtualMachine::set_contract_trace]), the virtual
+//! machine records every contract application and every flip of the polarity of a label. After a
+//! blame error, the trace tells which contracts have been applied to which values, and how the
+//! blame has been propagated through higher-order contracts, which is often hard to reconstruct
+//! from the final error alone.
+//!
+//! The trace is returned by [VirtualMachine::take_contract_trace]. It can be reported as a
+//! diagnostic, in any [crate::error::report::ErrorFormat], or converted to a list of serializable
+//! [ContractTraceEntry].
+use serde::Serialize;
+
+use super::{cache::Cache, VirtualMachine};
+use crate::{
+    cache::ImportResolver,
+    error::{Diagnostic, Diagnostics, IntoDiagnostics},
+    files::Files,
+    label::{Label, Polarity},
+    position::{RawSpan, TermPos},
+    pretty::PrettyPrintCap,
+};
+
+/// The maximal width of the rendering of a contract in the trace.
+const CONTRACT_MAX_WIDTH: usize = 80;
+
+/// The kind of a traced event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractEventKind {
+    /// A contract has been applied to a value.
+    Apply,
+    /// The polarity of a label has been flipped, typically when checking the domain of a function
+    /// contract.
+    FlipPolarity,
+}
+
+/// A contract application or a polarity flip.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContractEvent {
+    pub kind: ContractEventKind,
+    /// The contract, as written by the user.
+    pub contract: String,
+    /// The polarity of the label after the event.
+    pub polarity: Polarity,
+    /// The position of the contract annotation.
+    pub contract_span: RawSpan,
+    /// The position of the value checked by the contract.
+    pub value_pos: TermPos,
+}
+
+/// The sequence of contract events recorded during an evaluation, in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContractTrace(pub Vec<ContractEvent>);
+
+/// A [ContractEvent] where positions have been resolved to locations, for serialization.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ContractTraceEntry {
+    pub event: ContractEventKind,
+    pub contract: String,
+    pub polarity: &'static str,
+    /// The location of the contract annotation, as `file:line:column`.
+    pub contract_location: Option<String>,
+    /// The location of the value checked by the contract, as `file:line:column`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_location: Option<String>,
+}
+
+impl ContractTrace {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Resolve the positions of the events of the trace.
+    pub fn entries(&self, files: &Files) -> Vec<ContractTraceEntry> {
+        self.0
+            .iter()
+            .map(|event| ContractTraceEntry {
+                event: event.kind,
+                contract: event.contract.clone(),
+                polarity: polarity_str(event.polarity),
+                contract_location: location(files, event.contract_span),
+                value_location: event
+                    .value_pos
+                    .into_opt()
+                    .and_then(|span| location(files, span)),
+            })
+            .collect()
+    }
+}
+
+impl IntoDiagnostics for ContractTrace {
+    fn into_diagnostics(self, files: &Files) -> Diagnostics {
+        let notes = self
+            .entries(files)
+            .into_iter()
+            .map(|entry| {
+                let action = match entry.event {
+                    ContractEventKind::Apply => "applied",
+                    ContractEventKind::FlipPolarity => "flipped polarity of",
+                };
+                let contract_location = entry.contract_location.as_deref().unwrap_or("<unknown>");
+                let mut note = format!(
+                    "{action} `{}` ({}), defined at {contract_location}",
+                    entry.contract, entry.polarity
+                );
+
+                if let Some(value_location) = entry.value_location {
+                    note.push_str(&format!(", on the value at {value_location}"));
+                }
+
+                note
+            })
+            .collect();
+
+        vec![Diagnostic::note()
+            .with_message(format!("contract trace ({} events)", self.0.len()))
+            .with_notes(notes)]
+        .into()
+    }
+}
+
+impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
+    /// Enable or disable contract tracing. Tracing has a cost, and should only be enabled for
+    /// debugging purposes. Disabling tracing discards the events recorded so far.
+    pub fn set_contract_trace(&mut self, enabled: bool) {
+        self.contract_trace = enabled.then(ContractTrace::default);
+    }
+
+    /// Return `true` if contract tracing is enabled.
+    pub fn is_contract_trace_enabled(&self) -> bool {
+        self.contract_trace.is_some()
+    }
+
+    /// Return the contract events recorded since the last call to this function. Return an empty
+    /// trace if tracing isn't enabled.
+    pub fn take_contract_trace(&mut self) -> ContractTrace {
+        self.contract_trace
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Record a contract event on `label`, if tracing is enabled.
+    pub(super) fn trace_contract(&mut self, kind: ContractEventKind, label: &Label) {
+        if let Some(trace) = &mut self.contract_trace {
+            trace.0.push(ContractEvent {
+                kind,
+                contract: label.typ.pretty_print_cap(CONTRACT_MAX_WIDTH),
+                polarity: label.polarity,
+                contract_span: label.span,
+                value_pos: label.arg_pos,
+            });
+        }
+    }
+}
+
+fn polarity_str(polarity: Polarity) -> &'static str {
+    match polarity {
+        Polarity::Positive => "positive",
+        Polarity::Negative => "negative",
+    }
+}
+
+fn location(files: &Files, span: RawSpan) -> Option<String> {
+    let location = files.location(span.src_id, span.start).ok()?;
+
+    Some(format!(
+        "{}:{}:{}",
+        files.name(span.src_id).to_string_lossy(),
+        location.line.to_usize() + 1,
+        location.column.to_usize() + 1
+    ))
+}
@@ -0,0 +1,66 @@
+//! Interrupting a running evaluation.
+//!
+//! The main evaluation loop can be run for a bounded number of steps (see
+//! [super::VirtualMachine::eval_closure_bounded]), after which it gives control back to the
+//! caller with the state required to resume. This is what the asynchronous evaluation functions
+//! build upon: they run the virtual machine by slices, yielding to the executor in-between, so
+//! that a long evaluation doesn't block an executor thread.
+//!
+//! Independently, a [CancellationToken] can be attached to the virtual machine. The token is
+//! checked regularly by the evaluation loop, which stops with [crate::error::EvalError::Cancelled]
+//! as soon as the token has been cancelled, possibly from another thread.
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// The number of evaluation steps between two checks of the cancellation token.
+pub(crate) const CANCELLATION_CHECK_INTERVAL: usize = 1024;
+
+/// A shareable flag to cancel a running evaluation. Clones of a token share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the cancellation of the evaluations using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A future which yields to the executor once before completing. This doesn't depend on any
+/// specific async runtime.
+pub(crate) struct YieldNow(bool);
+
+impl YieldNow {
+    pub(crate) fn new() -> Self {
+        YieldNow(false)
+    }
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Limits on the resources used by an evaluation.
+//!
+//! A virtual machine evaluating untrusted programs can be given [ResourceLimits] (see
+//! [VirtualMachine::set_resource_limits]). The evaluation loop then aborts with
+//! [crate::error::EvalError::ResourceExhausted] as soon as a limit is exceeded, instead of running
+//! for an unbounded amount of time.
+//!
+//! The memory limit is approximate: it only accounts for the thunks allocated by the evaluation
+//! cache, which make up most of the memory allocated at runtime.
+//!
+//! The depth of the evaluation stack can be limited as well. The evaluation stack is allocated on
+//! the heap, so deep recursion doesn't overflow the native stack, but it can still consume an
+//! unbounded amount of memory. Exceeding the maximal depth aborts the evaluation with
+//! [crate::error::EvalError::StackOverflow].
+//!
+//! The resources used are accounted for from the last [VirtualMachine::reset], that is, for each
+//! evaluation of a [crate::program::Program]. Nested evaluations performed by a few primitive
+//! operations are accounted for as well.
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use super::{cache::Cache, interrupt, VirtualMachine};
+use crate::{cache::ImportResolver, error::EvalError, position::TermPos};
+
+/// The limits on the resources used by an evaluation. A limit set to `None` isn't enforced, which
+/// is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The maximal number of steps of the evaluation loop. This is a deterministic measure of the
+    /// work done, independent of the machine.
+    pub max_steps: Option<u64>,
+    /// The maximal wall-clock duration of the evaluation. The clock is checked periodically, so
+    /// the evaluation may run slightly longer.
+    ///
+    /// The clock isn't available on every platform, notably `wasm32-unknown-unknown`, where this
+    /// limit must not be set.
+    pub timeout: Option<Duration>,
+    /// The maximal depth of the evaluation stack, that is the number of pending arguments,
+    /// continuations and thunk updates. This roughly corresponds to the depth of the non-tail
+    /// recursive calls.
+    pub max_depth: Option<usize>,
+    /// The maximal number of bytes allocated for thunks during the evaluation. This is an
+    /// approximation (see [Cache::allocated_bytes]): it doesn't account for the other objects, and
+    /// it counts allocations rather than live objects, so it can only grow during an evaluation.
+    pub max_memory: Option<usize>,
+}
+
+/// A resource which has been exhausted, together with the limit that has been exceeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resource {
+    Steps(u64),
+    Time(Duration),
+    /// The limit is in bytes.
+    Memory(usize),
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resource::Steps(max) => write!(f, "the limit of {max} evaluation steps"),
+            Resource::Time(timeout) => write!(f, "the time limit of {timeout:?}"),
+            Resource::Memory(max) => write!(f, "the memory limit of {max} bytes"),
+        }
+    }
+}
+
+/// The resources used by the current evaluation.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct ResourceUsage {
+    pub(super) steps: u64,
+    start: Option<Instant>,
+    /// The bytes allocated by the evaluation cache before the evaluation started.
+    allocated_base: usize,
+}
+
+impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
+    /// Set the limits on the resources used by each evaluation. See [ResourceLimits].
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    /// Return the limits on the resources used by each evaluation.
+    pub fn resource_limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// Account for one more step of the evaluation loop, evaluating the term at `pos`, and fail if a
+    /// limit has been exceeded.
+    pub(super) fn check_limits(&mut self, pos: TermPos) -> Result<(), EvalError> {
+        if let Some(resource) = self.consume_step() {
+            return Err(EvalError::ResourceExhausted {
+                resource,
+                pos,
+                call_stack: self.call_stack.clone(),
+            });
+        }
+
+        if let Some(max_depth) = self.limits.max_depth {
+            if self.stack.len() > max_depth {
+                return Err(EvalError::StackOverflow {
+                    max_depth,
+                    pos,
+                    call_stack: self.call_stack.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Account for one more step of the evaluation loop, and return the exhausted resource if a
+    /// limit has been exceeded.
+    fn consume_step(&mut self) -> Option<Resource> {
+        let usage = &mut self.usage;
+        usage.steps += 1;
+
+        if let Some(max_steps) = self.limits.max_steps {
+            if usage.steps > max_steps {
+                return Some(Resource::Steps(max_steps));
+            }
+        }
+
+        if let Some(timeout) = self.limits.timeout {
+            let start = *usage.start.get_or_insert_with(Instant::now);
+
+            if usage
+                .steps
+                .is_multiple_of(interrupt::CANCELLATION_CHECK_INTERVAL as u64)
+                && start.elapsed() > timeout
+            {
+                return Some(Resource::Time(timeout));
+            }
+        }
+
+        if let Some(max_memory) = self.limits.max_memory {
+            if self.cache.allocated_bytes() - usage.allocated_base > max_memory {
+                return Some(Resource::Memory(max_memory));
+            }
+        }
+
+        None
+    }
+
+    /// Forget about the resources used so far.
+    pub(super) fn reset_usage(&mut self) {
+        self.usage = ResourceUsage {
+            allocated_base: self.cache.allocated_bytes(),
+            ..Default::default()
+        };
+    }
+}
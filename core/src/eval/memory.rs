@@ -0,0 +1,178 @@
+//! Introspection of the memory used by the runtime representation of a program.
+//!
+//! [MemoryStats] is a snapshot of the objects reachable from a virtual machine: the terms stored
+//! in the import cache (including the standard library), the initial environment, and
+//! transitively everything they refer to through thunks and environments. Objects shared between
+//! several parents are counted only once.
+//!
+//! Sizes are approximate: they account for the objects themselves and their direct heap
+//! allocations (string content, record fields, hashmap buckets, etc.), but not for the allocator
+//! overhead. They are meant to monitor memory growth over time rather than to give an exact
+//! account of the heap.
+//!
+//! Collecting statistics walks the whole reachable heap, which is linear in the size of the
+//! program. It should be done periodically, for example by a long-running embedder which needs to
+//! monitor and alert on memory growth, but not in a hot path.
+use super::{
+    cache::{lazy::ThunkData, Cache, CacheIndex},
+    Environment, VirtualMachine,
+};
+use crate::{
+    cache::Cache as ImportCache,
+    identifier::{self, Ident, LocIdent},
+    term::{record::Field, RichTerm, Term, Traverse, TraverseControl},
+};
+
+use serde::Serialize;
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    mem::size_of,
+    rc::Rc,
+};
+
+/// The size of the reference counts stored alongside the content of an [Rc].
+const RC_OVERHEAD: usize = 2 * size_of::<usize>();
+
+/// The number of live objects of a given kind, together with their approximate size in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ObjectStats {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+impl ObjectStats {
+    fn record(&mut self, bytes: usize) {
+        self.count += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Statistics about the runtime objects reachable from a virtual machine. See the [module
+/// documentation][self].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryStats {
+    /// Term nodes, including the terms stored in the import cache.
+    pub terms: ObjectStats,
+    /// Thunks, that is the elements of the evaluation cache holding suspended or evaluated
+    /// closures.
+    pub thunks: ObjectStats,
+    /// Environment layers.
+    pub env_layers: ObjectStats,
+    /// Interned strings (identifiers). The interner is global, so this accounts for all the
+    /// identifiers interned by the current process, and not only by the current program.
+    pub interned_strings: ObjectStats,
+}
+
+impl MemoryStats {
+    /// The approximate number of bytes used by all the objects accounted for.
+    pub fn total_bytes(&self) -> usize {
+        self.terms.bytes + self.thunks.bytes + self.env_layers.bytes + self.interned_strings.bytes
+    }
+}
+
+impl<C: Cache> VirtualMachine<ImportCache, C> {
+    /// Collect statistics about the objects currently reachable from this virtual machine. See
+    /// [MemoryStats].
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut collector = Collector::default();
+
+        for entry in self.import_resolver.terms().values() {
+            collector.visit_term(&entry.term);
+        }
+
+        collector.visit_env(&self.initial_env);
+        collector.run();
+
+        let (count, bytes) = identifier::interner_stats();
+        collector.stats.interned_strings = ObjectStats { count, bytes };
+
+        collector.stats
+    }
+}
+
+/// Walks the heap and accumulates statistics. Thunks are processed through a worklist instead of
+/// recursively, as chains of thunks and environments can be arbitrarily long.
+#[derive(Default)]
+struct Collector {
+    stats: MemoryStats,
+    visited_terms: HashSet<usize>,
+    visited_thunks: HashSet<usize>,
+    visited_layers: HashSet<usize>,
+    pending_thunks: Vec<CacheIndex>,
+}
+
+impl Collector {
+    fn run(&mut self) {
+        while let Some(thunk) = self.pending_thunks.pop() {
+            self.visit_thunk(&thunk);
+        }
+    }
+
+    fn visit_term(&mut self, rt: &RichTerm) {
+        rt.traverse_ref(
+            &mut |rt: &RichTerm, _: &()| -> TraverseControl<(), ()> {
+                if !self.visited_terms.insert(rt.term.uid()) {
+                    return TraverseControl::SkipBranch;
+                }
+
+                self.stats.terms.record(term_size(rt.as_ref()));
+
+                if let Term::Closure(idx) = rt.as_ref() {
+                    self.pending_thunks.push(idx.clone());
+                }
+
+                TraverseControl::Continue
+            },
+            &(),
+        );
+    }
+
+    fn visit_env(&mut self, env: &Environment) {
+        for layer in env.iter_layers() {
+            if !self.visited_layers.insert(Rc::as_ptr(layer) as usize) {
+                continue;
+            }
+
+            self.stats.env_layers.record(
+                RC_OVERHEAD
+                    + size_of::<HashMap<Ident, CacheIndex>>()
+                    // Each bucket of the underlying hashmap also has a one byte control tag.
+                    + layer.capacity() * (size_of::<(Ident, CacheIndex)>() + 1),
+            );
+
+            self.pending_thunks.extend(layer.values().cloned());
+        }
+    }
+
+    fn visit_thunk(&mut self, thunk: &CacheIndex) {
+        if !self.visited_thunks.insert(thunk.uid()) {
+            return;
+        }
+
+        self.stats
+            .thunks
+            .record(RC_OVERHEAD + size_of::<RefCell<ThunkData>>());
+
+        thunk.for_each_closure(|closure| {
+            self.visit_term(&closure.body);
+            self.visit_env(&closure.env);
+        });
+    }
+}
+
+/// Approximate size of a term node, including its direct heap allocations but not its children,
+/// which are accounted for separately.
+fn term_size(term: &Term) -> usize {
+    let heap = match term {
+        Term::Str(s) => s.len(),
+        Term::Record(data) | Term::RecRecord(data, ..) => {
+            data.fields.len() * size_of::<(LocIdent, Field)>()
+        }
+        Term::Array(array, _) => array.len() * size_of::<RichTerm>(),
+        _ => 0,
+    };
+
+    RC_OVERHEAD + size_of::<Term>() + heap
+}
@@ -436,6 +436,16 @@ fn merge_fields<'a, C: Cache, I: DoubleEndedIterator<Item = &'a LocIdent> + Clon
         RuntimeContract::push_dedup(&mut pending_contracts, &empty, ctr2, &empty);
     }
 
+    // The contracts coming from a non-secret definition must not leak the value of the field
+    // either.
+    let secret = metadata1.secret || metadata2.secret;
+
+    if secret {
+        for ctr in pending_contracts.iter_mut() {
+            ctr.label.secret = true;
+        }
+    }
+
     Ok(Field {
         metadata: FieldMetadata {
             doc: merge_doc(metadata1.doc, metadata2.doc),
@@ -445,6 +455,9 @@ fn merge_fields<'a, C: Cache, I: DoubleEndedIterator<Item = &'a LocIdent> + Clon
             opt: metadata1.opt && metadata2.opt,
             not_exported: metadata1.not_exported || metadata2.not_exported,
             priority,
+            // The field is deprecated if one of the definitions is.
+            deprecated: metadata1.deprecated.or(metadata2.deprecated),
+            secret,
         },
         value,
         pending_contracts,
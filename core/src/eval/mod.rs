@@ -77,13 +77,14 @@ use crate::{
     cache::{Cache as ImportCache, Envs, ImportResolver},
     closurize::{closurize_rec_record, Closurize},
     environment::Environment as GenericEnvironment,
-    error::{Error, EvalError},
+    error::{suggest, Error, EvalError, EvalWarning},
     files::FileId,
     identifier::Ident,
     identifier::LocIdent,
     match_sharedterm,
     metrics::{increment, measure_runtime},
-    position::TermPos,
+    policy::CapabilityPolicy,
+    position::{RawSpan, TermPos},
     program::FieldPath,
     term::{
         array::ArrayAttrs,
@@ -96,16 +97,27 @@ use crate::{
     },
 };
 
-use std::io::Write;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    num::NonZeroUsize,
+};
 
 pub mod cache;
 pub mod callstack;
+pub mod contract_trace;
 pub mod fixpoint;
+pub mod interrupt;
+pub mod limits;
+pub mod memory;
 pub mod merge;
 pub mod operation;
 pub mod stack;
+pub mod stream;
+pub mod unused;
 
 use callstack::*;
+use interrupt::{CancellationToken, YieldNow};
 use operation::OperationCont;
 use stack::{Stack, StrAccData};
 
@@ -131,6 +143,40 @@ pub struct VirtualMachine<R: ImportResolver, C: Cache> {
     initial_env: Environment,
     // The stream for writing trace output.
     trace: Box<dyn Write>,
+    // The token checked regularly to cancel the evaluation, if any.
+    cancellation: Option<CancellationToken>,
+    // The warnings emitted so far.
+    warnings: Vec<EvalWarning>,
+    // If set, warnings abort the evaluation with an error.
+    deny_warnings: bool,
+    // The positions of the terms evaluated so far, if demand tracking is enabled. See [unused].
+    demanded: Option<HashSet<RawSpan>>,
+    // The positions of the values of the secret fields evaluated so far, which are redacted from
+    // the output of `std.trace`, indexed by file. As these are positions in the source, there are
+    // at most as many as there are secret fields in the sources.
+    secret_spans: HashMap<FileId, HashSet<RawSpan>>,
+    // If set, constructs whose result may differ across runs or machines are rejected.
+    reproducible: bool,
+    // The capability policy, deciding which native functions can be called.
+    policy: CapabilityPolicy,
+    // The contract applications and polarity flips so far, if contract tracing is enabled. See
+    // [contract_trace].
+    contract_trace: Option<contract_trace::ContractTrace>,
+    // The limits on the resources used by an evaluation. See [limits].
+    limits: limits::ResourceLimits,
+    // The resources used by the current evaluation.
+    usage: limits::ResourceUsage,
+}
+
+/// The outcome of a bounded run of the evaluation loop. See
+/// [VirtualMachine::eval_closure_bounded].
+#[derive(Debug)]
+pub enum EvalStep {
+    /// The evaluation is done, and this is the result.
+    Done(Closure),
+    /// The evaluation has been suspended. Passing this closure back to
+    /// [VirtualMachine::eval_closure_bounded] resumes it.
+    Suspended(Closure),
 }
 
 impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
@@ -142,6 +188,16 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             cache: Cache::new(),
             initial_env: Environment::new(),
             trace: Box::new(trace),
+            cancellation: None,
+            warnings: Vec::new(),
+            deny_warnings: false,
+            demanded: None,
+            secret_spans: HashMap::new(),
+            reproducible: false,
+            policy: CapabilityPolicy::default(),
+            contract_trace: None,
+            limits: Default::default(),
+            usage: Default::default(),
         }
     }
 
@@ -153,6 +209,16 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             cache,
             trace: Box::new(trace),
             initial_env: Environment::new(),
+            cancellation: None,
+            warnings: Vec::new(),
+            deny_warnings: false,
+            demanded: None,
+            secret_spans: HashMap::new(),
+            reproducible: false,
+            policy: CapabilityPolicy::default(),
+            contract_trace: None,
+            limits: Default::default(),
+            usage: Default::default(),
         }
     }
 
@@ -161,6 +227,111 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     pub fn reset(&mut self) {
         self.call_stack.0.clear();
         self.stack.reset(&mut self.cache);
+        self.reset_usage();
+    }
+
+    /// Attach a cancellation token to this virtual machine, or detach the current one if `token`
+    /// is `None`. See [interrupt].
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation = token;
+    }
+
+    /// Turn warnings into errors: if `deny` is `true`, the first warning emitted aborts the
+    /// evaluation with [EvalError::DeniedWarning].
+    pub fn set_deny_warnings(&mut self, deny: bool) {
+        self.deny_warnings = deny;
+    }
+
+    /// Reject the constructs whose result may differ across runs or machines: calls to native
+    /// functions which haven't been declared deterministic, and floating-point operations whose
+    /// result depends on the platform's math library, such as `std.number.sin`.
+    pub fn set_reproducible(&mut self, reproducible: bool) {
+        self.reproducible = reproducible;
+    }
+
+    /// Return `true` if the reproducible mode is enabled. See [Self::set_reproducible].
+    pub fn is_reproducible(&self) -> bool {
+        self.reproducible
+    }
+
+    /// Return `true` if warnings are turned into errors. See [Self::set_deny_warnings].
+    pub fn denies_warnings(&self) -> bool {
+        self.deny_warnings
+    }
+
+    /// Set the capability policy checked when calling native functions. See
+    /// [crate::program::Program::set_policy].
+    pub fn set_policy(&mut self, policy: CapabilityPolicy) {
+        self.policy = policy;
+    }
+
+    /// Return the capability policy checked when calling native functions.
+    pub fn policy(&self) -> &CapabilityPolicy {
+        &self.policy
+    }
+
+    /// Fail with [EvalError::NonReproducible] if the reproducible mode is enabled.
+    fn check_reproducible(
+        &self,
+        construct: impl FnOnce() -> String,
+        reason: &str,
+        pos: TermPos,
+    ) -> Result<(), EvalError> {
+        if self.reproducible {
+            Err(EvalError::NonReproducible {
+                construct: construct(),
+                reason: reason.to_owned(),
+                pos,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Return the warnings emitted since the last call to this function. Identical warnings are
+    /// only reported once.
+    pub fn take_warnings(&mut self) -> Vec<EvalWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Emit a warning, or fail if warnings are denied.
+    fn warn(&mut self, warning: EvalWarning) -> Result<(), EvalError> {
+        if self.deny_warnings {
+            Err(EvalError::DeniedWarning(warning))
+        } else {
+            if !self.warnings.contains(&warning) {
+                self.warnings.push(warning);
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Record the positions of the values of secret fields, so that they can be redacted from the
+    /// output of `std.trace`.
+    fn register_secrets<'a>(&mut self, fields: impl Iterator<Item = &'a Field>) {
+        let spans = fields
+            .filter(|field| field.metadata.secret)
+            .filter_map(|field| field.value.as_ref()?.pos.into_opt());
+
+        for span in spans {
+            self.secret_spans
+                .entry(span.src_id)
+                .or_default()
+                .insert(span);
+        }
+    }
+
+    /// Return `true` if a term at position `pos` is the value of a secret field, or the result of
+    /// its evaluation.
+    fn is_secret(&self, pos: TermPos) -> bool {
+        pos.into_opt().is_some_and(|span| {
+            self.secret_spans.get(&span.src_id).is_some_and(|secrets| {
+                secrets
+                    .iter()
+                    .any(|secret| secret.start <= span.start && span.end <= secret.end)
+            })
+        })
     }
 
     pub fn import_resolver(&self) -> &R {
@@ -231,19 +402,40 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
         self
     }
 
+    /// Asynchronous version of [Self::eval_full_for_export_closure]. The evaluation is run by
+    /// slices of `steps_per_yield` steps, yielding to the executor in-between. See [interrupt].
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "eval", skip_all))]
+    pub async fn eval_full_for_export_closure_async(
+        &mut self,
+        closure: Closure,
+        steps_per_yield: NonZeroUsize,
+    ) -> Result<RichTerm, EvalError> {
+        let mut closure = force_closure(closure, true);
+
+        loop {
+            match self.eval_closure_bounded(closure, steps_per_yield.get())? {
+                EvalStep::Done(result) => {
+                    break Ok(subst(
+                        &self.cache,
+                        result.body,
+                        &self.initial_env,
+                        &result.env,
+                    ))
+                }
+                EvalStep::Suspended(next) => {
+                    closure = next;
+                    YieldNow::new().await;
+                }
+            }
+        }
+    }
+
     fn eval_deep_closure_impl(
         &mut self,
-        mut closure: Closure,
+        closure: Closure,
         for_export: bool,
     ) -> Result<Closure, EvalError> {
-        closure.body = mk_term::op1(
-            UnaryOp::Force {
-                ignore_not_exported: for_export,
-            },
-            closure.body,
-        );
-
-        self.eval_closure(closure)
+        self.eval_closure(force_closure(closure, for_export))
     }
 
     /// Take a term and a field path, and evaluate until the corresponding field can be extracted.
@@ -323,7 +515,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             let Some(current_value) = field.value else {
                 return Err(EvalError::MissingFieldDef {
                     id: *prev_id,
-                    metadata: field.metadata,
+                    metadata: Box::new(field.metadata),
                     pos_record: prev_pos,
                     pos_access: TermPos::None,
                 });
@@ -377,7 +569,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
         if field.value.is_none() && require_defined {
             return Err(EvalError::MissingFieldDef {
                 id: *prev_id,
-                metadata: field.metadata,
+                metadata: Box::new(field.metadata),
                 pos_record: prev_pos,
                 pos_access: TermPos::None,
             });
@@ -485,11 +677,59 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     /// Either:
     ///  - an evaluation error
     ///  - the evaluated term with its final environment
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "eval", skip_all))]
     pub fn eval_closure(&mut self, mut clos: Closure) -> Result<Closure, EvalError> {
+        loop {
+            match self.eval_closure_bounded(clos, usize::MAX)? {
+                EvalStep::Done(result) => break Ok(result),
+                EvalStep::Suspended(next) => clos = next,
+            }
+        }
+    }
+
+    /// Run the main evaluation loop for at most `max_steps` steps. If the evaluation isn't done
+    /// by then, return the closure to resume from. The rest of the state of the evaluation lives
+    /// in the virtual machine, which must not be used for anything else until the evaluation is
+    /// resumed.
+    ///
+    /// Note that a few primitive operations run a nested evaluation, which isn't bounded.
+    pub fn eval_closure_bounded(
+        &mut self,
+        mut clos: Closure,
+        max_steps: usize,
+    ) -> Result<EvalStep, EvalError> {
         #[cfg(feature = "metrics")]
         let start_time = std::time::Instant::now();
 
+        let mut steps: usize = 0;
+
         let result = loop {
+            if steps == max_steps {
+                break Ok(EvalStep::Suspended(clos));
+            }
+
+            steps += 1;
+
+            if let Err(err) = self.check_limits(clos.body.pos) {
+                break Err(err);
+            }
+
+            // The token is checked when the evaluation is resumed, and then regularly. The
+            // interval is counted over the whole evaluation, as the slices of an asynchronous
+            // evaluation may be shorter than the interval.
+            if (steps == 1
+                || self
+                    .usage
+                    .steps
+                    .is_multiple_of(interrupt::CANCELLATION_CHECK_INTERVAL as u64))
+                && self
+                    .cancellation
+                    .as_ref()
+                    .is_some_and(CancellationToken::is_cancelled)
+            {
+                break Err(EvalError::Cancelled(clos.body.pos));
+            }
+
             let Closure {
                 body:
                     RichTerm {
@@ -499,6 +739,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 mut env,
             } = clos;
 
+            if let (Some(demanded), TermPos::Original(span)) = (&mut self.demanded, pos) {
+                demanded.insert(span);
+            }
+
             let has_cont_on_stack = self.stack.is_top_idx() || self.stack.is_top_cont();
 
             clos = match_sharedterm!(match (shared_term) {
@@ -555,7 +799,14 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         .get(&x.ident())
                         .or_else(|| self.initial_env.get(&x.ident()))
                         .cloned()
-                        .ok_or(EvalError::UnboundIdentifier(x, pos))?;
+                        .ok_or_else(|| EvalError::UnboundIdentifier {
+                            id: x,
+                            pos,
+                            in_scope: suggest::InScope::new([
+                                env.clone(),
+                                self.initial_env.clone(),
+                            ]),
+                        })?;
 
                     self.enter_cache_index(Some(x), idx, pos, env)?
                 }
@@ -592,7 +843,6 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         env.insert(x.ident(), idx);
                     }
 
-                    let names = env.iter_elems().map(|(k, _v)| k).collect::<Vec<_>>();
                     for idx in indices {
                         self.cache.patch(idx, |cl| cl.env = env.clone());
                     }
@@ -698,6 +948,8 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 // Closurize the record if it's not already done. Usually this is done at the first
                 // time this record is evaluated.
                 Term::Record(data) if !data.attrs.closurized => {
+                    self.register_secrets(data.fields.values());
+
                     Closure {
                         body: RichTerm::new(
                             Term::Record(data.closurize(&mut self.cache, env)),
@@ -707,6 +959,12 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     }
                 }
                 Term::RecRecord(data, dyn_fields, deps) => {
+                    self.register_secrets(
+                        data.fields
+                            .values()
+                            .chain(dyn_fields.iter().map(|(_, field)| field)),
+                    );
+
                     // We start by closurizing the fields, which might not be if the record is
                     // coming out of the parser.
 
@@ -883,10 +1141,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         env.insert(x.ident(), idx);
                         Closure { body: t, env }
                     } else {
-                        break Ok(Closure {
+                        break Ok(EvalStep::Done(Closure {
                             body: RichTerm::new(Term::Fun(x, t), pos),
                             env,
-                        });
+                        }));
                     }
                 }
                 // A match expression acts as a function (in Nickel, a match expression corresponds
@@ -904,10 +1162,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                             env,
                         }
                     } else {
-                        break Ok(Closure {
+                        break Ok(EvalStep::Done(Closure {
                             body: RichTerm::new(Term::Match(data), pos),
                             env,
-                        });
+                        }));
                     }
                 }
                 // At this point, we've evaluated the current term to a weak head normal form.
@@ -940,7 +1198,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     // Finally, if the stack is empty, it's all good: it just means we are done
                     // evaluating.
                     else {
-                        break Ok(evaluated);
+                        break Ok(EvalStep::Done(evaluated));
                     }
                 }
             })
@@ -1004,7 +1262,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                             } else {
                                 acc.push(EvalError::MissingFieldDef {
                                     id: *id,
-                                    metadata: field.metadata.clone(),
+                                    metadata: Box::new(field.metadata.clone()),
                                     pos_record: pos,
                                     pos_access: TermPos::None,
                                 });
@@ -1147,6 +1405,18 @@ pub fn env_add<C: Cache>(
     env.insert(id.ident(), cache.add(closure, BindingType::Normal));
 }
 
+/// Wrap a closure in a [UnaryOp::Force] operation, which evaluates it deeply.
+fn force_closure(mut closure: Closure, for_export: bool) -> Closure {
+    closure.body = mk_term::op1(
+        UnaryOp::Force {
+            ignore_not_exported: for_export,
+        },
+        closure.body,
+    );
+
+    closure
+}
+
 /// Pop and update all the indices on the top of the stack with the given closure.
 fn update_at_indices<C: Cache>(cache: &mut C, stack: &mut Stack<C>, closure: &Closure) {
     while let Some(idx) = stack.pop_update_index() {
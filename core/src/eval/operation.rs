@@ -8,6 +8,7 @@
 //! receive evaluated operands and implement the actual semantics of operators.
 use super::{
     cache::lazy::Thunk,
+    contract_trace::ContractEventKind,
     merge::{self, split, MergeMode},
     stack::StrAccData,
     subst, Cache, Closure, Environment, ImportResolver, VirtualMachine,
@@ -18,12 +19,13 @@ use crate::nix_ffi;
 
 use crate::{
     closurize::Closurize,
-    error::{EvalError, IllegalPolymorphicTailAction},
+    error::{EvalError, EvalWarning, IllegalPolymorphicTailAction},
     identifier::LocIdent,
     label::{ty_path, Polarity, TypeVarData},
     match_sharedterm,
     metrics::increment,
     mk_app, mk_fun, mk_record,
+    native::NativeCallError,
     parser::utils::parse_number_sci,
     position::TermPos,
     serialize,
@@ -60,6 +62,10 @@ use std::{convert::TryFrom, iter::Extend};
 
 generate_counter!(FreshVariableCounter, usize);
 
+/// The reason why transcendental floating-point operations are rejected in reproducible mode.
+const PLATFORM_DEPENDENT_FLOAT: &str = "the result of this floating-point operation depends on \
+    the math library of the platform, and may differ across machines";
+
 /// Result of the equality of two terms.
 ///
 /// The equality of two terms can either be computed directly for base types (`Number`, `String`,
@@ -115,6 +121,60 @@ impl std::fmt::Debug for OperationCont {
 }
 
 impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
+    /// Emit a warning if the field `id` of `record`, accessed at `pos_access`, is deprecated.
+    fn warn_if_deprecated(
+        &mut self,
+        record: &RecordData,
+        id: LocIdent,
+        pos_access: TermPos,
+    ) -> Result<(), EvalError> {
+        match record.fields.get_key_value(&id) {
+            Some((def_id, field)) => match &field.metadata.deprecated {
+                Some(message) => self.warn(EvalWarning::DeprecatedField {
+                    id: *def_id,
+                    message: message.clone(),
+                    pos_access,
+                }),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Emit a warning for each field which is deprecated in one operand of a merge and defined in
+    /// the other one, typically a renamed option of a schema which is still set by a
+    /// configuration. Operands which aren't records are ignored.
+    fn warn_if_merging_deprecated(&mut self, t1: &Term, t2: &Term) -> Result<(), EvalError> {
+        let (Term::Record(r1), Term::Record(r2)) = (t1, t2) else {
+            return Ok(());
+        };
+
+        for (decl, def) in [(r1, r2), (r2, r1)] {
+            for (decl_id, decl_field) in &decl.fields {
+                let Some(message) = &decl_field.metadata.deprecated else {
+                    continue;
+                };
+
+                // If both operands are deprecated, we are merging the declaration with itself or
+                // with another declaration, and not defining the field.
+                match def.fields.get_key_value(decl_id) {
+                    Some((def_id, def_field))
+                        if def_field.value.is_some() && def_field.metadata.deprecated.is_none() =>
+                    {
+                        self.warn(EvalWarning::DeprecatedField {
+                            id: *decl_id,
+                            message: message.clone(),
+                            pos_access: def_id.pos,
+                        })?
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Process to the next step of the evaluation of an operation.
     ///
     /// Depending on the content of the stack, it either starts the evaluation of the first
@@ -209,6 +269,20 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             };
         }
 
+        if let UnaryOp::NumberArcCos
+        | UnaryOp::NumberArcSin
+        | UnaryOp::NumberArcTan
+        | UnaryOp::NumberCos
+        | UnaryOp::NumberSin
+        | UnaryOp::NumberTan = u_op
+        {
+            self.check_reproducible(
+                || format!("operation `{u_op}`"),
+                PLATFORM_DEPENDENT_FLOAT,
+                pos_op,
+            )?;
+        }
+
         match u_op {
             UnaryOp::IfThenElse => {
                 if let Term::Bool(b) = *t {
@@ -381,6 +455,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 Term::Lbl(l) => {
                     let mut l = l;
                     l.polarity = l.polarity.flip();
+                    self.trace_contract(ContractEventKind::FlipPolarity, &l);
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Lbl(l),
                         pos_op_inh,
@@ -454,6 +529,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         .map_err(|err| err.into_eval_err(pos, pos_op))?
                     {
                         Some(value) => {
+                            self.warn_if_deprecated(record, id, pos_op)?;
                             self.call_stack.enter_field(id, pos, value.pos, pos_op);
                             Ok(Closure { body: value, env })
                         }
@@ -1060,6 +1136,20 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
 
                 match_sharedterm!(match (t) {
                     Term::Record(record) if !record.fields.is_empty() => {
+                        if ignore_not_exported {
+                            for (id, field) in record.fields.iter() {
+                                if let Some(message) = &field.metadata.deprecated {
+                                    if !field.metadata.not_exported && !field.is_empty_optional() {
+                                        self.warn(EvalWarning::DeprecatedField {
+                                            id: *id,
+                                            message: message.clone(),
+                                            pos_access: TermPos::None,
+                                        })?;
+                                    }
+                                }
+                            }
+                        }
+
                         let fields = record
                             .fields
                             .into_iter()
@@ -1173,7 +1263,11 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             }),
             UnaryOp::Trace => {
                 if let Term::Str(s) = &*t {
-                    let _ = writeln!(self.trace, "std.trace: {s}");
+                    if self.is_secret(pos) {
+                        let _ = writeln!(self.trace, "std.trace: {REDACTED}");
+                    } else {
+                        let _ = writeln!(self.trace, "std.trace: {s}");
+                    }
                     Ok(())
                 } else {
                     mk_type_error!("String")
@@ -1199,6 +1293,20 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             }
             #[cfg(feature = "nix-experimental")]
             UnaryOp::EvalNix => {
+                self.check_reproducible(
+                    || String::from("evaluation of Nix code"),
+                    "Nix code may read files, environment variables or the network",
+                    pos_op,
+                )?;
+
+                self.policy
+                    .check_nix()
+                    .map_err(|reason| EvalError::CapabilityDenied {
+                        action: String::from("evaluation of Nix code"),
+                        reason,
+                        pos: pos_op,
+                    })?;
+
                 if let Term::Str(s) = &*t {
                     let json = nix_ffi::eval_to_json(&String::from(s)).map_err(|e| {
                         EvalError::Other(
@@ -1414,6 +1522,45 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 "number/tan",
                 f64::tan,
             ),
+            UnaryOp::NativeCall(fun) => {
+                let arg = RichTerm { term: t, pos };
+                // The wrapper function doesn't have a position: we report errors at the argument
+                // of the call instead.
+                let call_pos = if arg_pos.is_def() { arg_pos } else { pos };
+
+                if !fun.is_deterministic() {
+                    self.check_reproducible(
+                        || format!("call to the native function `{}`", fun.name()),
+                        "native functions may depend on the environment, the time, or the \
+                        network, unless they are registered as deterministic",
+                        call_pos,
+                    )?;
+                }
+
+                self.policy
+                    .check_native(&fun)
+                    .map_err(|reason| EvalError::CapabilityDenied {
+                        action: format!("call to the native function `{}`", fun.name()),
+                        reason,
+                        pos: call_pos,
+                    })?;
+
+                match fun.call(&arg) {
+                    Ok(result) => Ok(Closure::atomic_closure(
+                        result.with_pos(call_pos.into_inherited()),
+                    )),
+                    Err(NativeCallError::ArgumentType) => Err(EvalError::UnaryPrimopTypeError {
+                        primop: format!("native.{}", fun.name()),
+                        expected: fun.domain().to_string(),
+                        arg_pos: call_pos,
+                        arg_evaluated: arg,
+                    }),
+                    Err(NativeCallError::Failed(msg)) => Err(EvalError::Other(
+                        format!("native function `{}` failed: {msg}", fun.name()),
+                        call_pos,
+                    )),
+                }
+            }
         }
     }
 
@@ -1642,6 +1789,12 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 )))
             }
             BinaryOp::NumberLog => {
+                self.check_reproducible(
+                    || "operation `number/log`".to_owned(),
+                    PLATFORM_DEPENDENT_FLOAT,
+                    pos_op,
+                )?;
+
                 let Term::Num(ref n1) = *t1 else {
                     return mk_type_error!("Number", 1, t1, pos1);
                 };
@@ -1693,6 +1846,12 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         let result = if let Ok(n2_as_i64) = i64::try_from(n2) {
                             n1.pow(n2_as_i64)
                         } else {
+                            self.check_reproducible(
+                                || "exponentiation with a non-integer exponent".to_owned(),
+                                PLATFORM_DEPENDENT_FLOAT,
+                                pos_op,
+                            )?;
+
                             let result_as_f64 = f64::rounding_from(n1, RoundingMode::Nearest)
                                 .0
                                 .powf(f64::rounding_from(n2, RoundingMode::Nearest).0);
@@ -1808,6 +1967,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     // We update the label and convert it back to a term form that can be cheaply cloned
                     label.arg_pos = self.cache.get_then(idx.clone(), |c| c.body.pos);
                     label.arg_idx = Some(idx.clone());
+                    self.trace_contract(ContractEventKind::Apply, &label);
                     let new_label = RichTerm::new(Term::Lbl(label), pos2);
 
                     // If we're evaluating a plain contract application but we are applying
@@ -2134,6 +2294,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                 |missing_field_err| missing_field_err.into_eval_err(pos2, pos_op),
                             )? {
                                 Some(value) => {
+                                    self.warn_if_deprecated(record, ident, pos_op)?;
                                     self.call_stack.enter_field(ident, pos2, value.pos, pos_op);
                                     Ok(Closure {
                                         body: value,
@@ -2493,22 +2654,26 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 (Term::Array(..), _) => mk_type_error!("Number", 2, t2, pos2),
                 (_, _) => mk_type_error!("Array", 1, t1, pos1),
             },
-            BinaryOp::Merge(merge_label) => merge::merge(
-                &mut self.cache,
-                RichTerm {
-                    term: t1,
-                    pos: pos1,
-                },
-                env1,
-                RichTerm {
-                    term: t2,
-                    pos: pos2,
-                },
-                env2,
-                pos_op,
-                MergeMode::Standard(merge_label),
-                &mut self.call_stack,
-            ),
+            BinaryOp::Merge(merge_label) => {
+                self.warn_if_merging_deprecated(&t1, &t2)?;
+
+                merge::merge(
+                    &mut self.cache,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                    env1,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                    env2,
+                    pos_op,
+                    MergeMode::Standard(merge_label),
+                    &mut self.call_stack,
+                )
+            }
             BinaryOp::Hash => {
                 let mk_err_fst =
                     |t1| mk_type_error!("[| 'Md5, 'Sha1, 'Sha256, 'Sha512 |]", 1, t1, pos1);
@@ -3112,6 +3277,8 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
 
                 match_sharedterm!(match (t1) {
                     Term::Lbl(lbl) => {
+                        self.warn_if_merging_deprecated(&t2, &t3)?;
+
                         merge::merge(
                             &mut self.cache,
                             RichTerm {
@@ -3753,7 +3920,7 @@ fn eq<C: Cache>(
 
                             Some(Err(EvalError::MissingFieldDef {
                                 id,
-                                metadata,
+                                metadata: Box::new(metadata),
                                 pos_record,
                                 pos_access: pos_op,
                             }))
@@ -3871,7 +4038,7 @@ where
                         );
                         f(id, value_with_ctrs)
                     })
-                    .ok_or(record::MissingFieldDefError {
+                    .ok_or_else(|| record::MissingFieldDefError {
                         id,
                         metadata: field.metadata.clone(),
                     })?;
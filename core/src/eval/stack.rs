@@ -147,6 +147,15 @@ impl<C: Cache> Stack<C> {
         }
     }
 
+    /// The number of elements of the stack.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     /// Count the number of arguments at the top of the stack.
     pub fn count_args(&self) -> usize {
         Stack::count(self, Marker::is_arg)
@@ -0,0 +1,228 @@
+//! Streaming export of the result of an evaluation.
+//!
+//! [VirtualMachine::eval_full_for_export_closure] evaluates the whole result before it's
+//! serialized, so that the fully evaluated term must fit in memory on top of the output. For very
+//! large outputs, [VirtualMachine::stream_json_closure] instead forces each value right before
+//! writing it: a subtree which has been written can be freed as soon as it isn't shared with the
+//! rest of the program anymore.
+//!
+//! The output is the same as the one of [crate::serialize::to_writer_json] on the fully evaluated
+//! term. However, because there's no validation pass before writing, an evaluation or export
+//! error might be raised after a part of the document has already been written.
+use std::io::Write;
+
+use serde_json::ser::{CompactFormatter, Formatter, PrettyFormatter};
+
+use super::{cache::Cache, Closure, VirtualMachine};
+use crate::{
+    cache::ImportResolver,
+    error::{Error, EvalWarning, ExportError, ExportErrorData},
+    identifier::Ident,
+    position::TermPos,
+    serialize::{self, ExportFormat, JsonLayout, KeyOrder, NickelPointer, NickelPointerElem},
+    term::{record::MissingFieldDefError, RichTerm, RuntimeContract, Term},
+};
+
+/// A value evaluated to a weak head normal form, whose subterms haven't been forced yet.
+enum Node {
+    Record(Vec<(Ident, Closure)>),
+    Array(Vec<Closure>),
+    Scalar(RichTerm),
+}
+
+impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
+    /// Evaluate a closure and write the result as JSON with the given layout, forcing each value
+    /// only when it's written. As for [Self::eval_full_for_export_closure], the fields marked as
+    /// `not_exported` are skipped and the pending contracts are applied. The fields of records are
+    /// written in the given order.
+    pub fn stream_json_closure(
+        &mut self,
+        closure: Closure,
+        writer: impl Write,
+        layout: JsonLayout,
+        key_order: KeyOrder,
+    ) -> Result<(), Error> {
+        match layout {
+            JsonLayout::Compact => JsonStream {
+                writer,
+                formatter: CompactFormatter,
+                key_order,
+                path: Vec::new(),
+            }
+            .write_value(self, closure),
+            JsonLayout::Pretty { indent } => {
+                let indent = " ".repeat(indent);
+
+                JsonStream {
+                    writer,
+                    formatter: PrettyFormatter::with_indent(indent.as_bytes()),
+                    key_order,
+                    path: Vec::new(),
+                }
+                .write_value(self, closure)
+            }
+        }
+    }
+
+    /// Evaluate a closure to a weak head normal form and return its direct subterms, with their
+    /// pending contracts applied. The evaluated term is dropped before returning, so that it
+    /// doesn't outlive the serialization of its first subterm.
+    fn unfold(&mut self, closure: Closure, key_order: KeyOrder) -> Result<Node, Error> {
+        let Closure { body, env } = self.eval_closure(closure)?;
+        let pos = body.pos;
+
+        let node = match body.as_ref() {
+            Term::Record(record) => {
+                let mut fields = Vec::with_capacity(record.fields.len());
+
+                for (id, field) in record.fields.iter() {
+                    if field.is_empty_optional() || field.metadata.not_exported {
+                        continue;
+                    }
+
+                    if let Some(message) = &field.metadata.deprecated {
+                        self.warn(EvalWarning::DeprecatedField {
+                            id: *id,
+                            message: message.clone(),
+                            pos_access: TermPos::None,
+                        })?;
+                    }
+
+                    let value = field.value.clone().ok_or_else(|| {
+                        MissingFieldDefError {
+                            id: *id,
+                            metadata: field.metadata.clone(),
+                        }
+                        .into_eval_err(pos, TermPos::None)
+                    })?;
+                    let value_pos = value.pos;
+                    let value = RuntimeContract::apply_all(
+                        value,
+                        field.pending_contracts.iter().cloned(),
+                        value_pos,
+                    );
+
+                    fields.push((
+                        id.ident(),
+                        Closure {
+                            body: value,
+                            env: env.clone(),
+                        },
+                    ));
+                }
+
+                if key_order == KeyOrder::Sorted {
+                    fields.sort_by_key(|(id, _)| *id);
+                }
+
+                Node::Record(fields)
+            }
+            Term::Array(ts, attrs) => Node::Array(
+                ts.iter()
+                    .map(|t| Closure {
+                        body: RuntimeContract::apply_all(
+                            t.clone(),
+                            attrs.pending_contracts.iter().cloned(),
+                            pos.into_inherited(),
+                        ),
+                        env: env.clone(),
+                    })
+                    .collect(),
+            ),
+            _ => Node::Scalar(body.clone()),
+        };
+
+        Ok(node)
+    }
+}
+
+/// The state of a JSON document being written.
+struct JsonStream<W, F> {
+    writer: W,
+    formatter: F,
+    key_order: KeyOrder,
+    /// The path to the value being written, for error reporting.
+    path: Vec<NickelPointerElem>,
+}
+
+impl<W: Write, F: Formatter> JsonStream<W, F> {
+    fn write_value<R: ImportResolver, C: Cache>(
+        &mut self,
+        vm: &mut VirtualMachine<R, C>,
+        closure: Closure,
+    ) -> Result<(), Error> {
+        match vm.unfold(closure, self.key_order)? {
+            Node::Record(fields) => {
+                self.formatter
+                    .begin_object(&mut self.writer)
+                    .map_err(|err| self.other(err))?;
+
+                for (index, (id, value)) in fields.into_iter().enumerate() {
+                    self.formatter
+                        .begin_object_key(&mut self.writer, index == 0)
+                        .map_err(|err| self.other(err))?;
+                    serde_json::to_writer(&mut self.writer, id.label())
+                        .map_err(|err| self.other(err))?;
+                    self.formatter
+                        .end_object_key(&mut self.writer)
+                        .and_then(|()| self.formatter.begin_object_value(&mut self.writer))
+                        .map_err(|err| self.other(err))?;
+
+                    self.path.push(NickelPointerElem::Field(id));
+                    self.write_value(vm, value)?;
+                    self.path.pop();
+
+                    self.formatter
+                        .end_object_value(&mut self.writer)
+                        .map_err(|err| self.other(err))?;
+                }
+
+                self.formatter
+                    .end_object(&mut self.writer)
+                    .map_err(|err| self.other(err))
+            }
+            Node::Array(elts) => {
+                self.formatter
+                    .begin_array(&mut self.writer)
+                    .map_err(|err| self.other(err))?;
+
+                for (index, elt) in elts.into_iter().enumerate() {
+                    self.formatter
+                        .begin_array_value(&mut self.writer, index == 0)
+                        .map_err(|err| self.other(err))?;
+
+                    self.path.push(NickelPointerElem::Index(index));
+                    self.write_value(vm, elt)?;
+                    self.path.pop();
+
+                    self.formatter
+                        .end_array_value(&mut self.writer)
+                        .map_err(|err| self.other(err))?;
+                }
+
+                self.formatter
+                    .end_array(&mut self.writer)
+                    .map_err(|err| self.other(err))
+            }
+            Node::Scalar(rt) => {
+                // Containers have been handled above, so this only checks the range of numbers and
+                // rejects the values which can't be serialized.
+                serialize::validate(ExportFormat::Json, &rt).map_err(|err| self.error(err.data))?;
+                serde_json::to_writer(&mut self.writer, &rt).map_err(|err| self.other(err))
+            }
+        }
+    }
+
+    /// Build an export error located at the current path.
+    fn error(&self, data: ExportErrorData) -> Error {
+        ExportError {
+            path: NickelPointer(self.path.clone()),
+            data,
+        }
+        .into()
+    }
+
+    fn other(&self, err: impl ToString) -> Error {
+        self.error(ExportErrorData::Other(err.to_string()))
+    }
+}
@@ -0,0 +1,161 @@
+//! Detection of the record fields and let bindings which are never evaluated.
+//!
+//! When demand tracking is enabled (see [VirtualMachine::set_track_demand]), the virtual machine
+//! records the position of every term it evaluates. After an evaluation, [VirtualMachine::unused]
+//! compares these positions with the record fields and the let bindings defined in the Nickel
+//! sources of the program (the standard library excluded): definitions whose value has never been
+//! evaluated are reported as unused. In a large configuration, those are typically dead settings,
+//! such as defaults which are always overridden or fields which aren't exported and never read.
+//!
+//! The result depends on the evaluation which has been run: after an export, every exported field
+//! is evaluated, but a mere evaluation to a weak head normal form leaves most fields unused. When
+//! the value of an unused definition contains other definitions, only the outermost one is
+//! reported.
+use std::collections::HashSet;
+
+use super::{cache::Cache, VirtualMachine};
+use crate::{
+    cache::{Cache as ImportCache, InputFormat},
+    error::{Diagnostic, Diagnostics, IntoDiagnostics, Label},
+    files::{FileId, Files},
+    identifier::LocIdent,
+    position::{RawSpan, TermPos},
+    term::{RichTerm, Term, Traverse, TraverseControl},
+};
+
+/// The kind of an unused definition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnusedKind {
+    Field,
+    LetBinding,
+}
+
+/// A definition whose value has never been evaluated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unused {
+    pub kind: UnusedKind,
+    /// The name of the field or of the variable, with the position of its definition.
+    pub id: LocIdent,
+    /// The position of the value which has never been evaluated.
+    pub span: RawSpan,
+}
+
+impl<C: Cache> VirtualMachine<ImportCache, C> {
+    /// Enable or disable the tracking of the evaluated terms, which is required by
+    /// [Self::unused]. Tracking has a cost, and should only be enabled for analysis purposes.
+    /// Disabling tracking discards the terms recorded so far.
+    pub fn set_track_demand(&mut self, enabled: bool) {
+        self.demanded = enabled.then(HashSet::new);
+    }
+
+    /// Return the record fields and let bindings of the program whose value has never been
+    /// evaluated since demand tracking has been enabled, sorted by position. Return an empty
+    /// vector if demand tracking isn't enabled.
+    pub fn unused(&self) -> Vec<Unused> {
+        let Some(demanded) = &self.demanded else {
+            return Vec::new();
+        };
+
+        let mut file_ids: Vec<FileId> = self
+            .import_resolver
+            .terms()
+            .keys()
+            .copied()
+            .filter(|file_id| {
+                !self.import_resolver.is_stdlib_module(*file_id)
+                    && self
+                        .import_resolver
+                        .input_format(*file_id)
+                        .unwrap_or_default()
+                        == InputFormat::Nickel
+            })
+            .collect();
+        file_ids.sort();
+
+        let mut unused = Vec::new();
+
+        for file_id in file_ids {
+            // We analyze the original source, as the terms stored in the cache have been
+            // transformed and closurized.
+            if let Ok((term, _)) = self.import_resolver.parse_nocache(file_id) {
+                collect_unused(&term, demanded, &mut unused);
+            }
+        }
+
+        unused.sort_by_key(|unused| (unused.span.src_id, unused.span.start));
+        unused
+    }
+}
+
+/// Collect the unused definitions of a term. The definitions nested inside an unused definition
+/// aren't reported.
+fn collect_unused(rt: &RichTerm, demanded: &HashSet<RawSpan>, unused: &mut Vec<Unused>) {
+    let check =
+        |kind: UnusedKind, id: &LocIdent, value: &RichTerm, unused: &mut Vec<Unused>| match value
+            .pos
+        {
+            TermPos::Original(span) if !demanded.contains(&span) => {
+                if !id.label().starts_with('_') {
+                    unused.push(Unused {
+                        kind,
+                        id: *id,
+                        span,
+                    });
+                }
+            }
+            _ => collect_unused(value, demanded, unused),
+        };
+
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| -> TraverseControl<(), ()> {
+            match rt.as_ref() {
+                Term::Record(data) | Term::RecRecord(data, ..) => {
+                    for (id, field) in data.fields.iter() {
+                        if let Some(value) = &field.value {
+                            check(UnusedKind::Field, id, value, unused);
+                        }
+                    }
+
+                    if let Term::RecRecord(_, dyn_fields, _) = rt.as_ref() {
+                        for value in dyn_fields
+                            .iter()
+                            .filter_map(|(_, field)| field.value.as_ref())
+                        {
+                            collect_unused(value, demanded, unused);
+                        }
+                    }
+
+                    TraverseControl::SkipBranch
+                }
+                Term::Let(bindings, body, _) => {
+                    for (id, bound) in bindings.iter() {
+                        check(UnusedKind::LetBinding, id, bound, unused);
+                    }
+
+                    collect_unused(body, demanded, unused);
+                    TraverseControl::SkipBranch
+                }
+                _ => TraverseControl::Continue,
+            }
+        },
+        &(),
+    );
+}
+
+impl IntoDiagnostics for Unused {
+    fn into_diagnostics(self, _files: &Files) -> Diagnostics {
+        let what = match self.kind {
+            UnusedKind::Field => "field",
+            UnusedKind::LetBinding => "let binding",
+        };
+
+        vec![Diagnostic::warning()
+            .with_message(format!("unused {what} `{}`", self.id))
+            .with_labels(vec![Label::primary(
+                self.span.src_id,
+                self.span.start.to_usize()..self.span.end.to_usize(),
+            )
+            .with_message("this value is never evaluated")])]
+        .into()
+    }
+}
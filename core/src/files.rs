@@ -21,6 +21,12 @@ use crate::{position::RawSpan, stdlib::StdlibModule};
 )]
 pub struct FileId(u32);
 
+impl FileId {
+    /// A file id which doesn't refer to any file, to parse a source which isn't part of a
+    /// [`Files`] when the file id of the resulting positions is irrelevant.
+    pub(crate) const DETACHED: FileId = FileId(u32::MAX);
+}
+
 #[derive(Debug, Clone)]
 struct File {
     /// The name of the file.
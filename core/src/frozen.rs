@@ -0,0 +1,215 @@
+//! Thread-safe snapshots of evaluated values.
+//!
+//! Nickel terms are reference-counted with [std::rc::Rc] and can't be shared between threads. A
+//! [FrozenValue] is an immutable deep copy of a fully evaluated value, which is `Send + Sync` and
+//! cheap to clone: a service can evaluate a configuration once, freeze the result and answer
+//! queries against it from many threads. Independent programs can already be evaluated
+//! concurrently, as long as each program stays on the thread that created it.
+//!
+//! Only data can be frozen: null, booleans, numbers, strings, enum tags and variants, arrays and
+//! records. A frozen value can be turned back into a term with [RichTerm::from], for example to
+//! be merged with per-request data by a new program.
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    error::{ExportError, ExportErrorData},
+    identifier::LocIdent,
+    serialize::{NickelPointer, NickelPointerElem},
+    term::{array::ArrayAttrs, record::RecordData, EnumVariantAttrs, Number, RichTerm, Term},
+};
+
+/// An immutable, thread-safe snapshot of a fully evaluated value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrozenValue {
+    Null,
+    Bool(bool),
+    Number(Arc<Number>),
+    String(Arc<str>),
+    Enum(Arc<str>),
+    EnumVariant(Arc<str>, Arc<FrozenValue>),
+    Array(Arc<[FrozenValue]>),
+    /// Record fields, sorted by name. Fields marked `not_exported` are not included.
+    Record(Arc<BTreeMap<String, FrozenValue>>),
+}
+
+impl FrozenValue {
+    /// Follow a path of field names from this record. Return `None` if a field is missing or if
+    /// an intermediate value isn't a record.
+    pub fn get<I, S>(&self, path: I) -> Option<&FrozenValue>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        path.into_iter()
+            .try_fold(self, |current, name| match current {
+                FrozenValue::Record(fields) => fields.get(name.as_ref()),
+                _ => None,
+            })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FrozenValue::String(s) => Some(s.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            FrozenValue::Number(n) => Some(n.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FrozenValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&RichTerm> for FrozenValue {
+    type Error = ExportError;
+
+    /// Freeze a fully evaluated term. Fail with [ExportErrorData::NonSerializable] on anything else
+    /// than data, including unevaluated terms.
+    fn try_from(rt: &RichTerm) -> Result<Self, ExportError> {
+        freeze(rt, &mut NickelPointer::new())
+    }
+}
+
+fn freeze(rt: &RichTerm, path: &mut NickelPointer) -> Result<FrozenValue, ExportError> {
+    let with_path = |data: ExportErrorData, path: &NickelPointer| ExportError {
+        path: path.clone(),
+        data,
+    };
+
+    Ok(match rt.as_ref() {
+        Term::Null => FrozenValue::Null,
+        Term::Bool(b) => FrozenValue::Bool(*b),
+        Term::Num(n) => FrozenValue::Number(Arc::new(n.clone())),
+        Term::Str(s) => FrozenValue::String(Arc::from(s.as_ref())),
+        Term::Enum(tag) => FrozenValue::Enum(Arc::from(tag.label())),
+        Term::EnumVariant { tag, arg, .. } => {
+            FrozenValue::EnumVariant(Arc::from(tag.label()), Arc::new(freeze(arg, path)?))
+        }
+        Term::Array(elts, _) => {
+            let mut frozen = Vec::with_capacity(elts.len());
+
+            for (index, elt) in elts.iter().enumerate() {
+                path.0.push(NickelPointerElem::Index(index));
+                frozen.push(freeze(elt, path)?);
+                path.0.pop();
+            }
+
+            FrozenValue::Array(frozen.into())
+        }
+        Term::Record(record) => {
+            let mut frozen = BTreeMap::new();
+
+            for entry in record.iter_serializable() {
+                let (id, value) = entry.map_err(|missing_def| {
+                    with_path(
+                        ExportErrorData::Other(format!(
+                            "missing definition for field `{}`",
+                            missing_def.id
+                        )),
+                        path,
+                    )
+                })?;
+
+                path.0.push(NickelPointerElem::Field(id));
+                frozen.insert(id.into_label(), freeze(value, path)?);
+                path.0.pop();
+            }
+
+            FrozenValue::Record(Arc::new(frozen))
+        }
+        _ => {
+            return Err(with_path(
+                ExportErrorData::NonSerializable(rt.clone()),
+                path,
+            ))
+        }
+    })
+}
+
+impl From<&FrozenValue> for RichTerm {
+    fn from(value: &FrozenValue) -> Self {
+        let term = match value {
+            FrozenValue::Null => Term::Null,
+            FrozenValue::Bool(b) => Term::Bool(*b),
+            FrozenValue::Number(n) => Term::Num(Number::clone(n)),
+            FrozenValue::String(s) => Term::Str(s.as_ref().into()),
+            FrozenValue::Enum(tag) => Term::Enum(LocIdent::new(tag)),
+            FrozenValue::EnumVariant(tag, arg) => Term::EnumVariant {
+                tag: LocIdent::new(tag),
+                arg: RichTerm::from(arg.as_ref()),
+                attrs: EnumVariantAttrs::default(),
+            },
+            FrozenValue::Array(elts) => Term::Array(
+                elts.iter().map(RichTerm::from).collect(),
+                ArrayAttrs::default(),
+            ),
+            FrozenValue::Record(fields) => Term::Record(RecordData::with_field_values(
+                fields
+                    .iter()
+                    .map(|(id, value)| (LocIdent::new(id), RichTerm::from(value))),
+            )),
+        };
+
+        RichTerm::from(term)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::program::tests::program;
+
+    fn eval_frozen(source: &str) -> Result<FrozenValue, ExportError> {
+        FrozenValue::try_from(&program(source).eval_full_for_export().unwrap())
+    }
+
+    #[test]
+    fn share_between_threads() {
+        let frozen = eval_frozen(
+            "{ server = { port = 8000 + 80, mode = 'Prod }, users = [\"alice\", \"bob\"] }",
+        )
+        .unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen = frozen.clone();
+                thread::spawn(move || {
+                    frozen
+                        .get(["server", "port"])
+                        .and_then(FrozenValue::as_number)
+                        .cloned()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Some(Number::from(8080)));
+        }
+
+        assert_eq!(
+            frozen.get(["server", "mode"]),
+            Some(&FrozenValue::Enum(Arc::from("Prod")))
+        );
+        assert_eq!(
+            FrozenValue::try_from(&RichTerm::from(&frozen)).unwrap(),
+            frozen
+        );
+    }
+
+    #[test]
+    fn non_data() {
+        let err = eval_frozen("{ foo = { bar = [fun x => x] } }").unwrap_err();
+        assert_eq!(err.path.to_string(), "foo.bar[0]");
+    }
+}
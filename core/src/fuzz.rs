@@ -0,0 +1,248 @@
+//! Entry points for fuzzing the interpreter, and generation of arbitrary terms.
+//!
+//! This module is enabled by the `arbitrary` feature. It implements [Arbitrary] for [RichTerm],
+//! and provides the properties checked by the fuzz targets of the `fuzz` directory at the root of
+//! the repository, which downstream users can also call from their own fuzzing harnesses:
+//!
+//! - [parse]: parsing any input terminates without panicking,
+//! - [eval]: evaluating an arbitrary term with a bounded amount of fuel terminates without
+//!   panicking. Evaluation errors are expected and ignored,
+//! - [pretty_print_roundtrip]: pretty-printing an arbitrary term gives a source which parses, and
+//!   pretty-printing is idempotent on parsed terms.
+//!
+//! The generated terms cover a subset of the language: constants, variables, functions, let
+//! bindings, conditionals, records, arrays, enums, field accesses and the common primitive
+//! operators. Variables and fields are drawn from a small set of names, so that generated terms
+//! often refer to the bindings they define.
+use std::{
+    future::Future,
+    io::Cursor,
+    num::NonZeroUsize,
+    sync::Arc,
+    task::{Context, Wake, Waker},
+};
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::{
+    error::ParseErrors,
+    eval::cache::CacheImpl,
+    files::Files,
+    identifier::LocIdent,
+    label::Label,
+    parser::{grammar::TermParser, lexer::Lexer, ErrorTolerantParser},
+    policy::CapabilityPolicy,
+    program::Program,
+    term::{
+        array::{Array, ArrayAttrs},
+        make as mk_term,
+        record::RecordData,
+        BinaryOp, Number, RecordOpKind, RichTerm, Term, UnaryOp,
+    },
+};
+
+/// The maximum depth of the generated terms.
+pub const MAX_DEPTH: usize = 6;
+
+/// The number of evaluation steps run between two checks of the remaining fuel.
+const STEPS_PER_SLICE: usize = 256;
+
+/// The names of the generated variables, parameters and fields.
+const NAMES: &[&str] = &["x", "y", "z", "foo", "bar"];
+
+/// The names of the generated enum tags.
+const TAGS: &[&str] = &["Foo", "Bar", "baz"];
+
+const UNARY_OPS: &[fn() -> UnaryOp] = &[
+    || UnaryOp::Typeof,
+    || UnaryOp::BoolNot,
+    || UnaryOp::ArrayLength,
+    || UnaryOp::StringLength,
+    || UnaryOp::StringUppercase,
+    || UnaryOp::ToString,
+    || UnaryOp::RecordFields(RecordOpKind::IgnoreEmptyOpt),
+    || UnaryOp::RecordValues,
+];
+
+const BINARY_OPS: &[fn() -> BinaryOp] = &[
+    || BinaryOp::Plus,
+    || BinaryOp::Sub,
+    || BinaryOp::Mult,
+    || BinaryOp::Div,
+    || BinaryOp::Modulo,
+    || BinaryOp::Pow,
+    || BinaryOp::StringConcat,
+    || BinaryOp::Eq,
+    || BinaryOp::LessThan,
+    || BinaryOp::LessOrEq,
+    || BinaryOp::ArrayConcat,
+    || BinaryOp::ArrayAt,
+    || BinaryOp::Merge(Label::default().into()),
+];
+
+impl<'a> Arbitrary<'a> for RichTerm {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_term(u, MAX_DEPTH)
+    }
+}
+
+/// Generate a term of depth at most `depth`.
+pub fn arbitrary_term(u: &mut Unstructured<'_>, depth: usize) -> arbitrary::Result<RichTerm> {
+    if depth == 0 || u.is_empty() {
+        return arbitrary_leaf(u);
+    }
+
+    let depth = depth - 1;
+
+    let term = match u.int_in_range(0..=10)? {
+        0 => arbitrary_leaf(u)?,
+        1 => Term::Fun(arbitrary_name(u)?, arbitrary_term(u, depth)?).into(),
+        2 => Term::App(arbitrary_term(u, depth)?, arbitrary_term(u, depth)?).into(),
+        3 => {
+            let rec = u.arbitrary()?;
+            let mut bindings = Vec::new();
+
+            for _ in 0..u.int_in_range(1..=2)? {
+                bindings.push((arbitrary_name(u)?, arbitrary_term(u, depth)?));
+            }
+
+            mk_term::let_in(rec, bindings, arbitrary_term(u, depth)?)
+        }
+        4 => mk_term::if_then_else(
+            arbitrary_term(u, depth)?,
+            arbitrary_term(u, depth)?,
+            arbitrary_term(u, depth)?,
+        ),
+        5 => {
+            let mut fields = Vec::new();
+
+            for _ in 0..u.int_in_range(0..=3)? {
+                let id = arbitrary_name(u)?;
+
+                if fields.iter().all(|(other, _)| *other != id) {
+                    fields.push((id, arbitrary_term(u, depth)?));
+                }
+            }
+
+            Term::Record(RecordData::with_field_values(fields)).into()
+        }
+        6 => {
+            let mut elts = Vec::new();
+
+            for _ in 0..u.int_in_range(0..=3)? {
+                elts.push(arbitrary_term(u, depth)?);
+            }
+
+            Term::Array(elts.into_iter().collect::<Array>(), ArrayAttrs::default()).into()
+        }
+        7 => mk_term::static_access(arbitrary_term(u, depth)?, [*u.choose(NAMES)?]),
+        8 => mk_term::enum_variant(*u.choose(TAGS)?, arbitrary_term(u, depth)?),
+        9 => mk_term::op1(u.choose(UNARY_OPS)?(), arbitrary_term(u, depth)?),
+        _ => mk_term::op2(
+            u.choose(BINARY_OPS)?(),
+            arbitrary_term(u, depth)?,
+            arbitrary_term(u, depth)?,
+        ),
+    };
+
+    Ok(term)
+}
+
+fn arbitrary_name(u: &mut Unstructured<'_>) -> arbitrary::Result<LocIdent> {
+    u.choose(NAMES).map(|name| LocIdent::from(*name))
+}
+
+fn arbitrary_leaf(u: &mut Unstructured<'_>) -> arbitrary::Result<RichTerm> {
+    let term = match u.int_in_range(0..=6)? {
+        0 => Term::Null,
+        1 => Term::Bool(u.arbitrary()?),
+        2 => Term::Num(Number::from(u.arbitrary::<i32>()?)),
+        3 => {
+            let numerator = Number::from(u.arbitrary::<i32>()?);
+            let denominator = Number::from(u.int_in_range(1..=1000_i32)?);
+            Term::Num(numerator / denominator)
+        }
+        4 => Term::Str(u.arbitrary::<String>()?.into()),
+        5 => Term::Enum((*u.choose(TAGS)?).into()),
+        _ => Term::Var((*u.choose(NAMES)?).into()),
+    };
+
+    Ok(term.into())
+}
+
+fn parse_strict(source: &str) -> Result<RichTerm, ParseErrors> {
+    let file_id = Files::new().add("<fuzz>", source);
+    TermParser::new().parse_strict(file_id, Lexer::new(source))
+}
+
+/// Parse `data` as a Nickel source, both in strict and in error-tolerant mode. Parse errors are
+/// expected: this only checks that the parser doesn't panic.
+pub fn parse(data: &[u8]) {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let file_id = Files::new().add("<fuzz>", source);
+    let _ = TermParser::new().parse_tolerant(file_id, Lexer::new(source));
+    let _ = parse_strict(source);
+}
+
+/// Evaluate `term` fully, running at most `fuel` steps of the virtual machine, with a policy
+/// denying imports and native functions. Evaluation errors and running out of fuel are expected:
+/// this only checks that the interpreter doesn't panic.
+///
+/// The term goes through the whole pipeline, from its pretty-printed source: typechecking and
+/// program transformations establish invariants the virtual machine relies on. Note that a few
+/// primitive operations run a nested evaluation, which isn't accounted for in the fuel.
+pub fn eval(term: &RichTerm, fuel: usize) {
+    let Ok(mut program) = Program::<CacheImpl>::new_from_source(
+        Cursor::new(term.to_string()),
+        "<fuzz>",
+        std::io::sink(),
+    ) else {
+        return;
+    };
+    program.set_policy(CapabilityPolicy::deny_all());
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    // unwrap(): `STEPS_PER_SLICE` isn't zero.
+    let mut future =
+        Box::pin(program.eval_full_for_export_async(NonZeroUsize::new(STEPS_PER_SLICE).unwrap()));
+
+    // The evaluation yields after each slice of steps. Dropping the future stops it.
+    for _ in 0..fuel.div_ceil(STEPS_PER_SLICE) {
+        if future.as_mut().poll(&mut cx).is_ready() {
+            break;
+        }
+    }
+}
+
+/// Check that the pretty-printed source of `term` parses, and that pretty-printing the result is
+/// idempotent, that is that pretty-printing, parsing and pretty-printing again gives the same
+/// source.
+///
+/// # Panics
+///
+/// Panics if the property doesn't hold.
+pub fn pretty_print_roundtrip(term: &RichTerm) {
+    let printed = term.to_string();
+    let parsed = parse_strict(&printed)
+        .unwrap_or_else(|err| panic!("pretty-printed term doesn't parse: {err:?}\n{printed}"));
+
+    let reprinted = parsed.to_string();
+    let reparsed = parse_strict(&reprinted)
+        .unwrap_or_else(|err| panic!("pretty-printed term doesn't parse: {err:?}\n{reprinted}"));
+
+    assert_eq!(
+        reprinted,
+        reparsed.to_string(),
+        "pretty-printing isn't idempotent"
+    );
+}
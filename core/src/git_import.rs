@@ -0,0 +1,377 @@
+//! Imports of files from git repositories, pinned by a lockfile.
+//!
+//! When enabled on the cache (see [crate::program::Program::set_git_imports]), a Nickel source can
+//! import a file from a git repository. The path of the file in the repository follows the URL of
+//! the repository and a double slash, and the branch, tag or commit to use is given as the
+//! fragment (`HEAD` by default):
+//!
+//! ```text
+//! let lib = import "git+https://example.com/contracts.git//lib/main.ncl#v1.2" in
+//! ```
+//!
+//! The commit corresponding to each repository and reference is recorded in a [Lockfile], so that
+//! the evaluation is reproducible even when a branch or a tag is moved. An import which isn't
+//! pinned by the lockfile fails, unless the configuration is in update mode, where the reference is
+//! resolved and the commit is added to the lockfile. This is what the `nickel lock` command does.
+//!
+//! Repositories are cloned with the `git` executable into a local cache directory, once for each
+//! commit. Imported files are then read from the checkout, and can import other files of the same
+//! repository with relative paths. Git imports require the
+//! [Network](crate::policy::Capability::Network) capability, and the files read from the checkout
+//! are subject to the same policy checks as the other imported files.
+//!
+//! Only repositories with a URL scheme (`https://`, `ssh://`, etc.) are accepted, and the path of
+//! the file can't escape the checkout.
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The name of the lockfile used by the CLI, in the current directory.
+pub const LOCKFILE_NAME: &str = "nickel.lock";
+
+/// The prefix of the URLs of git imports.
+pub const PREFIX: &str = "git+";
+
+/// The reference used when an import doesn't specify one.
+const DEFAULT_REFERENCE: &str = "HEAD";
+
+/// The URL schemes accepted for repositories. Anything else, such as a string starting with `-`,
+/// could be interpreted as an option by `git`.
+const SCHEMES: &[&str] = &["https://", "http://", "ssh://", "git://", "file://"];
+
+/// The commits pinned for each repository and reference, indexed by `<repository>#<reference>`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub git: BTreeMap<String, String>,
+}
+
+impl Lockfile {
+    /// Read a lockfile. Return an empty lockfile if the file doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut content = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        content.push('\n');
+        fs::write(path, content)
+    }
+}
+
+/// A parsed git import.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitImport<'a> {
+    /// The URL of the repository.
+    pub repository: &'a str,
+    /// The path of the imported file in the repository.
+    pub path: &'a str,
+    /// The branch, tag or commit.
+    pub reference: &'a str,
+}
+
+/// Return `true` if `path` is the URL of a git import.
+pub fn is_git(path: &OsStr) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with(PREFIX))
+}
+
+impl<'a> GitImport<'a> {
+    /// Parse the URL of a git import.
+    pub fn parse(url: &'a str) -> Result<Self, String> {
+        let rest = url.strip_prefix(PREFIX).unwrap_or(url);
+        let (rest, reference) = rest.split_once('#').unwrap_or((rest, DEFAULT_REFERENCE));
+
+        // The double slash following the scheme of the repository isn't the separator.
+        let scheme_end = rest.find("://").map_or(0, |idx| idx + 3);
+        let (repository, path) = rest[scheme_end..]
+            .split_once("//")
+            .map(|(repository, path)| (&rest[..scheme_end + repository.len()], path))
+            .filter(|(_, path)| !path.is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "git imports must specify the path of the file in the repository, as in \
+                    `{PREFIX}<repository>//<path>#<reference>`"
+                )
+            })?;
+
+        check_repository(repository)?;
+
+        // The path is joined to the directory of the checkout: it must not be able to escape it.
+        if path.starts_with(['/', '\\'])
+            || path.split(['/', '\\']).any(|component| component == "..")
+        {
+            return Err(format!(
+                "invalid path `{path}`: the path of the file in the repository must be relative, \
+                and can't contain `..`"
+            ));
+        }
+
+        Ok(GitImport {
+            repository,
+            path,
+            reference,
+        })
+    }
+
+    /// The key of the import in the lockfile.
+    pub fn pin_key(&self) -> String {
+        format!("{}#{}", self.repository, self.reference)
+    }
+}
+
+/// The configuration of git imports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitImportConfig {
+    /// The directory storing the checkouts.
+    pub cache_dir: PathBuf,
+    /// The pinned commits.
+    pub lockfile: Lockfile,
+    /// If set, the imports which aren't pinned are resolved and added to the lockfile.
+    pub update: bool,
+}
+
+impl GitImportConfig {
+    pub fn new(cache_dir: impl Into<PathBuf>, lockfile: Lockfile) -> Self {
+        GitImportConfig {
+            cache_dir: cache_dir.into(),
+            lockfile,
+            update: false,
+        }
+    }
+
+    pub fn update(self, update: bool) -> Self {
+        GitImportConfig { update, ..self }
+    }
+
+    /// Return the local path of the imported file, cloning the repository at the pinned commit if
+    /// needed. Return the reason of the failure otherwise.
+    pub fn fetch(&mut self, import: &GitImport<'_>) -> Result<PathBuf, String> {
+        let key = import.pin_key();
+
+        let commit = match self.lockfile.git.get(&key) {
+            Some(commit) => commit.clone(),
+            None if self.update => {
+                let commit = resolve_reference(import.repository, import.reference)?;
+                self.lockfile.git.insert(key, commit.clone());
+                commit
+            }
+            None => {
+                return Err(format!(
+                    "`{key}` isn't pinned by the lockfile; run `nickel lock` to pin it"
+                ))
+            }
+        };
+
+        let checkout = checkout(&self.cache_dir, import.repository, &commit)?;
+        let path = checkout.join(import.path);
+
+        // The repository may contain symbolic links pointing outside of the checkout.
+        if let (Ok(checkout), Ok(canonical)) = (checkout.canonicalize(), path.canonicalize()) {
+            if !canonical.starts_with(checkout) {
+                return Err(format!(
+                    "`{}` points outside of the repository",
+                    import.path
+                ));
+            }
+        }
+
+        Ok(path)
+    }
+}
+
+/// Check that `repository` is a URL with one of the accepted [SCHEMES].
+pub(crate) fn check_repository(repository: &str) -> Result<(), String> {
+    if SCHEMES.iter().any(|scheme| repository.starts_with(scheme)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unsupported repository `{repository}`: the URL must start with one of {}",
+            SCHEMES
+                .iter()
+                .map(|scheme| format!("`{scheme}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+}
+
+/// Return `true` if `commit` is a full commit hash, made of exactly 40 hexadecimal digits.
+fn is_commit_hash(commit: &str) -> bool {
+    commit.len() == 40 && commit.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Return the directory of the checkout of `repository` at `commit` in `cache_dir`, cloning the
+/// repository if needed.
+pub(crate) fn checkout(
+    cache_dir: &Path,
+    repository: &str,
+    commit: &str,
+) -> Result<PathBuf, String> {
+    check_repository(repository)?;
+
+    // The commit usually comes from a lockfile. It's used both as a directory name and as an
+    // argument of `git`, so it must be validated.
+    if !is_commit_hash(commit) {
+        return Err(format!(
+            "invalid commit `{commit}`: expected 40 hexadecimal digits"
+        ));
+    }
+
+    let checkout = cache_dir
+        .join(format!("{:.16x}", Sha256::digest(repository.as_bytes())))
+        .join(commit);
+
+    if !checkout.exists() {
+        clone(repository, commit, &checkout)?;
+    }
+
+    Ok(checkout)
+}
+
+/// Run `git` with the given arguments, and return its standard output.
+pub(crate) fn git(args: &[&OsStr]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|err| format!("couldn't run git: {err}"))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format!(
+            "git failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Return the commit a reference of a remote repository points to.
+pub(crate) fn resolve_reference(repository: &str, reference: &str) -> Result<String, String> {
+    check_repository(repository)?;
+
+    if is_commit_hash(reference) {
+        return Ok(reference.to_ascii_lowercase());
+    }
+
+    git(&[
+        "ls-remote".as_ref(),
+        "--".as_ref(),
+        repository.as_ref(),
+        reference.as_ref(),
+    ])?
+    .split_whitespace()
+    .next()
+    .filter(|commit| is_commit_hash(commit))
+    .map(str::to_owned)
+    .ok_or_else(|| format!("`{reference}` doesn't exist in `{repository}`"))
+}
+
+/// Clone `repository` at `commit` into `dest`. The repository is cloned into a temporary directory
+/// first, so that an interrupted clone doesn't leave an incomplete checkout behind.
+fn clone(repository: &str, commit: &str, dest: &Path) -> Result<(), String> {
+    let tmp = dest.with_extension("tmp");
+    let _ = fs::remove_dir_all(&tmp);
+
+    git(&[
+        "clone".as_ref(),
+        "--quiet".as_ref(),
+        "--".as_ref(),
+        repository.as_ref(),
+        tmp.as_os_str(),
+    ])?;
+    git(&[
+        "-C".as_ref(),
+        tmp.as_os_str(),
+        "checkout".as_ref(),
+        "--quiet".as_ref(),
+        "--detach".as_ref(),
+        commit.as_ref(),
+    ])?;
+
+    fs::rename(&tmp, dest).map_err(|err| {
+        format!(
+            "couldn't write to the local cache `{}`: {err}",
+            dest.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            GitImport::parse("git+https://example.com/lib.git//contracts/main.ncl#v1.2"),
+            Ok(GitImport {
+                repository: "https://example.com/lib.git",
+                path: "contracts/main.ncl",
+                reference: "v1.2",
+            })
+        );
+        assert_eq!(
+            GitImport::parse("git+ssh://git@example.com/lib.git//main.ncl"),
+            Ok(GitImport {
+                repository: "ssh://git@example.com/lib.git",
+                path: "main.ncl",
+                reference: "HEAD",
+            })
+        );
+        assert!(GitImport::parse("git+https://example.com/lib.git").is_err());
+        assert!(GitImport::parse("git+https://example.com/lib.git//").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_unsafe_imports() {
+        // Repositories which `git` could interpret as options.
+        assert!(GitImport::parse("git+--upload-pack=touch /tmp/pwned//x#.").is_err());
+        assert!(GitImport::parse("git+-c//main.ncl").is_err());
+        assert!(GitImport::parse("git+example.com:lib.git//main.ncl").is_err());
+
+        // Paths escaping the checkout.
+        assert!(GitImport::parse("git+https://example.com/lib.git///etc/passwd").is_err());
+        assert!(GitImport::parse("git+https://example.com/lib.git//../../../etc/passwd").is_err());
+        assert!(GitImport::parse("git+https://example.com/lib.git//lib/../../main.ncl").is_err());
+    }
+
+    #[test]
+    fn invalid_commit() {
+        let url = "git+https://example.invalid/lib.git//main.ncl#main";
+        let mut lockfile = Lockfile::default();
+        lockfile.git.insert(
+            "https://example.invalid/lib.git#main".to_owned(),
+            "--orphan=x".to_owned(),
+        );
+        let mut config = GitImportConfig::new(std::env::temp_dir(), lockfile);
+
+        assert!(config
+            .fetch(&GitImport::parse(url).unwrap())
+            .unwrap_err()
+            .contains("invalid commit"));
+    }
+
+    #[test]
+    fn unpinned() {
+        let url = "git+https://example.invalid/lib.git//main.ncl#main";
+        let mut config = GitImportConfig::new(std::env::temp_dir(), Lockfile::default());
+
+        assert!(config
+            .fetch(&GitImport::parse(url).unwrap())
+            .unwrap_err()
+            .contains("nickel lock"));
+    }
+}
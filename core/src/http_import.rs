@@ -0,0 +1,186 @@
+//! Imports of remote files served over HTTPS.
+//!
+//! When enabled on the cache (see [crate::program::Program::set_http_imports]), a Nickel source
+//! can import a file from an HTTPS URL. The SHA-256 hash of the content is mandatory, and is given
+//! as the fragment of the URL:
+//!
+//! ```text
+//! let lib = import "https://example.com/lib.ncl#sha256=6b86b273ff34fce19d6b804eff5a3f5747ada4eaa22f1d49c01e52ddb7875b4b" in
+//! ```
+//!
+//! A remote file is thus immutable: if the content served at the URL changes, the import fails
+//! instead of silently changing the result of the evaluation. Downloaded files are stored in a
+//! local cache directory, named after their hash, so that each file is only downloaded once. In
+//! offline mode, remote imports are only served from the local cache.
+//!
+//! Remote files can import other remote files, but not relative paths. Remote imports require the
+//! [Network](crate::policy::Capability::Network) capability.
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+/// The scheme of the URLs of remote imports.
+pub const SCHEME: &str = "https://";
+
+/// The prefix of the fragment giving the hash of a remote import.
+const HASH_PREFIX: &str = "sha256=";
+
+/// The configuration of remote imports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpImportConfig {
+    /// The directory storing the downloaded files.
+    pub cache_dir: PathBuf,
+    /// If set, remote files are never downloaded, and must be in the local cache.
+    pub offline: bool,
+}
+
+/// A parsed remote import.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteImport<'a> {
+    /// The URL, without the fragment.
+    pub url: &'a str,
+    /// The expected SHA-256 hash of the content, as a lowercase hexadecimal string.
+    pub sha256: String,
+}
+
+/// Return `true` if `path` is the URL of a remote import.
+pub fn is_remote(path: &OsStr) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with(SCHEME))
+}
+
+impl<'a> RemoteImport<'a> {
+    /// Parse the URL of a remote import, including its hash.
+    pub fn parse(url: &'a str) -> Result<Self, String> {
+        let Some((url, fragment)) = url.split_once('#') else {
+            return Err(format!(
+                "remote imports must specify the hash of their content, as in `{url}#{HASH_PREFIX}<hash>`"
+            ));
+        };
+
+        let sha256 = fragment
+            .strip_prefix(HASH_PREFIX)
+            .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| {
+                format!("invalid hash `{fragment}`: expected `{HASH_PREFIX}` followed by 64 hexadecimal digits")
+            })?;
+
+        Ok(RemoteImport {
+            url,
+            sha256: sha256.to_ascii_lowercase(),
+        })
+    }
+}
+
+impl HttpImportConfig {
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        HttpImportConfig {
+            cache_dir: cache_dir.into(),
+            offline: false,
+        }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        HttpImportConfig { offline, ..self }
+    }
+
+    /// Return the content of a remote import, from the local cache if it's there, or by
+    /// downloading it otherwise. Return the reason of the failure otherwise.
+    pub fn fetch(&self, import: &RemoteImport<'_>) -> Result<String, String> {
+        let cached = self.cached_path(import);
+
+        // A file of the local cache could have been modified or partially written, so its hash
+        // is checked as well.
+        if let Ok(content) = fs::read_to_string(&cached) {
+            if sha256(&content) == import.sha256 {
+                return Ok(content);
+            }
+        }
+
+        if self.offline {
+            return Err(format!(
+                "`{}` isn't in the local cache, and downloads are disabled in offline mode",
+                import.url
+            ));
+        }
+
+        let content = ureq::get(import.url)
+            .call()
+            .map_err(|err| format!("download failed: {err}"))?
+            .into_string()
+            .map_err(|err| format!("download failed: {err}"))?;
+
+        let hash = sha256(&content);
+        if hash != import.sha256 {
+            return Err(format!(
+                "integrity check failed: expected the SHA-256 hash {}, got {hash}",
+                import.sha256
+            ));
+        }
+
+        fs::create_dir_all(&self.cache_dir)
+            .and_then(|()| fs::write(&cached, &content))
+            .map_err(|err| {
+                format!(
+                    "couldn't write to the local cache `{}`: {err}",
+                    self.cache_dir.display()
+                )
+            })?;
+
+        Ok(content)
+    }
+
+    fn cached_path(&self, import: &RemoteImport<'_>) -> PathBuf {
+        Path::new(&self.cache_dir).join(&import.sha256)
+    }
+}
+
+fn sha256(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "6b86b273ff34fce19d6b804eff5a3f5747ada4eaa22f1d49c01e52ddb7875b4b";
+
+    #[test]
+    fn parse() {
+        let url = format!("https://example.com/lib.ncl#sha256={}", HASH.to_uppercase());
+        assert_eq!(
+            RemoteImport::parse(&url),
+            Ok(RemoteImport {
+                url: "https://example.com/lib.ncl",
+                sha256: HASH.to_owned(),
+            })
+        );
+
+        assert!(RemoteImport::parse("https://example.com/lib.ncl").is_err());
+        assert!(RemoteImport::parse("https://example.com/lib.ncl#md5=abc").is_err());
+        assert!(RemoteImport::parse("https://example.com/lib.ncl#sha256=abc").is_err());
+    }
+
+    #[test]
+    fn local_cache() {
+        let dir = std::env::temp_dir().join(format!("nickel-http-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let url = format!("https://example.invalid/one.ncl#sha256={HASH}");
+        let import = RemoteImport::parse(&url).unwrap();
+        let config = HttpImportConfig::new(&dir).offline(true);
+
+        assert!(config.fetch(&import).is_err());
+
+        // The hash of `1`.
+        fs::write(dir.join(HASH), "1").unwrap();
+        assert_eq!(config.fetch(&import), Ok("1".to_owned()));
+
+        // A corrupted file of the local cache is ignored.
+        fs::write(dir.join(HASH), "2").unwrap();
+        assert!(config.fetch(&import).is_err());
+    }
+}
@@ -43,6 +43,12 @@ impl Ident {
     }
 }
 
+/// Return the number of strings interned so far by the current process, together with the
+/// approximate number of bytes they use.
+pub(crate) fn interner_stats() -> (usize, usize) {
+    INTERNER.stats()
+}
+
 impl fmt::Display for Ident {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.label())
@@ -259,6 +265,19 @@ mod interner {
             self.0.write().unwrap().intern(string)
         }
 
+        /// Returns the number of interned strings and the approximate number of bytes they use,
+        /// including the interner's own bookkeeping.
+        pub(crate) fn stats(&self) -> (usize, usize) {
+            let inner = self.0.read().unwrap();
+            let count = inner.vec.len();
+            let content: usize = inner.vec.iter().map(|s| s.len()).sum();
+            // Each string is referenced once in `vec` and once in `map`, together with its symbol.
+            let bookkeeping =
+                count * (2 * std::mem::size_of::<&str>() + std::mem::size_of::<Symbol>());
+
+            (count, content + bookkeeping)
+        }
+
         /// Looks up for the stored string corresponding to the [Symbol].
         ///
         /// This operation cannot fails since the only way to have a [Symbol] is to have
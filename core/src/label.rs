@@ -110,6 +110,26 @@ pub mod ty_path {
         pub span: RawSpan,
         pub last: Option<Elem>,
         pub last_arrow_elem: Option<Elem>,
+        /// The number of elements of the path which have been followed to reach the subtype. It
+        /// can be smaller than the length of the path if the type doesn't match the path (see
+        /// [span]).
+        pub depth: usize,
+    }
+
+    /// Return the position, starting from 1, of the argument designated by the last `Domain`
+    /// element of `path`, if any. The argument position is one plus the number of `Codomain`
+    /// elements directly preceding this `Domain` element: for example, the path of `String` in
+    /// `Number -> String -> Bool` is `[Codomain, Domain]`, which designates the second argument.
+    pub fn arg_position(path: &[Elem]) -> Option<usize> {
+        let domain_index = path.iter().rposition(|elem| matches!(elem, Elem::Domain))?;
+
+        let preceding_codomains = path[..domain_index]
+            .iter()
+            .rev()
+            .take_while(|elem| matches!(elem, Elem::Codomain))
+            .count();
+
+        Some(preceding_codomains + 1)
     }
 
     /// Return the span encoded (as well as additional data: see [PathSpan]) by a type path in the
@@ -125,8 +145,8 @@ pub mod ty_path {
     ///
     /// - Type path: `[Codomain, Domain]`
     /// - Type : `Num -> Num -> Num`
-    /// - Return: `{start: 7, end: 10, last: Some(Domain), last_arrow_elem: Some(Domain)}`. The
-    ///   span `(7, 10)` corresponds to the second `Num` occurrence.
+    /// - Return: `{start: 7, end: 10, last: Some(Domain), last_arrow_elem: Some(Domain), depth:
+    ///   2}`. The span `(7, 10)` corresponds to the second `Num` occurrence.
     ///
     /// # Mismatch between `path` and `ty`
     ///
@@ -158,6 +178,7 @@ pub mod ty_path {
                     Some(PathSpan {
                         last: path_span.last.or(Some(*next)),
                         last_arrow_elem: path_span.last_arrow_elem.or(Some(*next)),
+                        depth: path_span.depth + 1,
                         ..path_span
                     })
                 }
@@ -166,6 +187,7 @@ pub mod ty_path {
                     Some(PathSpan {
                         last: path_span.last.or(Some(*next)),
                         last_arrow_elem: path_span.last_arrow_elem.or(Some(*next)),
+                        depth: path_span.depth + 1,
                         ..path_span
                     })
                 }
@@ -183,6 +205,7 @@ pub mod ty_path {
                             return Some(PathSpan {
                                 last: path_span.last.or_else(|| next.copied()),
                                 last_arrow_elem: path_span.last_arrow_elem,
+                                depth: path_span.depth + 1,
                                 ..path_span
                             });
                         }
@@ -209,6 +232,7 @@ pub mod ty_path {
                 Some(PathSpan {
                     last: path_span.last.or_else(|| next.copied()),
                     last_arrow_elem: path_span.last_arrow_elem,
+                    depth: path_span.depth + 1,
                     ..path_span
                 })
             }
@@ -218,6 +242,7 @@ pub mod ty_path {
                 Some(PathSpan {
                     last: path_span.last.or_else(|| next.copied()),
                     last_arrow_elem: path_span.last_arrow_elem,
+                    depth: path_span.depth + 1,
                     ..path_span
                 })
             }
@@ -226,6 +251,7 @@ pub mod ty_path {
                 span,
                 last: None,
                 last_arrow_elem: None,
+                depth: 0,
             }),
         }
     }
@@ -306,6 +332,10 @@ pub struct Label {
     /// while first transforming a record as part of the pending contract generation.
     /// Contract applications outside of records will have this field set to `None`.
     pub field_name: Option<LocIdent>,
+
+    /// If the value being checked is the value of a secret field, in which case blame errors
+    /// don't show it.
+    pub secret: bool,
 }
 
 /// Data about type variables that is needed for polymorphic contracts to decide which actions to
@@ -533,6 +563,7 @@ impl Default for Label {
             path: Default::default(),
             type_environment: Default::default(),
             field_name: None,
+            secret: false,
         }
     }
 }
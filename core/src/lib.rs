@@ -1,21 +1,42 @@
+#[cfg(feature = "fs-imports")]
+pub mod build_helper;
 #[cfg(feature = "bytecode-experimental")]
 pub mod bytecode;
 pub mod cache;
+pub mod check;
 pub mod closurize;
 pub mod combine;
+pub mod contract;
 pub mod deserialize;
+pub mod diff;
 pub mod environment;
 pub mod error;
 pub mod eval;
 pub mod files;
+pub mod frozen;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+#[cfg(feature = "git-imports")]
+pub mod git_import;
+#[cfg(feature = "http-imports")]
+pub mod http_import;
 pub mod identifier;
 pub mod label;
+pub mod lint;
+pub mod metadata;
+pub mod native;
 #[cfg(feature = "nix-experimental")]
 pub mod nix_ffi;
+#[cfg(feature = "packages")]
+pub mod package;
 pub mod parser;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod policy;
 pub mod position;
 pub mod pretty;
 pub mod program;
+pub mod provenance;
 pub mod repl;
 pub mod serialize;
 pub mod stdlib;
@@ -23,8 +44,13 @@ pub mod term;
 pub mod transform;
 pub mod typ;
 pub mod typecheck;
+pub mod visit;
 
 pub(crate) mod metrics;
 
+#[cfg(feature = "format")]
+pub mod codemod;
+#[cfg(feature = "format")]
+pub mod cst;
 #[cfg(feature = "format")]
 pub mod format;
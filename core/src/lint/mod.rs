@@ -0,0 +1,274 @@
+//! Static analysis of Nickel sources reporting style issues and suspicious constructs.
+//!
+//! A [Linter] runs a set of [Rule]s on the AST of a source, before any transformation or
+//! evaluation. Each rule has a name, such as `magic-number`, and a [LintLevel]: rules at the
+//! [LintLevel::Allow] level aren't run, while the diagnostics of the other rules are reported as
+//! warnings or errors depending on their level. The built-in rules are listed in [rules], and
+//! embedders can register their own.
+//!
+//! The level of the rules and their options are set by a [LintConfig], which is usually read
+//! from a `nickel-lint.toml` file at the root of a project:
+//!
+//! ```toml
+//! max-nesting-depth = 5
+//! allowed-numbers = [0, 1, 2, 100]
+//!
+//! [rules]
+//! missing-doc = "warn"
+//! magic-number = "deny"
+//! naming-convention = "allow"
+//! ```
+use std::{collections::HashMap, fmt};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{Diagnostic, Diagnostics, IntoDiagnostics, Label},
+    files::Files,
+    position::RawSpan,
+    term::RichTerm,
+};
+
+pub mod rules;
+
+/// The name of the configuration file looked up by the `nickel lint` command.
+pub const CONFIG_FILE_NAME: &str = "nickel-lint.toml";
+
+/// The level of a lint rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    /// The rule is disabled.
+    Allow,
+    /// The diagnostics of the rule are reported as warnings.
+    Warn,
+    /// The diagnostics of the rule are reported as errors.
+    Deny,
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintLevel::Allow => write!(f, "allow"),
+            LintLevel::Warn => write!(f, "warn"),
+            LintLevel::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// The configuration of a [Linter].
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LintConfig {
+    /// The level of the rules, overriding their default level.
+    pub rules: HashMap<String, LintLevel>,
+    /// The maximum depth of nested record literals allowed by the `deep-nesting` rule.
+    pub max_nesting_depth: usize,
+    /// The numbers which aren't considered magic by the `magic-number` rule.
+    pub allowed_numbers: Vec<f64>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            rules: HashMap::new(),
+            max_nesting_depth: 8,
+            allowed_numbers: vec![0.0, 1.0, 2.0],
+        }
+    }
+}
+
+impl LintConfig {
+    /// Parse a configuration from the content of a TOML file.
+    pub fn from_toml(source: &str) -> Result<Self, LintConfigError> {
+        toml::from_str(source).map_err(|err| LintConfigError::Parse(err.message().to_owned()))
+    }
+}
+
+/// An error in a lint configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintConfigError {
+    /// The configuration isn't valid TOML, or doesn't have the expected shape.
+    Parse(String),
+    /// The configuration sets the level of a rule which doesn't exist.
+    UnknownRule(String),
+}
+
+impl fmt::Display for LintConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintConfigError::Parse(msg) => write!(f, "invalid lint configuration: {msg}"),
+            LintConfigError::UnknownRule(name) => write!(f, "unknown lint rule `{name}`"),
+        }
+    }
+}
+
+impl std::error::Error for LintConfigError {}
+
+/// A diagnostic emitted by a lint rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintDiagnostic {
+    /// The name of the rule which emitted the diagnostic.
+    pub rule: &'static str,
+    /// The level of the rule. Never [LintLevel::Allow].
+    pub level: LintLevel,
+    pub message: String,
+    pub span: RawSpan,
+    pub notes: Vec<String>,
+}
+
+impl IntoDiagnostics for LintDiagnostic {
+    fn into_diagnostics(self, _files: &Files) -> Diagnostics {
+        let diagnostic = match self.level {
+            LintLevel::Deny => Diagnostic::error(),
+            LintLevel::Allow | LintLevel::Warn => Diagnostic::warning(),
+        };
+
+        vec![diagnostic
+            .with_code(self.rule)
+            .with_message(self.message)
+            .with_labels(vec![Label::primary(
+                self.span.src_id,
+                self.span.start.to_usize()..self.span.end.to_usize(),
+            )])
+            .with_notes(self.notes)]
+        .into()
+    }
+}
+
+/// The context passed to a rule when checking a term, used to access the configuration and to
+/// report diagnostics.
+pub struct LintContext<'a> {
+    config: &'a LintConfig,
+    rule: &'static str,
+    level: LintLevel,
+    diagnostics: &'a mut Vec<LintDiagnostic>,
+}
+
+impl LintContext<'_> {
+    pub fn config(&self) -> &LintConfig {
+        self.config
+    }
+
+    /// Report an issue at `span`.
+    pub fn report(&mut self, span: RawSpan, message: impl Into<String>) {
+        self.report_with_notes(span, message, Vec::new());
+    }
+
+    /// Report an issue at `span`, with additional notes such as a suggestion.
+    pub fn report_with_notes(
+        &mut self,
+        span: RawSpan,
+        message: impl Into<String>,
+        notes: Vec<String>,
+    ) {
+        self.diagnostics.push(LintDiagnostic {
+            rule: self.rule,
+            level: self.level,
+            message: message.into(),
+            span,
+            notes,
+        });
+    }
+}
+
+/// A lint rule.
+pub trait Rule {
+    /// The name of the rule, in kebab case, used in the configuration and in the diagnostics.
+    fn name(&self) -> &'static str;
+
+    /// A short description of what the rule checks.
+    fn description(&self) -> &'static str;
+
+    /// The level of the rule when the configuration doesn't set it.
+    fn default_level(&self) -> LintLevel;
+
+    /// Check the whole AST of a source, reporting issues through `ctx`. `rt` is the term as
+    /// parsed, before any program transformation.
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>);
+}
+
+/// A set of lint rules together with their configuration. See the [module documentation](self).
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    config: LintConfig,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Linter {
+    /// Create a linter with the built-in rules and the default configuration.
+    pub fn new() -> Self {
+        Linter {
+            rules: rules::builtin(),
+            config: LintConfig::default(),
+        }
+    }
+
+    /// Register an additional rule. The configuration should be set after all the rules have
+    /// been registered, so that it can refer to them.
+    pub fn register(&mut self, rule: Box<dyn Rule>) {
+        self.rules.push(rule);
+    }
+
+    /// Set the configuration of the linter, checking that it only refers to registered rules.
+    pub fn configure(&mut self, config: LintConfig) -> Result<(), LintConfigError> {
+        if let Some(name) = config
+            .rules
+            .keys()
+            .find(|name| !self.rules.iter().any(|rule| rule.name() == name.as_str()))
+        {
+            return Err(LintConfigError::UnknownRule(name.clone()));
+        }
+
+        self.config = config;
+        Ok(())
+    }
+
+    /// The registered rules.
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|rule| rule.as_ref())
+    }
+
+    /// The level of a rule, as set by the configuration or by default.
+    pub fn level(&self, rule: &dyn Rule) -> LintLevel {
+        self.config
+            .rules
+            .get(rule.name())
+            .copied()
+            .unwrap_or_else(|| rule.default_level())
+    }
+
+    /// Run all the enabled rules on a term, and return their diagnostics sorted by position.
+    pub fn lint(&self, rt: &RichTerm) -> Vec<LintDiagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for rule in self.rules.iter() {
+            let level = self.level(rule.as_ref());
+
+            if level == LintLevel::Allow {
+                continue;
+            }
+
+            rule.check(
+                rt,
+                &mut LintContext {
+                    config: &self.config,
+                    rule: rule.name(),
+                    level,
+                    diagnostics: &mut diagnostics,
+                },
+            );
+        }
+
+        diagnostics.sort_by_key(|diag| (diag.span.src_id, diag.span.start, diag.rule));
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,534 @@
+//! The built-in lint rules.
+use malachite::{num::conversion::traits::RoundingFrom, rounding_modes::RoundingMode};
+
+use super::{LintContext, LintLevel, Rule};
+use crate::{
+    identifier::{Ident, LocIdent},
+    term::{BinaryOp, RichTerm, Term, Traverse, TraverseControl, UnaryOp},
+};
+
+/// The built-in rules, in the order in which they are run.
+pub fn builtin() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(NamingConvention),
+        Box::new(MissingDoc),
+        Box::new(MagicNumber),
+        Box::new(DeepNesting),
+        Box::new(UnusedBinding),
+        Box::new(ShadowedStdlib),
+        Box::new(ConstantCondition),
+        Box::new(FunctionEquality),
+    ]
+}
+
+/// Let-bound variables and function parameters must be in snake case, such as `max_retries`,
+/// or in Pascal case for contracts, such as `Port`. Record fields aren't checked, as their names
+/// often mirror an external schema.
+pub struct NamingConvention;
+
+impl NamingConvention {
+    fn is_valid(name: &str) -> bool {
+        let name = name.trim_start_matches('_').trim_end_matches('\'');
+        let mut chars = name.chars();
+
+        match chars.next() {
+            // Identifiers made only of underscores, such as `_`, are fine.
+            None => true,
+            Some(c) if c.is_ascii_lowercase() => {
+                chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            }
+            Some(c) if c.is_ascii_uppercase() => chars.all(|c| c.is_ascii_alphanumeric()),
+            Some(_) => false,
+        }
+    }
+
+    fn check_ident(id: &LocIdent, ctx: &mut LintContext<'_>) {
+        let Some(span) = id.pos.into_opt() else {
+            return;
+        };
+
+        if !Self::is_valid(id.label()) {
+            ctx.report_with_notes(
+                span,
+                format!("`{id}` should be in snake case or in Pascal case"),
+                vec![format!(
+                    "Consider renaming it to `{}`",
+                    id.label().replace('-', "_").to_lowercase()
+                )],
+            );
+        }
+    }
+}
+
+impl Rule for NamingConvention {
+    fn name(&self) -> &'static str {
+        "naming-convention"
+    }
+
+    fn description(&self) -> &'static str {
+        "variables should be in snake case, and contracts in Pascal case"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warn
+    }
+
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>) {
+        rt.traverse_ref(
+            &mut |rt: &RichTerm, _: &()| -> TraverseControl<(), ()> {
+                match rt.as_ref() {
+                    Term::Let(bindings, _, _) => {
+                        for (id, _) in bindings.iter() {
+                            Self::check_ident(id, ctx);
+                        }
+                    }
+                    Term::Fun(id, _) => Self::check_ident(id, ctx),
+                    _ => (),
+                }
+
+                TraverseControl::Continue
+            },
+            &(),
+        );
+    }
+}
+
+/// The exported fields of the record at the root of a file must be documented. This rule is
+/// allowed by default, and is meant for libraries and schemas.
+pub struct MissingDoc;
+
+impl Rule for MissingDoc {
+    fn name(&self) -> &'static str {
+        "missing-doc"
+    }
+
+    fn description(&self) -> &'static str {
+        "the exported fields at the root of a file should be documented"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Allow
+    }
+
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>) {
+        let mut root = rt;
+
+        // We look through the let-bindings and the annotations wrapping the root record, as in
+        // `let Schema = ... in { ... } | Schema`.
+        let data = loop {
+            match root.as_ref() {
+                Term::Let(_, body, _) => root = body,
+                Term::Annotated(_, inner) => root = inner,
+                Term::Record(data) | Term::RecRecord(data, ..) => break data,
+                _ => return,
+            }
+        };
+
+        for (id, field) in data.fields.iter() {
+            if field.metadata.not_exported || field.metadata.doc.is_some() {
+                continue;
+            }
+
+            if let Some(span) = id.pos.into_opt() {
+                ctx.report_with_notes(
+                    span,
+                    format!("field `{id}` has no documentation"),
+                    vec![format!(
+                        "Document it with `{id} | doc \"...\"`, or mark it `not_exported` if it's \
+                        an implementation detail"
+                    )],
+                );
+            }
+        }
+    }
+}
+
+/// Number literals used in arithmetic operations and comparisons should be given a name, except
+/// for the numbers set in the `allowed-numbers` option.
+pub struct MagicNumber;
+
+impl Rule for MagicNumber {
+    fn name(&self) -> &'static str {
+        "magic-number"
+    }
+
+    fn description(&self) -> &'static str {
+        "number literals in arithmetic operations and comparisons should be named"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warn
+    }
+
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>) {
+        rt.traverse_ref(
+            &mut |rt: &RichTerm, _: &()| -> TraverseControl<(), ()> {
+                let Term::Op2(op, t1, t2) = rt.as_ref() else {
+                    return TraverseControl::Continue;
+                };
+
+                if !matches!(
+                    op,
+                    BinaryOp::Plus
+                        | BinaryOp::Sub
+                        | BinaryOp::Mult
+                        | BinaryOp::Div
+                        | BinaryOp::Modulo
+                        | BinaryOp::Pow
+                        | BinaryOp::Eq
+                        | BinaryOp::LessThan
+                        | BinaryOp::LessOrEq
+                        | BinaryOp::GreaterThan
+                        | BinaryOp::GreaterOrEq
+                ) {
+                    return TraverseControl::Continue;
+                }
+
+                for operand in [t1, t2] {
+                    let (Term::Num(n), Some(span)) = (operand.as_ref(), operand.pos.into_opt())
+                    else {
+                        continue;
+                    };
+
+                    let value = f64::rounding_from(n, RoundingMode::Nearest).0;
+
+                    if !ctx.config().allowed_numbers.contains(&value) {
+                        ctx.report_with_notes(
+                            span,
+                            format!("magic number `{value}`"),
+                            vec!["Consider binding it to a descriptive name with `let`".to_owned()],
+                        );
+                    }
+                }
+
+                TraverseControl::Continue
+            },
+            &(),
+        );
+    }
+}
+
+/// Record literals shouldn't be nested deeper than the `max-nesting-depth` option. Only the
+/// outermost record exceeding the limit is reported.
+pub struct DeepNesting;
+
+impl Rule for DeepNesting {
+    fn name(&self) -> &'static str {
+        "deep-nesting"
+    }
+
+    fn description(&self) -> &'static str {
+        "record literals shouldn't be nested too deeply"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warn
+    }
+
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>) {
+        let max_depth = ctx.config().max_nesting_depth;
+
+        rt.traverse_ref(
+            &mut |rt: &RichTerm, depth: &usize| -> TraverseControl<usize, ()> {
+                if !matches!(rt.as_ref(), Term::Record(_) | Term::RecRecord(..)) {
+                    return TraverseControl::Continue;
+                }
+
+                let depth = depth + 1;
+
+                match rt.pos.into_opt() {
+                    Some(span) if depth > max_depth => {
+                        ctx.report_with_notes(
+                            span,
+                            format!("record nested {depth} levels deep"),
+                            vec![format!(
+                                "The maximum nesting depth is {max_depth}. Consider splitting this \
+                                record into separate definitions"
+                            )],
+                        );
+                        TraverseControl::SkipBranch
+                    }
+                    _ => TraverseControl::ContinueWithScope(depth),
+                }
+            },
+            &0,
+        );
+    }
+}
+
+/// Return `true` if the variable `id` occurs in `rt`. Shadowing isn't taken into account, so that
+/// a variable is considered used if another variable with the same name is.
+fn uses_var(rt: &RichTerm, id: Ident) -> bool {
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| match rt.as_ref() {
+            Term::Var(var) if var.ident() == id => TraverseControl::Return(()),
+            _ => TraverseControl::Continue,
+        },
+        &(),
+    )
+    .is_some()
+}
+
+/// Let-bound variables and function parameters must be used. Variables starting with an
+/// underscore, such as `_unused`, are ignored.
+pub struct UnusedBinding;
+
+impl UnusedBinding {
+    fn check_ident<'a>(
+        id: &LocIdent,
+        mut scope: impl Iterator<Item = &'a RichTerm>,
+        ctx: &mut LintContext<'_>,
+    ) {
+        let Some(span) = id.pos.into_opt() else {
+            return;
+        };
+
+        if id.label().starts_with('_') || scope.any(|rt| uses_var(rt, id.ident())) {
+            return;
+        }
+
+        ctx.report_with_notes(
+            span,
+            format!("`{id}` is never used"),
+            vec![format!(
+                "If this is intentional, prefix it with an underscore: `_{id}`"
+            )],
+        );
+    }
+}
+
+impl Rule for UnusedBinding {
+    fn name(&self) -> &'static str {
+        "unused-binding"
+    }
+
+    fn description(&self) -> &'static str {
+        "let-bound variables and function parameters should be used"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warn
+    }
+
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>) {
+        rt.traverse_ref(
+            &mut |rt: &RichTerm, _: &()| -> TraverseControl<(), ()> {
+                match rt.as_ref() {
+                    Term::Let(bindings, body, attrs) => {
+                        for (id, _) in bindings.iter() {
+                            // The bound values of a recursive let are in the scope of the
+                            // bindings.
+                            let values = bindings
+                                .iter()
+                                .map(|(_, value)| value)
+                                .filter(|_| attrs.rec);
+                            Self::check_ident(id, std::iter::once(body).chain(values), ctx);
+                        }
+                    }
+                    Term::Fun(id, body) => Self::check_ident(id, std::iter::once(body), ctx),
+                    _ => (),
+                }
+
+                TraverseControl::Continue
+            },
+            &(),
+        );
+    }
+}
+
+/// Variables, function parameters and fields of recursive records shouldn't be named `std`, which
+/// would hide the standard library in their scope.
+pub struct ShadowedStdlib;
+
+impl ShadowedStdlib {
+    fn check_ident(id: &LocIdent, ctx: &mut LintContext<'_>) {
+        let Some(span) = id.pos.into_opt() else {
+            return;
+        };
+
+        if id.label() == "std" {
+            ctx.report_with_notes(
+                span,
+                "`std` shadows the standard library",
+                vec![
+                    "The standard library isn't accessible where this name is in scope. \
+                    Consider choosing another name"
+                        .to_owned(),
+                ],
+            );
+        }
+    }
+}
+
+impl Rule for ShadowedStdlib {
+    fn name(&self) -> &'static str {
+        "shadowed-stdlib"
+    }
+
+    fn description(&self) -> &'static str {
+        "bindings shouldn't shadow the standard library"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warn
+    }
+
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>) {
+        rt.traverse_ref(
+            &mut |rt: &RichTerm, _: &()| -> TraverseControl<(), ()> {
+                match rt.as_ref() {
+                    Term::Let(bindings, _, _) => {
+                        for (id, _) in bindings.iter() {
+                            Self::check_ident(id, ctx);
+                        }
+                    }
+                    Term::Fun(id, _) => Self::check_ident(id, ctx),
+                    Term::RecRecord(data, ..) => {
+                        for id in data.fields.keys() {
+                            Self::check_ident(id, ctx);
+                        }
+                    }
+                    _ => (),
+                }
+
+                TraverseControl::Continue
+            },
+            &(),
+        );
+    }
+}
+
+/// The condition of an `if` shouldn't be a boolean literal, possibly negated, which makes one of
+/// the branches dead code.
+pub struct ConstantCondition;
+
+impl ConstantCondition {
+    fn constant_value(rt: &RichTerm) -> Option<bool> {
+        match rt.as_ref() {
+            Term::Bool(b) => Some(*b),
+            Term::Op1(UnaryOp::BoolNot, t) => Self::constant_value(t).map(|b| !b),
+            _ => None,
+        }
+    }
+}
+
+impl Rule for ConstantCondition {
+    fn name(&self) -> &'static str {
+        "constant-condition"
+    }
+
+    fn description(&self) -> &'static str {
+        "conditions shouldn't be constant"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warn
+    }
+
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>) {
+        rt.traverse_ref(
+            &mut |rt: &RichTerm, _: &()| -> TraverseControl<(), ()> {
+                if let Term::Op1(UnaryOp::IfThenElse, cond) = rt.as_ref() {
+                    if let (Some(value), Some(span)) =
+                        (Self::constant_value(cond), cond.pos.into_opt())
+                    {
+                        let dead_branch = if value { "else" } else { "then" };
+
+                        ctx.report_with_notes(
+                            span,
+                            format!("this condition is always `{value}`"),
+                            vec![format!("The `{dead_branch}` branch is never evaluated")],
+                        );
+                    }
+                }
+
+                TraverseControl::Continue
+            },
+            &(),
+        );
+    }
+}
+
+/// Functions shouldn't be compared with `==` or `!=`, which always fails at runtime. A value is
+/// considered a function if it's a function literal, or a variable bound to a function literal by
+/// an enclosing `let`.
+pub struct FunctionEquality;
+
+impl FunctionEquality {
+    fn is_function(rt: &RichTerm, functions: &[Ident]) -> bool {
+        match rt.as_ref() {
+            Term::Fun(..) | Term::FunPattern(..) | Term::Match(_) => true,
+            Term::Annotated(_, inner) => Self::is_function(inner, functions),
+            Term::Var(id) => functions.contains(&id.ident()),
+            _ => false,
+        }
+    }
+}
+
+impl Rule for FunctionEquality {
+    fn name(&self) -> &'static str {
+        "function-equality"
+    }
+
+    fn description(&self) -> &'static str {
+        "functions can't be compared for equality"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Deny
+    }
+
+    fn check(&self, rt: &RichTerm, ctx: &mut LintContext<'_>) {
+        rt.traverse_ref(
+            &mut |rt: &RichTerm, functions: &Vec<Ident>| -> TraverseControl<Vec<Ident>, ()> {
+                match rt.as_ref() {
+                    Term::Op2(BinaryOp::Eq, t1, t2) => {
+                        let function = [t1, t2]
+                            .into_iter()
+                            .find(|t| Self::is_function(t, functions));
+                        let span =
+                            function.and_then(|t| rt.pos.into_opt().or_else(|| t.pos.into_opt()));
+
+                        if let Some(span) = span {
+                            ctx.report_with_notes(
+                                span,
+                                "comparing a function for equality always fails",
+                                vec!["Functions can't be compared: evaluating this expression \
+                                    raises an error"
+                                    .to_owned()],
+                            );
+                        }
+
+                        TraverseControl::Continue
+                    }
+                    // The variables bound by a `let` shadow the ones of the enclosing scope. For
+                    // simplicity, they're considered in scope in the bound values as well.
+                    Term::Let(bindings, ..) => {
+                        let mut functions = functions.clone();
+
+                        for (id, value) in bindings.iter() {
+                            functions.retain(|f| *f != id.ident());
+
+                            if Self::is_function(value, &[]) {
+                                functions.push(id.ident());
+                            }
+                        }
+
+                        TraverseControl::ContinueWithScope(functions)
+                    }
+                    Term::Fun(id, _) if functions.contains(&id.ident()) => {
+                        TraverseControl::ContinueWithScope(
+                            functions
+                                .iter()
+                                .copied()
+                                .filter(|f| *f != id.ident())
+                                .collect(),
+                        )
+                    }
+                    _ => TraverseControl::Continue,
+                }
+            },
+            &Vec::new(),
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use super::*;
+use crate::program::tests::program;
+
+/// Lint a source and return the rule and the text of the span of each diagnostic.
+fn lint(source: &str, linter: &Linter) -> Vec<(&'static str, LintLevel, String)> {
+    program(source)
+        .lint(linter)
+        .unwrap()
+        .into_iter()
+        .map(|diag| {
+            let text = &source[diag.span.start.to_usize()..diag.span.end.to_usize()];
+            (diag.rule, diag.level, text.to_owned())
+        })
+        .collect()
+}
+
+#[test]
+fn builtin_rules() {
+    let source = r#"
+let maxRetries = 3 in
+let Port = std.number.Nat in
+let scale = fun some-factor => some-factor * 1024 in
+{
+  port | Port = 8080,
+  retries = maxRetries + 1,
+  nested = { a = { b = { c = 1 } } },
+}
+"#;
+
+    let mut linter = Linter::new();
+    linter
+        .configure(LintConfig {
+            max_nesting_depth: 3,
+            ..Default::default()
+        })
+        .unwrap();
+
+    assert_eq!(
+        lint(source, &linter),
+        [
+            (
+                "naming-convention",
+                LintLevel::Warn,
+                "maxRetries".to_owned()
+            ),
+            ("unused-binding", LintLevel::Warn, "scale".to_owned()),
+            (
+                "naming-convention",
+                LintLevel::Warn,
+                "some-factor".to_owned()
+            ),
+            ("magic-number", LintLevel::Warn, "1024".to_owned()),
+            ("deep-nesting", LintLevel::Warn, "{ c = 1 }".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn suspicious_code() {
+    let source = r#"
+let unused = 1 in
+let _ignored = 2 in
+let rec fact = fun n => if n == 0 then 1 else n * fact (n - 1) in
+let id = fun x => x in
+let std = { foo = 1 } in
+{
+  a = if true then fact 3 else 0,
+  b = id == id,
+  c = (fun x => x) != 1,
+  d = std.foo,
+}
+"#;
+
+    let mut linter = Linter::new();
+    linter
+        .configure(LintConfig {
+            rules: HashMap::from([("magic-number".to_owned(), LintLevel::Allow)]),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let diagnostics: Vec<_> = lint(source, &linter)
+        .into_iter()
+        .map(|(rule, level, _)| (rule, level))
+        .collect();
+
+    assert_eq!(
+        diagnostics,
+        [
+            ("unused-binding", LintLevel::Warn),
+            ("shadowed-stdlib", LintLevel::Warn),
+            ("constant-condition", LintLevel::Warn),
+            ("function-equality", LintLevel::Deny),
+            ("function-equality", LintLevel::Deny),
+        ]
+    );
+}
+
+#[test]
+fn configuration() {
+    let config = LintConfig::from_toml(
+        r#"
+allowed-numbers = [0, 1, 1024]
+
+[rules]
+missing-doc = "deny"
+naming-convention = "allow"
+"#,
+    )
+    .unwrap();
+
+    let mut linter = Linter::new();
+    linter.configure(config).unwrap();
+
+    assert_eq!(
+        lint(
+            r#"{ Undocumented = 1024 * 3, documented | doc "Fine" = 0, hidden | not_exported = 1 }"#,
+            &linter
+        ),
+        [
+            ("missing-doc", LintLevel::Deny, "Undocumented".to_owned()),
+            ("magic-number", LintLevel::Warn, "3".to_owned()),
+        ]
+    );
+
+    assert_eq!(
+        Linter::new()
+            .configure(LintConfig::from_toml("[rules]\nno-such-rule = \"warn\"").unwrap())
+            .unwrap_err(),
+        LintConfigError::UnknownRule("no-such-rule".into())
+    );
+    assert!(matches!(
+        LintConfig::from_toml("[rules]\nmagic-number = \"forbid\""),
+        Err(LintConfigError::Parse(_))
+    ));
+}
@@ -0,0 +1,239 @@
+//! Metadata of the fields of a whole configuration.
+//!
+//! [MetadataTree] gathers, for every field path of a record, the documentation, the type and
+//! contract annotations, the default value and the optionality of the field, as a tree mirroring
+//! the structure of the record. It's the common ground of the tools which browse a configuration
+//! schema: the documentation generator, the hover of the language server or external schema
+//! browsers.
+//!
+//! Building the tree never forces the value of a field. It can be built from a parsed or
+//! typechecked term, where nested records are record literals, or from an evaluated term (see
+//! [Program::metadata](crate::program::Program::metadata)), where the values of fields are thunks.
+//! The fields of a nested record are only known if the record is already available without
+//! further evaluation: a record literal, or a thunk which has already been evaluated to a record.
+//! Otherwise, the corresponding [FieldInfo::fields] is `None`.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    identifier::LocIdent,
+    pretty::PrettyPrintCap,
+    term::{
+        record::{Field, RecordData},
+        MergePriority, RichTerm, Term,
+    },
+};
+
+/// The maximal width of the rendering of a default value.
+const DEFAULT_VALUE_MAX_WIDTH: usize = 80;
+
+/// The metadata of the fields of a record, indexed by field name.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct MetadataTree {
+    pub fields: BTreeMap<String, FieldInfo>,
+}
+
+/// The metadata of a field.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FieldInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    /// The type annotation, as written by the user.
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+    /// The contract annotations, as written by the user.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub contracts: Vec<String>,
+    /// The rendering of the value of the field, if it has the default priority.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    pub optional: bool,
+    pub not_exported: bool,
+    pub priority: MergePriority,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    /// The metadata of the subfields, if the value of the field is a record which is available
+    /// without evaluation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<MetadataTree>,
+}
+
+impl MetadataTree {
+    /// Build the metadata tree of a record, or return `None` if `rt` isn't a record. Let-bindings
+    /// and annotations around the record are looked through.
+    pub fn from_term(rt: &RichTerm) -> Option<Self> {
+        match rt.as_ref() {
+            Term::Record(record) | Term::RecRecord(record, ..) => Some(Self::from_record(record)),
+            Term::Let(_, body, _) | Term::LetPattern(_, body, _) | Term::Annotated(_, body) => {
+                Self::from_term(body)
+            }
+            Term::Closure(thunk) => {
+                // The thunk is locked while we visit it, so that we don't loop on recursive
+                // values. A locked thunk is an ancestor of the current field.
+                if !thunk.lock() {
+                    return None;
+                }
+
+                let body = thunk.borrow().body.clone();
+                let result = Self::from_term(&body);
+                thunk.unlock();
+                result
+            }
+            _ => None,
+        }
+    }
+
+    fn from_record(record: &RecordData) -> Self {
+        let fields = record
+            .fields
+            .iter()
+            .map(|(id, field)| (id.label().to_owned(), FieldInfo::from_field(field)))
+            .collect();
+
+        MetadataTree { fields }
+    }
+
+    /// Return the metadata of the field at `path`, if any.
+    pub fn get(&self, path: &[LocIdent]) -> Option<&FieldInfo> {
+        let (first, rest) = path.split_first()?;
+        let info = self.fields.get(first.label())?;
+
+        if rest.is_empty() {
+            Some(info)
+        } else {
+            info.fields.as_ref()?.get(rest)
+        }
+    }
+
+    /// Return every field of the tree with its full path, in depth-first order, parents before
+    /// their subfields.
+    pub fn paths(&self) -> Vec<(Vec<&str>, &FieldInfo)> {
+        let mut result = Vec::new();
+        self.collect_paths(&mut Vec::new(), &mut result);
+        result
+    }
+
+    fn collect_paths<'a>(
+        &'a self,
+        prefix: &mut Vec<&'a str>,
+        result: &mut Vec<(Vec<&'a str>, &'a FieldInfo)>,
+    ) {
+        for (name, info) in &self.fields {
+            prefix.push(name);
+            result.push((prefix.clone(), info));
+
+            if let Some(fields) = &info.fields {
+                fields.collect_paths(prefix, result);
+            }
+
+            prefix.pop();
+        }
+    }
+}
+
+impl FieldInfo {
+    fn from_field(field: &Field) -> Self {
+        let metadata = &field.metadata;
+
+        let default = field
+            .value
+            .as_ref()
+            .filter(|_| metadata.priority == MergePriority::Bottom)
+            .map(|value| peek(value).pretty_print_cap(DEFAULT_VALUE_MAX_WIDTH));
+
+        FieldInfo {
+            doc: metadata.doc.clone(),
+            // We use the original user-written types stored in the labels, as the types of the
+            // annotations may have been transformed by program transformations.
+            typ: metadata
+                .annotation
+                .typ
+                .as_ref()
+                .map(|typ| typ.label.typ.to_string()),
+            contracts: metadata
+                .annotation
+                .contracts
+                .iter()
+                .map(|ctr| ctr.label.typ.to_string())
+                .collect(),
+            default,
+            optional: metadata.opt,
+            not_exported: metadata.not_exported,
+            priority: metadata.priority.clone(),
+            deprecated: metadata.deprecated.clone(),
+            fields: field.value.as_ref().and_then(MetadataTree::from_term),
+        }
+    }
+}
+
+/// Return the term stored in a thunk, without evaluating it, or the term itself if it isn't a
+/// thunk.
+fn peek(rt: &RichTerm) -> RichTerm {
+    match rt.as_ref() {
+        Term::Closure(thunk) => thunk.borrow().body.clone(),
+        _ => rt.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{program::tests::program, typecheck::TypecheckMode};
+
+    #[test]
+    fn unforced_values() {
+        let mut program = program(
+            r#"
+            let Port = std.number.Nat in
+            {
+              server | doc "The server" = {
+                port | Port | default = 80,
+                host | String | optional,
+              },
+              crash = std.fail_with "not forced",
+            }
+            "#,
+        );
+        let tree = program.metadata().unwrap();
+
+        let paths: Vec<_> = tree
+            .paths()
+            .into_iter()
+            .map(|(path, _)| path.join("."))
+            .collect();
+        assert_eq!(paths, ["crash", "server", "server.host", "server.port"]);
+
+        let server = tree.get(&[LocIdent::from("server")]).unwrap();
+        assert_eq!(server.doc.as_deref(), Some("The server"));
+
+        let port = tree
+            .get(&[LocIdent::from("server"), LocIdent::from("port")])
+            .unwrap();
+        assert_eq!(port.contracts, ["Port"]);
+        assert_eq!(port.default.as_deref(), Some("80"));
+        assert!(port.fields.is_none());
+
+        let host = server.fields.as_ref().unwrap().fields.get("host").unwrap();
+        assert_eq!(host.contracts, ["String"]);
+        assert!(host.optional);
+        assert!(host.default.is_none());
+    }
+
+    #[test]
+    fn typechecked_term() {
+        let mut program = program("{ a | doc \"A\" = { b | Number = 1 } }");
+        program.typecheck(TypecheckMode::Walk).unwrap();
+
+        let rt = program.parse().unwrap();
+        let tree = MetadataTree::from_term(&rt).unwrap();
+
+        assert_eq!(
+            tree.get(&[LocIdent::from("a"), LocIdent::from("b")])
+                .unwrap()
+                .contracts,
+            ["Number"]
+        );
+    }
+}
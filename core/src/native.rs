@@ -0,0 +1,377 @@
+//! Native functions, implemented in Rust by the embedder and callable from Nickel.
+//!
+//! A native function is registered on a program with
+//! [Program::register_fn](crate::program::Program::register_fn), or directly on the cache with
+//! [Cache::register_native](crate::cache::Cache::register_native). Registered functions are
+//! exposed to Nickel code as the fields of the `native` record of the initial environment:
+//!
+//! ```nickel
+//! { password = native.resolve_secret "db" }
+//! ```
+//!
+//! Native functions take one argument. The argument is evaluated to a weak head normal form and
+//! converted to the Rust type of the parameter through [FromNickel], and the return value is
+//! converted back through [IntoNickel]. Both traits also provide the Nickel type of the function,
+//! which is used to typecheck calls in statically typed code. Argument conversion failures and
+//! errors returned by the function are reported as evaluation errors, located at the argument of
+//! the call.
+use std::{fmt, rc::Rc};
+
+use malachite::{num::conversion::traits::RoundingFrom, rounding_modes::RoundingMode};
+
+use crate::{
+    identifier::{Ident, LocIdent},
+    policy::Capability,
+    term::{string::NickelString, Number, RichTerm, Term, UnaryOp},
+    typ::{Type, TypeF},
+};
+
+/// The name of the record containing the native functions in the initial environment.
+pub const NATIVE_RECORD: &str = "native";
+
+/// Conversion from an evaluated Nickel term to the argument of a native function.
+pub trait FromNickel: Sized {
+    /// The Nickel type corresponding to this Rust type.
+    fn nickel_type() -> Type;
+
+    /// Convert a term in weak head normal form, or return `None` if it doesn't have the expected
+    /// shape.
+    fn from_nickel(term: &RichTerm) -> Option<Self>;
+}
+
+/// Conversion from the result of a native function to a Nickel term.
+pub trait IntoNickel {
+    /// The Nickel type corresponding to this Rust type.
+    fn nickel_type() -> Type;
+
+    /// Convert the value to a Nickel term, or return an error message if the value can't be
+    /// represented in Nickel.
+    fn into_nickel(self) -> Result<RichTerm, String>;
+}
+
+/// The return type of a native function: either a value convertible to Nickel, or a `Result` of
+/// such a value, where the error is reported as an evaluation error.
+pub trait NativeResult {
+    fn nickel_type() -> Type;
+
+    fn into_result(self) -> Result<RichTerm, String>;
+}
+
+impl<T: IntoNickel> NativeResult for T {
+    fn nickel_type() -> Type {
+        T::nickel_type()
+    }
+
+    fn into_result(self) -> Result<RichTerm, String> {
+        self.into_nickel()
+    }
+}
+
+impl<T: IntoNickel, E: fmt::Display> NativeResult for Result<T, E> {
+    fn nickel_type() -> Type {
+        T::nickel_type()
+    }
+
+    fn into_result(self) -> Result<RichTerm, String> {
+        self.map_err(|err| err.to_string())
+            .and_then(IntoNickel::into_nickel)
+    }
+}
+
+impl FromNickel for String {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::String)
+    }
+
+    fn from_nickel(term: &RichTerm) -> Option<Self> {
+        match term.as_ref() {
+            Term::Str(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl FromNickel for bool {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Bool)
+    }
+
+    fn from_nickel(term: &RichTerm) -> Option<Self> {
+        match term.as_ref() {
+            Term::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromNickel for Number {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Number)
+    }
+
+    fn from_nickel(term: &RichTerm) -> Option<Self> {
+        match term.as_ref() {
+            Term::Num(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromNickel for f64 {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Number)
+    }
+
+    fn from_nickel(term: &RichTerm) -> Option<Self> {
+        match term.as_ref() {
+            Term::Num(n) => Some(f64::rounding_from(n, RoundingMode::Nearest).0),
+            _ => None,
+        }
+    }
+}
+
+impl FromNickel for i64 {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Number)
+    }
+
+    fn from_nickel(term: &RichTerm) -> Option<Self> {
+        match term.as_ref() {
+            Term::Num(n) => i64::try_from(n).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Pass the argument as is, without any conversion. The term is only evaluated to a weak head
+/// normal form: the content of arrays and records might still be unevaluated.
+impl FromNickel for RichTerm {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Dyn)
+    }
+
+    fn from_nickel(term: &RichTerm) -> Option<Self> {
+        Some(term.clone())
+    }
+}
+
+impl IntoNickel for String {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::String)
+    }
+
+    fn into_nickel(self) -> Result<RichTerm, String> {
+        Ok(Term::Str(NickelString::from(self)).into())
+    }
+}
+
+impl IntoNickel for &str {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::String)
+    }
+
+    fn into_nickel(self) -> Result<RichTerm, String> {
+        Ok(Term::Str(NickelString::from(self)).into())
+    }
+}
+
+impl IntoNickel for bool {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Bool)
+    }
+
+    fn into_nickel(self) -> Result<RichTerm, String> {
+        Ok(Term::Bool(self).into())
+    }
+}
+
+impl IntoNickel for Number {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Number)
+    }
+
+    fn into_nickel(self) -> Result<RichTerm, String> {
+        Ok(Term::Num(self).into())
+    }
+}
+
+impl IntoNickel for i64 {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Number)
+    }
+
+    fn into_nickel(self) -> Result<RichTerm, String> {
+        Ok(Term::Num(Number::from(self)).into())
+    }
+}
+
+impl IntoNickel for f64 {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Number)
+    }
+
+    fn into_nickel(self) -> Result<RichTerm, String> {
+        Number::try_from_float_simplest(self)
+            .map(|n| Term::Num(n).into())
+            .map_err(|_| {
+                format!(
+                    "couldn't convert {self} to a Nickel number: \
+                    Nickel doesn't support NaN nor infinity"
+                )
+            })
+    }
+}
+
+/// `None` is converted to `null`.
+impl<T: IntoNickel> IntoNickel for Option<T> {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Dyn)
+    }
+
+    fn into_nickel(self) -> Result<RichTerm, String> {
+        match self {
+            Some(value) => value.into_nickel(),
+            None => Ok(Term::Null.into()),
+        }
+    }
+}
+
+/// The result is inserted as is in the evaluated program. It must not have free variables.
+impl IntoNickel for RichTerm {
+    fn nickel_type() -> Type {
+        Type::from(TypeF::Dyn)
+    }
+
+    fn into_nickel(self) -> Result<RichTerm, String> {
+        Ok(self)
+    }
+}
+
+/// The error of a native function call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NativeCallError {
+    /// The argument couldn't be converted to the expected Rust type.
+    ArgumentType,
+    /// The function returned an error, or its result couldn't be converted to a Nickel term.
+    Failed(String),
+}
+
+type NativeFn = dyn Fn(&RichTerm) -> Result<RichTerm, NativeCallError>;
+
+/// A native function together with its name and its Nickel type.
+#[derive(Clone)]
+pub struct NativeFunction {
+    name: Ident,
+    domain: Type,
+    codomain: Type,
+    fun: Rc<NativeFn>,
+    deterministic: bool,
+    capabilities: Vec<Capability>,
+}
+
+impl NativeFunction {
+    pub fn new<A, R, F>(name: impl AsRef<str>, fun: F) -> Self
+    where
+        A: FromNickel,
+        R: NativeResult,
+        F: Fn(A) -> R + 'static,
+    {
+        NativeFunction {
+            name: Ident::new(name),
+            domain: A::nickel_type(),
+            codomain: R::nickel_type(),
+            fun: Rc::new(move |arg| {
+                let arg = A::from_nickel(arg).ok_or(NativeCallError::ArgumentType)?;
+                fun(arg).into_result().map_err(NativeCallError::Failed)
+            }),
+            deterministic: false,
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Declare that the function always returns the same result for the same argument, on any
+    /// machine. Only deterministic native functions can be called in reproducible mode (see
+    /// [Program::set_reproducible](crate::program::Program::set_reproducible)).
+    pub fn deterministic(self) -> Self {
+        NativeFunction {
+            deterministic: true,
+            ..self
+        }
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Declare that the function needs a capability, such as [Capability::Env] for a function
+    /// reading environment variables. The function can only be called if the capability policy
+    /// of the program grants it (see [crate::policy]).
+    pub fn requires(mut self, capability: Capability) -> Self {
+        if !self.capabilities.contains(&capability) {
+            self.capabilities.push(capability);
+        }
+
+        self
+    }
+
+    /// The capabilities declared with [Self::requires].
+    pub fn required_capabilities(&self) -> &[Capability] {
+        &self.capabilities
+    }
+
+    /// The name of the function, which is also its field name in the `native` record.
+    pub fn name(&self) -> Ident {
+        self.name
+    }
+
+    /// The type of the argument of the function.
+    pub fn domain(&self) -> &Type {
+        &self.domain
+    }
+
+    /// The type of the result of the function.
+    pub fn codomain(&self) -> &Type {
+        &self.codomain
+    }
+
+    /// The Nickel type of the function.
+    pub fn typ(&self) -> Type {
+        Type::from(TypeF::Arrow(
+            Box::new(self.domain.clone()),
+            Box::new(self.codomain.clone()),
+        ))
+    }
+
+    /// Call the function on an argument in weak head normal form.
+    pub fn call(&self, arg: &RichTerm) -> Result<RichTerm, NativeCallError> {
+        (self.fun)(arg)
+    }
+
+    /// Build the Nickel function `fun x => %native_call% x` wrapping this native function.
+    pub fn to_term(&self) -> RichTerm {
+        let param = LocIdent::fresh();
+
+        Term::Fun(
+            param,
+            Term::Op1(
+                UnaryOp::NativeCall(Rc::new(self.clone())),
+                Term::Var(param).into(),
+            )
+            .into(),
+        )
+        .into()
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native function {}>", self.name)
+    }
+}
+
+// Native functions are compared by identity, as is the case for closures in Rust.
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Rc::ptr_eq(&self.fun, &other.fun)
+    }
+}
@@ -0,0 +1,741 @@
+//! Packages: versioned Nickel libraries, with their dependencies.
+//!
+//! A package is a directory with a manifest, `Nickel-pkg.toml`, giving its name, its version and
+//! its dependencies:
+//!
+//! ```toml
+//! name = "app"
+//! version = "0.1.0"
+//!
+//! [dependencies]
+//! contracts = { git = "https://example.com/contracts.git", version = "^1.2" }
+//! utils = { path = "../utils" }
+//! ```
+//!
+//! A git dependency is fetched at the highest tag matching the version requirement, where tags
+//! are versions optionally prefixed with `v`, or at the branch, tag or commit given by `ref`, or
+//! at `HEAD` otherwise. A path dependency is a package in a local directory, relative to the
+//! package depending on it. The dependencies of the dependencies are resolved as well. Package
+//! names are global: each name is resolved to exactly one package, and two packages requiring
+//! incompatible versions of the same dependency are a conflict.
+//!
+//! The [Resolver] records the commit and the version of each dependency in a [Lockfile],
+//! `Nickel-pkg.lock`, so that the resolution is reproducible. Once resolved, the root directory of
+//! each package is given to the cache (see [crate::program::Program::set_packages]), and a package
+//! is imported with the `pkg:` prefix followed by its name, and optionally the path of a file in
+//! the package (`main.ncl` by default):
+//!
+//! ```text
+//! let contracts = import "pkg:contracts" in
+//! let strings = import "pkg:utils/strings.ncl" in
+//! ```
+//!
+//! Git packages are cloned into the local cache directory of [crate::git_import]. They can also
+//! be copied into a vendor directory (see [Resolution::vendor]), to be used without network access.
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+pub use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::git_import;
+
+/// The name of the manifest of a package, in its root directory.
+pub const MANIFEST_NAME: &str = "Nickel-pkg.toml";
+
+/// The name of the lockfile of a package, in its root directory.
+pub const LOCKFILE_NAME: &str = "Nickel-pkg.lock";
+
+/// The name of the directory of vendored dependencies, in the root directory of a package.
+pub const VENDOR_DIR: &str = "vendor";
+
+/// The prefix of package imports.
+pub const PREFIX: &str = "pkg:";
+
+/// The file imported when a package import doesn't specify one.
+pub const DEFAULT_ENTRY: &str = "main.ncl";
+
+/// The manifest of a package.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub name: String,
+    pub version: Version,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+/// A dependency, as declared in a manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    Git {
+        /// The URL of the repository.
+        git: String,
+        /// The versions accepted, matched against the tags of the repository.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<VersionReq>,
+        /// The branch, tag or commit, used when there is no version requirement.
+        #[serde(default, rename = "ref", skip_serializing_if = "Option::is_none")]
+        reference: Option<String>,
+    },
+    Path {
+        /// The directory of the package, relative to the package depending on it.
+        path: PathBuf,
+    },
+}
+
+/// Where a resolved package comes from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Source {
+    Git {
+        git: String,
+        commit: String,
+    },
+    /// The path is relative to the root package.
+    Path {
+        path: PathBuf,
+    },
+}
+
+/// A resolved package, as recorded in a lockfile.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub source: Source,
+    pub version: Version,
+    /// The names of the dependencies of the package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+}
+
+/// The packages resolved for the dependencies of a root package, indexed by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub packages: BTreeMap<String, LockedPackage>,
+}
+
+/// An error while reading a manifest or resolving dependencies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PackageError {
+    /// A manifest or a lockfile couldn't be read or written, or is invalid.
+    File { path: PathBuf, msg: String },
+    /// A package couldn't be fetched.
+    Fetch { name: String, msg: String },
+    /// No version of a package matches the version requirement.
+    NoMatchingVersion { name: String, req: VersionReq },
+    /// Two packages depend on incompatible versions of the same package.
+    Conflict { name: String },
+    /// A dependency isn't in the lockfile, and the resolution is frozen.
+    NotLocked { name: String },
+    /// The name of a package doesn't match the name of the dependency.
+    NameMismatch { expected: String, found: String },
+    /// A git package has a path dependency, which can't be resolved outside of its repository.
+    PathInGitPackage { name: String, dependency: String },
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageError::File { path, msg } => write!(f, "{}: {msg}", path.display()),
+            PackageError::Fetch { name, msg } => write!(f, "couldn't fetch `{name}`: {msg}"),
+            PackageError::NoMatchingVersion { name, req } => {
+                write!(f, "no version of `{name}` matches `{req}`")
+            }
+            PackageError::Conflict { name } => write!(
+                f,
+                "packages depend on incompatible versions or sources of `{name}`"
+            ),
+            PackageError::NotLocked { name } => write!(
+                f,
+                "`{name}` isn't pinned by the lockfile; run `nickel package update` to pin it"
+            ),
+            PackageError::NameMismatch { expected, found } => write!(
+                f,
+                "the dependency `{expected}` refers to a package named `{found}`"
+            ),
+            PackageError::PathInGitPackage { name, dependency } => write!(
+                f,
+                "the git package `{name}` has a path dependency `{dependency}`, which isn't \
+                supported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PackageError {}
+
+/// Read a TOML file. Return `None` if the file doesn't exist.
+fn load_toml<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Option<T>, PackageError> {
+    let error = |msg: String| PackageError::File {
+        path: path.to_owned(),
+        msg,
+    };
+
+    match fs::read_to_string(path) {
+        Ok(content) => toml::from_str(&content)
+            .map(Some)
+            .map_err(|err| error(err.message().to_owned())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(error(err.to_string())),
+    }
+}
+
+fn save_toml<T: Serialize>(value: &T, path: &Path) -> Result<(), PackageError> {
+    let error = |msg: String| PackageError::File {
+        path: path.to_owned(),
+        msg,
+    };
+
+    let content = toml::to_string_pretty(value).map_err(|err| error(err.to_string()))?;
+    fs::write(path, content).map_err(|err| error(err.to_string()))
+}
+
+impl Manifest {
+    /// A manifest without dependencies, at version `0.1.0`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Manifest {
+            name: name.into(),
+            version: Version::new(0, 1, 0),
+            description: None,
+            dependencies: BTreeMap::new(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PackageError> {
+        let path = path.as_ref();
+
+        load_toml(path)?.ok_or_else(|| PackageError::File {
+            path: path.to_owned(),
+            msg: "the manifest doesn't exist; run `nickel package init` to create it".to_owned(),
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PackageError> {
+        save_toml(self, path.as_ref())
+    }
+}
+
+impl Lockfile {
+    /// Read a lockfile. Return an empty lockfile if the file doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PackageError> {
+        Ok(load_toml(path.as_ref())?.unwrap_or_default())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PackageError> {
+        save_toml(self, path.as_ref())
+    }
+
+    /// The root directories of the locked packages, vendored in `dir` (see [Resolution::vendor]).
+    pub fn vendored(&self, dir: impl AsRef<Path>) -> BTreeMap<String, PathBuf> {
+        self.packages
+            .keys()
+            .map(|name| (name.clone(), dir.as_ref().join(name)))
+            .collect()
+    }
+}
+
+/// Return `true` if `path` is a package import.
+pub fn is_package(path: &OsStr) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with(PREFIX))
+}
+
+/// Split a package import into the name of the package and the path of the imported file in the
+/// package.
+pub fn parse_import(import: &str) -> (&str, &str) {
+    let rest = import.strip_prefix(PREFIX).unwrap_or(import);
+    rest.split_once('/').unwrap_or((rest, DEFAULT_ENTRY))
+}
+
+/// The packages resolved for the dependencies of a root package.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Resolution {
+    pub lockfile: Lockfile,
+    /// The root directory of each package.
+    pub roots: BTreeMap<String, PathBuf>,
+}
+
+impl Resolution {
+    /// Copy the packages into `dir`, one directory per package, without their git metadata. The
+    /// previous content of these directories is removed.
+    pub fn vendor(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        for (name, root) in &self.roots {
+            let dest = dir.as_ref().join(name);
+
+            if dest.exists() {
+                fs::remove_dir_all(&dest)?;
+            }
+
+            copy_dir(root, &dest)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+
+        if entry.file_name() == ".git" {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &dest.join(entry.file_name()))?;
+        } else {
+            fs::copy(entry.path(), dest.join(entry.file_name()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A package whose dependencies are being resolved.
+#[derive(Clone, Debug)]
+enum Dependent {
+    /// A path package, with its directory relative to the root package.
+    Path(PathBuf),
+    /// A git package, with its name.
+    Git(String),
+}
+
+/// Resolves the dependencies of a package. See the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resolver {
+    /// The directory storing the checkouts of git packages.
+    pub cache_dir: PathBuf,
+    /// The packages resolved previously. A package is kept at its locked version as long as it's
+    /// still accepted by the packages depending on it.
+    pub lockfile: Lockfile,
+    /// If set, the packages which aren't in the lockfile aren't resolved, but are an error.
+    pub frozen: bool,
+}
+
+impl Resolver {
+    pub fn new(cache_dir: impl Into<PathBuf>, lockfile: Lockfile) -> Self {
+        Resolver {
+            cache_dir: cache_dir.into(),
+            lockfile,
+            frozen: false,
+        }
+    }
+
+    pub fn frozen(self, frozen: bool) -> Self {
+        Resolver { frozen, ..self }
+    }
+
+    /// Resolve the dependencies of the package whose root directory is `root`, transitively.
+    pub fn resolve(&self, root: &Path, manifest: &Manifest) -> Result<Resolution, PackageError> {
+        let mut resolution = Resolution::default();
+        // The dependencies to resolve, together with the package depending on them.
+        let mut pending: Vec<(String, Dependency, Dependent)> = manifest
+            .dependencies
+            .iter()
+            .rev()
+            .map(|(name, dep)| (name.clone(), dep.clone(), Dependent::Path(PathBuf::new())))
+            .collect();
+
+        while let Some((name, dep, dependent)) = pending.pop() {
+            let source = match (&dep, dependent) {
+                (Dependency::Path { path }, Dependent::Path(base)) => Source::Path {
+                    path: normalize(&base.join(path)),
+                },
+                (Dependency::Path { path }, Dependent::Git(dependent)) => {
+                    return Err(PackageError::PathInGitPackage {
+                        name: dependent,
+                        dependency: path.display().to_string(),
+                    })
+                }
+                (Dependency::Git { .. }, _) => {
+                    if let Some(resolved) = resolution.lockfile.packages.get(&name) {
+                        if accepts(&dep, resolved) {
+                            continue;
+                        }
+
+                        return Err(PackageError::Conflict { name });
+                    }
+
+                    self.resolve_git(&name, &dep)?
+                }
+            };
+
+            if let Some(resolved) = resolution.lockfile.packages.get(&name) {
+                if resolved.source == source {
+                    continue;
+                }
+
+                return Err(PackageError::Conflict { name });
+            }
+
+            let dir = match &source {
+                Source::Path { path } => root.join(path),
+                Source::Git { git, commit } => git_import::checkout(&self.cache_dir, git, commit)
+                    .map_err(|msg| PackageError::Fetch {
+                    name: name.clone(),
+                    msg,
+                })?,
+            };
+
+            let package = Manifest::load(dir.join(MANIFEST_NAME))?;
+
+            if package.name != name {
+                return Err(PackageError::NameMismatch {
+                    expected: name,
+                    found: package.name,
+                });
+            }
+
+            if let Dependency::Git {
+                version: Some(req), ..
+            } = &dep
+            {
+                if !req.matches(&package.version) {
+                    return Err(PackageError::NoMatchingVersion {
+                        name,
+                        req: req.clone(),
+                    });
+                }
+            }
+
+            let dependent = match &source {
+                Source::Path { path } => Dependent::Path(path.clone()),
+                Source::Git { .. } => Dependent::Git(name.clone()),
+            };
+
+            pending.extend(
+                package
+                    .dependencies
+                    .iter()
+                    .rev()
+                    .map(|(dep_name, dep)| (dep_name.clone(), dep.clone(), dependent.clone())),
+            );
+
+            resolution.roots.insert(name.clone(), dir);
+            resolution.lockfile.packages.insert(
+                name,
+                LockedPackage {
+                    source,
+                    version: package.version,
+                    dependencies: package.dependencies.into_keys().collect(),
+                },
+            );
+        }
+
+        Ok(resolution)
+    }
+
+    /// Return the source of a git dependency, from the lockfile if it's still accepted, or by
+    /// resolving its version or reference otherwise.
+    fn resolve_git(&self, name: &str, dep: &Dependency) -> Result<Source, PackageError> {
+        let Dependency::Git {
+            git,
+            version,
+            reference,
+        } = dep
+        else {
+            unreachable!("resolve_git() called on a path dependency")
+        };
+
+        if let Some(locked) = self.lockfile.packages.get(name) {
+            if accepts(dep, locked) {
+                return Ok(locked.source.clone());
+            }
+        }
+
+        if self.frozen {
+            return Err(PackageError::NotLocked {
+                name: name.to_owned(),
+            });
+        }
+
+        let fetch_error = |msg: String| PackageError::Fetch {
+            name: name.to_owned(),
+            msg,
+        };
+
+        let commit = match version {
+            Some(req) => {
+                git_import::check_repository(git).map_err(fetch_error)?;
+
+                let tags = git_import::git(&[
+                    "ls-remote".as_ref(),
+                    "--tags".as_ref(),
+                    "--".as_ref(),
+                    git.as_ref(),
+                ])
+                .map_err(fetch_error)?;
+
+                select_version(&tags, req)
+                    .ok_or_else(|| PackageError::NoMatchingVersion {
+                        name: name.to_owned(),
+                        req: req.clone(),
+                    })?
+                    .1
+            }
+            None => git_import::resolve_reference(git, reference.as_deref().unwrap_or("HEAD"))
+                .map_err(fetch_error)?,
+        };
+
+        Ok(Source::Git {
+            git: git.clone(),
+            commit,
+        })
+    }
+}
+
+/// Remove the `.` and `..` components of a relative path, as far as possible, so that the
+/// different paths of a path package are recorded as the same source.
+fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Return `true` if the resolved package `package` satisfies the git dependency `dep`. The
+/// reference of a dependency without version requirement isn't checked, since a branch can move:
+/// the package is only resolved again on update.
+fn accepts(dep: &Dependency, package: &LockedPackage) -> bool {
+    match (dep, &package.source) {
+        (Dependency::Git { git, version, .. }, Source::Git { git: locked, .. }) => {
+            git == locked
+                && version
+                    .as_ref()
+                    .map_or(true, |req| req.matches(&package.version))
+        }
+        _ => false,
+    }
+}
+
+/// Return the highest version matching `req` among the tags listed by `git ls-remote --tags`,
+/// together with its commit.
+fn select_version(ls_remote: &str, req: &VersionReq) -> Option<(Version, String)> {
+    let mut tags: BTreeMap<Version, String> = BTreeMap::new();
+
+    for line in ls_remote.lines() {
+        let Some((commit, tag)) =
+            line.split_once(char::is_whitespace)
+                .and_then(|(commit, reference)| {
+                    Some((commit, reference.trim().strip_prefix("refs/tags/")?))
+                })
+        else {
+            continue;
+        };
+
+        // Annotated tags are listed twice, and the peeled entry gives the commit they point to.
+        let (tag, peeled) = match tag.strip_suffix("^{}") {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+
+        let Ok(version) = Version::parse(tag.strip_prefix('v').unwrap_or(tag)) else {
+            continue;
+        };
+
+        if peeled || !tags.contains_key(&version) {
+            tags.insert(version, commit.to_owned());
+        }
+    }
+
+    tags.into_iter()
+        .rev()
+        .find(|(version, _)| req.matches(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nickel-pkg-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_package(dir: &Path, manifest: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(MANIFEST_NAME), manifest).unwrap();
+    }
+
+    #[test]
+    fn manifest() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            name = "app"
+            version = "1.0.0"
+
+            [dependencies]
+            contracts = { git = "https://example.com/contracts.git", version = "^1.2" }
+            utils = { path = "../utils" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.dependencies["contracts"],
+            Dependency::Git {
+                git: "https://example.com/contracts.git".to_owned(),
+                version: Some(VersionReq::parse("^1.2").unwrap()),
+                reference: None,
+            }
+        );
+        assert_eq!(
+            manifest.dependencies["utils"],
+            Dependency::Path {
+                path: PathBuf::from("../utils"),
+            }
+        );
+        assert!(toml::from_str::<Manifest>("name = \"app\"\nversion = \"1\"").is_err());
+    }
+
+    #[test]
+    fn imports() {
+        assert!(is_package(OsStr::new("pkg:utils")));
+        assert!(!is_package(OsStr::new("utils.ncl")));
+        assert_eq!(parse_import("pkg:utils"), ("utils", DEFAULT_ENTRY));
+        assert_eq!(
+            parse_import("pkg:utils/lib/strings.ncl"),
+            ("utils", "lib/strings.ncl")
+        );
+    }
+
+    #[test]
+    fn select_version_from_tags() {
+        let tags = "\
+            1111111111111111111111111111111111111111\trefs/tags/v1.2.0\n\
+            2222222222222222222222222222222222222222\trefs/tags/v1.3.0\n\
+            3333333333333333333333333333333333333333\trefs/tags/v1.3.0^{}\n\
+            4444444444444444444444444444444444444444\trefs/tags/v2.0.0\n\
+            5555555555555555555555555555555555555555\trefs/tags/nightly\n";
+
+        assert_eq!(
+            select_version(tags, &VersionReq::parse("^1.2").unwrap()),
+            Some((
+                Version::new(1, 3, 0),
+                "3333333333333333333333333333333333333333".to_owned()
+            ))
+        );
+        assert_eq!(
+            select_version(tags, &VersionReq::parse("=1.2.0").unwrap()).map(|(v, _)| v),
+            Some(Version::new(1, 2, 0))
+        );
+        assert_eq!(
+            select_version(tags, &VersionReq::parse("^3").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_path_dependencies() {
+        let root = temp_dir("path");
+        write_package(
+            &root.join("app"),
+            "name = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nutils = { path = \"../utils\" }\nstrings = { path = \"../strings\" }\n",
+        );
+        write_package(
+            &root.join("utils"),
+            "name = \"utils\"\nversion = \"1.0.0\"\n\n[dependencies]\nstrings = { path = \"../strings\" }\n",
+        );
+        write_package(
+            &root.join("strings"),
+            "name = \"strings\"\nversion = \"0.2.0\"\n",
+        );
+
+        let app = root.join("app");
+        let manifest = Manifest::load(app.join(MANIFEST_NAME)).unwrap();
+        let resolution = Resolver::new(root.join("cache"), Lockfile::default())
+            .frozen(true)
+            .resolve(&app, &manifest)
+            .unwrap();
+
+        assert_eq!(
+            resolution.roots.keys().collect::<Vec<_>>(),
+            vec!["strings", "utils"]
+        );
+        assert_eq!(
+            resolution.lockfile.packages["utils"].dependencies,
+            vec!["strings".to_owned()]
+        );
+        assert_eq!(
+            resolution.lockfile.packages["strings"].version,
+            Version::new(0, 2, 0)
+        );
+
+        let vendor = root.join(VENDOR_DIR);
+        resolution.vendor(&vendor).unwrap();
+        assert!(vendor.join("utils").join(MANIFEST_NAME).exists());
+        assert_eq!(
+            resolution.lockfile.vendored(&vendor)["strings"],
+            vendor.join("strings")
+        );
+    }
+
+    #[test]
+    fn resolve_errors() {
+        let root = temp_dir("errors");
+        write_package(
+            &root.join("utils"),
+            "name = \"other\"\nversion = \"1.0.0\"\n",
+        );
+
+        let mut manifest = Manifest::new("app");
+        manifest.dependencies.insert(
+            "utils".to_owned(),
+            Dependency::Path {
+                path: PathBuf::from("utils"),
+            },
+        );
+
+        let resolver = Resolver::new(root.join("cache"), Lockfile::default()).frozen(true);
+        assert_eq!(
+            resolver.resolve(&root, &manifest),
+            Err(PackageError::NameMismatch {
+                expected: "utils".to_owned(),
+                found: "other".to_owned(),
+            })
+        );
+
+        let mut manifest = Manifest::new("app");
+        manifest.dependencies.insert(
+            "contracts".to_owned(),
+            Dependency::Git {
+                git: "https://example.invalid/contracts.git".to_owned(),
+                version: None,
+                reference: None,
+            },
+        );
+        assert_eq!(
+            resolver.resolve(&root, &manifest),
+            Err(PackageError::NotLocked {
+                name: "contracts".to_owned(),
+            })
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use codespan_reporting::diagnostic::Label;
 
-use crate::{files::FileId, identifier::LocIdent, position::RawSpan};
+use crate::{error::DiagnosticFileId, identifier::LocIdent, position::RawSpan};
 use std::ops::Range;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -39,9 +39,12 @@ pub enum InvalidRecordTypeError {
 }
 
 impl InvalidRecordTypeError {
-    pub fn labels(&self) -> Vec<Label<FileId>> {
+    pub fn labels(&self) -> Vec<Label<DiagnosticFileId>> {
         let label = |span: &RawSpan| {
-            Label::secondary(span.src_id, span.start.to_usize()..span.end.to_usize())
+            Label::secondary(
+                span.src_id.into(),
+                span.start.to_usize()..span.end.to_usize(),
+            )
         };
         match self {
             InvalidRecordTypeError::InvalidField(pos) => {
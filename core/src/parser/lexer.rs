@@ -326,6 +326,10 @@ pub enum NormalToken<'input> {
     Force,
     #[token("not_exported")]
     NotExported,
+    #[token("deprecated")]
+    Deprecated,
+    #[token("secret")]
+    Secret,
 
     #[token("%hash%")]
     OpHash,
@@ -446,6 +450,8 @@ pub const KEYWORDS: &[&str] = &[
     "priority",
     "force",
     "not_exported",
+    "deprecated",
+    "secret",
 ];
 
 #[derive(Debug, Clone, PartialEq)]
@@ -108,8 +108,19 @@ pub trait ErrorTolerantParser<T> {
         lexer: lexer::Lexer,
     ) -> Result<(T, ParseErrors), ParseError>;
 
-    /// Parse a value from a lexer with the given `file_id`, failing at the first encountered
-    /// error.
+    /// Parse a value from a lexer with the given `file_id` in an error-tolerant way, collecting
+    /// every error. On success, the result may contain [crate::term::Term::ParseError] nodes
+    /// where the parser recovered from an error. If the parser encountered a non-recoverable
+    /// error, all the errors are returned instead: the recovered ones, followed by the
+    /// non-recoverable one.
+    fn parse_recover(
+        &self,
+        file_id: FileId,
+        lexer: lexer::Lexer,
+    ) -> Result<(T, ParseErrors), ParseErrors>;
+
+    /// Parse a value from a lexer with the given `file_id`, failing if any error has been
+    /// encountered. All the errors are reported, as with [Self::parse_recover].
     fn parse_strict(&self, file_id: FileId, lexer: lexer::Lexer) -> Result<T, ParseErrors>;
 }
 
@@ -122,24 +133,37 @@ where
         file_id: FileId,
         lexer: lexer::Lexer,
     ) -> Result<(T, ParseErrors), ParseError> {
+        // unwrap(): on failure, the last error is the non-recoverable one, so there's at least
+        // one error.
+        self.parse_recover(file_id, lexer)
+            .map_err(|mut errs| errs.errors.pop().unwrap())
+    }
+
+    fn parse_recover(
+        &self,
+        file_id: FileId,
+        lexer: lexer::Lexer,
+    ) -> Result<(T, ParseErrors), ParseErrors> {
         let mut parse_errors = Vec::new();
         let mut next_wildcard_id = 0;
         let result = self
             .parse(file_id, &mut parse_errors, &mut next_wildcard_id, lexer)
             .map_err(|err| ParseError::from_lalrpop(err, file_id));
 
-        let parse_errors = ParseErrors::from_recoverable(parse_errors, file_id);
+        let mut parse_errors = ParseErrors::from_recoverable(parse_errors, file_id);
         match result {
             Ok(t) => Ok((t, parse_errors)),
-            Err(e) => Err(e),
+            Err(e) => {
+                parse_errors.errors.push(e);
+                Err(parse_errors)
+            }
         }
     }
 
     fn parse_strict(&self, file_id: FileId, lexer: lexer::Lexer) -> Result<T, ParseErrors> {
-        match self.parse_tolerant(file_id, lexer) {
+        match self.parse_recover(file_id, lexer) {
             Ok((t, e)) if e.no_errors() => Ok(t),
-            Ok((_, e)) => Err(e),
-            Err(e) => Err(e.into()),
+            Ok((_, e)) | Err(e) => Err(e),
         }
     }
 }
@@ -567,3 +567,16 @@ fn import() {
         )
     );
 }
+
+#[test]
+fn multiple_errors() {
+    let s = "{ a = , b = 1";
+    let id = Files::new().add("<test>", String::from(s));
+
+    let errors = super::grammar::TermParser::new()
+        .parse_strict(id, Lexer::new(s))
+        .unwrap_err()
+        .errors;
+    assert!(errors.len() > 1);
+    assert_matches!(errors.last(), Some(ParseError::UnexpectedEOF(..)));
+}
@@ -331,6 +331,8 @@ impl UniRecord {
                             opt: false,
                             not_exported: false,
                             priority: MergePriority::Neutral,
+                            deprecated: None,
+                            secret: false,
                         },
                     // At this stage, this field should always be empty. It's a run-time thing, and
                     // is only filled during program transformation.
@@ -366,6 +368,8 @@ impl UniRecord {
                             opt: false,
                             not_exported: false,
                             priority: MergePriority::Neutral,
+                            deprecated: None,
+                            secret: false,
                         },
                     // At this stage, this field should always be empty. It's a run-time thing, and
                     // is only filled during program transformation.
@@ -313,6 +313,8 @@ impl Combine for FieldMetadata {
             // The resulting field will be suppressed from serialization if either of the fields to be merged is.
             not_exported: left.not_exported || right.not_exported,
             priority,
+            deprecated: left.deprecated.or(right.deprecated),
+            secret: left.secret || right.secret,
         }
     }
 }
@@ -594,6 +596,8 @@ fn merge_fields(id_span: RawSpan, field1: Field, field2: Field) -> Field {
             opt: field1.metadata.opt && field2.metadata.opt,
             not_exported: field1.metadata.not_exported || field2.metadata.not_exported,
             priority,
+            deprecated: field1.metadata.deprecated.or(field2.metadata.deprecated),
+            secret: field1.metadata.secret || field2.metadata.secret,
         },
         pending_contracts: Vec::new(),
     }
@@ -0,0 +1,361 @@
+//! Plugins providing extra native functions, loaded from dynamic libraries at runtime.
+//!
+//! A plugin is a dynamic library exporting a function `nickel_plugin_descriptor`, of C type
+//! `const NickelPluginDescriptor *nickel_plugin_descriptor(void)`, which describes the plugin:
+//!
+//! ```c
+//! typedef struct {
+//!     const char *name;
+//!     // Call the function on a UTF-8, NUL-terminated argument. On success, return 0 and store in
+//!     // `*out` the JSON encoding of the result. On failure, return a non-zero value and store in
+//!     // `*out` an error message. `*out` is released with the plugin's `free_string`.
+//!     int32_t (*call)(const char *arg, char **out);
+//! } NickelPluginFunction;
+//!
+//! typedef struct {
+//!     uint32_t abi_version; // NICKEL_PLUGIN_ABI_VERSION
+//!     const char *name;
+//!     // NULL-terminated list of the capabilities required by the plugin.
+//!     const char *const *capabilities;
+//!     const NickelPluginFunction *functions;
+//!     size_t functions_len;
+//!     void (*free_string)(char *);
+//! } NickelPluginDescriptor;
+//! ```
+//!
+//! Plugins declare the capabilities they need, such as `network` for a plugin querying a cloud
+//! provider. A plugin is only loaded if the embedder explicitly grants all of them through a
+//! [CapabilityGrant]: the interpreter doesn't sandbox native code, but the grant makes the
+//! decision to run a plugin with a given set of permissions visible and deliberate. The functions
+//! of the plugin also require these capabilities at call time, so that the capability policy of
+//! a program (see [crate::policy]) can deny them.
+//!
+//! Each function of a plugin `p` is registered as a native function `p_<function>` (see
+//! [crate::native]) of type `String -> Dyn`. The result of a call is parsed from JSON.
+use std::{
+    collections::BTreeSet,
+    ffi::{c_char, CStr, CString},
+    fmt,
+    path::Path,
+    rc::Rc,
+};
+
+use crate::{native::NativeFunction, term::RichTerm};
+
+/// The version of the plugin ABI implemented by this interpreter.
+pub const ABI_VERSION: u32 = 1;
+
+/// The name of the symbol exported by plugins.
+pub const DESCRIPTOR_SYMBOL: &[u8] = b"nickel_plugin_descriptor";
+
+/// A function exported by a plugin. See the [module documentation](self).
+#[repr(C)]
+pub struct PluginFunction {
+    pub name: *const c_char,
+    pub call: unsafe extern "C" fn(arg: *const c_char, out: *mut *mut c_char) -> i32,
+}
+
+/// The description of a plugin. See the [module documentation](self).
+#[repr(C)]
+pub struct PluginDescriptor {
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub capabilities: *const *const c_char,
+    pub functions: *const PluginFunction,
+    pub functions_len: usize,
+    pub free_string: unsafe extern "C" fn(*mut c_char),
+}
+
+type DescriptorFn = unsafe extern "C" fn() -> *const PluginDescriptor;
+
+/// The set of capabilities the embedder allows plugins to use.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CapabilityGrant(BTreeSet<String>);
+
+impl CapabilityGrant {
+    /// A grant allowing no capability. Only plugins which don't require any capability can be
+    /// loaded.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Allow a capability.
+    pub fn allow(mut self, capability: impl Into<String>) -> Self {
+        self.0.insert(capability.into());
+        self
+    }
+
+    pub fn allows(&self, capability: &str) -> bool {
+        self.0.contains(capability)
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for CapabilityGrant {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        CapabilityGrant(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// An error occurring when loading a plugin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginError {
+    /// The library couldn't be loaded, or doesn't export a descriptor.
+    Load(String),
+    /// The plugin was built for another version of the plugin ABI.
+    AbiVersion { plugin: String, version: u32 },
+    /// The plugin requires a capability which hasn't been granted.
+    MissingCapability { plugin: String, capability: String },
+    /// The descriptor is malformed: null pointers or invalid UTF-8 names.
+    InvalidDescriptor(String),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Load(msg) => write!(f, "failed to load plugin: {msg}"),
+            PluginError::AbiVersion { plugin, version } => write!(
+                f,
+                "plugin `{plugin}` uses version {version} of the plugin ABI, \
+                but this interpreter supports version {ABI_VERSION}"
+            ),
+            PluginError::MissingCapability { plugin, capability } => write!(
+                f,
+                "plugin `{plugin}` requires the capability `{capability}`, which hasn't been granted"
+            ),
+            PluginError::InvalidDescriptor(msg) => write!(f, "invalid plugin descriptor: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// A loaded plugin.
+#[derive(Clone, Debug)]
+pub struct Plugin {
+    pub name: String,
+    pub capabilities: Vec<String>,
+    pub functions: Vec<NativeFunction>,
+}
+
+/// Load the plugin at `path`, checking that all the capabilities it requires are in `grant`.
+///
+/// # Safety
+///
+/// Loading a dynamic library runs its initialization code, and calling the functions of the
+/// plugin runs arbitrary native code. The library must implement the plugin ABI described in the
+/// [module documentation](self).
+pub unsafe fn load(path: impl AsRef<Path>, grant: &CapabilityGrant) -> Result<Plugin, PluginError> {
+    let path = path.as_ref();
+    let library =
+        libloading::Library::new(path).map_err(|err| PluginError::Load(err.to_string()))?;
+    let descriptor = {
+        let descriptor_fn = library
+            .get::<DescriptorFn>(DESCRIPTOR_SYMBOL)
+            .map_err(|err| PluginError::Load(format!("{}: {err}", path.display())))?;
+        descriptor_fn()
+    };
+
+    if descriptor.is_null() {
+        return Err(PluginError::InvalidDescriptor(format!(
+            "{}: null descriptor",
+            path.display()
+        )));
+    }
+
+    from_descriptor(&*descriptor, Rc::new(library), grant)
+}
+
+/// Build a plugin from its descriptor. `library` is kept alive as long as the functions of the
+/// plugin are.
+///
+/// # Safety
+///
+/// The pointers of `descriptor` must be valid for as long as `library` is alive.
+pub(crate) unsafe fn from_descriptor<L: 'static>(
+    descriptor: &PluginDescriptor,
+    library: Rc<L>,
+    grant: &CapabilityGrant,
+) -> Result<Plugin, PluginError> {
+    let name = c_str(descriptor.name, "plugin name")?;
+
+    if descriptor.abi_version != ABI_VERSION {
+        return Err(PluginError::AbiVersion {
+            plugin: name,
+            version: descriptor.abi_version,
+        });
+    }
+
+    let mut capabilities = Vec::new();
+    let mut current = descriptor.capabilities;
+
+    while !current.is_null() && !(*current).is_null() {
+        capabilities.push(c_str(*current, "capability")?);
+        current = current.add(1);
+    }
+
+    if let Some(capability) = capabilities.iter().find(|cap| !grant.allows(cap)) {
+        return Err(PluginError::MissingCapability {
+            plugin: name,
+            capability: capability.clone(),
+        });
+    }
+
+    if descriptor.functions.is_null() && descriptor.functions_len > 0 {
+        return Err(PluginError::InvalidDescriptor(format!(
+            "plugin `{name}`: null function array"
+        )));
+    }
+
+    let free_string = descriptor.free_string;
+    let mut functions = Vec::with_capacity(descriptor.functions_len);
+
+    for index in 0..descriptor.functions_len {
+        let function = &*descriptor.functions.add(index);
+        let fun_name = c_str(function.name, "function name")?;
+        let call = function.call;
+        let library = Rc::clone(&library);
+
+        let native = NativeFunction::new(
+            format!("{name}_{fun_name}"),
+            move |arg: String| -> Result<RichTerm, String> {
+                // Capturing the library ensures that it isn't unloaded while the function can
+                // still be called.
+                let _library = &library;
+                let arg = CString::new(arg)
+                    .map_err(|_| "the argument of a plugin function can't contain NUL bytes")?;
+                let mut out: *mut c_char = std::ptr::null_mut();
+                let status = unsafe { call(arg.as_ptr(), &mut out) };
+
+                if out.is_null() {
+                    return Err(format!("plugin function `{fun_name}` returned no output"));
+                }
+
+                let output = unsafe {
+                    let output = CStr::from_ptr(out).to_string_lossy().into_owned();
+                    free_string(out);
+                    output
+                };
+
+                if status != 0 {
+                    return Err(output);
+                }
+
+                serde_json::from_str::<serde_json::Value>(&output)
+                    .map(RichTerm::from)
+                    .map_err(|err| {
+                        format!("plugin function `{fun_name}` returned invalid JSON: {err}")
+                    })
+            },
+        );
+
+        functions.push(
+            capabilities
+                .iter()
+                .fold(native, |native, cap| native.requires(cap.as_str().into())),
+        );
+    }
+
+    Ok(Plugin {
+        name,
+        capabilities,
+        functions,
+    })
+}
+
+unsafe fn c_str(ptr: *const c_char, what: &str) -> Result<String, PluginError> {
+    if ptr.is_null() {
+        return Err(PluginError::InvalidDescriptor(format!("null {what}")));
+    }
+
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(String::from)
+        .map_err(|_| PluginError::InvalidDescriptor(format!("{what} isn't valid UTF-8")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::tests::program;
+
+    unsafe extern "C" fn lookup(arg: *const c_char, out: *mut *mut c_char) -> i32 {
+        let region = CStr::from_ptr(arg).to_str().unwrap();
+        let (status, output) = match region {
+            "eu-west-1" => (0, r#"{ "ami": "ami-1234", "zones": 3 }"#.to_owned()),
+            _ => (1, format!("unknown region `{region}`")),
+        };
+        *out = CString::new(output).unwrap().into_raw();
+        status
+    }
+
+    unsafe extern "C" fn free_string(s: *mut c_char) {
+        drop(CString::from_raw(s));
+    }
+
+    fn with_descriptor<T>(f: impl FnOnce(&PluginDescriptor) -> T) -> T {
+        let name = CString::new("cloud").unwrap();
+        let network = CString::new("network").unwrap();
+        let capabilities = [network.as_ptr(), std::ptr::null()];
+        let lookup_name = CString::new("lookup").unwrap();
+        let functions = [PluginFunction {
+            name: lookup_name.as_ptr(),
+            call: lookup,
+        }];
+
+        f(&PluginDescriptor {
+            abi_version: ABI_VERSION,
+            name: name.as_ptr(),
+            capabilities: capabilities.as_ptr(),
+            functions: functions.as_ptr(),
+            functions_len: functions.len(),
+            free_string,
+        })
+    }
+
+    #[test]
+    fn capabilities() {
+        let err = with_descriptor(|descriptor| unsafe {
+            from_descriptor(descriptor, Rc::new(()), &CapabilityGrant::none())
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            err,
+            PluginError::MissingCapability {
+                plugin: "cloud".into(),
+                capability: "network".into()
+            }
+        );
+    }
+
+    #[test]
+    fn call_plugin() {
+        let plugin = with_descriptor(|descriptor| unsafe {
+            from_descriptor(
+                descriptor,
+                Rc::new(()),
+                &CapabilityGrant::none().allow("network"),
+            )
+        })
+        .unwrap();
+
+        assert_eq!(plugin.capabilities, ["network"]);
+
+        let program = |source: &str| {
+            let mut p = program(source);
+            p.register_plugin(plugin.clone());
+            p
+        };
+
+        assert_eq!(
+            program("(native.cloud_lookup \"eu-west-1\").zones + 1")
+                .eval_full()
+                .unwrap()
+                .as_i64(),
+            Ok(4)
+        );
+        assert!(program("native.cloud_lookup \"mars-1\"")
+            .eval_full()
+            .is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! Capability policies, restricting the side effects an evaluation may perform.
+//!
+//! Evaluating a Nickel program is pure, except for a few abilities which reach out to the
+//! machine running the interpreter: importing files, and calling the native functions registered
+//! by the embedder (see [crate::native]), which may read environment variables, query the network
+//! or do anything else Rust code can do. A [CapabilityPolicy] set on a program with
+//! [Program::set_policy](crate::program::Program::set_policy) decides which of these abilities
+//! an untrusted configuration may use:
+//!
+//! - imports require the [Capability::Filesystem] capability, and can additionally be restricted
+//!   to a set of directories, while remote imports require the [Capability::Network] capability,
+//! - native functions must be allowed by the policy, and the capabilities they declare with
+//!   [NativeFunction::requires] must be granted,
+//! - evaluating Nix code, with the `nix-experimental` feature, requires the filesystem,
+//!   environment and network capabilities.
+//!
+//! Apart from remote imports, the interpreter itself doesn't access environment variables nor the
+//! network: the corresponding capabilities are declared by the native functions and the plugins
+//! which do. Actions denied by
+//! the policy are reported as
+//! [EvalError::CapabilityDenied](crate::error::EvalError::CapabilityDenied) or
+//! [ImportError::CapabilityDenied](crate::error::ImportError::CapabilityDenied), located at the
+//! offending term.
+//!
+//! The default policy grants everything, which is the behavior of the interpreter without a
+//! policy. Policies for untrusted code should start from [CapabilityPolicy::deny_all]:
+//!
+//! ```
+//! # use nickel_lang_core::policy::{Capability, CapabilityPolicy};
+//! let policy = CapabilityPolicy::deny_all()
+//!     .grant(Capability::Filesystem)
+//!     .restrict_imports_to("/etc/myapp")
+//!     .allow_native("resolve_secret");
+//! ```
+//!
+//! [CapabilityPolicy::sandboxed] is the strictest policy, meant to evaluate snippets submitted by
+//! users: nothing is granted, and the denials explain that the program is sandboxed.
+use std::{
+    collections::BTreeSet,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{cache::normalize_path, identifier::Ident, native::NativeFunction};
+
+/// An ability to act on the world outside of the evaluation.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Capability {
+    /// Read files, through imports.
+    Filesystem,
+    /// Read environment variables.
+    Env,
+    /// Access the network.
+    Network,
+    /// A capability specific to a native function or to a plugin.
+    Other(String),
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::Filesystem => write!(f, "filesystem"),
+            Capability::Env => write!(f, "env"),
+            Capability::Network => write!(f, "network"),
+            Capability::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl From<&str> for Capability {
+    fn from(name: &str) -> Self {
+        match name {
+            "filesystem" => Capability::Filesystem,
+            "env" => Capability::Env,
+            "network" => Capability::Network,
+            _ => Capability::Other(name.to_owned()),
+        }
+    }
+}
+
+/// The set of capabilities granted to an evaluation. See the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapabilityPolicy {
+    /// The granted capabilities, or `None` if every capability is granted.
+    granted: Option<BTreeSet<Capability>>,
+    /// The directories imported files must be in, or `None` if imports aren't restricted to
+    /// specific directories.
+    import_roots: Option<Vec<PathBuf>>,
+    /// The native functions which can be called, or `None` if all of them can.
+    natives: Option<BTreeSet<Ident>>,
+    /// Whether the policy has been built by [Self::sandboxed], which only changes the reason of
+    /// the denials.
+    sandboxed: bool,
+}
+
+impl Default for CapabilityPolicy {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+impl CapabilityPolicy {
+    /// A policy granting every capability and allowing every native function.
+    pub fn allow_all() -> Self {
+        CapabilityPolicy {
+            granted: None,
+            import_roots: None,
+            natives: None,
+            sandboxed: false,
+        }
+    }
+
+    /// A policy granting no capability and allowing no native function.
+    pub fn deny_all() -> Self {
+        CapabilityPolicy {
+            granted: Some(BTreeSet::new()),
+            import_roots: None,
+            natives: Some(BTreeSet::new()),
+            sandboxed: false,
+        }
+    }
+
+    /// A policy for sandboxed evaluation: as [Self::deny_all], nothing is granted, so imports and
+    /// native functions (and thus environment and network access) are denied.
+    pub fn sandboxed() -> Self {
+        CapabilityPolicy {
+            sandboxed: true,
+            ..Self::deny_all()
+        }
+    }
+
+    /// Return `true` if the policy has been built by [Self::sandboxed].
+    pub fn is_sandboxed(&self) -> bool {
+        self.sandboxed
+    }
+
+    /// Grant a capability. This has no effect if every capability is already granted.
+    pub fn grant(mut self, capability: Capability) -> Self {
+        if let Some(granted) = &mut self.granted {
+            granted.insert(capability);
+        }
+
+        self
+    }
+
+    /// Only allow the import of files located in `dir` or in one of its subdirectories. Can be
+    /// called several times to allow several directories. Imports still require the
+    /// [Capability::Filesystem] capability.
+    pub fn restrict_imports_to(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.import_roots
+            .get_or_insert_with(Vec::new)
+            .push(dir.into());
+        self
+    }
+
+    /// Allow calls to the native function `name`, provided that the capabilities it requires are
+    /// granted. This has no effect if every native function is already allowed.
+    pub fn allow_native(mut self, name: impl AsRef<str>) -> Self {
+        if let Some(natives) = &mut self.natives {
+            natives.insert(Ident::new(name));
+        }
+
+        self
+    }
+
+    /// Return `true` if `capability` is granted.
+    pub fn is_granted(&self, capability: &Capability) -> bool {
+        self.granted
+            .as_ref()
+            .is_none_or(|granted| granted.contains(capability))
+    }
+
+    /// Check that the file at `path` can be imported. Return the reason of the denial otherwise.
+    pub fn check_import(&self, path: &Path) -> Result<(), String> {
+        self.check_capability(&Capability::Filesystem)?;
+
+        let Some(roots) = &self.import_roots else {
+            return Ok(());
+        };
+
+        // Symbolic links are resolved and paths are normalized, so that neither a link nor `..`
+        // components can be used to escape the allowed directories.
+        let allowed = resolve_path(path).is_ok_and(|path| {
+            roots
+                .iter()
+                .filter_map(|root| resolve_path(root).ok())
+                .any(|root| path.starts_with(root))
+        });
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!(
+                "`{}` is outside of the directories allowed by the capability policy",
+                path.display()
+            ))
+        }
+    }
+
+    /// Check that the native function `fun` can be called. Return the reason of the denial
+    /// otherwise.
+    pub fn check_native(&self, fun: &NativeFunction) -> Result<(), String> {
+        if let Some(natives) = &self.natives {
+            if !natives.contains(&fun.name()) {
+                if self.sandboxed {
+                    return Err(format!(
+                        "sandboxed programs can't call the native function `{}`",
+                        fun.name()
+                    ));
+                }
+
+                return Err(format!(
+                    "the native function `{}` isn't allowed by the capability policy",
+                    fun.name()
+                ));
+            }
+        }
+
+        fun.required_capabilities()
+            .iter()
+            .try_for_each(|capability| self.check_capability(capability))
+    }
+
+    /// Check that Nix code can be evaluated, which requires the filesystem, environment and
+    /// network capabilities, as Nix code can use all of them. Return the reason of the denial
+    /// otherwise.
+    pub fn check_nix(&self) -> Result<(), String> {
+        if self.sandboxed {
+            return Err("sandboxed programs can't evaluate Nix code".to_owned());
+        }
+
+        [Capability::Filesystem, Capability::Env, Capability::Network]
+            .iter()
+            .try_for_each(|capability| self.check_capability(capability))
+    }
+
+    /// Check that `capability` is granted. Return the reason of the denial otherwise.
+    pub fn check_capability(&self, capability: &Capability) -> Result<(), String> {
+        if self.is_granted(capability) {
+            Ok(())
+        } else if self.sandboxed {
+            Err(format!(
+                "sandboxed programs can't use the `{capability}` capability"
+            ))
+        } else {
+            Err(format!(
+                "the `{capability}` capability hasn't been granted by the capability policy"
+            ))
+        }
+    }
+}
+
+/// Return the absolute path designated by `path`, with the symbolic links resolved. The longest
+/// prefix of `path` which exists on the filesystem is canonicalized, and the rest of the path,
+/// which can't contain links, is normalized lexically. The path doesn't have to exist, as the
+/// paths of the files served by an [ImportLoader](crate::cache::ImportLoader) don't.
+fn resolve_path(path: &Path) -> io::Result<PathBuf> {
+    let path = if path.is_relative() {
+        std::env::current_dir()?.join(path)
+    } else {
+        path.to_owned()
+    };
+
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = ancestor.canonicalize() {
+            // unwrap(): `ancestor` is an ancestor of `path`.
+            return normalize_path(canonical.join(path.strip_prefix(ancestor).unwrap()));
+        }
+    }
+
+    normalize_path(path)
+}
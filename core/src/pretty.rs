@@ -6,7 +6,7 @@ use crate::identifier::LocIdent;
 use crate::parser::lexer::KEYWORDS;
 use crate::term::{
     pattern::*,
-    record::{Field, FieldMetadata, RecordData},
+    record::{Field, FieldMetadata, RecordData, REDACTED},
     *,
 };
 use crate::typ::*;
@@ -162,6 +162,20 @@ where
     doc.render_fmt(80, f)
 }
 
+/// Pretty-print a value like [fmt_pretty], but with the values of secret fields redacted. See
+/// [Allocator::redact_secrets].
+pub fn to_string_redacted<T>(value: T) -> String
+where
+    T: for<'a> Pretty<'a, Allocator, ()>,
+{
+    let allocator = Allocator::default().redact_secrets();
+    let doc: DocBuilder<_, ()> = value.pretty(&allocator);
+    let mut out = String::new();
+    // unwrap: writing to a `String` can't fail.
+    doc.render_fmt(80, &mut out).unwrap();
+    out
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 struct SizeBound {
     depth: usize,
@@ -182,6 +196,7 @@ struct SizeBound {
 pub struct Allocator {
     inner: pretty::BoxAllocator,
     bound: Option<Cell<SizeBound>>,
+    redact_secrets: bool,
 }
 
 /// The default `BoundedAllocator` imposes no constraints.
@@ -190,6 +205,7 @@ impl Default for Allocator {
         Self {
             inner: pretty::BoxAllocator,
             bound: None,
+            redact_secrets: false,
         }
     }
 }
@@ -203,9 +219,18 @@ impl Allocator {
                 depth: max_depth,
                 size: max_size,
             })),
+            redact_secrets: false,
         }
     }
 
+    /// Replaces the values of secret fields by a placeholder instead of printing them. This is
+    /// meant for output that may end up in logs, such as error messages: by default, the
+    /// pretty-printer is lossless.
+    pub fn redact_secrets(mut self) -> Self {
+        self.redact_secrets = true;
+        self
+    }
+
     /// Runs a callback with a "smaller" allocator.
     fn shrunken<'a, F: FnOnce(&'a Allocator) -> DocBuilder<'a, Self>>(
         &'a self,
@@ -502,6 +527,23 @@ impl Allocator {
             } else {
                 self.nil()
             },
+            if metadata.secret {
+                docs![self, self.line(), "| secret"]
+            } else {
+                self.nil()
+            },
+            metadata
+                .deprecated
+                .clone()
+                .map(|msg| {
+                    docs![
+                        self,
+                        self.line(),
+                        "| deprecated ",
+                        self.chunks(&[StrChunk::Literal(msg)], StringRenderStyle::Multiline),
+                    ]
+                })
+                .unwrap_or_else(|| self.nil()),
             match &metadata.priority {
                 MergePriority::Bottom => docs![self, self.line(), "| default"],
                 MergePriority::Neutral => self.nil(),
@@ -544,7 +586,11 @@ impl Allocator {
                     } else {
                         docs![self, " =", self.line()]
                     },
-                    value.pretty(self).nest(2)
+                    if field.metadata.secret && self.redact_secrets {
+                        self.text(format!("{REDACTED:?}"))
+                    } else {
+                        value.pretty(self).nest(2)
+                    }
                 ]
             } else {
                 self.nil()
@@ -1924,6 +1970,20 @@ mod tests {
         assert_format_eq("{ _ : _ }");
     }
 
+    #[test]
+    fn secret_fields() {
+        let term = parse_term(r#"{ password | secret = "hunter2" }"#);
+
+        let lossless = term.to_string();
+        assert!(lossless.contains("| secret"));
+        assert!(lossless.contains("hunter2"));
+
+        let redacted = to_string_redacted(&term);
+        assert!(redacted.contains("| secret"));
+        assert!(redacted.contains(REDACTED));
+        assert!(!redacted.contains("hunter2"));
+    }
+
     fn format_short_term(input: &str, depth: usize, size: usize) -> String {
         let term = parse_term(input);
         let allocator = Allocator::bounded(depth, size);
@@ -22,32 +22,53 @@
 //! Each such value is added to the initial environment before the evaluation of the program.
 use crate::{
     cache::*,
+    check::Violation,
     closurize::Closurize as _,
+    contract::NickelContract,
     error::{
-        report::{report, report_to_stdout, report_with, ColorOpt, ErrorFormat},
-        Error, EvalError, IOError, IntoDiagnostics, ParseError,
+        report::{
+            report, report_to_stdout, report_with_style, Aggregated, ColorOpt, ErrorFormat,
+            ReportStyle,
+        },
+        Error, EvalError, IOError, IntoDiagnostics, ParseError, Warning,
+    },
+    eval::{
+        cache::Cache as EvalCache, contract_trace::ContractTrace, interrupt::CancellationToken,
+        limits::ResourceLimits, memory::MemoryStats, unused::Unused, Closure, VirtualMachine,
     },
-    eval::{cache::Cache as EvalCache, Closure, VirtualMachine},
     files::FileId,
-    identifier::LocIdent,
+    identifier::{Ident, LocIdent},
     label::Label,
+    lint::{LintDiagnostic, Linter},
+    metadata::MetadataTree,
     metrics::increment,
+    native::{FromNickel, NativeFunction, NativeResult},
+    policy::CapabilityPolicy,
+    provenance::Provenance,
+    serialize::{ExportedValue, JsonLayout, KeyOrder},
     term::{
         make::{self as mk_term, builder},
         record::Field,
-        BinaryOp, MergePriority, RichTerm, Term,
+        BinaryOp, MergePriority, RecordOpKind, RichTerm, RuntimeContract, Term,
     },
+    typ::{Type, TypeF},
     typecheck::TypecheckMode,
 };
 
-use clap::ColorChoice;
+#[cfg(feature = "plugins")]
+use crate::plugin::{self, CapabilityGrant, Plugin, PluginError};
+
 use codespan_reporting::term::termcolor::{Ansi, NoColor, WriteColor};
+use serde::de::DeserializeOwned;
 
 use std::{
+    collections::{HashMap, HashSet},
     ffi::OsString,
     fmt,
     io::{self, Read, Write},
-    path::PathBuf,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    rc::Rc,
     result::Result,
 };
 
@@ -181,6 +202,8 @@ pub struct Program<EC: EvalCache> {
     vm: VirtualMachine<Cache, EC>,
     /// The color option to use when reporting errors.
     pub color_opt: ColorOpt,
+    /// The style of the errors reported as text.
+    pub report_style: ReportStyle,
     /// A list of [`FieldOverride`]s. During [`prepare_eval`], each
     /// override is imported in a separate in-memory source, for complete isolation (this way,
     /// overrides can't accidentally or intentionally capture other fields of the configuration).
@@ -188,10 +211,69 @@ pub struct Program<EC: EvalCache> {
     /// an import referring to the corresponding isolated value. This stub is finally merged with
     /// the current program before being evaluated for import.
     overrides: Vec<FieldOverride>,
+    /// Overrides given as terms by the embedder. See [Self::add_override].
+    value_overrides: Vec<(FieldPath, RichTerm)>,
     /// A specific field to act on. It is empty by default, which means that the whole program will
     /// be evaluated, but it can be set by the user (for example by the `--field` argument of the
     /// CLI) to evaluate only a specific field.
     pub field: FieldPath,
+    /// The warnings emitted by the program itself, as opposed to the ones emitted by the
+    /// evaluation, which are collected by the virtual machine.
+    warnings: Vec<Warning>,
+    /// If set, warnings are turned into errors.
+    deny_warnings: bool,
+}
+
+/// A program whose sources, imports and standard library have been parsed, typechecked and
+/// transformed, but not evaluated, created by [Program::snapshot].
+///
+/// Creating a new program from a snapshot is cheap: the terms and the sources are shared with
+/// the snapshot, and only the evaluation state is fresh. This is useful to evaluate the same
+/// configuration for many tenants with different overrides, without redoing the common work
+/// for each of them. Programs created from the same snapshot don't share any mutable state: a
+/// program can add overrides or sources to its own cache, or be evaluated, without affecting the
+/// snapshot or the other programs.
+#[derive(Clone)]
+pub struct ProgramSnapshot {
+    main_id: FileId,
+    cache: Cache,
+    color_opt: ColorOpt,
+    report_style: ReportStyle,
+    overrides: Vec<FieldOverride>,
+    value_overrides: Vec<(FieldPath, RichTerm)>,
+    field: FieldPath,
+    /// The settings of the virtual machine, which don't travel with the cache.
+    policy: CapabilityPolicy,
+    limits: ResourceLimits,
+    reproducible: bool,
+    deny_warnings: bool,
+    contract_trace: bool,
+}
+
+impl ProgramSnapshot {
+    /// Create a new program from this snapshot.
+    pub fn to_program<EC: EvalCache>(&self, trace: impl Write + 'static) -> Program<EC> {
+        increment!("Program::new");
+
+        let mut program = Program {
+            main_id: self.main_id,
+            vm: VirtualMachine::new(self.cache.clone(), trace),
+            color_opt: self.color_opt,
+            report_style: self.report_style,
+            overrides: self.overrides.clone(),
+            value_overrides: self.value_overrides.clone(),
+            field: self.field.clone(),
+            warnings: Vec::new(),
+            deny_warnings: false,
+        };
+
+        program.set_policy(self.policy.clone());
+        program.set_resource_limits(self.limits);
+        program.set_reproducible(self.reproducible);
+        program.set_deny_warnings(self.deny_warnings);
+        program.set_contract_trace(self.contract_trace);
+        program
+    }
 }
 
 /// The Possible Input Sources, anything that a Nickel program can be created from
@@ -233,9 +315,13 @@ impl<EC: EvalCache> Program<EC> {
         Ok(Self {
             main_id,
             vm,
-            color_opt: clap::ColorChoice::Auto.into(),
+            color_opt: ColorOpt::default(),
+            report_style: ReportStyle::default(),
             overrides: Vec::new(),
+            value_overrides: Vec::new(),
             field: FieldPath::new(),
+            warnings: Vec::new(),
+            deny_warnings: false,
         })
     }
 
@@ -282,9 +368,13 @@ impl<EC: EvalCache> Program<EC> {
         Ok(Self {
             main_id,
             vm,
-            color_opt: clap::ColorChoice::Auto.into(),
+            color_opt: ColorOpt::default(),
+            report_style: ReportStyle::default(),
             overrides: Vec::new(),
+            value_overrides: Vec::new(),
             field: FieldPath::new(),
+            warnings: Vec::new(),
+            deny_warnings: false,
         })
     }
 
@@ -326,6 +416,41 @@ impl<EC: EvalCache> Program<EC> {
         Self::new_from_input(Input::Source(source, source_name), trace)
     }
 
+    /// Create a program from a set of in-memory sources forming a virtual file tree, indexed by
+    /// path. The main program is the source at `main`. Imports are resolved inside the set,
+    /// relatively to the importing source, as they would be on the filesystem: from `lib/a.ncl`,
+    /// `import "./b.ncl"` refers to `lib/b.ncl`. The filesystem is never accessed.
+    ///
+    /// Fail with [std::io::ErrorKind::NotFound] if there's no source at `main`.
+    pub fn new_from_memory<I, P, S>(
+        sources: I,
+        main: impl AsRef<Path>,
+        trace: impl Write + 'static,
+    ) -> std::io::Result<Self>
+    where
+        I: IntoIterator<Item = (P, S)>,
+        P: Into<PathBuf>,
+        S: Into<String>,
+    {
+        let mut loader = MemoryLoader::new();
+
+        for (path, source) in sources {
+            loader.add(path, source);
+        }
+
+        let main_path = loader.resolve(main.as_ref(), None).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no in-memory source at `{}`", main.as_ref().display()),
+            )
+        })?;
+        let source = loader.load(&main_path)?;
+
+        let mut program = Self::new_from_source(io::Cursor::new(source), main_path, trace)?;
+        program.set_import_loader(loader);
+        Ok(program)
+    }
+
     /// Create program from possibly multiple sources. The main program will be
     /// the [`BinaryOp::Merge`] of all the inputs.
     pub fn new_from_sources<I, T, S>(
@@ -341,6 +466,44 @@ impl<EC: EvalCache> Program<EC> {
         Self::new_from_inputs(inputs, trace)
     }
 
+    /// Create a program validating a data file against a contract. The format of the data file is
+    /// determined by its extension, and `contract` is a Nickel file evaluating to a contract, such
+    /// as a record contract. Evaluating the program applies the contract to the data: violations
+    /// are reported as blame errors pointing to the data file.
+    pub fn new_validate(
+        data: impl Into<OsString>,
+        contract: impl Into<OsString>,
+        trace: impl Write + 'static,
+    ) -> Self {
+        increment!("Program::new");
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+
+        let data: OsString = data.into();
+        let format = InputFormat::from_path(Path::new(&data)).unwrap_or_default();
+        let data = RichTerm::from(Term::Import { path: data, format });
+        let contract = RichTerm::from(Term::Import {
+            path: contract.into(),
+            format: InputFormat::Nickel,
+        });
+
+        let main_id = cache.add_string(
+            SourcePath::Generated("validate".into()),
+            format!("let contract = {contract} in ({data}) | contract"),
+        );
+
+        Self {
+            main_id,
+            vm: VirtualMachine::new(cache, trace),
+            color_opt: ColorOpt::default(),
+            report_style: ReportStyle::default(),
+            overrides: Vec::new(),
+            value_overrides: Vec::new(),
+            field: FieldPath::new(),
+            warnings: Vec::new(),
+            deny_warnings: false,
+        }
+    }
+
     /// Parse an assignment of the form `path.to_field=value` as an override, with the provided
     /// merge priority. Assignments are typically provided by the user on the command line, as part
     /// of the customize mode.
@@ -367,6 +530,55 @@ impl<EC: EvalCache> Program<EC> {
         self.overrides.extend(overrides);
     }
 
+    /// Override the field at `path` with `value`, which is merged on top of the program with the
+    /// [top priority](MergePriority::Top) before evaluation, as with the `--override` argument of
+    /// the CLI. The field doesn't need to exist in the program.
+    ///
+    /// As opposed to [Self::add_overrides], the value is a term built by the embedder, for
+    /// example with [crate::term::make] or by deserializing data, rather than Nickel source code.
+    /// It isn't typechecked nor transformed, so it must be a value without free variables nor
+    /// imports.
+    pub fn add_override(&mut self, path: FieldPath, value: RichTerm) {
+        self.value_overrides.push((path, value));
+    }
+
+    /// Serve the imports of the program from `loader` instead of the filesystem, for example to
+    /// serve them from memory, a database or an archive. See [ImportLoader].
+    ///
+    /// The main source of a program created by [Self::new_from_source] is still the given source,
+    /// but an input given as a path to [Self::new_from_inputs] is loaded by the loader as well.
+    pub fn set_import_loader(&mut self, loader: impl ImportLoader + 'static) {
+        self.vm.import_resolver_mut().set_import_loader(loader);
+    }
+
+    /// Enable imports of remote files over HTTPS with the given configuration, or disable them if
+    /// `config` is `None`, which is the default. See [crate::http_import].
+    #[cfg(feature = "http-imports")]
+    pub fn set_http_imports(&mut self, config: Option<crate::http_import::HttpImportConfig>) {
+        self.vm.import_resolver_mut().set_http_imports(config);
+    }
+
+    /// Enable imports from git repositories with the given configuration, or disable them if
+    /// `config` is `None`, which is the default. See [crate::git_import].
+    #[cfg(feature = "git-imports")]
+    pub fn set_git_imports(&mut self, config: Option<crate::git_import::GitImportConfig>) {
+        self.vm.import_resolver_mut().set_git_imports(config);
+    }
+
+    /// The configuration of git imports, including the commits pinned while resolving the imports
+    /// of the program in update mode.
+    #[cfg(feature = "git-imports")]
+    pub fn git_imports(&self) -> Option<&crate::git_import::GitImportConfig> {
+        self.vm.import_resolver().git_imports()
+    }
+
+    /// Set the root directories of the packages which can be imported with the `pkg:` prefix,
+    /// indexed by name. See [crate::package].
+    #[cfg(feature = "packages")]
+    pub fn set_packages(&mut self, packages: std::collections::BTreeMap<String, PathBuf>) {
+        self.vm.import_resolver_mut().set_packages(packages);
+    }
+
     /// Adds import paths to the end of the list.
     pub fn add_import_paths<P>(&mut self, paths: impl Iterator<Item = P>)
     where
@@ -375,6 +587,30 @@ impl<EC: EvalCache> Program<EC> {
         self.vm.import_resolver_mut().add_import_paths(paths);
     }
 
+    /// The files on disk read by the program: the main files and the files they import,
+    /// transitively, in the order in which they have been found. Only the imports resolved so far
+    /// are known, so the list is only complete once the program has been evaluated, even if the
+    /// evaluation failed. Sources which aren't files, such as the standard input or in-memory
+    /// sources, are skipped.
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        let cache = self.vm.import_resolver();
+        let mut seen = HashSet::from([self.main_id]);
+        let mut stack = vec![self.main_id];
+        let mut files = Vec::new();
+
+        while let Some(file_id) = stack.pop() {
+            if let Some(path) = cache.get_path(file_id).map(Path::new) {
+                if path.is_file() {
+                    files.push(path.to_owned());
+                }
+            }
+
+            stack.extend(cache.get_imports(file_id).filter(|id| seen.insert(*id)));
+        }
+
+        files
+    }
+
     /// Only parse the program, don't typecheck or evaluate. returns the [`RichTerm`] AST
     pub fn parse(&mut self) -> Result<RichTerm, Error> {
         self.vm
@@ -427,7 +663,7 @@ impl<EC: EvalCache> Program<EC> {
     fn prepare_eval_impl(&mut self, for_query: bool) -> Result<Closure, Error> {
         // If there are no overrides, we avoid the boilerplate of creating an empty record and
         // merging it with the current program
-        let prepared_body = if self.overrides.is_empty() {
+        let prepared_body = if self.overrides.is_empty() && self.value_overrides.is_empty() {
             self.vm.prepare_eval(self.main_id)?
         } else {
             let mut record = builder::Record::new();
@@ -444,6 +680,13 @@ impl<EC: EvalCache> Program<EC> {
                     .value(Term::ResolvedImport(value_file_id));
             }
 
+            for (path, value) in self.value_overrides.iter().cloned() {
+                record = record
+                    .path(path.0)
+                    .priority(MergePriority::Top)
+                    .value(value);
+            }
+
             let t = self.vm.prepare_eval(self.main_id)?;
             let built_record = record.build();
             // For now, we can't do much better than using `Label::default`, but this is
@@ -492,6 +735,33 @@ impl<EC: EvalCache> Program<EC> {
         Ok(self.vm.eval_full_closure(prepared)?.body)
     }
 
+    /// Fully evaluate several fields of the program, as [Self::eval_full] does for `self.field`,
+    /// and return the results indexed by path. `self.field` is ignored.
+    ///
+    /// The program is prepared and evaluated to a weak head normal form once, and the fields are
+    /// then evaluated in the same cache: a value shared by several fields is only evaluated once.
+    /// This is cheaper than evaluating each field by a separate program. The evaluation stops at
+    /// the first error.
+    pub fn eval_fields(
+        &mut self,
+        paths: &[FieldPath],
+    ) -> Result<HashMap<FieldPath, RichTerm>, Error> {
+        let prepared = self.prepare_query()?;
+
+        self.vm.reset();
+        let root = self.vm.eval_closure(prepared)?;
+        let mut results = HashMap::with_capacity(paths.len());
+
+        for path in paths {
+            self.vm.reset();
+            let field = self.vm.extract_field_value_closure(root.clone(), path)?;
+            let value = self.vm.eval_full_closure(field)?.body;
+            results.insert(path.clone(), value);
+        }
+
+        Ok(results)
+    }
+
     /// Same as `eval`, but proceeds to a full evaluation. Optionally take a set of overrides that
     /// are to be applied to the term (in practice, to be merged with).
     ///
@@ -512,6 +782,107 @@ impl<EC: EvalCache> Program<EC> {
         Ok(self.vm.eval_full_for_export_closure(prepared)?)
     }
 
+    /// Same as [Self::eval_full_for_export], but validate the result, which can then be
+    /// serialized with any serde data format. See [ExportedValue].
+    pub fn eval_exported_value(&mut self) -> Result<ExportedValue, Error> {
+        Ok(ExportedValue::new(self.eval_full_for_export()?)?)
+    }
+
+    /// Evaluate the program for export and deserialize the result to a Rust value. A
+    /// deserialization failure is an [crate::error::ExportError] located at the offending value,
+    /// as for [crate::deserialize::from_term].
+    pub fn eval_deserialize<T: DeserializeOwned>(&mut self) -> Result<T, Error> {
+        Ok(crate::deserialize::from_term(
+            &self.eval_full_for_export()?,
+        )?)
+    }
+
+    /// Same as [Self::eval_deserialize], but check the result against the contract of `T` first.
+    /// A mismatch is then reported as a blame error pointing to the offending value of the
+    /// program, with the expected shape taken from the contract. See [crate::contract].
+    pub fn eval_deserialize_checked<T>(&mut self) -> Result<T, Error>
+    where
+        T: NickelContract + DeserializeOwned,
+    {
+        let config = self.eval_full_for_export()?;
+
+        let contract_id = self.vm.import_resolver_mut().add_string(
+            SourcePath::Generated(format!("contract of {}", std::any::type_name::<T>())),
+            T::contract(),
+        );
+        let contract = self.vm.prepare_eval(contract_id)?;
+
+        let mut label = Label {
+            typ: Rc::new(Type::from(TypeF::Contract(contract.clone()))),
+            ..Default::default()
+        };
+
+        if let Some(span) = contract.pos.into_opt() {
+            label.span = span;
+        }
+
+        let checked = RuntimeContract::new(contract, label).apply(config.clone(), config.pos);
+        self.vm.reset();
+        let checked = self.vm.eval_full_for_export(checked)?;
+
+        Ok(crate::deserialize::from_term(&checked)?)
+    }
+
+    /// Evaluate the program and write the result as JSON with the given layout and order of
+    /// fields, forcing each value only when it's written instead of evaluating the whole result
+    /// first. See [crate::eval::stream]. The output is the same as the one of
+    /// [Self::eval_full_for_export] followed by [crate::serialize::to_writer_json], but an error
+    /// might be raised after a part of it has been written.
+    pub fn stream_export_json(
+        &mut self,
+        writer: impl Write,
+        layout: JsonLayout,
+        key_order: KeyOrder,
+    ) -> Result<(), Error> {
+        let prepared = self.prepare_eval()?;
+
+        self.vm.reset();
+        self.vm
+            .stream_json_closure(prepared, writer, layout, key_order)
+    }
+
+    /// Asynchronous version of [Self::eval_full_for_export]. The evaluation is run by slices of
+    /// `steps_per_yield` steps of the virtual machine, yielding to the executor in-between, so that
+    /// a long evaluation doesn't block an executor thread. It doesn't depend on any specific async
+    /// runtime.
+    ///
+    /// The preparation of the program (parsing, typechecking and program transformations) isn't
+    /// interruptible and happens on the first poll. Since a program isn't `Send`, the returned
+    /// future isn't either: with Tokio, it must be run on a `LocalSet`. Dropping the future
+    /// cancels the evaluation; [Self::set_cancellation_token] can be used as well to cancel it
+    /// from another thread.
+    pub async fn eval_full_for_export_async(
+        &mut self,
+        steps_per_yield: NonZeroUsize,
+    ) -> Result<RichTerm, Error> {
+        let prepared = self.prepare_eval()?;
+
+        self.vm.reset();
+        Ok(self
+            .vm
+            .eval_full_for_export_closure_async(prepared, steps_per_yield)
+            .await?)
+    }
+
+    /// Attach a cancellation token to this program, or detach the current one if `token` is
+    /// `None`. Once the token is cancelled, the evaluation of the program stops with
+    /// [EvalError::Cancelled] shortly after.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.vm.set_cancellation_token(token);
+    }
+
+    /// Set the limits on the resources used by each evaluation of the program. Once a limit is
+    /// exceeded, the evaluation stops with [EvalError::ResourceExhausted]. See
+    /// [crate::eval::limits].
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        self.vm.set_resource_limits(limits);
+    }
+
     /// Same as `eval_full`, but does not substitute all variables.
     pub fn eval_deep(&mut self) -> Result<RichTerm, Error> {
         let prepared = self.prepare_eval()?;
@@ -528,6 +899,168 @@ impl<EC: EvalCache> Program<EC> {
         Ok(self.vm.query_closure(prepared, &self.field)?)
     }
 
+    /// The names of the fields of the record at `path`, in alphabetical order, excluding the
+    /// optional fields without definition. `self.field` is ignored. The list is empty if the value
+    /// at `path` isn't a record.
+    ///
+    /// This is a fast path for completing field paths: only the records along `path` are
+    /// evaluated, to a weak head normal form, and the values of their fields aren't evaluated.
+    pub fn field_names(&mut self, path: &FieldPath) -> Result<Vec<LocIdent>, Error> {
+        let prepared = self.prepare_query()?;
+        let field = self.vm.query_closure(prepared, path)?;
+
+        Ok(match field.value.as_ref().map(RichTerm::as_ref) {
+            Some(Term::Record(data)) => data.field_names(RecordOpKind::IgnoreEmptyOpt),
+            _ => Vec::new(),
+        })
+    }
+
+    /// Evaluate the program, or the field selected by `self.field`, to a weak head normal form,
+    /// and return the metadata of its fields as a tree, without forcing their values. See
+    /// [crate::metadata]. The tree is empty if the program doesn't evaluate to a record.
+    pub fn metadata(&mut self) -> Result<MetadataTree, Error> {
+        let rt = self.eval()?;
+        Ok(MetadataTree::from_term(&rt).unwrap_or_default())
+    }
+
+    /// Collect statistics about the terms, thunks, environments and interned strings currently
+    /// alive in this program. See [crate::eval::memory].
+    pub fn memory_stats(&self) -> MemoryStats {
+        self.vm.memory_stats()
+    }
+
+    /// Enable or disable demand tracking, which is required by [Self::unused]. See
+    /// [crate::eval::unused].
+    pub fn set_track_demand(&mut self, enabled: bool) {
+        self.vm.set_track_demand(enabled);
+    }
+
+    /// Return the record fields and let bindings of the program which haven't been evaluated
+    /// since demand tracking has been enabled. See [crate::eval::unused].
+    pub fn unused(&self) -> Vec<Unused> {
+        self.vm.unused()
+    }
+
+    /// Enable or disable contract tracing, which records the contract applications and polarity
+    /// flips of the next evaluations. See [crate::eval::contract_trace].
+    pub fn set_contract_trace(&mut self, enabled: bool) {
+        self.vm.set_contract_trace(enabled);
+    }
+
+    /// Return the contract events recorded since the last call to this function. See
+    /// [crate::eval::contract_trace].
+    pub fn take_contract_trace(&mut self) -> ContractTrace {
+        self.vm.take_contract_trace()
+    }
+
+    /// Run the rules of `linter` on the source of the program, without evaluating it. See
+    /// [crate::lint].
+    pub fn lint(&self, linter: &Linter) -> Result<Vec<LintDiagnostic>, Error> {
+        let (rt, errors) = self.vm.import_resolver().parse_nocache(self.main_id)?;
+
+        if !errors.no_errors() {
+            return Err(Error::ParseErrors(errors));
+        }
+
+        Ok(linter.lint(&rt))
+    }
+
+    /// Evaluate the program fully, as for an export, and check the result against the rules
+    /// defined in the file at `rules`. Return the violated rules, sorted by name. See
+    /// [crate::check].
+    pub fn check_rules(&mut self, rules: impl Into<OsString>) -> Result<Vec<Violation>, Error> {
+        let config = self.eval_full_for_export()?;
+
+        let rules_id = self
+            .vm
+            .import_resolver_mut()
+            .add_file(rules, InputFormat::Nickel)
+            .map_err(IOError::from)?;
+        let rules = self.vm.prepare_eval(rules_id)?;
+        self.vm.reset();
+        let rules = self.vm.eval_closure(Closure::atomic_closure(rules))?.body;
+
+        let Term::Record(record) = rules.as_ref() else {
+            return Err(EvalError::TypeError(
+                String::from("Record"),
+                String::from("a rules file must evaluate to a record of contracts"),
+                rules.pos,
+                rules.clone(),
+            )
+            .into());
+        };
+
+        let mut rules: Vec<_> = record
+            .fields
+            .iter()
+            .filter_map(|(id, field)| Some((id, field, field.value.as_ref()?)))
+            .collect();
+        rules.sort_by(|(id1, ..), (id2, ..)| id1.label().cmp(id2.label()));
+
+        let mut violations = Vec::new();
+
+        for (id, field, contract) in rules {
+            let rule_span = contract.pos.into_opt().or(id.pos.into_opt());
+            let mut label = Label {
+                typ: Rc::new(Type::from(TypeF::Contract(contract.clone()))),
+                ..Default::default()
+            };
+
+            if let Some(span) = rule_span {
+                label.span = span;
+            }
+
+            let checked =
+                RuntimeContract::new(contract.clone(), label).apply(config.clone(), config.pos);
+            self.vm.reset();
+
+            match self.vm.eval_deep_closure(Closure::atomic_closure(checked)) {
+                Ok(_) => (),
+                Err(EvalError::BlameError {
+                    evaluated_arg,
+                    label,
+                    ..
+                }) => {
+                    let diagnostic = label.current_diagnostic();
+
+                    violations.push(Violation {
+                        rule: id.label().to_owned(),
+                        rule_span,
+                        message: diagnostic
+                            .and_then(|diagnostic| diagnostic.message.clone())
+                            .or_else(|| field.metadata.doc.clone()),
+                        notes: diagnostic
+                            .map(|diagnostic| diagnostic.notes.clone())
+                            .unwrap_or_default(),
+                        field: label.field_name,
+                        value_pos: evaluated_arg.as_ref().map_or(label.arg_pos, |arg| arg.pos),
+                        provenance: evaluated_arg.as_ref().and_then(|arg| {
+                            Provenance::of(arg, None, self.vm.import_resolver().files())
+                        }),
+                    });
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(violations)
+    }
+
+    /// Parse the program and resolve its imports, transitively, without typechecking nor
+    /// evaluating it.
+    pub fn resolve_imports(&mut self) -> Result<(), Error> {
+        self.vm
+            .import_resolver_mut()
+            .parse(self.main_id, InputFormat::Nickel)?;
+        self.vm
+            .import_resolver_mut()
+            .resolve_imports(self.main_id)
+            .map_err(|cache_err| {
+                cache_err.unwrap_error("program::resolve_imports(): expected source to be parsed")
+            })?;
+        Ok(())
+    }
+
     /// Load, parse, and typecheck the program and the standard library, if not already done.
     pub fn typecheck(&mut self, initial_mode: TypecheckMode) -> Result<(), Error> {
         self.vm
@@ -553,12 +1086,217 @@ impl<EC: EvalCache> Program<EC> {
         Ok(())
     }
 
+    /// Register a native function `fun`, implemented in Rust, under the name `name`. It's then
+    /// available to the program as `native.<name>`. See [crate::native] for the supported
+    /// argument and return types.
+    ///
+    /// Native functions must be registered before the program is typechecked or evaluated.
+    pub fn register_fn<A, R, F>(&mut self, name: impl AsRef<str>, fun: F)
+    where
+        A: FromNickel,
+        R: NativeResult,
+        F: Fn(A) -> R + 'static,
+    {
+        self.vm
+            .import_resolver_mut()
+            .register_native(NativeFunction::new(name, fun));
+    }
+
+    /// Register a native function built beforehand, for example to declare it as
+    /// [deterministic](NativeFunction::deterministic). See [Self::register_fn].
+    pub fn register_native(&mut self, fun: NativeFunction) {
+        self.vm.import_resolver_mut().register_native(fun);
+    }
+
+    /// Bind each identifier of `bindings` to the associated value in the initial environment of the
+    /// program, as with the standard library. Hosts can use it to expose values such as build
+    /// metadata or feature flags without templating them into the source. Bindings are typed as
+    /// `Dyn` and shadowed by any local definition of the same name.
+    ///
+    /// As for [Self::add_override], values aren't typechecked nor transformed, so they must not
+    /// have free variables nor imports. Bindings must be added before the program is typechecked
+    /// or evaluated.
+    pub fn add_bindings(&mut self, bindings: impl IntoIterator<Item = (Ident, RichTerm)>) {
+        for (id, value) in bindings {
+            self.vm.import_resolver_mut().add_binding(id, value);
+        }
+    }
+
+    /// Builder variant of [Self::add_bindings].
+    pub fn with_bindings(mut self, bindings: impl IntoIterator<Item = (Ident, RichTerm)>) -> Self {
+        self.add_bindings(bindings);
+        self
+    }
+
+    /// Register the functions of a plugin. See [crate::plugin].
+    #[cfg(feature = "plugins")]
+    pub fn register_plugin(&mut self, plugin: Plugin) {
+        for fun in plugin.functions {
+            self.vm.import_resolver_mut().register_native(fun);
+        }
+    }
+
+    /// Load the plugin at `path` and register its functions, provided that all the capabilities
+    /// it requires are in `grant`. Plugins must be loaded before the program is typechecked or
+    /// evaluated.
+    ///
+    /// # Safety
+    ///
+    /// See [crate::plugin::load].
+    #[cfg(feature = "plugins")]
+    pub unsafe fn load_plugin(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        grant: &CapabilityGrant,
+    ) -> Result<(), PluginError> {
+        self.register_plugin(plugin::load(path, grant)?);
+        Ok(())
+    }
+
+    /// Parse, typecheck and transform the program, its imports and the standard library if not
+    /// already done, and take a snapshot of the result. See [ProgramSnapshot].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program has already been evaluated: the prepared terms then hold evaluation
+    /// state, which can't be shared between programs.
+    pub fn snapshot(&mut self) -> Result<ProgramSnapshot, Error> {
+        let Envs { type_ctxt, .. } = self.vm.prepare_stdlib()?;
+        let cache = self.vm.import_resolver_mut();
+        cache.prepare(self.main_id, &type_ctxt)?;
+
+        assert!(
+            cache.entry_state(self.main_id) < Some(EntryState::Closurized),
+            "program::snapshot(): can't take a snapshot of a program which has been evaluated"
+        );
+
+        Ok(ProgramSnapshot {
+            main_id: self.main_id,
+            cache: cache.clone(),
+            color_opt: self.color_opt,
+            report_style: self.report_style,
+            overrides: self.overrides.clone(),
+            value_overrides: self.value_overrides.clone(),
+            field: self.field.clone(),
+            policy: self.vm.policy().clone(),
+            limits: self.vm.resource_limits(),
+            reproducible: self.vm.is_reproducible(),
+            deny_warnings: self.deny_warnings,
+            contract_trace: self.vm.is_contract_trace_enabled(),
+        })
+    }
+
+    /// Enable or disable the reproducible mode, which rejects the constructs that could make the
+    /// result of the evaluation differ across runs or machines:
+    ///
+    /// - calls to native functions and plugins, unless registered as
+    ///   [deterministic](NativeFunction::deterministic),
+    /// - floating-point operations depending on the math library of the platform, such as
+    ///   `std.number.sin`,
+    /// - imports of absolute paths,
+    /// - evaluation of Nix code, with the `nix-experimental` feature.
+    ///
+    /// Serialization is always deterministic, as record fields are exported in alphabetical order.
+    /// Note that the embedder remains responsible for the import paths it adds with
+    /// [Self::add_import_paths].
+    pub fn set_reproducible(&mut self, reproducible: bool) {
+        self.vm.set_reproducible(reproducible);
+        self.vm.import_resolver_mut().set_reproducible(reproducible);
+    }
+
+    /// Set the capability policy of the program, restricting the files it can import and the
+    /// native functions it can call. Denied actions are reported as
+    /// [EvalError::CapabilityDenied] or
+    /// [ImportError::CapabilityDenied](crate::error::ImportError::CapabilityDenied). See
+    /// [crate::policy].
+    ///
+    /// The policy doesn't apply to the main file of the program and to the inputs given to
+    /// [Self::new_from_inputs], which are chosen by the embedder, but to the imports they contain.
+    pub fn set_policy(&mut self, policy: CapabilityPolicy) {
+        // The virtual machine checks native calls and the cache checks imports. Both copies must
+        // stay in sync, which is why [ProgramSnapshot::to_program] goes through this method too.
+        self.vm.set_policy(policy.clone());
+        self.vm.import_resolver_mut().set_policy(policy);
+    }
+
+    /// Builder variant of [Self::set_policy] with [CapabilityPolicy::sandboxed], to safely evaluate
+    /// snippets submitted by users: importing files and calling native functions or plugins fail
+    /// with a capability error. The rest of the language is pure.
+    ///
+    /// The sandbox doesn't bound the resources used by the evaluation, which should be limited as
+    /// well with [Self::set_resource_limits].
+    pub fn sandboxed(mut self) -> Self {
+        self.set_policy(CapabilityPolicy::sandboxed());
+        self
+    }
+
+    /// Turn warnings, such as accesses to deprecated fields, into errors. See
+    /// [VirtualMachine::set_deny_warnings] and [Self::warn].
+    pub fn set_deny_warnings(&mut self, deny: bool) {
+        self.deny_warnings = deny;
+        self.vm.set_deny_warnings(deny);
+    }
+
+    /// Emit a warning about this program, or fail with the corresponding error if warnings are
+    /// denied. Identical warnings are only collected once.
+    pub fn warn(&mut self, warning: impl Into<Warning>) -> Result<(), Error> {
+        let warning = warning.into();
+
+        if self.deny_warnings {
+            Err(warning.into())
+        } else {
+            if !self.warnings.contains(&warning) {
+                self.warnings.push(warning);
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Return the warnings emitted by this program, either directly with [Self::warn] or by its
+    /// evaluations, since the last call to this function.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        let mut warnings = std::mem::take(&mut self.warnings);
+        warnings.extend(self.vm.take_warnings().into_iter().map(Warning::from));
+        warnings
+    }
+
+    /// Report the warnings returned by [Self::take_warnings] on the standard error stream, and
+    /// return how many have been emitted. Identical warnings at different locations are
+    /// aggregated (see [Aggregated]). If `deny` is `true`, the warnings are reported as errors
+    /// instead.
+    pub fn report_warnings(&mut self, format: ErrorFormat, deny: bool) -> usize {
+        let warnings = self.take_warnings();
+        let count = warnings.len();
+
+        if deny {
+            let errors = warnings.into_iter().map(Error::from).collect();
+            self.report(Aggregated(errors), format);
+        } else {
+            self.report(Aggregated(warnings), format);
+        }
+
+        count
+    }
+
+    /// Return a mutable reference to the cache of this program, holding the sources and the
+    /// terms of the program and of its imports.
+    pub fn cache_mut(&mut self) -> &mut Cache {
+        self.vm.import_resolver_mut()
+    }
+
     /// Wrapper for [`report`].
     pub fn report<E>(&mut self, error: E, format: ErrorFormat)
     where
         E: IntoDiagnostics,
     {
-        report(self.vm.import_resolver_mut(), error, format, self.color_opt)
+        report(
+            self.vm.import_resolver(),
+            error,
+            format,
+            self.color_opt,
+            self.report_style,
+        )
     }
 
     /// Wrapper for [`report_to_stdout`].
@@ -566,7 +1304,13 @@ impl<EC: EvalCache> Program<EC> {
     where
         E: IntoDiagnostics,
     {
-        report_to_stdout(self.vm.import_resolver_mut(), error, format, self.color_opt)
+        report_to_stdout(
+            self.vm.import_resolver(),
+            error,
+            format,
+            self.color_opt,
+            self.report_style,
+        )
     }
 
     /// Build an error report as a string and return it.
@@ -574,21 +1318,31 @@ impl<EC: EvalCache> Program<EC> {
     where
         E: IntoDiagnostics,
     {
-        let cache = self.vm.import_resolver_mut();
+        self.report_as_str_with_format(error, ErrorFormat::Text)
+    }
+
+    /// Same as [Self::report_as_str], but in the given format. Colors are only used for text
+    /// reports.
+    pub fn report_as_str_with_format<E>(&mut self, error: E, format: ErrorFormat) -> String
+    where
+        E: IntoDiagnostics,
+    {
+        let cache = self.vm.import_resolver();
 
         let mut buffer = Vec::new();
         let mut with_color;
         let mut no_color;
-        let writer: &mut dyn WriteColor = if self.color_opt.0 == ColorChoice::Never {
-            no_color = NoColor::new(&mut buffer);
-            &mut no_color
-        } else {
-            with_color = Ansi::new(&mut buffer);
-            &mut with_color
-        };
-
-        report_with(writer, &mut cache.files().clone(), error, ErrorFormat::Text);
-        // unwrap(): report_with() should only print valid utf8 to the the buffer
+        let writer: &mut dyn WriteColor =
+            if self.color_opt == ColorOpt::NEVER || format != ErrorFormat::Text {
+                no_color = NoColor::new(&mut buffer);
+                &mut no_color
+            } else {
+                with_color = Ansi::new(&mut buffer);
+                &mut with_color
+            };
+
+        report_with_style(writer, cache.files(), error, format, self.report_style);
+        // unwrap(): report_with_style() should only print valid utf8 to the the buffer
         String::from_utf8(buffer).unwrap()
     }
 
@@ -859,12 +1613,13 @@ impl<EC: EvalCache> Program<EC> {
 #[cfg(feature = "doc")]
 mod doc {
     use crate::error::{Error, ExportErrorData, IOError};
-    use crate::term::{RichTerm, Term};
+    use crate::pretty::PrettyPrintCap;
+    use crate::term::{MergePriority, RichTerm, Term};
     use comrak::arena_tree::NodeEdge;
     use comrak::nodes::{
         Ast, AstNode, ListDelimType, ListType, NodeCode, NodeHeading, NodeList, NodeValue,
     };
-    use comrak::{format_commonmark, parse_document, Arena, ComrakOptions};
+    use comrak::{format_commonmark, format_html, parse_document, Arena, ComrakOptions};
     use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
     use std::io::Write;
@@ -886,8 +1641,16 @@ mod doc {
         contracts: Vec<String>,
         /// Rendered documentation, if any
         documentation: Option<String>,
+        /// Rendered default value, if any. Only the values of fields which aren't records are
+        /// rendered.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        default: Option<String>,
     }
 
+    /// The maximum width of a rendered default value. Beyond this limit, the value is cut and
+    /// terminated by an ellipsis.
+    const DEFAULT_MAX_WIDTH: usize = 80;
+
     fn ast_node<'a>(val: NodeValue) -> AstNode<'a> {
         // comrak allows for ast nodes to be tagged with source location. This location
         // isn't need for rendering; it seems to be mainly for plugins to use. Since our
@@ -927,6 +1690,13 @@ mod doc {
 
                             let documentation = field.metadata.doc.clone();
 
+                            let default = match (&field.metadata.priority, &field.value, &fields) {
+                                (MergePriority::Bottom, Some(value), None) => {
+                                    Some(value.pretty_print_cap(DEFAULT_MAX_WIDTH))
+                                }
+                                _ => None,
+                            };
+
                             (
                                 ident.label().to_owned(),
                                 DocumentationField {
@@ -934,6 +1704,7 @@ mod doc {
                                     typ,
                                     contracts,
                                     documentation,
+                                    default,
                                 },
                             )
                         })
@@ -950,6 +1721,25 @@ mod doc {
         }
 
         pub fn write_markdown(&self, out: &mut dyn Write) -> Result<(), Error> {
+            self.write_with(out, format_commonmark)
+        }
+
+        /// Write the documentation as an HTML fragment, made of one section per field, with a
+        /// header level increasing with the depth of the field.
+        pub fn write_html(&self, out: &mut dyn Write) -> Result<(), Error> {
+            self.write_with(out, format_html)
+        }
+
+        /// Build the Markdown document and render it with the given comrak formatter.
+        fn write_with(
+            &self,
+            out: &mut dyn Write,
+            format: for<'a> fn(
+                &'a AstNode<'a>,
+                &ComrakOptions,
+                &mut dyn Write,
+            ) -> std::io::Result<()>,
+        ) -> Result<(), Error> {
             let document = ast_node(NodeValue::Document);
 
             // Our nodes in the Markdown document are owned by this arena
@@ -960,8 +1750,7 @@ mod doc {
             let options = ComrakOptions::default();
 
             self.markdown_append(0, &arena, &document, &options);
-            format_commonmark(&document, &options, out)
-                .map_err(|e| Error::IOError(IOError(e.to_string())))?;
+            format(&document, &options, out).map_err(|e| Error::IOError(IOError(e.to_string())))?;
 
             Ok(())
         }
@@ -982,12 +1771,13 @@ mod doc {
                 let header = mk_header(ident, header_level + 1, arena);
                 document.append(header);
 
-                if field.typ.is_some() || !field.contracts.is_empty() {
+                if field.typ.is_some() || !field.contracts.is_empty() || field.default.is_some() {
                     document.append(mk_types_and_contracts(
                         ident,
                         arena,
                         field.typ.as_deref(),
                         field.contracts.as_ref(),
+                        field.default.as_deref(),
                     ))
                 }
 
@@ -1084,6 +1874,7 @@ mod doc {
         arena: &'a Arena<AstNode<'a>>,
         typ: Option<&'a str>,
         contracts: &'a [String],
+        default: Option<&'a str>,
     ) -> &'a AstNode<'a> {
         let list = arena.alloc(ast_node(NodeValue::List(NodeList {
             list_type: ListType::Bullet,
@@ -1103,6 +1894,10 @@ mod doc {
             list.append(mk_type(ident, '|', contract, arena));
         }
 
+        if let Some(value) = default {
+            list.append(mk_type(ident, '=', value, arena));
+        }
+
         list
     }
 
@@ -1144,40 +1939,31 @@ mod doc {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use crate::error::EvalError;
-    use crate::eval::cache::CacheImpl;
+    use crate::error::{EvalError, EvalWarning, ImportError};
+    use crate::eval::{cache::CacheImpl, contract_trace::ContractEventKind, unused::UnusedKind};
     use crate::identifier::LocIdent;
+    use crate::label::Polarity;
+    use crate::mk_record;
+    use crate::policy::Capability;
     use crate::position::TermPos;
     use crate::term::array::ArrayAttrs;
     use assert_matches::assert_matches;
     use std::io::Cursor;
 
+    /// Create a program from the source `s`, whose diagnostics are discarded. Also used by the
+    /// tests of the other modules.
+    pub(crate) fn program(s: &str) -> Program<CacheImpl> {
+        Program::new_from_source(Cursor::new(s), "<test>", std::io::sink()).unwrap()
+    }
+
     fn eval_full(s: &str) -> Result<RichTerm, Error> {
-        let src = Cursor::new(s);
-
-        let mut p: Program<CacheImpl> = Program::new_from_source(src, "<test>", std::io::sink())
-            .map_err(|io_err| {
-                Error::EvalError(EvalError::Other(
-                    format!("IO error: {io_err}"),
-                    TermPos::None,
-                ))
-            })?;
-        p.eval_full()
+        program(s).eval_full()
     }
 
     fn typecheck(s: &str) -> Result<(), Error> {
-        let src = Cursor::new(s);
-
-        let mut p: Program<CacheImpl> = Program::new_from_source(src, "<test>", std::io::sink())
-            .map_err(|io_err| {
-                Error::EvalError(EvalError::Other(
-                    format!("IO error: {io_err}"),
-                    TermPos::None,
-                ))
-            })?;
-        p.typecheck(TypecheckMode::Walk)
+        program(s).typecheck(TypecheckMode::Walk)
     }
 
     #[test]
@@ -1214,12 +2000,995 @@ mod tests {
     }
 
     #[test]
-    // Regression test for issue 715 (https://github.com/tweag/nickel/issues/715)
-    // Check that program::typecheck() fail on parse error
-    fn typecheck_invalid_input() {
-        assert_matches!(
-            typecheck("{foo = 1 + `, bar : Str = \"a\"}"),
-            Err(Error::ParseErrors(_))
+    fn memory_stats() {
+        let mut p = program("let x = [1, 2, 3] in {foo = x, bar = std.array.length x}");
+
+        let before = p.memory_stats();
+        p.eval_full().unwrap();
+        let after = p.memory_stats();
+
+        assert!(after.terms.count > before.terms.count);
+        assert!(after.thunks.count > 0);
+        assert!(after.env_layers.count > 0);
+        assert!(after.interned_strings.count > 0);
+        assert!(after.total_bytes() > after.terms.bytes);
+    }
+
+    #[test]
+    fn native_functions() {
+        let program = |s: &str| -> Program<CacheImpl> {
+            let mut p = program(s);
+            p.register_fn("resolve_secret", |name: String| match name.as_str() {
+                "db" => Ok("hunter2"),
+                _ => Err(format!("unknown secret `{name}`")),
+            });
+            p.register_fn("double", |n: i64| n * 2);
+            p
+        };
+
+        assert_eq!(
+            program("native.resolve_secret \"db\" ++ \"!\"")
+                .eval_full()
+                .unwrap()
+                .without_pos(),
+            Term::Str("hunter2!".into()).into()
+        );
+
+        // Calls are typechecked against the declared type of the native function.
+        assert_matches!(
+            program("(native.double 21 : Number)").typecheck(TypecheckMode::Walk),
+            Ok(())
+        );
+        assert_matches!(
+            program("(native.double \"a\" : Number)").typecheck(TypecheckMode::Walk),
+            Err(Error::TypecheckError(_))
+        );
+
+        assert_matches!(
+            program("native.double \"a\"").eval_full(),
+            Err(Error::EvalError(EvalError::UnaryPrimopTypeError { .. }))
+        );
+        assert_matches!(
+            program("native.resolve_secret \"api\"").eval_full(),
+            Err(Error::EvalError(EvalError::Other(msg, pos)))
+                if msg.contains("unknown secret `api`") && pos.is_def()
+        );
+    }
+
+    #[test]
+    fn eval_async() {
+        use std::{
+            future::Future,
+            sync::Arc,
+            task::{Context, Poll, Wake, Waker},
+        };
+
+        struct NoopWaker;
+
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let mut p = program("{ sum = std.array.fold_left (+) 0 (std.array.range 0 100) }");
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(p.eval_full_for_export_async(NonZeroUsize::new(100).unwrap()));
+        let mut pending = 0;
+
+        let result = loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => break result.unwrap(),
+                Poll::Pending => pending += 1,
+            }
+        };
+
+        assert!(pending > 0);
+        assert_eq!(
+            crate::serialize::to_string(crate::serialize::ExportFormat::Json, &result).unwrap(),
+            "{\n  \"sum\": 4950\n}"
+        );
+    }
+
+    #[test]
+    fn cancellation() {
+        let mut p = program("std.array.fold_left (+) 0 (std.array.range 0 100000)");
+
+        let token = CancellationToken::new();
+        p.set_cancellation_token(Some(token.clone()));
+        token.cancel();
+
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::Cancelled(_)))
+        );
+    }
+
+    #[test]
+    fn cancellation_async() {
+        use std::{
+            future::Future,
+            sync::Arc,
+            task::{Context, Poll, Wake, Waker},
+        };
+
+        struct NoopWaker;
+
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        // The program is prepared by evaluating it to a weak head normal form before the first
+        // slice. The sum is wrapped in a record so that it's only computed by the slices.
+        let mut p = program("{ sum = std.array.fold_left (+) 0 (std.array.range 0 100000) }");
+
+        let token = CancellationToken::new();
+        p.set_cancellation_token(Some(token.clone()));
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        // The slices are shorter than the interval between two checks of the token.
+        let mut future = Box::pin(p.eval_full_for_export_async(NonZeroUsize::new(100).unwrap()));
+
+        // Let the evaluation start before cancelling it.
+        for _ in 0..10 {
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+        }
+        token.cancel();
+
+        let result = loop {
+            if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+                break result;
+            }
+        };
+
+        assert_matches!(result, Err(Error::EvalError(EvalError::Cancelled(_))));
+    }
+
+    #[test]
+    fn resource_limits() {
+        use crate::eval::limits::Resource;
+        use std::time::Duration;
+
+        let mut p = program("let rec loop = fun n => if n >= 0 then loop (n + 1) else n in loop 0");
+        p.set_resource_limits(ResourceLimits {
+            max_steps: Some(10_000),
+            ..Default::default()
+        });
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::ResourceExhausted {
+                resource: Resource::Steps(10_000),
+                ..
+            }))
+        );
+
+        let mut p = program("let rec loop = fun n => if n >= 0 then loop (n + 1) else n in loop 0");
+        p.set_resource_limits(ResourceLimits {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::ResourceExhausted {
+                resource: Resource::Time(_),
+                ..
+            }))
+        );
+
+        let mut p = program("std.array.generate (fun i => { value = i }) 100000");
+        p.set_resource_limits(ResourceLimits {
+            max_memory: Some(1_000_000),
+            ..Default::default()
+        });
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::ResourceExhausted {
+                resource: Resource::Memory(1_000_000),
+                ..
+            }))
+        );
+
+        // The budget is per evaluation, and not exhausted by a terminating one.
+        let mut p = program("std.array.fold_left (+) 0 (std.array.range 0 100)");
+        p.set_resource_limits(ResourceLimits {
+            max_steps: Some(100_000),
+            max_memory: Some(1_000_000),
+            ..Default::default()
+        });
+        for _ in 0..3 {
+            assert_eq!(p.eval_full().unwrap().without_pos(), mk_term::integer(4950));
+        }
+
+        // The limits are carried over to the programs created from a snapshot.
+        let mut p = program("let rec loop = fun n => if n >= 0 then loop (n + 1) else n in loop 0");
+        p.set_resource_limits(ResourceLimits {
+            max_steps: Some(10_000),
+            ..Default::default()
+        });
+        let mut p: Program<CacheImpl> = p.snapshot().unwrap().to_program(std::io::sink());
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::ResourceExhausted {
+                resource: Resource::Steps(10_000),
+                ..
+            }))
+        );
+    }
+
+    #[test]
+    fn memory_limit() {
+        let program = |s: &str| -> Program<CacheImpl> {
+            let mut p = program(s);
+            p.set_resource_limits(ResourceLimits {
+                max_memory: Some(100_000),
+                ..Default::default()
+            });
+            p
+        };
+
+        let mut p = program("std.array.map (fun i => { value = i + 1 }) (std.array.range 0 10000)");
+        let error = p.eval_full().unwrap_err();
+        assert_matches!(
+            error,
+            Error::EvalError(EvalError::ResourceExhausted {
+                resource: crate::eval::limits::Resource::Memory(100_000),
+                ..
+            })
+        );
+        assert!(p
+            .report_as_str(error)
+            .contains("evaluation exceeded the memory limit of 100000 bytes"));
+
+        // A program staying under the ceiling is unaffected.
+        let mut p = program("std.array.length (std.array.range 0 10)");
+        assert_eq!(p.eval_full().unwrap().as_i64(), Ok(10));
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let source =
+            |n: usize| format!("let rec f = fun n => if n == 0 then 0 else 1 + f (n - 1) in f {n}");
+        let limits = ResourceLimits {
+            max_depth: Some(1000),
+            ..Default::default()
+        };
+
+        let mut p = program(&source(100_000));
+        p.set_resource_limits(limits);
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::StackOverflow {
+                max_depth: 1000,
+                ..
+            }))
+        );
+
+        let mut p = program(&source(10));
+        p.set_resource_limits(limits);
+        assert_eq!(p.eval_full().unwrap().without_pos(), mk_term::integer(10));
+    }
+
+    #[test]
+    // Regression test for issue 715 (https://github.com/tweag/nickel/issues/715)
+    // Check that program::typecheck() fail on parse error
+    fn typecheck_invalid_input() {
+        assert_matches!(
+            typecheck("{foo = 1 + `, bar : Str = \"a\"}"),
+            Err(Error::ParseErrors(_))
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut base =
+            program("{ port | default = 80, url = \"http://localhost:%{std.to_string port}\" }");
+        let snapshot = base.snapshot().unwrap();
+
+        let url = |program: &mut Program<CacheImpl>| {
+            let result = program.eval_full().unwrap();
+            result.field("url").unwrap().as_str().unwrap().to_owned()
+        };
+
+        let mut tenant: Program<CacheImpl> = snapshot.to_program(std::io::sink());
+        let ovd = tenant
+            .parse_override("port=8080".to_owned(), MergePriority::Neutral)
+            .unwrap();
+        tenant.add_overrides([ovd]);
+        assert_eq!(url(&mut tenant), "http://localhost:8080");
+
+        let mut other: Program<CacheImpl> = snapshot.to_program(std::io::sink());
+        assert_eq!(url(&mut other), "http://localhost:80");
+        assert_eq!(url(&mut base), "http://localhost:80");
+    }
+
+    #[test]
+    fn value_overrides() {
+        let mut p = program(
+            "{ server.port | Number = 80, url = \"localhost:%{std.to_string server.port}\" }",
+        );
+
+        let port = p.parse_field_path("server.port".to_owned()).unwrap();
+        p.add_override(port, mk_term::integer(8080));
+        let debug = p.parse_field_path("debug".to_owned()).unwrap();
+        p.add_override(debug, Term::Bool(true).into());
+
+        let result = p.eval_full().unwrap();
+        assert_eq!(
+            result.field("url").unwrap().as_str().unwrap(),
+            "localhost:8080"
+        );
+        assert_eq!(
+            result.field("debug").unwrap().clone().without_pos(),
+            RichTerm::from(Term::Bool(true))
+        );
+    }
+
+    #[test]
+    fn host_bindings() {
+        let mut p =
+            program("{ version = \"v%{build.version}\", debug = if flags.debug then 1 else 0 }")
+                .with_bindings([
+                    (
+                        Ident::new("build"),
+                        mk_record!(("version", mk_term::string("1.2.0"))),
+                    ),
+                    (Ident::new("flags"), mk_record!(("debug", Term::Bool(true)))),
+                ]);
+
+        p.typecheck(TypecheckMode::Walk).unwrap();
+        let result = p.eval_full().unwrap();
+        assert_eq!(result.field("version").unwrap().as_str().unwrap(), "v1.2.0");
+        assert_eq!(
+            result.field("debug").unwrap().clone().without_pos(),
+            mk_term::integer(1)
+        );
+    }
+
+    #[test]
+    fn import_loader() {
+        let mut loader = MemoryLoader::new();
+        loader.add("lib/a.ncl", "{ value = (import \"../b.ncl\").base * 2 }");
+        loader.add("b.ncl", "{ base = 20 }");
+
+        let program = |source: &str| {
+            let mut p: Program<CacheImpl> =
+                Program::new_from_source(Cursor::new(source), "main.ncl", std::io::sink()).unwrap();
+            p.set_import_loader(loader.clone());
+            p
+        };
+
+        assert_eq!(
+            program("(import \"lib/a.ncl\").value + (import \"b.ncl\").base")
+                .eval_full()
+                .unwrap()
+                .as_i64(),
+            Ok(60)
+        );
+        assert_matches!(
+            program("import \"lib/missing.ncl\"").eval_full(),
+            Err(Error::ImportError(ImportError::IOError(..)))
+        );
+    }
+
+    #[test]
+    fn in_memory_program() {
+        let sources = [
+            (
+                "main.ncl",
+                "let lib = import \"./lib/lib.ncl\" in lib.greet \"world\"",
+            ),
+            (
+                "lib/lib.ncl",
+                "{ greet = fun name => \"%{(import \"./prefix.ncl\")} %{name}\" }",
+            ),
+            ("lib/prefix.ncl", "\"hello\""),
+        ];
+
+        let mut p: Program<CacheImpl> =
+            Program::new_from_memory(sources, "main.ncl", std::io::sink()).unwrap();
+        assert_eq!(p.eval_full().unwrap().as_str().unwrap(), "hello world");
+
+        assert_eq!(
+            Program::<CacheImpl>::new_from_memory(sources, "other.ncl", std::io::sink())
+                .err()
+                .map(|err| err.kind()),
+            Some(std::io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn error_api() {
+        let err = eval_full("let x = 1 in x + \"a\"").unwrap_err();
+
+        assert_eq!(err.kind(), crate::error::ErrorKind::Eval);
+        assert!(err.span().is_some());
+        assert!(err.file_id().is_some());
+        assert!(err
+            .to_string()
+            .starts_with("dynamic type error: (+) expects its 2nd argument to be a Number"));
+
+        let err: Box<dyn std::error::Error> = Box::new(eval_full("{ foo = bar }").unwrap_err());
+        assert_eq!(err.to_string(), "unbound identifier `bar`");
+    }
+
+    #[test]
+    fn deprecated_fields() {
+        let source = "let r = { old | deprecated \"use new\" = 1, new = 2 } in r.old + r.new";
+        let mut p = program(source);
+        assert_eq!(p.eval_full().unwrap().as_i64(), Ok(3));
+        let warnings = p.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_matches!(
+            &warnings[0],
+            Warning::EvalWarning(EvalWarning::DeprecatedField { id, message, pos_access })
+                if id.label() == "old" && message == "use new" && pos_access.into_opt().is_some()
+        );
+
+        let mut p = program("{ old | deprecated \"use new\" = 1, new = 2 }");
+        p.eval_full_for_export().unwrap();
+        assert_eq!(p.take_warnings().len(), 1);
+
+        for source in [
+            "{ old | deprecated \"use new\" | optional } & { old = 1 }",
+            "{ old = 1 } | { old | deprecated \"use new\" | optional }",
+        ] {
+            let mut p = program(source);
+            p.eval_full().unwrap();
+            let warnings = p.take_warnings();
+            assert_eq!(warnings.len(), 1);
+            assert_matches!(
+                &warnings[0],
+                Warning::EvalWarning(EvalWarning::DeprecatedField { id, pos_access, .. })
+                    if id.pos != *pos_access && pos_access.into_opt().is_some()
+            );
+        }
+
+        let mut p = program(source);
+        p.set_deny_warnings(true);
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::DeniedWarning(_)))
+        );
+    }
+
+    #[test]
+    fn program_warnings() {
+        let mut p = program("{ a = 1 }");
+        let warning = EvalWarning::DeprecatedField {
+            id: LocIdent::from("a"),
+            message: "use b".to_owned(),
+            pos_access: TermPos::None,
+        };
+
+        p.warn(warning.clone()).unwrap();
+        p.warn(warning.clone()).unwrap();
+        assert_eq!(p.take_warnings(), [Warning::from(warning.clone())]);
+        assert!(p.take_warnings().is_empty());
+
+        p.set_deny_warnings(true);
+        assert_matches!(
+            p.warn(warning),
+            Err(Error::EvalError(EvalError::DeniedWarning(_)))
+        );
+    }
+
+    #[test]
+    fn reproducible() {
+        let program = |source: &str| -> Program<CacheImpl> {
+            let mut p = program(source);
+            p.register_fn("now", |_: String| 1_700_000_000_i64);
+            p.register_native(NativeFunction::new("double", |x: i64| 2 * x).deterministic());
+            p.set_reproducible(true);
+            p
+        };
+
+        assert_eq!(
+            program("std.number.pow (native.double 2) 2")
+                .eval_full()
+                .unwrap()
+                .as_i64(),
+            Ok(16)
+        );
+
+        for source in [
+            "native.now \"utc\"",
+            "std.number.sin 1",
+            "std.number.log 3 5",
+            "std.number.pow 2 0.5",
+        ] {
+            assert_matches!(
+                program(source).eval_full(),
+                Err(Error::EvalError(EvalError::NonReproducible { .. })),
+                "{source}"
+            );
+        }
+
+        assert_matches!(
+            program("import \"/etc/nickel/defaults.ncl\"").eval_full(),
+            Err(Error::ImportError(ImportError::NonReproducible(..)))
+        );
+    }
+
+    #[test]
+    fn capability_policy() {
+        let dir = std::env::temp_dir().join(format!("nickel-policy-{}", std::process::id()));
+        let allowed = dir.join("allowed");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::fs::write(allowed.join("lib.ncl"), "{ port = 80 }").unwrap();
+        std::fs::write(dir.join("private.ncl"), r#"{ token = "hunter2" }"#).unwrap();
+
+        let program = |source: &str, policy: CapabilityPolicy| -> Program<CacheImpl> {
+            let mut p: Program<CacheImpl> = Program::new_from_source(
+                Cursor::new(source),
+                allowed.join("main.ncl"),
+                std::io::sink(),
+            )
+            .unwrap();
+            p.register_fn("now", |_: String| 1_700_000_000_i64);
+            p.register_native(
+                NativeFunction::new("env", |name: String| name.len() as i64)
+                    .requires(Capability::Env),
+            );
+            p.set_policy(policy);
+            p
+        };
+
+        for source in [
+            "(import \"lib.ncl\").port",
+            "(import \"../private.ncl\").token",
+            "native.now \"utc\"",
+            "native.env \"HOME\"",
+        ] {
+            assert!(
+                program(source, CapabilityPolicy::allow_all())
+                    .eval_full()
+                    .is_ok(),
+                "{source}"
+            );
+        }
+
+        assert_matches!(
+            program("import \"lib.ncl\"", CapabilityPolicy::deny_all()).eval_full(),
+            Err(Error::ImportError(ImportError::CapabilityDenied(..)))
+        );
+        assert_matches!(
+            program("native.now \"utc\"", CapabilityPolicy::deny_all()).eval_full(),
+            Err(Error::EvalError(EvalError::CapabilityDenied { .. }))
+        );
+
+        let sandboxed = |source: &str| {
+            let mut p = program(source, CapabilityPolicy::allow_all()).sandboxed();
+            p.eval_full()
+        };
+        assert_matches!(
+            sandboxed("import \"lib.ncl\""),
+            Err(Error::ImportError(ImportError::CapabilityDenied(_, reason, _)))
+                if reason.starts_with("sandboxed programs")
+        );
+        assert_matches!(
+            sandboxed("native.env \"HOME\""),
+            Err(Error::EvalError(EvalError::CapabilityDenied { reason, .. }))
+                if reason.starts_with("sandboxed programs")
+        );
+        assert_eq!(sandboxed("1 + 1").unwrap().as_i64(), Ok(2));
+        assert!(CapabilityPolicy::sandboxed()
+            .check_nix()
+            .is_err_and(|reason| reason.starts_with("sandboxed programs")));
+        assert!(CapabilityPolicy::allow_all().check_nix().is_ok());
+
+        let restricted = || {
+            CapabilityPolicy::deny_all()
+                .grant(Capability::Filesystem)
+                .restrict_imports_to(&allowed)
+                .allow_native("env")
+        };
+        assert_eq!(
+            program("(import \"lib.ncl\").port", restricted())
+                .eval_full()
+                .unwrap()
+                .as_i64(),
+            Ok(80)
+        );
+        assert_matches!(
+            program("import \"../private.ncl\"", restricted()).eval_full(),
+            Err(Error::ImportError(ImportError::CapabilityDenied(..)))
+        );
+        assert_matches!(
+            program("native.env \"HOME\"", restricted()).eval_full(),
+            Err(Error::EvalError(EvalError::CapabilityDenied { .. }))
+        );
+        assert_eq!(
+            program("native.env \"HOME\"", restricted().grant(Capability::Env))
+                .eval_full()
+                .unwrap()
+                .as_i64(),
+            Ok(4)
+        );
+    }
+
+    #[cfg(feature = "nix-experimental")]
+    #[test]
+    fn sandboxed_eval_nix() {
+        let mut p = program("%eval_nix% \"1 + 1\"").sandboxed();
+
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::CapabilityDenied { reason, .. }))
+                if reason.starts_with("sandboxed programs")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn capability_policy_symlinks() {
+        let dir = std::env::temp_dir().join(format!("nickel-symlinks-{}", std::process::id()));
+        let allowed = dir.join("allowed");
+        let outside = dir.join("outside");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(allowed.join("lib.ncl"), "{ port = 80 }").unwrap();
+        std::fs::write(outside.join("secret.ncl"), r#"{ token = "hunter2" }"#).unwrap();
+        std::fs::write(dir.join("private.ncl"), r#"{ token = "hunter2" }"#).unwrap();
+        let _ = std::os::unix::fs::symlink(&outside, allowed.join("link"));
+
+        let program = |source: &str| -> Program<CacheImpl> {
+            let mut p: Program<CacheImpl> = Program::new_from_source(
+                Cursor::new(source),
+                allowed.join("main.ncl"),
+                std::io::sink(),
+            )
+            .unwrap();
+            p.set_policy(
+                CapabilityPolicy::deny_all()
+                    .grant(Capability::Filesystem)
+                    .restrict_imports_to(&allowed),
+            );
+            p
+        };
+
+        assert_eq!(
+            program("(import \"lib.ncl\").port")
+                .eval_full()
+                .unwrap()
+                .as_i64(),
+            Ok(80)
+        );
+        // Both paths are lexically inside the allowed directory, but point outside of it.
+        for source in [
+            "(import \"link/secret.ncl\").token",
+            "(import \"link/../private.ncl\").token",
+        ] {
+            assert_matches!(
+                program(source).eval_full(),
+                Err(Error::ImportError(ImportError::CapabilityDenied(..))),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn sandboxed_snapshot() {
+        let mut base = program("{ home = native.env \"HOME\" }");
+        base.register_native(
+            NativeFunction::new("env", |name: String| name.len() as i64).requires(Capability::Env),
+        );
+        let mut base = base.sandboxed();
+        let snapshot = base.snapshot().unwrap();
+
+        // The natives travel with the cache of the snapshot, so the policy must travel as well.
+        for _ in 0..2 {
+            let mut tenant: Program<CacheImpl> = snapshot.to_program(std::io::sink());
+            assert_matches!(
+                tenant.eval_full(),
+                Err(Error::EvalError(EvalError::CapabilityDenied { reason, .. }))
+                    if reason.starts_with("sandboxed programs")
+            );
+        }
+    }
+
+    #[test]
+    fn secret_fields() {
+        let report = |source: &str| -> String {
+            let mut p = program(source);
+            let error = p.eval_full().unwrap_err();
+            p.report_as_str(error)
+        };
+
+        let public = report(r#"{ token | Number = "hunter" ++ "2" }"#);
+        assert!(public.contains("hunter2"));
+
+        let secret = report(r#"{ token | secret | Number = "hunter" ++ "2" }"#);
+        assert!(!secret.contains("hunter2"));
+        assert!(secret.contains("The value of `token` is secret and has been redacted."));
+
+        let merged = report(r#"{ token | Number } & { token | secret = "hunter" ++ "2" }"#);
+        assert!(!merged.contains("hunter2"));
+    }
+
+    #[test]
+    fn unused() {
+        let mut p = program(
+            "let unused_helper = 1 in
+                 let used = 2 in
+                 { port | default = 80, foo = used, hidden | not_exported = 3 } & { port = 8080 }",
+        );
+
+        p.set_track_demand(true);
+        p.eval_full_for_export().unwrap();
+
+        let unused: Vec<_> = p
+            .unused()
+            .into_iter()
+            .map(|unused| (unused.kind, unused.id.label().to_owned()))
+            .collect();
+
+        assert_eq!(
+            unused,
+            [
+                (UnusedKind::LetBinding, "unused_helper".to_owned()),
+                (UnusedKind::Field, "port".to_owned()),
+                (UnusedKind::Field, "hidden".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn eval_fields() {
+        let mut p = program(
+            "let shared = 1 in
+                 { a.b = shared + 1, c = shared + 2, d = std.fail_with \"not forced\" }",
+        );
+
+        let a_b = p.parse_field_path("a.b".to_owned()).unwrap();
+        let c = p.parse_field_path("c".to_owned()).unwrap();
+        let results = p.eval_fields(&[a_b.clone(), c.clone()]).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[&a_b].clone().without_pos(),
+            mk_term::integer(2).without_pos()
+        );
+        assert_eq!(
+            results[&c].clone().without_pos(),
+            mk_term::integer(3).without_pos()
+        );
+
+        let missing = p.parse_field_path("e".to_owned()).unwrap();
+        assert_matches!(
+            p.eval_fields(&[missing]),
+            Err(Error::EvalError(EvalError::FieldMissing { .. }))
+        );
+    }
+
+    #[test]
+    fn contract_trace() {
+        let mut p = program("((fun x => x) | Number -> Number) \"a\"");
+
+        p.set_contract_trace(true);
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::BlameError { .. }))
+        );
+
+        let trace = p.take_contract_trace();
+        let events: Vec<_> = trace
+            .0
+            .iter()
+            .filter(|event| event.contract == "Number -> Number")
+            .map(|event| (event.kind, event.polarity))
+            .collect();
+
+        // The function contract is applied to the function, then its codomain is applied to the
+        // result of the call, and finally its domain is applied to the argument, with a flipped
+        // polarity.
+        assert_eq!(
+            events,
+            [
+                (ContractEventKind::Apply, Polarity::Positive),
+                (ContractEventKind::Apply, Polarity::Positive),
+                (ContractEventKind::FlipPolarity, Polarity::Negative),
+                (ContractEventKind::Apply, Polarity::Negative),
+            ]
+        );
+        assert!(p.take_contract_trace().is_empty());
+    }
+
+    #[test]
+    fn validate() {
+        let dir = std::env::temp_dir().join(format!("nickel-validate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("schema.ncl"),
+            "{ host | String, port | std.number.PosNat }",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("good.json"),
+            r#"{ "host": "localhost", "port": 80 }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("bad.yaml"), "host: localhost\nport: -1\n").unwrap();
+
+        let validate = |data: &str| {
+            let mut program: Program<CacheImpl> =
+                Program::new_validate(dir.join(data), dir.join("schema.ncl"), std::io::sink());
+            program.eval_full_for_export()
+        };
+
+        assert!(validate("good.json").is_ok());
+        assert_matches!(
+            validate("bad.yaml"),
+            Err(Error::EvalError(EvalError::BlameError { .. }))
+        );
+    }
+
+    #[test]
+    fn check_rules() {
+        let dir = std::env::temp_dir().join(format!("nickel-check-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("rules.ncl"),
+            r#"{
+  has_limits
+    | doc "Every container must set limits"
+    = { containers | Array { limits | { cpu | String }, .. }, .. },
+  named = { name | String, .. },
+}"#,
+        )
+        .unwrap();
+
+        let mut program = program(r#"{ name = "app", containers = [{ limits.cpu = 1 }] }"#);
+        let violations = program.check_rules(dir.join("rules.ncl")).unwrap();
+
+        assert_eq!(violations.len(), 1);
+        let violation = &violations[0];
+        assert_eq!(violation.rule, "has_limits");
+        assert_eq!(
+            violation.message.as_deref(),
+            Some("Every container must set limits")
+        );
+        assert_eq!(
+            violation.provenance.as_ref().unwrap().to_string(),
+            "<test>:1:46"
+        );
+    }
+
+    #[test]
+    fn field_names() {
+        let mut program = program(
+            r#"{
+                  b = { y = 1, x = 2, z | optional },
+                  a = std.fail_with "not evaluated",
+                  c | Number,
+                }"#,
+        );
+
+        let names = |program: &mut Program<CacheImpl>, path: &[&str]| -> Vec<String> {
+            let path = FieldPath(path.iter().map(|&id| LocIdent::from(id)).collect());
+            program
+                .field_names(&path)
+                .unwrap()
+                .iter()
+                .map(|id| id.label().to_owned())
+                .collect()
+        };
+
+        assert_eq!(names(&mut program, &[]), ["a", "b", "c"]);
+        assert_eq!(names(&mut program, &["b"]), ["x", "y"]);
+        assert!(names(&mut program, &["b", "x"]).is_empty());
+    }
+
+    #[test]
+    fn source_files() {
+        let dir = std::env::temp_dir().join(format!("nickel-sources-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.ncl"), r#"(import "lib.ncl") & { b = 2 }"#).unwrap();
+        std::fs::write(dir.join("lib.ncl"), r#"{ a = import "data.json" }"#).unwrap();
+        std::fs::write(dir.join("data.json"), "1").unwrap();
+
+        let mut program: Program<CacheImpl> =
+            Program::new_from_file(dir.join("main.ncl"), std::io::sink()).unwrap();
+        program.eval_full_for_export().unwrap();
+
+        let names: Vec<_> = program
+            .source_files()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["main.ncl", "lib.ncl", "data.json"]);
+    }
+
+    #[test]
+    fn stream_export_json() {
+        let src = r#"
+            {
+              b = [1, { d | not_exported = 2, c = "x" }],
+              a | { x | Number, y | optional } = { x = 1 + 1 },
+              e = null,
+            }
+        "#;
+
+        for (layout, key_order) in [
+            (JsonLayout::Compact, KeyOrder::Sorted),
+            (JsonLayout::Pretty { indent: 4 }, KeyOrder::Sorted),
+            (JsonLayout::default(), KeyOrder::Declaration),
+        ] {
+            let mut expected = Vec::new();
+            crate::serialize::to_writer_json(
+                &mut expected,
+                &program(src).eval_full_for_export().unwrap(),
+                layout,
+                key_order,
+            )
+            .unwrap();
+
+            let mut streamed = Vec::new();
+            program(src)
+                .stream_export_json(&mut streamed, layout, key_order)
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(streamed).unwrap(),
+                String::from_utf8(expected).unwrap()
+            );
+        }
+
+        let mut p = program("{ a = [1, fun x => x] }");
+        let err = p.stream_export_json(std::io::sink(), JsonLayout::default(), KeyOrder::Sorted);
+        assert_matches!(
+            err,
+            Err(Error::ExportError(crate::error::ExportError { path, .. }))
+                if path.to_string() == "a[1]"
+        );
+
+        let mut p = program("{ a | Number = \"x\" }");
+        assert_matches!(
+            p.stream_export_json(std::io::sink(), JsonLayout::default(), KeyOrder::Sorted),
+            Err(Error::EvalError(EvalError::BlameError { .. }))
+        );
+    }
+
+    #[test]
+    fn eval_deserialize() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Server {
+            host: String,
+            ports: Vec<u16>,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            name: String,
+            servers: Vec<Server>,
+        }
+
+        let config: Config = program(
+            r#"{ name = "app", servers = [{ host = "a", ports = [80, 8000 + 80] }], debug | not_exported = true }"#,
+        )
+        .eval_deserialize()
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "app".to_owned(),
+                servers: vec![Server {
+                    host: "a".to_owned(),
+                    ports: vec![80, 8080],
+                }],
+            }
+        );
+
+        let err = program(
+            r#"{ name = "app", servers = [{ host = "a", ports = [80] }, { host = 1, ports = [] }] }"#,
+        )
+        .eval_deserialize::<Config>()
+        .unwrap_err();
+        assert_matches!(
+            err,
+            Error::ExportError(crate::error::ExportError {
+                path,
+                data: crate::error::ExportErrorData::Deserialization { term, .. },
+            }) if path.to_string() == "servers[1].host" && term.pos.into_opt().is_some()
         );
     }
 }
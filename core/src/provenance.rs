@@ -0,0 +1,296 @@
+//! Provenance of exported values.
+//!
+//! The position of an evaluated value is the position of the expression which defined it. After
+//! an export, this position tells where each value of the result comes from, even when the
+//! configuration is spread over several files and layers of merging. This module exposes it in
+//! two forms:
+//!
+//! - [provenance_tree] builds a term with the same shape as the exported value, where each leaf
+//!   is replaced by a record describing its [Provenance]. This tree can be exported alongside the
+//!   configuration, in any format.
+//! - [to_yaml_with_provenance] renders the exported value as YAML, where each leaf is followed
+//!   by a comment giving its provenance.
+//!
+//! Leaves are the values which aren't records nor arrays, as well as empty records and arrays, and
+//! records whose fields aren't exported.
+use std::fmt;
+
+use crate::{
+    error::{ExportError, ExportErrorData},
+    files::Files,
+    identifier::LocIdent,
+    serialize::{self, ExportFormat, KeyOrder},
+    term::{array::ArrayAttrs, record::RecordData, MergePriority, RichTerm, Term},
+};
+
+/// The location of the definition of an exported value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Provenance {
+    /// The name of the file defining the value.
+    pub file: String,
+    /// The line of the definition, starting at 1.
+    pub line: usize,
+    /// The column of the definition, starting at 1.
+    pub column: usize,
+    /// The merge priority of the field holding the value, which tells from which layer of a merge
+    /// the value comes. `None` for the elements of an array and for the root value.
+    pub priority: Option<MergePriority>,
+}
+
+impl Provenance {
+    /// Compute the provenance of a value, or return `None` if it doesn't have a position.
+    pub fn of(value: &RichTerm, priority: Option<&MergePriority>, files: &Files) -> Option<Self> {
+        let span = value.pos.into_opt()?;
+        let location = files.location(span.src_id, span.start).ok()?;
+
+        Some(Provenance {
+            file: files.name(span.src_id).to_string_lossy().into_owned(),
+            line: location.line.to_usize() + 1,
+            column: location.column.to_usize() + 1,
+            priority: priority.cloned(),
+        })
+    }
+
+    /// Convert the provenance to a Nickel record, with the fields `file`, `line`, `column` and, if
+    /// the value is held by a record field, `priority`.
+    pub fn to_term(&self) -> RichTerm {
+        let mut fields = vec![
+            (
+                LocIdent::from("file"),
+                Term::Str(self.file.clone().into()).into(),
+            ),
+            (LocIdent::from("line"), Term::Num(self.line.into()).into()),
+            (
+                LocIdent::from("column"),
+                Term::Num(self.column.into()).into(),
+            ),
+        ];
+
+        if let Some(priority) = &self.priority {
+            fields.push((
+                LocIdent::from("priority"),
+                Term::Str(priority.to_string().into()).into(),
+            ));
+        }
+
+        Term::Record(RecordData::with_field_values(fields)).into()
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)?;
+
+        match &self.priority {
+            Some(priority @ (MergePriority::Bottom | MergePriority::Top)) => {
+                write!(f, " ({priority})")
+            }
+            Some(priority @ MergePriority::Numeral(_)) => write!(f, " (priority {priority})"),
+            Some(MergePriority::Neutral) | None => Ok(()),
+        }
+    }
+}
+
+/// Build the provenance tree of a fully evaluated term: a term of the same shape, where each leaf
+/// is replaced by its [Provenance] (see [Provenance::to_term]), or by an empty record if its
+/// provenance is unknown. The fields which aren't exported are skipped.
+pub fn provenance_tree(rt: &RichTerm, files: &Files) -> Result<RichTerm, ExportError> {
+    tree(rt, None, files)
+}
+
+fn tree(
+    rt: &RichTerm,
+    priority: Option<&MergePriority>,
+    files: &Files,
+) -> Result<RichTerm, ExportError> {
+    let result = match rt.as_ref() {
+        Term::Record(record) if !is_leaf(rt) => {
+            // The fields are kept in declaration order: the order of the output is selected when
+            // the tree is serialized.
+            let fields = serialize::ordered_entries(record, KeyOrder::Declaration)
+                .map_err(ExportErrorData::Other)?
+                .into_iter()
+                .map(|(id, value)| {
+                    let priority = &record.fields[&LocIdent::from(id)].metadata.priority;
+                    Ok((LocIdent::from(id), tree(value, Some(priority), files)?))
+                })
+                .collect::<Result<Vec<_>, ExportError>>()?;
+
+            Term::Record(RecordData::with_field_values(fields)).into()
+        }
+        Term::Array(elts, _) if !is_leaf(rt) => Term::Array(
+            elts.iter()
+                .map(|elt| tree(elt, None, files))
+                .collect::<Result<_, _>>()?,
+            ArrayAttrs::default(),
+        )
+        .into(),
+        _ => Provenance::of(rt, priority, files)
+            .map(|provenance| provenance.to_term())
+            .unwrap_or_else(|| Term::Record(RecordData::empty()).into()),
+    };
+
+    Ok(result)
+}
+
+/// Render a fully evaluated term as YAML, where each leaf is followed by a comment giving its
+/// [Provenance]. The result is the same YAML document as the one produced by
+/// [serialize::to_writer_with_key_order] with the same order of fields, up to the layout.
+pub fn to_yaml_with_provenance(
+    rt: &RichTerm,
+    files: &Files,
+    key_order: KeyOrder,
+) -> Result<String, ExportError> {
+    serialize::validate(ExportFormat::Yaml, rt)?;
+
+    let mut output = String::new();
+    write_yaml(&mut output, rt, None, files, key_order, 0)?;
+    Ok(output)
+}
+
+/// Write a value as a YAML block at the given indentation. Leaves are written on a single line.
+fn write_yaml(
+    out: &mut String,
+    rt: &RichTerm,
+    priority: Option<&MergePriority>,
+    files: &Files,
+    key_order: KeyOrder,
+    indent: usize,
+) -> Result<(), ExportError> {
+    let padding = " ".repeat(indent);
+
+    match rt.as_ref() {
+        Term::Record(record) if !is_leaf(rt) => {
+            for (id, value) in
+                serialize::ordered_entries(record, key_order).map_err(ExportErrorData::Other)?
+            {
+                let priority = &record.fields[&LocIdent::from(id)].metadata.priority;
+                let key = yaml_scalar(&Term::Str(id.label().into()).into())?;
+
+                if is_leaf(value) {
+                    out.push_str(&format!("{padding}{key}: "));
+                    write_yaml_leaf(out, value, Some(priority), files)?;
+                } else {
+                    out.push_str(&format!("{padding}{key}:\n"));
+                    // As serde_yaml does, arrays aren't indented with respect to their key.
+                    let indent = match value.as_ref() {
+                        Term::Array(..) => indent,
+                        _ => indent + 2,
+                    };
+                    write_yaml(out, value, Some(priority), files, key_order, indent)?;
+                }
+            }
+        }
+        Term::Array(elts, _) if !is_leaf(rt) => {
+            for elt in elts.iter() {
+                if is_leaf(elt) {
+                    out.push_str(&format!("{padding}- "));
+                    write_yaml_leaf(out, elt, None, files)?;
+                } else {
+                    // We render the element as a block indented by two more spaces, and put the
+                    // dash in the indentation of its first line.
+                    let mut block = String::new();
+                    write_yaml(&mut block, elt, None, files, key_order, indent + 2)?;
+                    out.push_str(&format!("{padding}- {}", &block[indent + 2..]));
+                }
+            }
+        }
+        _ => write_yaml_leaf(out, rt, priority, files)?,
+    }
+
+    Ok(())
+}
+
+fn write_yaml_leaf(
+    out: &mut String,
+    rt: &RichTerm,
+    priority: Option<&MergePriority>,
+    files: &Files,
+) -> Result<(), ExportError> {
+    out.push_str(&yaml_scalar(rt)?);
+
+    if let Some(provenance) = Provenance::of(rt, priority, files) {
+        out.push_str(&format!(" # {provenance}"));
+    }
+
+    out.push('\n');
+    Ok(())
+}
+
+fn is_leaf(rt: &RichTerm) -> bool {
+    match rt.as_ref() {
+        Term::Record(record) => record.iter_serializable().next().is_none(),
+        Term::Array(elts, _) => elts.is_empty(),
+        _ => true,
+    }
+}
+
+/// Render a leaf as a YAML scalar on a single line. Strings which serde_yaml would render as
+/// multi-line block scalars are rendered as JSON strings instead, which are valid YAML.
+fn yaml_scalar(rt: &RichTerm) -> Result<String, ExportError> {
+    let rendered =
+        serde_yaml::to_string(rt).map_err(|err| ExportErrorData::Other(err.to_string()))?;
+    let rendered = rendered.trim_end_matches('\n');
+
+    if rendered.contains('\n') {
+        serde_json::to_string(rt).map_err(|err| ExportErrorData::Other(err.to_string()).into())
+    } else {
+        Ok(rendered.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{eval::cache::CacheImpl, program::Program};
+
+    fn export(source: &str) -> (RichTerm, Files) {
+        let mut program: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(source), "main.ncl", std::io::sink()).unwrap();
+        let rt = program.eval_full_for_export().unwrap();
+        (rt, program.cache_mut().files().clone())
+    }
+
+    #[test]
+    fn yaml_comments() {
+        let (rt, files) = export(
+            "{ port | default = 80, tags = [\"a\", { b = 1 }], empty = {} } & { port = 8080 }",
+        );
+
+        assert_eq!(
+            to_yaml_with_provenance(&rt, &files, KeyOrder::Sorted).unwrap(),
+            "empty: {} # main.ncl:1:57\n\
+             port: 8080 # main.ncl:1:73\n\
+             tags:\n\
+             - a # main.ncl:1:32\n\
+             - b: 1 # main.ncl:1:43\n"
+        );
+    }
+
+    #[test]
+    fn parallel_tree() {
+        let (rt, files) = export("{ a | force = 1, b = [true] }");
+
+        assert_eq!(
+            serialize::to_string(ExportFormat::Json, &provenance_tree(&rt, &files).unwrap())
+                .unwrap(),
+            r#"{
+  "a": {
+    "column": 15,
+    "file": "main.ncl",
+    "line": 1,
+    "priority": "force"
+  },
+  "b": [
+    {
+      "column": 23,
+      "file": "main.ncl",
+      "line": 1
+    }
+  ]
+}"#
+        );
+    }
+}
@@ -6,8 +6,11 @@ use std::fmt;
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum CommandType {
     Load,
+    Reload,
     Typecheck,
+    Type,
     Query,
+    Doc,
     Print,
     Help,
     Exit,
@@ -15,7 +18,17 @@ pub enum CommandType {
 
 impl CommandType {
     pub fn all() -> Vec<&'static str> {
-        vec!["load", "typecheck", "query", "print", "help", "exit"]
+        vec![
+            "load",
+            "reload",
+            "typecheck",
+            "type",
+            "query",
+            "doc",
+            "print",
+            "help",
+            "exit",
+        ]
     }
 }
 
@@ -24,8 +37,11 @@ impl CommandType {
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum Command {
     Load(OsString),
+    Reload,
     Typecheck(String),
+    Type(String),
     Query(String),
+    Doc(String),
     Print(String),
     Help(Option<String>),
     Exit,
@@ -53,8 +69,11 @@ impl FromStr for CommandType {
 
         match s {
             "load" | "l" => Ok(Load),
+            "reload" | "r" => Ok(Reload),
             "typecheck" | "tc" => Ok(Typecheck),
+            "type" | "t" => Ok(Type),
             "query" | "q" => Ok(Query),
+            "doc" | "d" => Ok(Doc),
             "print" | "p" => Ok(Print),
             "help" | "?" | "h" => Ok(Help),
             "exit" | "e" => Ok(Exit),
@@ -70,8 +89,11 @@ impl CommandType {
 
         match self {
             Load => vec![String::from("l")],
+            Reload => vec![String::from("r")],
             Typecheck => vec![String::from("tc")],
+            Type => vec![String::from("t")],
             Query => vec![String::from("q")],
+            Doc => vec![String::from("d")],
             Print => vec![String::from("p")],
             Help => vec![String::from("h"), String::from("?")],
             Exit => vec![String::from("e")],
@@ -85,8 +107,11 @@ impl std::fmt::Display for CommandType {
 
         match self {
             Load => write!(f, "load"),
+            Reload => write!(f, "reload"),
             Typecheck => write!(f, "typecheck"),
+            Type => write!(f, "type"),
             Query => write!(f, "query"),
+            Doc => write!(f, "doc"),
             Print => write!(f, "print"),
             Help => write!(f, "help"),
             Exit => write!(f, "exit"),
@@ -121,14 +146,23 @@ impl FromStr for Command {
                 println!("{arg}");
                 Ok(Command::Load(OsString::from(arg)))
             }
+            CommandType::Reload => Ok(Command::Reload),
             CommandType::Typecheck => {
                 require_arg(cmd, &arg, None)?;
                 Ok(Command::Typecheck(arg))
             }
+            CommandType::Type => {
+                require_arg(cmd, &arg, None)?;
+                Ok(Command::Type(arg))
+            }
             CommandType::Query => {
                 require_arg(cmd, &arg, None)?;
                 Ok(Command::Query(arg))
             }
+            CommandType::Doc => {
+                require_arg(cmd, &arg, None)?;
+                Ok(Command::Doc(arg))
+            }
             CommandType::Print => {
                 require_arg(cmd, &arg, None)?;
                 Ok(Command::Print(arg))
@@ -153,8 +187,11 @@ impl Command {
 
         match self {
             Load(..) => CommandType::Load,
+            Reload => CommandType::Reload,
             Typecheck(..) => CommandType::Typecheck,
+            Type(..) => CommandType::Type,
             Query { .. } => CommandType::Query,
+            Doc(..) => CommandType::Doc,
             Print(..) => CommandType::Print,
             Help(..) => CommandType::Help,
             Exit => CommandType::Exit,
@@ -0,0 +1,182 @@
+//! Completion of REPL inputs.
+//!
+//! Completion is purely static: the candidates are computed from the typing context of the REPL,
+//! without evaluating anything. The fields of a record are thus only known when the record is
+//! defined by a record literal, which is the case of the stdlib modules, of top-level lets and of
+//! the files loaded with `:load`, but not of the result of a function call or of a merge.
+use super::command::CommandType;
+use crate::{
+    identifier::{Ident, LocIdent},
+    pretty::ident_quoted,
+    term::{RichTerm, Term},
+    typecheck::{eq::SimpleTermEnvironment, Context},
+};
+
+/// The maximum number of variables and annotations followed to find a record literal. Variables
+/// may be bound recursively, so we can't just follow them until there isn't any left.
+const MAX_INDIRECTIONS: usize = 32;
+
+/// Complete the word ending at `pos` in `line`. Return the position where the completed part of
+/// the word starts, together with the candidates for this part.
+///
+/// - A command, that is a word at the beginning of the line right after the command prefix `:`,
+///   is completed with the names of the commands.
+/// - A word without a dot is completed with the identifiers in scope.
+/// - A word with a dot, such as `std.array.ma`, is completed with the statically known fields of
+///   the record being accessed.
+pub fn complete(ctxt: &Context, line: &str, pos: usize) -> (usize, Vec<String>) {
+    let line = &line[..pos];
+    let start = line
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map_or(pos, |(idx, _)| idx);
+    let word = &line[start..];
+
+    if start == 1 && line.starts_with(':') {
+        let commands = CommandType::all().into_iter().map(String::from);
+        return (start, with_prefix(commands, word));
+    }
+
+    match word.rfind('.') {
+        Some(dot) => {
+            let path = word[..dot].split('.').map(Ident::new);
+            let fields = fields(ctxt, path).into_iter();
+            (start + dot + 1, with_prefix(fields, &word[dot + 1..]))
+        }
+        None => (start, with_prefix(identifiers(ctxt), word)),
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '\'' | '.')
+}
+
+/// Keep the candidates starting with a prefix, sorted and without duplicates.
+fn with_prefix(candidates: impl Iterator<Item = String>, prefix: &str) -> Vec<String> {
+    let mut result: Vec<String> = candidates.filter(|c| c.starts_with(prefix)).collect();
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// The identifiers in scope. The identifiers which can't be written without quotes, such as the
+/// internal ones of the stdlib, are left out.
+fn identifiers(ctxt: &Context) -> impl Iterator<Item = String> + '_ {
+    ctxt.type_env
+        .iter_elems()
+        .map(|(id, _)| ident_quoted(&LocIdent::from(*id)))
+        .filter(|id| !id.starts_with('"'))
+}
+
+/// The statically known fields of the record at the end of a path, such as `std.array`.
+fn fields(ctxt: &Context, mut path: impl Iterator<Item = Ident>) -> Vec<String> {
+    let mut current = path.next().and_then(|id| ctxt.term_env.0.get(&id).cloned());
+
+    for id in path {
+        current = current.and_then(|(term, env)| {
+            let (record, env) = record_literal(term, env)?;
+
+            match record.as_ref() {
+                Term::Record(data) | Term::RecRecord(data, ..) => {
+                    let value = data.fields.get(&LocIdent::from(id))?.value.clone()?;
+                    Some((value, env))
+                }
+                _ => None,
+            }
+        });
+    }
+
+    current
+        .and_then(|(term, env)| record_literal(term, env))
+        .map(|(record, _)| match record.as_ref() {
+            Term::Record(data) | Term::RecRecord(data, ..) => {
+                data.fields.keys().map(ident_quoted).collect()
+            }
+            _ => Vec::new(),
+        })
+        .unwrap_or_default()
+}
+
+/// Follow the variables and the annotations of a term until a record literal is found.
+fn record_literal(
+    mut term: RichTerm,
+    mut env: SimpleTermEnvironment,
+) -> Option<(RichTerm, SimpleTermEnvironment)> {
+    for _ in 0..MAX_INDIRECTIONS {
+        match term.as_ref() {
+            Term::Record(..) | Term::RecRecord(..) => return Some((term, env)),
+            Term::Annotated(_, inner) => term = inner.clone(),
+            Term::Var(id) => (term, env) = env.0.get(&id.ident())?.clone(),
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        files::Files,
+        parser::{grammar::TermParser, lexer::Lexer, ErrorTolerantParser},
+        typecheck::mk_uniftype,
+    };
+
+    fn context() -> Context {
+        let mut ctxt = Context::new();
+        let mut bind = |name: &str, source: &str| {
+            let file_id = Files::new().add("<test>", source);
+            let term = TermParser::new()
+                .parse_strict(file_id, Lexer::new(source))
+                .unwrap();
+            ctxt.type_env
+                .insert(Ident::new(name), mk_uniftype::dynamic());
+            ctxt.term_env
+                .0
+                .insert(Ident::new(name), (term, SimpleTermEnvironment::new()));
+        };
+
+        bind(
+            "config",
+            "{ server = { port = 80, host = \"a\" }, path = \"/\" }",
+        );
+        bind("count", "1");
+        ctxt
+    }
+
+    #[test]
+    fn complete_identifiers() {
+        assert_eq!(
+            complete(&context(), "con", 3),
+            (0, vec![String::from("config")])
+        );
+        assert_eq!(
+            complete(&context(), "1 + co", 6),
+            (4, vec![String::from("config"), String::from("count")])
+        );
+    }
+
+    #[test]
+    fn complete_fields() {
+        assert_eq!(
+            complete(&context(), "config.server.", 14),
+            (14, vec![String::from("host"), String::from("port")])
+        );
+        assert_eq!(
+            complete(&context(), "config.p", 8),
+            (7, vec![String::from("path")])
+        );
+        assert_eq!(complete(&context(), "count.", 6), (6, Vec::new()));
+    }
+
+    #[test]
+    fn complete_commands() {
+        assert_eq!(
+            complete(&context(), ":ty", 3),
+            (1, vec![String::from("type"), String::from("typecheck")])
+        );
+    }
+}
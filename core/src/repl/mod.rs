@@ -8,7 +8,7 @@
 //! formatting), etc.
 use crate::cache::{Cache, Envs, ErrorTolerance, InputFormat, SourcePath};
 use crate::error::{
-    report::{self, ColorOpt, ErrorFormat},
+    report::{self, ColorOpt, ErrorFormat, ReportStyle},
     Error, EvalError, IOError, IntoDiagnostics, ParseError, ParseErrors, ReplError,
 };
 use crate::eval::cache::Cache as EvalCache;
@@ -38,6 +38,7 @@ use rustyline::validate::{ValidationContext, ValidationResult};
 generate_counter!(InputNameCounter, usize);
 
 pub mod command;
+pub mod completion;
 pub mod query_print;
 #[cfg(feature = "repl")]
 pub mod rustyline_frontend;
@@ -69,8 +70,16 @@ pub trait Repl {
     fn eval_full(&mut self, exp: &str) -> Result<EvalResult, Error>;
     /// Load the content of a file in the environment. Return the loaded record.
     fn load(&mut self, path: impl AsRef<OsStr>) -> Result<RichTerm, Error>;
+    /// Load again the files loaded with [Repl::load] which have changed since, either directly or
+    /// through one of their imports, as well as the files loaded after them, so that the latest
+    /// bindings still take precedence. Return the paths of the reloaded files.
+    fn reload(&mut self) -> Result<Vec<OsString>, Error>;
     /// Typecheck an expression and return its [apparent type][crate::typecheck::ApparentType].
     fn typecheck(&mut self, exp: &str) -> Result<Type, Error>;
+    /// Infer the type of an expression, as if it was statically typed. If the expression isn't
+    /// typeable, which is common for untyped code, typecheck it and return its apparent type
+    /// instead, which is given by its annotations. See [crate::typecheck::infer_type].
+    fn infer_type(&mut self, exp: &str) -> Result<Type, Error>;
     /// Query the metadata of an expression.
     fn query(&mut self, path: String) -> Result<Field, Error>;
     /// Required for error reporting on the frontend.
@@ -89,6 +98,9 @@ pub struct ReplImpl<EC: EvalCache> {
     initial_type_ctxt: typecheck::Context,
     /// The state of the Nickel virtual machine, holding a cache of loaded files and parsed terms.
     vm: VirtualMachine<Cache, EC>,
+    /// The files loaded with `:load`, in the order of loading, together with the id of their
+    /// current version in the cache.
+    loaded: Vec<(OsString, FileId)>,
 }
 
 impl<EC: EvalCache> ReplImpl<EC> {
@@ -99,6 +111,7 @@ impl<EC: EvalCache> ReplImpl<EC> {
             env: Envs::new(),
             initial_type_ctxt: typecheck::Context::new(),
             vm: VirtualMachine::new(Cache::new(ErrorTolerance::Strict), trace),
+            loaded: Vec::new(),
         }
     }
 
@@ -217,8 +230,39 @@ impl<EC: EvalCache> ReplImpl<EC> {
         }
     }
 
+    /// Parse an expression to be typechecked and resolve its imports.
+    fn parse_for_typecheck(&mut self, exp: &str) -> Result<RichTerm, Error> {
+        let file_id = self
+            .vm
+            .import_resolver_mut()
+            .replace_string(SourcePath::ReplTypecheck, String::from(exp));
+        // We ignore non fatal errors while type checking.
+        let (term, _) = self.vm.import_resolver().parse_nocache(file_id)?;
+        let import_resolution::strict::ResolveResult {
+            transformed_term: term,
+            resolved_ids: pending,
+        } = import_resolution::strict::resolve_imports(term, self.vm.import_resolver_mut())?;
+
+        for id in &pending {
+            self.vm.import_resolver_mut().resolve_imports(*id).unwrap();
+        }
+
+        Ok(term)
+    }
+
+    /// The current typing context, including the bindings made in the REPL.
+    pub fn type_ctxt(&self) -> &typecheck::Context {
+        &self.env.type_ctxt
+    }
+
     fn report(&mut self, err: impl IntoDiagnostics, color_opt: ColorOpt) {
-        report::report(self.cache_mut(), err, ErrorFormat::Text, color_opt);
+        report::report(
+            self.cache_mut(),
+            err,
+            ErrorFormat::Text,
+            color_opt,
+            ReportStyle::default(),
+        );
     }
 }
 
@@ -232,10 +276,11 @@ impl<EC: EvalCache> Repl for ReplImpl<EC> {
     }
 
     fn load(&mut self, path: impl AsRef<OsStr>) -> Result<RichTerm, Error> {
+        let path = OsString::from(path.as_ref());
         let file_id = self
             .vm
             .import_resolver_mut()
-            .add_file(OsString::from(path.as_ref()), InputFormat::Nickel)
+            .add_file(path.clone(), InputFormat::Nickel)
             .map_err(IOError::from)?;
         self.vm
             .import_resolver_mut()
@@ -243,6 +288,7 @@ impl<EC: EvalCache> Repl for ReplImpl<EC> {
 
         let term = self.vm.import_resolver().get_owned(file_id).unwrap();
         let pos = term.pos;
+        let parsed = term.clone();
 
         let term = self.prepare(None, term)?;
 
@@ -279,25 +325,54 @@ impl<EC: EvalCache> Repl for ReplImpl<EC> {
         )
         .unwrap();
 
+        // Like for top-level lets, the unevaluated fields are bound in the term environment, which
+        // is used to compare contracts and to complete the fields of the loaded records.
+        if let Term::Record(record) | Term::RecRecord(record, ..) = parsed.as_ref() {
+            for (id, field) in &record.fields {
+                if let Some(value) = &field.value {
+                    self.env.type_ctxt.term_env.0.insert(
+                        id.ident(),
+                        (value.clone(), typecheck::eq::SimpleTermEnvironment::new()),
+                    );
+                }
+            }
+        }
+
+        self.loaded.retain(|(loaded, _)| loaded != &path);
+        self.loaded.push((path, file_id));
+
         Ok(term)
     }
 
-    fn typecheck(&mut self, exp: &str) -> Result<Type, Error> {
-        let file_id = self
-            .vm
-            .import_resolver_mut()
-            .replace_string(SourcePath::ReplTypecheck, String::from(exp));
-        // We ignore non fatal errors while type checking.
-        let (term, _) = self.vm.import_resolver().parse_nocache(file_id)?;
-        let import_resolution::strict::ResolveResult {
-            transformed_term: term,
-            resolved_ids: pending,
-        } = import_resolution::strict::resolve_imports(term, self.vm.import_resolver_mut())?;
+    fn reload(&mut self) -> Result<Vec<OsString>, Error> {
+        let roots: Vec<FileId> = self.loaded.iter().map(|(_, file_id)| *file_id).collect();
+        let stale = self.vm.import_resolver_mut().invalidate_stale(roots);
 
-        for id in &pending {
-            self.vm.import_resolver_mut().resolve_imports(*id).unwrap();
+        // A loaded file is affected if it has changed itself, or if its cached term has been
+        // invalidated because one of its imports has changed.
+        let first_affected = self.loaded.iter().position(|(_, file_id)| {
+            stale.contains(file_id) || self.vm.import_resolver().entry_state(*file_id).is_none()
+        });
+
+        let Some(first_affected) = first_affected else {
+            return Ok(Vec::new());
+        };
+
+        let paths: Vec<OsString> = self.loaded[first_affected..]
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in &paths {
+            self.load(path)?;
         }
 
+        Ok(paths)
+    }
+
+    fn typecheck(&mut self, exp: &str) -> Result<Type, Error> {
+        let term = self.parse_for_typecheck(exp)?;
+
         let wildcards = typecheck::type_check(
             &term,
             self.env.type_ctxt.clone(),
@@ -325,6 +400,16 @@ impl<EC: EvalCache> Repl for ReplImpl<EC> {
         .into())
     }
 
+    fn infer_type(&mut self, exp: &str) -> Result<Type, Error> {
+        let term = self.parse_for_typecheck(exp)?;
+
+        match typecheck::infer_type(&term, self.env.type_ctxt.clone(), self.vm.import_resolver()) {
+            Ok(typ) => Ok(typ),
+            // Untyped code isn't necessarily typeable: we fall back to the annotations.
+            Err(_) => self.typecheck(exp),
+        }
+    }
+
     fn query(&mut self, path: String) -> Result<Field, Error> {
         self.vm.reset();
 
@@ -376,7 +461,9 @@ pub enum InputStatus {
 /// Validator enabling multiline input.
 ///
 /// The behavior is the following:
-/// - always end an input that starts with the command prefix `:`
+/// - end an input that starts with the command prefix `:`, unless the command takes an expression
+///   as an argument (`:typecheck`, `:type` or `:print`), in which case the argument is handled
+///   as a normal input
 /// - otherwise, try to parse the input. If an unexpected end of file error occurs, continue
 ///   the input in a new line. Otherwise, accept and end the input.
 //TODO: the validator throws away the result of parsing, or the parse error, when accepting an
@@ -384,17 +471,15 @@ pub enum InputStatus {
 //reused. This overhead shouldn't be dramatic for the typical REPL input size, though.
 #[cfg_attr(
     feature = "repl",
-    derive(
-        rustyline_derive::Completer,
-        rustyline_derive::Helper,
-        rustyline_derive::Hinter
-    )
+    derive(rustyline_derive::Helper, rustyline_derive::Hinter)
 )]
 pub struct InputParser {
     parser: grammar::ExtendedTermParser,
     /// Currently the parser expect a `FileId` to fill in location information. For this
     /// validator, this may be a dummy one, since for now location information is not used.
     file_id: FileId,
+    /// The typing context of the REPL, used to complete identifiers and record fields.
+    ctxt: typecheck::Context,
 }
 
 impl InputParser {
@@ -402,14 +487,37 @@ impl InputParser {
         InputParser {
             parser: grammar::ExtendedTermParser::new(),
             file_id,
+            ctxt: typecheck::Context::new(),
         }
     }
 
+    /// Update the typing context used for completion, which changes each time a new binding is
+    /// made in the REPL.
+    pub fn set_context(&mut self, ctxt: typecheck::Context) {
+        self.ctxt = ctxt;
+    }
+
     pub fn parse(&self, input: &str) -> InputStatus {
-        if input.starts_with(':') || input.trim().is_empty() {
+        if input.trim().is_empty() {
             return InputStatus::Command;
         }
 
+        if let Some(cmd) = input.strip_prefix(':') {
+            return match cmd.split_once(char::is_whitespace) {
+                Some((name, arg)) if !arg.trim().is_empty() && takes_expression(name) => {
+                    match self.parse_expr(arg) {
+                        InputStatus::Partial => InputStatus::Partial,
+                        _ => InputStatus::Command,
+                    }
+                }
+                _ => InputStatus::Command,
+            };
+        }
+
+        self.parse_expr(input)
+    }
+
+    fn parse_expr(&self, input: &str) -> InputStatus {
         let result = self
             .parser
             .parse_tolerant(self.file_id, lexer::Lexer::new(input));
@@ -431,6 +539,17 @@ impl InputParser {
     }
 }
 
+/// Whether a command, given by its name or one of its aliases, takes a Nickel expression as an
+/// argument.
+fn takes_expression(name: &str) -> bool {
+    use command::CommandType;
+
+    matches!(
+        name.parse::<CommandType>(),
+        Ok(CommandType::Typecheck | CommandType::Type | CommandType::Print)
+    )
+}
+
 #[cfg(feature = "repl")]
 impl rustyline::highlight::Highlighter for InputParser {
     fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
@@ -443,6 +562,20 @@ impl rustyline::highlight::Highlighter for InputParser {
     }
 }
 
+#[cfg(feature = "repl")]
+impl rustyline::completion::Completer for InputParser {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(completion::complete(&self.ctxt, line, pos))
+    }
+}
+
 #[cfg(feature = "repl")]
 impl rustyline::validate::Validator for InputParser {
     fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
@@ -495,6 +628,23 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
                 writeln!(out, "- `:{c} std.array.any`")?;
                 writeln!(out, "- `:{c} mylib.contracts.\"special#chars.\".bar`")?;
             }
+            Ok(c @ CommandType::Doc) => {
+                writeln!(out, ":{c} <field path>")?;
+                print_aliases(out, c)?;
+                writeln!(
+                    out,
+                    "Print the documentation of a field, together with its contracts, \
+                    its type and its default value if any"
+                )?;
+                writeln!(
+                    out,
+                    "<field path> is a dot-separated sequence of identifiers pointing to a field, \
+                    as for `:query`.\n"
+                )?;
+                writeln!(out, "Examples:")?;
+                writeln!(out, "- `:{c} std.array.fold_left`")?;
+                writeln!(out, "- `:{c} config.server.port`")?;
+            }
             Ok(c @ CommandType::Load) => {
                 writeln!(out, ":{c} <file>")?;
                 print_aliases(out, c)?;
@@ -508,6 +658,20 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
                     "Fail if the content of <file> doesn't evaluate to a record."
                 )?;
             }
+            Ok(c @ CommandType::Reload) => {
+                writeln!(out, ":{c}")?;
+                print_aliases(out, c)?;
+                writeln!(
+                    out,
+                    "Load again the files loaded with `:load` which have changed since, \
+                    either directly or through one of their imports."
+                )?;
+                writeln!(
+                    out,
+                    "Unchanged imports aren't parsed again, and the bindings made since \
+                    are kept."
+                )?;
+            }
             Ok(c @ CommandType::Typecheck) => {
                 writeln!(out, ":{c} <expression>")?;
                 print_aliases(out, c)?;
@@ -516,6 +680,19 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
                     "Typecheck the given expression and print its top-level type"
                 )?;
             }
+            Ok(c @ CommandType::Type) => {
+                writeln!(out, ":{c} <expression>")?;
+                print_aliases(out, c)?;
+                writeln!(
+                    out,
+                    "Infer the type of the given expression, as if it was statically typed, \
+                    and print it. If the expression isn't typeable, print the type given by its \
+                    annotations instead"
+                )?;
+                writeln!(out, "Examples:")?;
+                writeln!(out, "- `:{c} fun x => x`")?;
+                writeln!(out, "- `:{c} std.array.map (fun x => x + 1)`")?;
+            }
             Ok(c @ CommandType::Print) => {
                 writeln!(out, ":{c} <expression>")?;
                 print_aliases(out, c)?;
@@ -538,6 +715,9 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
 
         Ok(())
     } else {
-        writeln!(out, "Available commands: help query load typecheck exit")
+        writeln!(
+            out,
+            "Available commands: help query doc load reload typecheck type exit"
+        )
     }
 }
@@ -177,6 +177,17 @@ impl Default for Attributes {
     }
 }
 
+impl Attributes {
+    /// The attributes documenting a field for its users: its documentation, contracts, type and
+    /// default value, but not its actual value.
+    pub fn documentation() -> Self {
+        Attributes {
+            value: false,
+            ..Default::default()
+        }
+    }
+}
+
 /// Render the result of a metadata query, automatically selecting an adapted query printer at
 /// compile time.
 ///
@@ -8,23 +8,31 @@ use crate::{error::report::ColorOpt, eval::cache::CacheImpl};
 use ansi_term::Style;
 use rustyline::{error::ReadlineError, Config, EditMode, Editor};
 
+/// The maximum number of entries kept in the history file.
+const MAX_HISTORY_SIZE: usize = 1000;
+
 /// The config of rustyline's editor.
-pub fn config(color_opt: ColorOpt) -> Config {
-    Config::builder()
+pub fn config(color_opt: ColorOpt) -> rustyline::Result<Config> {
+    Ok(Config::builder()
         .history_ignore_space(true)
+        .history_ignore_dups(true)?
+        .max_history_size(MAX_HISTORY_SIZE)?
         .edit_mode(EditMode::Emacs)
+        .bracketed_paste(true)
         .color_mode(color_opt.into())
         .auto_add_history(true)
-        .build()
+        .build())
 }
 
 impl From<ColorOpt> for rustyline::config::ColorMode {
     fn from(c: ColorOpt) -> Self {
+        use crate::error::report::ColorMode as ReportColorMode;
         use rustyline::config::ColorMode;
+
         match c.0 {
-            clap::ColorChoice::Always => ColorMode::Forced,
-            clap::ColorChoice::Auto => ColorMode::Enabled,
-            clap::ColorChoice::Never => ColorMode::Disabled,
+            ReportColorMode::Always => ColorMode::Forced,
+            ReportColorMode::Auto => ColorMode::Enabled,
+            ReportColorMode::Never => ColorMode::Disabled,
         }
     }
 }
@@ -46,15 +54,25 @@ pub fn repl(histfile: PathBuf, color_opt: ColorOpt) -> Result<(), InitError> {
             .replace_string(SourcePath::ReplInput(0), String::new()),
     );
 
-    let mut editor = Editor::with_config(config(color_opt))
+    let mut editor = config(color_opt)
+        .and_then(Editor::with_config)
         .map_err(|readline_err| InitError::ReadlineError(format!("{readline_err}")))?;
     let _ = editor.load_history(&histfile);
     editor.set_helper(Some(validator));
 
     let result = loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.set_context(repl.type_ctxt().clone());
+        }
+
         let line = editor.readline("nickel> ");
         let mut stdout = std::io::stdout();
 
+        // Save the history as we go, so that it isn't lost if the REPL is killed or crashes.
+        if line.is_ok() {
+            let _ = editor.append_history(&histfile);
+        }
+
         match line {
             Ok(line) if line.trim().is_empty() => (),
             Ok(line) if line.starts_with(':') => {
@@ -83,9 +101,19 @@ pub fn repl(histfile: PathBuf, color_opt: ColorOpt) -> Result<(), InitError> {
                         }
                         _ => (),
                     }),
+                    Ok(Command::Reload) => repl.reload().map(|paths| {
+                        if paths.is_empty() {
+                            println!("No loaded file has changed.")
+                        }
+
+                        for path in paths {
+                            println!("Reloaded {}.", path.to_string_lossy())
+                        }
+                    }),
                     Ok(Command::Typecheck(exp)) => {
                         repl.typecheck(&exp).map(|typ| println!("Ok: {typ}"))
                     }
+                    Ok(Command::Type(exp)) => repl.infer_type(&exp).map(|typ| println!("{typ}")),
                     Ok(Command::Query(path)) => repl.query(path).map(|field| {
                         query_print::write_query_result(
                             &mut stdout,
@@ -94,6 +122,18 @@ pub fn repl(histfile: PathBuf, color_opt: ColorOpt) -> Result<(), InitError> {
                         )
                         .unwrap();
                     }),
+                    Ok(Command::Doc(path)) => repl.query(path.clone()).map(|field| {
+                        let found = query_print::write_query_result(
+                            &mut stdout,
+                            &field,
+                            query_print::Attributes::documentation(),
+                        )
+                        .unwrap();
+
+                        if !found {
+                            println!("No documentation found for `{path}`.");
+                        }
+                    }),
                     Ok(Command::Print(exp)) => {
                         match repl.eval_full(&exp) {
                             Ok(EvalResult::Evaluated(rt)) => println!("{rt}"),
@@ -132,12 +172,10 @@ pub fn repl(histfile: PathBuf, color_opt: ColorOpt) -> Result<(), InitError> {
             }
             Err(ReadlineError::Interrupted) => (),
             Err(err) => {
-                let _ = editor.save_history(&histfile);
                 repl.report(Error::IOError(IOError(format!("{err}"))), color_opt);
             }
         }
     };
 
-    let _ = editor.save_history(&histfile);
     result
 }
@@ -45,10 +45,17 @@ pub fn input<R: Repl>(repl: &mut R, line: &str) -> Result<InputResult, InputErro
             Ok(Command::Load(_)) => Err(InputError::Other(String::from(
                 ":load is not enabled on this REPL.",
             ))),
+            Ok(Command::Reload) => Err(InputError::Other(String::from(
+                ":reload is not enabled on this REPL.",
+            ))),
             Ok(Command::Typecheck(exp)) => repl
                 .typecheck(&exp)
                 .map(|typ| InputResult::Success(format!("Ok: {typ}")))
                 .map_err(InputError::from),
+            Ok(Command::Type(exp)) => repl
+                .infer_type(&exp)
+                .map(|typ| InputResult::Success(format!("{typ}")))
+                .map_err(InputError::from),
             Ok(Command::Query(path)) => repl
                 .query(path)
                 .map(|t| {
@@ -62,6 +69,24 @@ pub fn input<R: Repl>(repl: &mut R, line: &str) -> Result<InputResult, InputErro
                     InputResult::Success(String::from_utf8(buffer.into_inner()).unwrap())
                 })
                 .map_err(InputError::from),
+            Ok(Command::Doc(path)) => repl
+                .query(path.clone())
+                .map(|t| {
+                    let mut buffer = Cursor::new(Vec::<u8>::new());
+                    let found = query_print::write_query_result(
+                        &mut buffer,
+                        &t,
+                        query_print::Attributes::documentation(),
+                    )
+                    .unwrap();
+
+                    if found {
+                        InputResult::Success(String::from_utf8(buffer.into_inner()).unwrap())
+                    } else {
+                        InputResult::Success(format!("No documentation found for `{path}`."))
+                    }
+                })
+                .map_err(InputError::from),
             Ok(Command::Print(exp)) => repl
                 .eval_full(&exp)
                 .map(|res| match res {
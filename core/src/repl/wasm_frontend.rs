@@ -2,16 +2,18 @@
 use super::simple_frontend::{input, serialize, InputError, InputResult};
 use super::{Repl, ReplImpl};
 use crate::cache::Cache;
-use crate::error::IntoDiagnostics;
+use crate::error::{DiagnosticFileId, DiagnosticFiles, Diagnostics, Error, IntoDiagnostics};
 use crate::eval::cache::CacheImpl;
-use crate::files::{FileId, Files};
+use crate::program::Program;
 use crate::serialize::ExportFormat;
+use crate::typecheck::TypecheckMode;
 use codespan_reporting::{
     diagnostic::{Diagnostic, Label, LabelStyle, Severity},
     term::termcolor::Ansi,
 };
 use serde::Serialize;
 use serde_repr::Serialize_repr;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io::Cursor;
 use wasm_bindgen::prelude::*;
@@ -84,7 +86,7 @@ pub struct WasmErrorDiagnostic {
 }
 
 impl WasmErrorDiagnostic {
-    fn from_codespan(files: &Files, diag: Diagnostic<FileId>) -> Self {
+    fn from_codespan(files: &DiagnosticFiles, diag: Diagnostic<DiagnosticFileId>) -> Self {
         WasmErrorDiagnostic {
             severity: diag.severity.into(),
             msg: diag.message,
@@ -110,23 +112,23 @@ pub struct WasmErrorLabel {
 }
 
 impl WasmErrorLabel {
-    fn from_codespan(files: &Files, label: Label<FileId>) -> Self {
-        let start_loc = files.location(label.file_id, label.range.start as u32);
-        let end_loc = files.location(label.file_id, label.range.end as u32);
+    fn from_codespan(files: &DiagnosticFiles, label: Label<DiagnosticFileId>) -> Self {
+        use codespan_reporting::files::Files as _;
+
+        // Lines and columns are counted from 0, while they are counted from 1 by codespan.
+        let location = |offset| {
+            files
+                .location(label.file_id, offset)
+                .map(|loc| (loc.line_number - 1, loc.column_number - 1))
+        };
+        let start_loc = location(label.range.start);
+        let end_loc = location(label.range.end);
 
         let (line_start, col_start, line_end, col_end) = match (start_loc, end_loc) {
-            (Ok(start_loc), Ok(end_loc)) => (
-                start_loc.line.to_usize(),
-                start_loc.column.to_usize(),
-                end_loc.line.to_usize(),
-                end_loc.column.to_usize(),
-            ),
-            (Ok(loc), _) | (_, Ok(loc)) => (
-                loc.line.to_usize(),
-                loc.column.to_usize(),
-                loc.line.to_usize(),
-                loc.column.to_usize(),
-            ),
+            (Ok((line_start, col_start)), Ok((line_end, col_end))) => {
+                (line_start, col_start, line_end, col_end)
+            }
+            (Ok((line, col)), _) | (_, Ok((line, col))) => (line, col, line, col),
             _ => (0, 0, 0, 0),
         };
 
@@ -194,13 +196,16 @@ impl WasmInputResult {
     fn error(cache: &mut Cache, error: InputError) -> Self {
         let (msg, errors) = match error {
             InputError::NickelError(err) => {
-                let mut files = cache.files().clone();
-                let diagnostics = err.into_diagnostics(&mut files);
+                let Diagnostics {
+                    diagnostics,
+                    snippets,
+                } = err.into_diagnostics(cache.files());
+                let files = DiagnosticFiles::new(cache.files(), &snippets);
 
                 let msg = diags_to_string(&files, &diagnostics);
                 let errors: Vec<WasmErrorDiagnostic> = diagnostics
                     .into_iter()
-                    .map(|diag| WasmErrorDiagnostic::from_codespan(cache.files(), diag))
+                    .map(|diag| WasmErrorDiagnostic::from_codespan(&files, diag))
                     .collect();
                 (msg, errors)
             }
@@ -308,7 +313,7 @@ impl std::io::Write for CallbackWriter {
 }
 
 /// Render error diagnostics as a string.
-pub fn diags_to_string(files: &Files, diags: &[Diagnostic<FileId>]) -> String {
+pub fn diags_to_string(files: &DiagnosticFiles, diags: &[Diagnostic<DiagnosticFileId>]) -> String {
     let mut buffer = Ansi::new(Cursor::new(Vec::new()));
     let config = codespan_reporting::term::Config::default();
 
@@ -324,9 +329,8 @@ pub fn diags_to_string(files: &Files, diags: &[Diagnostic<FileId>]) -> String {
 pub fn err_to_string(cache: &Cache, error: InputError) -> String {
     match error {
         InputError::NickelError(nickel_err) => {
-            let mut files = cache.files().clone();
-            let diags = nickel_err.into_diagnostics(&mut files);
-            diags_to_string(&files, &diags)
+            let diagnostics = nickel_err.into_diagnostics(cache.files());
+            diags_to_string(&diagnostics.files(cache.files()), &diagnostics.diagnostics)
         }
         InputError::Other(msg) => msg,
     }
@@ -366,3 +370,202 @@ pub fn repl_serialize(
         .map(WasmInputResult::from)
         .unwrap_or_else(|err| WasmInputResult::error(state.0.cache_mut(), err))
 }
+
+/// Convert an error raised while building a program to a `WasmInputResult`.
+fn io_error(err: std::io::Error) -> WasmInputResult {
+    WasmInputResult {
+        msg: err.to_string(),
+        tag: WasmResultTag::Error,
+        errors: WasmInputResult::empty_errors(),
+    }
+}
+
+/// Build a program from a source string. The program can't import anything from the filesystem
+/// when the `fs-imports` feature is disabled, as is the case for the WASM build.
+fn program_from_source(source: &str) -> std::io::Result<Program<CacheImpl>> {
+    Program::new_from_source(Cursor::new(source.to_owned()), "<input>", std::io::sink())
+}
+
+/// Run an operation on a program, and convert the result to a `WasmInputResult`, reporting
+/// errors as structured diagnostics.
+fn with_program(
+    program: std::io::Result<Program<CacheImpl>>,
+    op: impl FnOnce(&mut Program<CacheImpl>) -> Result<String, Error>,
+) -> WasmInputResult {
+    let mut program = match program {
+        Ok(program) => program,
+        Err(err) => return io_error(err),
+    };
+
+    match op(&mut program) {
+        Ok(msg) => InputResult::Success(msg).into(),
+        Err(err) => WasmInputResult::error(program.cache_mut(), err.into()),
+    }
+}
+
+fn parse(program: &mut Program<CacheImpl>) -> Result<String, Error> {
+    program.parse().map(|_| String::new())
+}
+
+fn typecheck(program: &mut Program<CacheImpl>) -> Result<String, Error> {
+    program
+        .typecheck(TypecheckMode::Walk)
+        .map(|_| String::new())
+}
+
+fn eval(program: &mut Program<CacheImpl>) -> Result<String, Error> {
+    program.eval_full().map(|rt| format!("{rt}"))
+}
+
+fn export(program: &mut Program<CacheImpl>, format: WasmExportFormat) -> Result<String, Error> {
+    let format = format.try_into().unwrap_or_default();
+    let rt = program.eval_full_for_export()?;
+    crate::serialize::validate(format, &rt)?;
+    Ok(crate::serialize::to_string(format, &rt)?)
+}
+
+/// Parse a Nickel program, without evaluating it.
+#[wasm_bindgen]
+pub fn program_parse(source: &str) -> WasmInputResult {
+    with_program(program_from_source(source), parse)
+}
+
+/// Typecheck a Nickel program, without evaluating it.
+#[wasm_bindgen]
+pub fn program_typecheck(source: &str) -> WasmInputResult {
+    with_program(program_from_source(source), typecheck)
+}
+
+/// Fully evaluate a Nickel program and pretty-print the result.
+#[wasm_bindgen]
+pub fn program_eval(source: &str) -> WasmInputResult {
+    with_program(program_from_source(source), eval)
+}
+
+/// Evaluate a Nickel program and serialize the result to the given format.
+#[wasm_bindgen]
+pub fn program_export(source: &str, format: WasmExportFormat) -> WasmInputResult {
+    with_program(program_from_source(source), |program| {
+        export(program, format)
+    })
+}
+
+/// A virtual file tree, for programs split across several files. Imports are resolved inside the
+/// tree, relatively to the importing file, as they would be on the filesystem. The filesystem is
+/// never accessed.
+///
+/// ```js
+/// const files = new VirtualFiles();
+/// files.add("main.ncl", "(import \"lib/server.ncl\") & { port = 80 }");
+/// files.add("lib/server.ncl", "{ host = \"localhost\", port | Number }");
+/// const result = files.export("main.ncl", WasmExportFormat.Json);
+/// ```
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct VirtualFiles(HashMap<String, String>);
+
+#[wasm_bindgen]
+impl VirtualFiles {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, replacing any file with the same path.
+    pub fn add(&mut self, path: &str, source: &str) {
+        self.0.insert(path.to_owned(), source.to_owned());
+    }
+
+    fn program(&self, main: &str) -> std::io::Result<Program<CacheImpl>> {
+        Program::new_from_memory(self.0.clone(), main, std::io::sink())
+    }
+
+    /// Parse the program at `main`, without evaluating it.
+    pub fn parse(&self, main: &str) -> WasmInputResult {
+        with_program(self.program(main), parse)
+    }
+
+    /// Typecheck the program at `main`, without evaluating it.
+    pub fn typecheck(&self, main: &str) -> WasmInputResult {
+        with_program(self.program(main), typecheck)
+    }
+
+    /// Fully evaluate the program at `main` and pretty-print the result.
+    pub fn eval(&self, main: &str) -> WasmInputResult {
+        with_program(self.program(main), eval)
+    }
+
+    /// Evaluate the program at `main` and serialize the result to the given format.
+    pub fn export(&self, main: &str, format: WasmExportFormat) -> WasmInputResult {
+        with_program(self.program(main), |program| export(program, format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_files(files: &VirtualFiles, main: &str) -> Result<String, Error> {
+        let mut program = files
+            .program(main)
+            .expect("main file should be in the tree");
+        eval(&mut program)
+    }
+
+    #[test]
+    fn single_file() {
+        let mut files = VirtualFiles::new();
+        files.add("main.ncl", "1 + 1");
+        assert_eq!(eval_files(&files, "main.ncl").unwrap(), "2");
+    }
+
+    #[test]
+    fn add_replaces_file() {
+        let mut files = VirtualFiles::new();
+        files.add("main.ncl", "1 + 1");
+        files.add("main.ncl", "\"replaced\"");
+        assert_eq!(eval_files(&files, "main.ncl").unwrap(), "\"replaced\"");
+    }
+
+    #[test]
+    fn missing_main() {
+        let mut files = VirtualFiles::new();
+        files.add("main.ncl", "1");
+        let err = files.program("other.ncl").err().expect("should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn relative_imports() {
+        let mut files = VirtualFiles::new();
+        files.add("main.ncl", "(import \"lib/server.ncl\").port");
+        files.add("lib/server.ncl", "{ port = (import \"port.ncl\") }");
+        files.add("lib/port.ncl", "80");
+        // Imports are relative to the importing file: this one must not be picked up.
+        files.add("port.ncl", "8080");
+        assert_eq!(eval_files(&files, "main.ncl").unwrap(), "80");
+    }
+
+    #[test]
+    fn export_imports() {
+        let mut files = VirtualFiles::new();
+        files.add("main.ncl", "(import \"lib/server.ncl\") & { port = 80 }");
+        files.add("lib/server.ncl", "{ host = \"localhost\", port | Number }");
+        let mut program = files.program("main.ncl").unwrap();
+        let json = export(&mut program, WasmExportFormat::Json).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({ "host": "localhost", "port": 80 })
+        );
+    }
+
+    #[test]
+    fn missing_import() {
+        let mut files = VirtualFiles::new();
+        files.add("main.ncl", "import \"lib.ncl\"");
+        assert!(matches!(
+            eval_files(&files, "main.ncl"),
+            Err(Error::ImportError(_))
+        ));
+    }
+}
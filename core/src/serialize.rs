@@ -5,7 +5,7 @@ use crate::{
     metrics,
     term::{
         array::{Array, ArrayAttrs},
-        record::RecordData,
+        record::{RecordData, REDACTED},
         IndexMap, Number, RichTerm, Term, TypeAnnotation,
     },
 };
@@ -24,19 +24,38 @@ use malachite::{
 };
 use once_cell::sync::Lazy;
 
-use std::{fmt, io};
+use std::{fmt, io, num::NonZeroUsize};
+
+use value::Value;
 
 /// Available export formats.
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 pub enum ExportFormat {
     /// Evalute a Nickel expression to a string and write that text to the output
     /// Note: `raw` is a deprecated alias for `text`; prefer `text` instead.
-    #[value(alias("raw"))]
+    #[cfg_attr(feature = "clap", value(alias("raw")))]
     Text,
     #[default]
     Json,
     Yaml,
     Toml,
+    /// MessagePack, a binary format
+    Msgpack,
+    /// CBOR (RFC 8949), a binary format
+    Cbor,
+    /// XML, following the mapping described in the manual
+    Xml,
+    /// Environment files, made of `KEY=value` lines, from a record of scalar values
+    Dotenv,
+    /// Shell scripts, made of `export KEY='value'` lines, from a record of scalar values
+    Shell,
+    /// INI files, whose sections are the top-level records
+    Ini,
+    /// Java properties files, where nested records are flattened into dotted keys
+    Properties,
+    /// HCL files for Terraform and Packer, following the mapping described in the manual
+    Hcl,
 }
 
 impl fmt::Display for ExportFormat {
@@ -46,12 +65,36 @@ impl fmt::Display for ExportFormat {
             Self::Json => write!(f, "json"),
             Self::Yaml => write!(f, "yaml"),
             Self::Toml => write!(f, "toml"),
+            Self::Msgpack => write!(f, "msgpack"),
+            Self::Cbor => write!(f, "cbor"),
+            Self::Xml => write!(f, "xml"),
+            Self::Dotenv => write!(f, "dotenv"),
+            Self::Shell => write!(f, "shell"),
+            Self::Ini => write!(f, "ini"),
+            Self::Properties => write!(f, "properties"),
+            Self::Hcl => write!(f, "hcl"),
         }
     }
 }
 
+/// The layout of JSON exports.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum JsonLayout {
+    /// A single line, without any whitespace.
+    Compact,
+    /// One value per line, with the given number of spaces for each level of indentation.
+    Pretty { indent: usize },
+}
+
+impl Default for JsonLayout {
+    fn default() -> Self {
+        JsonLayout::Pretty { indent: 2 }
+    }
+}
+
 /// Available metadata export formats.
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 pub enum MetadataExportFormat {
     #[default]
     Markdown,
@@ -146,29 +189,84 @@ where
     t.serialize(serializer)
 }
 
-/// Serializer for a record. Serialize fields in alphabetical order to get a deterministic output
+/// The order of the fields of serialized records.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum KeyOrder {
+    /// Alphabetical order
+    #[default]
+    Sorted,
+    /// The order in which the fields are defined. The fields of a merged record come from its left
+    /// operand first, then from its right operand
+    Declaration,
+}
+
+/// Serializer for a record. Serialize fields in alphabetical order, so that the output only
+/// depends on the serialized term. Use [Ordered] to select another order.
 pub fn serialize_record<S>(record: &RecordData, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
+    serialize_entries(record, KeyOrder::Sorted, serializer)
+}
+
+fn serialize_entries<S>(
+    record: &RecordData,
+    key_order: KeyOrder,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let entries = ordered_entries(record, key_order).map_err(serde::ser::Error::custom)?;
+
+    let mut map_ser = serializer.serialize_map(Some(entries.len()))?;
+    for (id, t) in entries.iter() {
+        map_ser.serialize_entry(&id.to_string(), &Ordered(t, key_order))?
+    }
+
+    map_ser.end()
+}
+
+/// Return the serializable fields of a record, in the given order.
+pub(crate) fn ordered_entries(
+    record: &RecordData,
+    key_order: KeyOrder,
+) -> Result<Vec<(Ident, &RichTerm)>, String> {
     let mut entries = record
         .iter_serializable()
         .collect::<Result<Vec<_>, _>>()
         .map_err(|missing_def_err| {
-            serde::ser::Error::custom(format!(
-                "missing field definition for `{}`",
-                missing_def_err.id
-            ))
+            format!("missing field definition for `{}`", missing_def_err.id)
         })?;
 
-    entries.sort_by_key(|(k, _)| *k);
-
-    let mut map_ser = serializer.serialize_map(Some(entries.len()))?;
-    for (id, t) in entries.iter() {
-        map_ser.serialize_entry(&id.to_string(), &t)?
+    if key_order == KeyOrder::Sorted {
+        entries.sort_by_key(|(k, _)| *k);
     }
 
-    map_ser.end()
+    Ok(entries)
+}
+
+/// A term serialized with the given order of the fields of its records, including the nested
+/// ones. Serializing a [RichTerm] directly always sorts the fields.
+pub struct Ordered<'a>(pub &'a RichTerm, pub KeyOrder);
+
+impl Serialize for Ordered<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let Ordered(rt, key_order) = *self;
+
+        match rt.as_ref() {
+            Term::Record(record) => serialize_entries(record, key_order, serializer),
+            Term::Array(elts, _) => {
+                serializer.collect_seq(elts.iter().map(|elt| Ordered(elt, key_order)))
+            }
+            Term::Annotated(_, inner) => Ordered(inner, key_order).serialize(serializer),
+            _ => rt.serialize(serializer),
+        }
+    }
 }
 
 /// Deserialize for a record. Required to set the record attributes to default.
@@ -329,11 +427,22 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
     //
     // `do_validate` is the method doing the actual validation. The only reason this code is put in
     // a separate subfunction is that since we reconstruct the path bottom-up, it needs to be
-    // reversed before finally returning from validate.
-    fn do_validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
+    // reversed before finally returning from validate. `top_level` is only set for the term being
+    // validated, as some formats don't support nested values.
+    fn do_validate(format: ExportFormat, t: &RichTerm, top_level: bool) -> Result<(), ExportError> {
         match t.as_ref() {
-            // TOML doesn't support null values
-            Null if format == ExportFormat::Json || format == ExportFormat::Yaml => Ok(()),
+            // TOML, dotenv, shell, INI and properties don't support null values
+            Null if !matches!(
+                format,
+                ExportFormat::Toml
+                    | ExportFormat::Dotenv
+                    | ExportFormat::Shell
+                    | ExportFormat::Ini
+                    | ExportFormat::Properties
+            ) =>
+            {
+                Ok(())
+            }
             Null => Err(ExportErrorData::UnsupportedNull(format, t.clone()).into()),
             Bool(_) | Str(_) | Enum(_) => Ok(()),
             Num(n) => {
@@ -347,6 +456,11 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
                     .into())
                 }
             }
+            Record(_) | Array(..)
+                if matches!(format, ExportFormat::Dotenv | ExportFormat::Shell) && !top_level =>
+            {
+                Err(ExportErrorData::UnsupportedNesting(format, t.clone()).into())
+            }
             Record(record) => {
                 record.iter_serializable().try_for_each(|binding| {
                     // unwrap(): terms must be fully evaluated before being validated for
@@ -359,14 +473,14 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
                         )
                     });
 
-                    do_validate(format, rt)
+                    do_validate(format, rt, false)
                         .map_err(|err| with_elem(err, NickelPointerElem::Field(id)))
                 })?;
                 Ok(())
             }
             Array(array, _) => {
                 array.iter().enumerate().try_for_each(|(index, t)| {
-                    do_validate(format, t)
+                    do_validate(format, t, false)
                         .map_err(|err| with_elem(err, NickelPointerElem::Index(index)))
                 })?;
                 Ok(())
@@ -381,8 +495,23 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
         } else {
             Err(ExportErrorData::NotAString(t.clone()).into())
         }
+    } else if matches!(
+        format,
+        ExportFormat::Toml
+            | ExportFormat::Xml
+            | ExportFormat::Dotenv
+            | ExportFormat::Shell
+            | ExportFormat::Ini
+            | ExportFormat::Properties
+            | ExportFormat::Hcl
+    ) && !matches!(t.term.as_ref(), Term::Record(_))
+    {
+        // A TOML document is a table, an XML document is exported from a record holding the root
+        // element, and the other formats from a record of keys, sections or blocks. Other values
+        // can only appear inside a record.
+        Err(ExportErrorData::NotARecord(format, t.clone()).into())
     } else {
-        let mut result = do_validate(format, t);
+        let mut result = do_validate(format, t, true);
 
         if let Err(ExportError { path, .. }) = &mut result {
             path.0.reverse();
@@ -420,7 +549,26 @@ where
     Ok(())
 }
 
-pub fn to_writer<W>(mut writer: W, format: ExportFormat, rt: &RichTerm) -> Result<(), ExportError>
+/// Serialize a fully evaluated term in the given format, with the fields of records in
+/// alphabetical order.
+pub fn to_writer<W>(writer: W, format: ExportFormat, rt: &RichTerm) -> Result<(), ExportError>
+where
+    W: io::Write,
+{
+    to_writer_with_key_order(writer, format, rt, KeyOrder::default())
+}
+
+/// Same as [to_writer], but with the given order of the fields of records.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "serialize", skip_all, fields(?format))
+)]
+pub fn to_writer_with_key_order<W>(
+    mut writer: W,
+    format: ExportFormat,
+    rt: &RichTerm,
+    key_order: KeyOrder,
+) -> Result<(), ExportError>
 where
     W: io::Write,
 {
@@ -428,17 +576,77 @@ where
     let start_time = std::time::Instant::now();
 
     match format {
-        ExportFormat::Json => serde_json::to_writer_pretty(writer, &rt)
-            .map_err(|err| ExportErrorData::Other(err.to_string())),
-        ExportFormat::Yaml => serde_yaml::to_writer(writer, &rt)
-            .map_err(|err| ExportErrorData::Other(err.to_string())),
-        ExportFormat::Toml => toml::to_string_pretty(rt)
+        ExportFormat::Json => write_json(writer, rt, JsonLayout::default(), key_order),
+        ExportFormat::Yaml => Value::from_term(rt, key_order)
+            .map_err(ExportErrorData::Other)
+            .and_then(|value| {
+                writer
+                    .write_all(yaml_ser::to_string(&value).as_bytes())
+                    .map_err(|err| ExportErrorData::Other(err.to_string()))
+            }),
+        ExportFormat::Toml => toml::to_string_pretty(&Ordered(rt, key_order))
             .map_err(|err| ExportErrorData::Other(err.to_string()))
             .and_then(|s| {
                 writer
                     .write_all(s.as_bytes())
                     .map_err(|err| ExportErrorData::Other(err.to_string()))
             }),
+        // Records are encoded as maps with string keys, which is what consumers of configurations
+        // expect, rather than as arrays as `rmp_serde` does by default for structs.
+        ExportFormat::Msgpack => {
+            rmp_serde::encode::write_named(&mut writer, &Ordered(rt, key_order))
+                .map_err(|err| ExportErrorData::Other(err.to_string()))
+        }
+        ExportFormat::Cbor => ciborium::into_writer(&Ordered(rt, key_order), writer)
+            .map_err(|err| ExportErrorData::Other(err.to_string())),
+        ExportFormat::Xml => Value::from_term(rt, key_order)
+            .map_err(ExportErrorData::Other)
+            .and_then(|value| xml_ser::to_string(&value).map_err(ExportErrorData::Other))
+            .and_then(|s| {
+                writer
+                    .write_all(s.as_bytes())
+                    .map_err(|err| ExportErrorData::Other(err.to_string()))
+            }),
+        ExportFormat::Dotenv => Value::from_term(rt, key_order)
+            .map_err(ExportErrorData::Other)
+            .and_then(|value| dotenv_ser::to_string(&value).map_err(ExportErrorData::Other))
+            .and_then(|s| {
+                writer
+                    .write_all(s.as_bytes())
+                    .map_err(|err| ExportErrorData::Other(err.to_string()))
+            }),
+        ExportFormat::Shell => Value::from_term(rt, key_order)
+            .map_err(ExportErrorData::Other)
+            .and_then(|value| dotenv_ser::to_shell_string(&value).map_err(ExportErrorData::Other))
+            .and_then(|s| {
+                writer
+                    .write_all(s.as_bytes())
+                    .map_err(|err| ExportErrorData::Other(err.to_string()))
+            }),
+        ExportFormat::Ini => Value::from_term(rt, key_order)
+            .map_err(ExportErrorData::Other)
+            .and_then(|value| ini_ser::to_string(&value).map_err(ExportErrorData::Other))
+            .and_then(|s| {
+                writer
+                    .write_all(s.as_bytes())
+                    .map_err(|err| ExportErrorData::Other(err.to_string()))
+            }),
+        ExportFormat::Properties => Value::from_term(rt, key_order)
+            .map_err(ExportErrorData::Other)
+            .and_then(|value| properties_ser::to_string(&value).map_err(ExportErrorData::Other))
+            .and_then(|s| {
+                writer
+                    .write_all(s.as_bytes())
+                    .map_err(|err| ExportErrorData::Other(err.to_string()))
+            }),
+        ExportFormat::Hcl => Value::from_term(rt, key_order)
+            .map_err(ExportErrorData::Other)
+            .and_then(|value| hcl_ser::to_string(&value).map_err(ExportErrorData::Other))
+            .and_then(|s| {
+                writer
+                    .write_all(s.as_bytes())
+                    .map_err(|err| ExportErrorData::Other(err.to_string()))
+            }),
         ExportFormat::Text => match rt.as_ref() {
             Term::Str(s) => writer
                 .write_all(s.as_bytes())
@@ -457,6 +665,45 @@ where
     Ok(())
 }
 
+/// Same as [to_writer_with_key_order] with [ExportFormat::Json], but with the given layout.
+pub fn to_writer_json<W>(
+    writer: W,
+    rt: &RichTerm,
+    layout: JsonLayout,
+    key_order: KeyOrder,
+) -> Result<(), ExportError>
+where
+    W: io::Write,
+{
+    Ok(write_json(writer, rt, layout, key_order)?)
+}
+
+fn write_json<W>(
+    writer: W,
+    rt: &RichTerm,
+    layout: JsonLayout,
+    key_order: KeyOrder,
+) -> Result<(), ExportErrorData>
+where
+    W: io::Write,
+{
+    let ordered = Ordered(rt, key_order);
+
+    match layout {
+        JsonLayout::Compact => serde_json::to_writer(writer, &ordered),
+        JsonLayout::Pretty { indent } => {
+            let indent = " ".repeat(indent);
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            ordered.serialize(&mut serde_json::Serializer::with_formatter(
+                writer, formatter,
+            ))
+        }
+    }
+    .map_err(|err| ExportErrorData::Other(err.to_string()))
+}
+
+/// Serialize a term to a string. The output of the binary formats isn't valid UTF-8 in general:
+/// use [to_writer] instead.
 pub fn to_string(format: ExportFormat, rt: &RichTerm) -> Result<String, ExportError> {
     let mut buffer: Vec<u8> = Vec::new();
     to_writer(&mut buffer, format, rt)?;
@@ -464,6 +711,387 @@ pub fn to_string(format: ExportFormat, rt: &RichTerm) -> Result<String, ExportEr
     Ok(String::from_utf8_lossy(&buffer).into_owned())
 }
 
+/// Split a fully evaluated term into its public part, where the values of the fields marked as
+/// `secret` are replaced by [REDACTED], and a term gathering the secret values at their original
+/// location, or `None` if there isn't any exported secret. In the secret part, the elements of an
+/// array which don't contain any secret are replaced by `null`.
+pub fn split_secrets(rt: &RichTerm) -> (RichTerm, Option<RichTerm>) {
+    match rt.as_ref() {
+        Term::Record(record) => {
+            let mut public = record.clone();
+            let mut secrets = Vec::new();
+
+            for (id, field) in public.fields.iter_mut() {
+                let Some(value) = field.value.take() else {
+                    continue;
+                };
+
+                let (value, secret) = if field.metadata.secret {
+                    let redacted = RichTerm::new(Term::Str(REDACTED.into()), value.pos);
+                    (redacted, Some(value))
+                } else {
+                    split_secrets(&value)
+                };
+
+                field.value = Some(value);
+
+                if let (Some(secret), false) = (secret, field.metadata.not_exported) {
+                    secrets.push((*id, secret));
+                }
+            }
+
+            let secrets = (!secrets.is_empty())
+                .then(|| RichTerm::from(Term::Record(RecordData::with_field_values(secrets))));
+
+            (RichTerm::new(Term::Record(public), rt.pos), secrets)
+        }
+        Term::Array(elts, attrs) => {
+            let (public, secrets): (Vec<_>, Vec<_>) = elts.iter().map(split_secrets).unzip();
+
+            let secrets = secrets.iter().any(Option::is_some).then(|| {
+                RichTerm::from(Term::Array(
+                    secrets
+                        .into_iter()
+                        .map(|secret| secret.unwrap_or_else(|| RichTerm::from(Term::Null)))
+                        .collect(),
+                    ArrayAttrs::default(),
+                ))
+            });
+
+            (
+                RichTerm::new(
+                    Term::Array(public.into_iter().collect(), attrs.clone()),
+                    rt.pos,
+                ),
+                secrets,
+            )
+        }
+        _ => (rt.clone(), None),
+    }
+}
+
+/// Convert a fully evaluated term to a JSON value. The term is validated first, as for an export
+/// to JSON.
+impl TryFrom<&RichTerm> for serde_json::Value {
+    type Error = ExportError;
+
+    fn try_from(rt: &RichTerm) -> Result<Self, ExportError> {
+        validate(ExportFormat::Json, rt)?;
+        serde_json::to_value(rt).map_err(|err| ExportErrorData::Other(err.to_string()).into())
+    }
+}
+
+impl TryFrom<RichTerm> for serde_json::Value {
+    type Error = ExportError;
+
+    fn try_from(rt: RichTerm) -> Result<Self, ExportError> {
+        serde_json::Value::try_from(&rt)
+    }
+}
+
+/// A fully evaluated term which has been validated for serialization, to be serialized by Rust
+/// code with any serde data format. The records are serialized in the selected order of fields.
+///
+/// Contrary to the built-in exporters, the format-specific restrictions aren't checked: for
+/// example, a TOML serializer rejects `null` values by itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportedValue {
+    term: RichTerm,
+    key_order: KeyOrder,
+}
+
+impl ExportedValue {
+    /// Validate a fully evaluated term, as for an export to JSON. The records are serialized in
+    /// alphabetical order.
+    pub fn new(term: RichTerm) -> Result<Self, ExportError> {
+        validate(ExportFormat::Json, &term)?;
+
+        Ok(ExportedValue {
+            term,
+            key_order: KeyOrder::default(),
+        })
+    }
+
+    /// Serialize the records in the given order of fields.
+    pub fn with_key_order(self, key_order: KeyOrder) -> Self {
+        ExportedValue { key_order, ..self }
+    }
+
+    pub fn as_term(&self) -> &RichTerm {
+        &self.term
+    }
+
+    pub fn into_term(self) -> RichTerm {
+        self.term
+    }
+}
+
+impl TryFrom<RichTerm> for ExportedValue {
+    type Error = ExportError;
+
+    fn try_from(term: RichTerm) -> Result<Self, ExportError> {
+        ExportedValue::new(term)
+    }
+}
+
+impl Serialize for ExportedValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Ordered(&self.term, self.key_order).serialize(serializer)
+    }
+}
+
+/// Build a Nickel literal from a JSON value. Integers are converted exactly, and other numbers
+/// are converted from their `f64` representation, as when importing a JSON file.
+impl From<serde_json::Value> for RichTerm {
+    fn from(value: serde_json::Value) -> Self {
+        use serde_json::Value;
+
+        let term = match value {
+            Value::Null => Term::Null,
+            Value::Bool(b) => Term::Bool(b),
+            Value::Number(n) => {
+                let number = if let Some(i) = n.as_i64() {
+                    Number::from(i)
+                } else if let Some(u) = n.as_u64() {
+                    Number::from(u)
+                } else {
+                    // unwrap(): JSON numbers are always finite. `as_f64` may only fail with the
+                    // `arbitrary_precision` feature of `serde_json`, which we don't use.
+                    Number::try_from_float_simplest(n.as_f64().unwrap()).unwrap()
+                };
+
+                Term::Num(number)
+            }
+            Value::String(s) => Term::Str(s.into()),
+            Value::Array(elts) => Term::Array(
+                elts.into_iter().map(RichTerm::from).collect(),
+                ArrayAttrs::default(),
+            ),
+            Value::Object(fields) => Term::Record(RecordData::with_field_values(
+                fields
+                    .into_iter()
+                    .map(|(id, value)| (LocIdent::from(id), RichTerm::from(value))),
+            )),
+        };
+
+        RichTerm::from(term)
+    }
+}
+
+/// Same as [to_writer_with_key_order], but render the top-level fields of a record on up to
+/// `threads` threads.
+///
+/// The term is validated first. Each top-level field is then converted and rendered by a worker
+/// thread, and the rendered fields are written in order by another thread as soon as they are
+/// available. At most twice as many fields as there are threads are rendered ahead of the output,
+/// so that the memory usage stays bounded. The output is the same as the one of
+/// [to_writer_with_key_order], and an error on a field is located at this field.
+///
+/// This is only supported for JSON and YAML. For other formats, or if `rt` isn't a record, this
+/// function falls back to [to_writer_with_key_order].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "serialize", skip_all, fields(?format))
+)]
+pub fn to_writer_parallel<W>(
+    writer: W,
+    format: ExportFormat,
+    rt: &RichTerm,
+    threads: NonZeroUsize,
+    key_order: KeyOrder,
+) -> Result<(), ExportError>
+where
+    W: io::Write + Send,
+{
+    let record = match (format, rt.as_ref()) {
+        (ExportFormat::Json | ExportFormat::Yaml, Term::Record(record)) if threads.get() > 1 => {
+            record
+        }
+        _ => return to_writer_with_key_order(writer, format, rt, key_order),
+    };
+
+    validate(format, rt)?;
+
+    let entries = ordered_entries(record, key_order).map_err(ExportErrorData::Other)?;
+
+    // The empty record has a special representation in both formats.
+    if entries.is_empty() {
+        return to_writer_with_key_order(writer, format, rt, key_order);
+    }
+
+    #[cfg(feature = "metrics")]
+    let start_time = std::time::Instant::now();
+
+    let fields = SharedFields(&entries);
+
+    match format {
+        ExportFormat::Json => {
+            render_parallel(writer, &fields, threads, ("{\n", ",\n", "\n}"), |id, t| {
+                // JSON strings can't contain a literal newline, so it's safe to indent the
+                // rendered value by inserting spaces after each newline.
+                let value = serde_json::to_string_pretty(&Ordered(t, key_order))
+                    .map_err(|err| err.to_string())?
+                    .replace('\n', "\n  ");
+                let key = serde_json::to_string(id.label()).map_err(|err| err.to_string())?;
+                Ok(format!("  {key}: {value}"))
+            })
+        }
+        // A YAML block mapping is just the concatenation of its entries, so we render each field
+        // as a singleton mapping.
+        ExportFormat::Yaml => render_parallel(writer, &fields, threads, ("", "", ""), |id, t| {
+            Ok(yaml_ser::entry_to_string(
+                id.label(),
+                &Value::from_term(t, key_order)?,
+            ))
+        }),
+        _ => unreachable!(),
+    }?;
+
+    metrics::increment!("runtime:serialize", start_time.elapsed().as_millis() as u64);
+
+    Ok(())
+}
+
+/// The top-level fields of a record, shared with the worker threads of [render_parallel].
+///
+/// Terms can't be shared between threads in general: they are reference-counted with
+/// [std::rc::Rc], and the closures they contain are updated by the evaluation. Rendering a field
+/// only reads it, though, as serializers don't clone terms nor look inside closures.
+struct SharedFields<'a>(&'a [(Ident, &'a RichTerm)]);
+
+impl<'a> SharedFields<'a> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, index: usize) -> (Ident, &'a RichTerm) {
+        self.0[index]
+    }
+}
+
+// SAFETY: the fields are only read by the workers, which never clone nor drop an `Rc`. Nothing
+// else can access the fields while the workers are running: all the owners of the `Rc`s
+// reachable from the fields live on the thread calling [render_parallel], which is blocked until
+// the workers are done, and the only foreign code which runs meanwhile is the writer, which is
+// `Send` and thus can't hold an `Rc`.
+unsafe impl Sync for SharedFields<'_> {}
+
+/// Render `fields` on up to `threads` scoped threads, and write them to `writer` in order,
+/// surrounded by `prefix` and `suffix` and separated by `separator`.
+///
+/// The writer runs on its own thread, which hands out the indices of the fields to render to the
+/// workers: at most twice as many fields as there are threads are being rendered or waiting to be
+/// written at any time.
+fn render_parallel<W, F>(
+    mut writer: W,
+    fields: &SharedFields<'_>,
+    threads: NonZeroUsize,
+    (prefix, separator, suffix): (&str, &str, &str),
+    render: F,
+) -> Result<(), ExportError>
+where
+    W: io::Write + Send,
+    F: Fn(Ident, &RichTerm) -> Result<String, String> + Sync,
+{
+    use std::{
+        collections::BTreeMap,
+        panic::{self, AssertUnwindSafe},
+        sync::{mpsc, Mutex},
+    };
+
+    // Export errors hold terms, which can't be sent between threads: the writer returns the
+    // location and the message of the error instead.
+    let io_err = |err: io::Error| (NickelPointer::new(), err.to_string());
+    let render = &render;
+    let len = fields.len();
+    let window = 2 * threads.get();
+
+    let (job_sender, job_receiver) = mpsc::channel();
+    let job_receiver = Mutex::new(job_receiver);
+    let (result_sender, result_receiver) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.get() {
+            let job_receiver = &job_receiver;
+            let result_sender = result_sender.clone();
+
+            scope.spawn(move || {
+                // The lock is released as soon as a job has been received, so that the other
+                // workers can pick the next ones while this one is rendering.
+                loop {
+                    let job = job_receiver.lock().unwrap().recv();
+                    let Ok(index) = job else { break };
+
+                    let (id, t) = fields.get(index);
+                    // A panic is handed over to the writer, which propagates it, instead of
+                    // leaving it waiting for this field forever.
+                    let rendered = panic::catch_unwind(AssertUnwindSafe(|| render(id, t)));
+
+                    if result_sender.send((index, rendered)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        // Only the workers should keep the result channel open.
+        drop(result_sender);
+
+        // The job channel is closed when the writer returns, even on error, which lets the
+        // workers terminate.
+        let output = scope.spawn(move || {
+            for index in 0..window.min(len) {
+                // unwrap(): the job receiver outlives the writer.
+                job_sender.send(index).unwrap();
+            }
+
+            // The fields rendered out of order, waiting for the previous ones to be written.
+            let mut pending = BTreeMap::new();
+
+            for next in 0..len {
+                let rendered = loop {
+                    if let Some(rendered) = pending.remove(&next) {
+                        break rendered;
+                    }
+
+                    // unwrap(): the workers only stop once the writer is done.
+                    let (index, rendered) = result_receiver.recv().unwrap();
+                    pending.insert(index, rendered);
+                };
+                let rendered = rendered
+                    .unwrap_or_else(|panic| panic::resume_unwind(panic))
+                    .map_err(|err| {
+                        let (id, _) = fields.get(next);
+                        (NickelPointer(vec![NickelPointerElem::Field(id)]), err)
+                    })?;
+
+                writer
+                    .write_all(if next == 0 { prefix } else { separator }.as_bytes())
+                    .map_err(io_err)?;
+                writer.write_all(rendered.as_bytes()).map_err(io_err)?;
+
+                if next + window < len {
+                    // unwrap(): the job receiver outlives the writer.
+                    job_sender.send(next + window).unwrap();
+                }
+            }
+
+            writer.write_all(suffix.as_bytes()).map_err(io_err)
+        });
+
+        output
+            .join()
+            .unwrap_or_else(|panic| panic::resume_unwind(panic))
+    })
+    .map_err(|(path, message)| ExportError {
+        path,
+        data: ExportErrorData::Other(message),
+    })
+}
+
 /// We don't use serde to deserialize toml, because
 /// - this bug: <https://github.com/toml-rs/toml/issues/798>
 /// - the machinery for getting spans for toml+serde is more code than
@@ -550,7 +1178,9 @@ pub mod toml_deser {
                     RecordAttrs::default(),
                     None,
                 )),
-                Value::Datetime(_) => todo!(),
+                // Nickel doesn't have a datetime type: datetimes are represented as strings, in
+                // the RFC 3339 format used by TOML.
+                Value::Datetime(dt) => Term::Str(dt.value().to_string().into()),
             }
         }
     }
@@ -579,39 +1209,1750 @@ pub mod toml_deser {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::cache::resolvers::DummyResolver;
-    use crate::eval::cache::CacheImpl;
-    use crate::eval::VirtualMachine;
-    use crate::program::Program;
-    use crate::term::{make as mk_term, BinaryOp};
-    use serde_json::json;
-    use std::io::Cursor;
+/// We parse JSON with a small parser of our own rather than with serde, which doesn't give the
+/// spans of the values, so that the terms of an imported JSON file point to their location in the
+/// file. The source is validated by `serde_json` first, which reports the syntax errors. The
+/// scalars are then converted by `serde_json` as well, so that they are deserialized exactly as
+/// before.
+pub mod json_deser {
+    use crate::{
+        files::FileId,
+        identifier::LocIdent,
+        position::{RawSpan, TermPos},
+        term::{array::ArrayAttrs, record::RecordData, RichTerm, Term},
+    };
+    use codespan::ByteIndex;
+    use indexmap::IndexMap;
+    use serde::de::IgnoredAny;
 
-    fn eval(s: &str) -> RichTerm {
-        let src = Cursor::new(s);
-        let mut prog =
-            Program::<CacheImpl>::new_from_source(src, "<test>", std::io::stderr()).unwrap();
-        prog.eval_full().expect("program eval should succeed")
+    struct Parser<'a> {
+        src: &'a str,
+        offset: usize,
+        file_id: FileId,
     }
 
-    #[track_caller]
-    fn assert_json_eq<T: Serialize>(term: &str, expected: T) {
-        assert_eq!(
-            serde_json::to_string(&eval(term)).unwrap(),
-            serde_json::to_string(&expected).unwrap()
-        )
-    }
+    impl<'a> Parser<'a> {
+        fn peek(&self) -> Option<u8> {
+            self.src.as_bytes().get(self.offset).copied()
+        }
 
-    #[track_caller]
-    fn assert_nickel_eq(term: RichTerm, expected: RichTerm) {
-        assert_eq!(
-            VirtualMachine::<_, CacheImpl>::new(DummyResolver {}, std::io::stderr())
-                .eval(mk_term::op2(BinaryOp::Eq, term, expected))
-                .map(Term::from),
-            Ok(Term::Bool(true))
+        fn skip_whitespace(&mut self) {
+            while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+                self.offset += 1;
+            }
+        }
+
+        /// Skip the whitespace, and then `c` if it's the next character. Return `true` if `c`
+        /// has been skipped.
+        fn eat(&mut self, c: u8) -> bool {
+            self.skip_whitespace();
+            let found = self.peek() == Some(c);
+
+            if found {
+                self.offset += 1;
+            }
+
+            found
+        }
+
+        /// The position from `start` to the current offset.
+        fn pos(&self, start: usize) -> TermPos {
+            RawSpan {
+                src_id: self.file_id,
+                start: ByteIndex(start as u32),
+                end: ByteIndex(self.offset as u32),
+            }
+            .into()
+        }
+
+        /// Skip a scalar, that is a string, a number, a boolean or `null`, and return its source.
+        fn scalar(&mut self) -> &'a str {
+            let start = self.offset;
+
+            if self.peek() == Some(b'"') {
+                self.offset += 1;
+
+                while let Some(c) = self.peek() {
+                    self.offset += if c == b'\\' { 2 } else { 1 };
+
+                    if c == b'"' {
+                        break;
+                    }
+                }
+            } else {
+                while self
+                    .peek()
+                    .is_some_and(|c| !c.is_ascii_whitespace() && !b",]}".contains(&c))
+                {
+                    self.offset += 1;
+                }
+            }
+
+            &self.src[start..self.offset]
+        }
+
+        fn value(&mut self) -> Result<RichTerm, serde_json::Error> {
+            self.skip_whitespace();
+            let start = self.offset;
+
+            let term = if self.eat(b'{') {
+                let mut fields = IndexMap::new();
+
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key_start = self.offset;
+                        let key: String = serde_json::from_str(self.scalar())?;
+                        let key = LocIdent::new(key).with_pos(self.pos(key_start));
+                        self.eat(b':');
+                        fields.insert(key, self.value()?);
+
+                        if !self.eat(b',') {
+                            self.eat(b'}');
+                            break;
+                        }
+                    }
+                }
+
+                Term::Record(RecordData::with_field_values(fields))
+            } else if self.eat(b'[') {
+                let mut values = Vec::new();
+
+                if !self.eat(b']') {
+                    loop {
+                        values.push(self.value()?);
+
+                        if !self.eat(b',') {
+                            self.eat(b']');
+                            break;
+                        }
+                    }
+                }
+
+                Term::Array(values.into_iter().collect(), ArrayAttrs::default())
+            } else {
+                return serde_json::from_str::<RichTerm>(self.scalar())
+                    .map(|term| term.with_pos(self.pos(start)));
+            };
+
+            Ok(RichTerm::new(term, self.pos(start)))
+        }
+    }
+
+    /// Deserialize a Nickel term with position information from a JSON source provided as a
+    /// string and the file id of this source.
+    pub fn from_str(s: &str, file_id: FileId) -> Result<RichTerm, serde_json::Error> {
+        serde_json::from_str::<IgnoredAny>(s)?;
+
+        Parser {
+            src: s,
+            offset: 0,
+            file_id,
+        }
+        .value()
+    }
+}
+
+/// As for JSON, we parse YAML with `yaml-rust2`, which gives the location of each value, rather
+/// than with serde. `serde_yaml` only reports locations for errors: the deserializer doesn't expose
+/// the position of the values it produces. This requires the `yaml-positions` feature, without
+/// which YAML files are parsed with `serde_yaml`. Scalars are resolved following the core schema of YAML 1.2, as `serde_yaml` does.
+/// The spans of scalars and collections are recovered from the source and are exact for the
+/// common cases, but may be approximate for multi-line plain scalars.
+#[cfg(feature = "yaml-positions")]
+pub mod yaml_deser {
+    use std::{collections::HashMap, fmt, str::Chars};
+
+    use crate::{
+        files::FileId,
+        identifier::LocIdent,
+        position::{RawSpan, TermPos},
+        term::{array::ArrayAttrs, record::RecordData, Number, RichTerm, Term},
+    };
+    use codespan::ByteIndex;
+    use indexmap::IndexMap;
+    use yaml_rust2::{
+        parser::{Event, Parser, Tag},
+        scanner::{Marker, TScalarStyle},
+    };
+
+    /// An error while parsing YAML, with its byte offset in the source, if any.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct YamlError {
+        pub message: String,
+        pub offset: Option<usize>,
+    }
+
+    impl fmt::Display for YamlError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.message)
+        }
+    }
+
+    struct Deserializer<'a> {
+        src: &'a str,
+        parser: Parser<Chars<'a>>,
+        file_id: FileId,
+        /// The byte offset of each character, if the source isn't ASCII. The markers of
+        /// `yaml-rust2` are character indices.
+        char_offsets: Option<Vec<usize>>,
+        /// The values of the anchors, indexed by their id.
+        anchors: HashMap<usize, RichTerm>,
+    }
+
+    impl<'a> Deserializer<'a> {
+        fn offset(&self, mark: Marker) -> usize {
+            match &self.char_offsets {
+                Some(offsets) => offsets.get(mark.index()).copied().unwrap_or(self.src.len()),
+                None => mark.index().min(self.src.len()),
+            }
+        }
+
+        fn next(&mut self) -> Result<(Event, usize), YamlError> {
+            match self.parser.next_token() {
+                Ok((event, mark)) => Ok((event, self.offset(mark))),
+                Err(err) => Err(YamlError {
+                    message: err.to_string(),
+                    offset: Some(self.offset(*err.marker())),
+                }),
+            }
+        }
+
+        /// The offset of the next event, without consuming it.
+        fn peek_offset(&mut self) -> usize {
+            let mark = self.parser.peek().ok().map(|(_, mark)| *mark);
+            mark.map_or(self.src.len(), |mark| self.offset(mark))
+        }
+
+        fn pos(&self, start: usize, end: usize) -> TermPos {
+            RawSpan {
+                src_id: self.file_id,
+                start: ByteIndex(start as u32),
+                end: ByteIndex(end.max(start) as u32),
+            }
+            .into()
+        }
+
+        /// The offset of the end of the source between `start` and `end`, without the trailing
+        /// whitespace.
+        fn trim_end(&self, start: usize, end: usize) -> usize {
+            start + self.src[start..end].trim_end().len()
+        }
+
+        /// The offset of the end of a scalar starting at `start`.
+        fn scalar_end(&mut self, start: usize, value: &str, style: TScalarStyle) -> usize {
+            let src = self.src;
+            let bytes = src.as_bytes();
+            let mut end = start + 1;
+
+            match style {
+                TScalarStyle::SingleQuoted => {
+                    while end < bytes.len() {
+                        if bytes[end] == b'\'' {
+                            if bytes.get(end + 1) != Some(&b'\'') {
+                                return end + 1;
+                            }
+                            end += 1;
+                        }
+                        end += 1;
+                    }
+                    bytes.len()
+                }
+                TScalarStyle::DoubleQuoted => {
+                    while end < bytes.len() {
+                        match bytes[end] {
+                            b'\\' => end += 1,
+                            b'"' => return end + 1,
+                            _ => (),
+                        }
+                        end += 1;
+                    }
+                    bytes.len()
+                }
+                TScalarStyle::Literal | TScalarStyle::Folded => {
+                    let next = self.peek_offset();
+                    self.trim_end(start, next.max(start))
+                }
+                // The source of a plain scalar on a single line is its value.
+                TScalarStyle::Plain => {
+                    let mut end = (start + value.len()).min(self.src.len());
+                    while !self.src.is_char_boundary(end) {
+                        end += 1;
+                    }
+                    end
+                }
+            }
+        }
+
+        /// The offset of the end of a collection whose end event is at `end`.
+        fn collection_end(&self, start: usize, end: usize) -> usize {
+            match self.src.as_bytes().get(end) {
+                Some(b']' | b'}') => end + 1,
+                _ => self.trim_end(start, end.max(start)),
+            }
+        }
+
+        fn value(&mut self, event: Event, start: usize) -> Result<RichTerm, YamlError> {
+            let (term, anchor) = match event {
+                Event::Alias(id) => {
+                    return self.anchors.get(&id).cloned().ok_or_else(|| YamlError {
+                        message: "unknown anchor".to_owned(),
+                        offset: Some(start),
+                    })
+                }
+                Event::Scalar(value, style, anchor, tag) => {
+                    let end = self.scalar_end(start, &value, style);
+                    let term = resolve_scalar(value, style, tag).map_err(|message| YamlError {
+                        message,
+                        offset: Some(start),
+                    })?;
+
+                    (RichTerm::new(term, self.pos(start, end)), anchor)
+                }
+                Event::SequenceStart(anchor, _) => {
+                    let mut values = Vec::new();
+
+                    let end = loop {
+                        match self.next()? {
+                            (Event::SequenceEnd, end) => break end,
+                            (event, offset) => values.push(self.value(event, offset)?),
+                        }
+                    };
+
+                    let term = Term::Array(values.into_iter().collect(), ArrayAttrs::default());
+                    let end = self.collection_end(start, end);
+                    (RichTerm::new(term, self.pos(start, end)), anchor)
+                }
+                Event::MappingStart(anchor, _) => {
+                    let mut fields = IndexMap::new();
+
+                    let end = loop {
+                        let key = match self.next()? {
+                            (Event::MappingEnd, end) => break end,
+                            (Event::Scalar(key, style, ..), key_start) => {
+                                let key_end = self.scalar_end(key_start, &key, style);
+                                LocIdent::new(key).with_pos(self.pos(key_start, key_end))
+                            }
+                            (_, offset) => {
+                                return Err(YamlError {
+                                    message: "only scalars are supported as keys".to_owned(),
+                                    offset: Some(offset),
+                                })
+                            }
+                        };
+
+                        let (event, offset) = self.next()?;
+                        fields.insert(key, self.value(event, offset)?);
+                    };
+
+                    let term = Term::Record(RecordData::with_field_values(fields));
+                    let end = self.collection_end(start, end);
+                    (RichTerm::new(term, self.pos(start, end)), anchor)
+                }
+                event => {
+                    return Err(YamlError {
+                        message: format!("unexpected YAML event {event:?}"),
+                        offset: Some(start),
+                    })
+                }
+            };
+
+            if anchor != 0 {
+                self.anchors.insert(anchor, term.clone());
+            }
+
+            Ok(term)
+        }
+    }
+
+    /// Resolve a scalar to a null value, a boolean, a number or a string, following the core
+    /// schema of YAML 1.2. Quoted scalars and scalars tagged with `!!str` are always strings.
+    fn resolve_scalar(
+        value: String,
+        style: TScalarStyle,
+        tag: Option<Tag>,
+    ) -> Result<Term, String> {
+        let is_str =
+            tag.is_some_and(|tag| tag.handle == "tag:yaml.org,2002:" && tag.suffix == "str");
+
+        if is_str || style != TScalarStyle::Plain {
+            return Ok(Term::Str(value.into()));
+        }
+
+        let float = |value: f64| {
+            Number::try_from_float_simplest(value).map_err(|_| {
+                format!(
+                    "couldn't convert {value} to a Nickel number: Nickel doesn't support NaN nor \
+                    infinity"
+                )
+            })
+        };
+
+        let term = match value.as_str() {
+            "" | "~" | "null" | "Null" | "NULL" => Term::Null,
+            "true" | "True" | "TRUE" => Term::Bool(true),
+            "false" | "False" | "FALSE" => Term::Bool(false),
+            ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => {
+                Term::Num(float(f64::INFINITY)?)
+            }
+            "-.inf" | "-.Inf" | "-.INF" => Term::Num(float(f64::NEG_INFINITY)?),
+            ".nan" | ".NaN" | ".NAN" => Term::Num(float(f64::NAN)?),
+            _ => {
+                let digits = value.trim_start_matches(['-', '+']);
+
+                if let Some(hex) = value.strip_prefix("0x") {
+                    match u64::from_str_radix(hex, 16) {
+                        Ok(n) => Term::Num(n.into()),
+                        Err(_) => Term::Str(value.into()),
+                    }
+                } else if let Some(octal) = value.strip_prefix("0o") {
+                    match u64::from_str_radix(octal, 8) {
+                        Ok(n) => Term::Num(n.into()),
+                        Err(_) => Term::Str(value.into()),
+                    }
+                } else if let Ok(n) = value.parse::<i64>() {
+                    Term::Num(n.into())
+                } else if let Ok(n) = value.parse::<u64>() {
+                    Term::Num(n.into())
+                } else if digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+                    && digits.contains(|c: char| c.is_ascii_digit())
+                    && digits
+                        .chars()
+                        .all(|c| c.is_ascii_digit() || ".eE+-".contains(c))
+                {
+                    match value.parse::<f64>() {
+                        Ok(n) => Term::Num(float(n)?),
+                        Err(_) => Term::Str(value.into()),
+                    }
+                } else {
+                    Term::Str(value.into())
+                }
+            }
+        };
+
+        Ok(term)
+    }
+
+    /// Deserialize a Nickel term with position information from a YAML source provided as a
+    /// string and the file id of this source. A source with several documents is deserialized as
+    /// an array, and an empty source as `null`.
+    pub fn from_str(s: &str, file_id: FileId) -> Result<RichTerm, YamlError> {
+        let mut de = Deserializer {
+            src: s,
+            parser: Parser::new(s.chars()),
+            file_id,
+            char_offsets: (!s.is_ascii())
+                .then(|| s.char_indices().map(|(offset, _)| offset).collect()),
+            anchors: HashMap::new(),
+        };
+        let mut documents = Vec::new();
+
+        loop {
+            match de.next()? {
+                (Event::StreamEnd, _) => break,
+                (Event::StreamStart | Event::DocumentStart | Event::DocumentEnd, _) => (),
+                (event, offset) => documents.push(de.value(event, offset)?),
+            }
+        }
+
+        Ok(match documents.len() {
+            0 => Term::Null.into(),
+            1 => documents.pop().expect("we just checked the length"),
+            _ => Term::Array(documents.into_iter().collect(), ArrayAttrs::default()).into(),
+        })
+    }
+}
+
+/// The values the text formats are rendered from. They're the same as [serde_json::Value], but
+/// the fields of an object keep the order in which they've been inserted, so that a term can be
+/// rendered in any [KeyOrder].
+pub mod value {
+    use serde::Serialize;
+
+    use super::{ordered_entries, KeyOrder};
+    use crate::term::{IndexMap, RichTerm, Term};
+
+    pub type Map<K, V> = IndexMap<K, V>;
+
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    #[serde(untagged)]
+    pub enum Value {
+        Null,
+        Bool(bool),
+        Number(serde_json::Number),
+        String(String),
+        Array(Vec<Value>),
+        Object(Map<String, Value>),
+    }
+
+    impl Value {
+        /// Convert a fully evaluated term, with the fields of its records in the given order.
+        pub fn from_term(rt: &RichTerm, key_order: KeyOrder) -> Result<Value, String> {
+            match rt.as_ref() {
+                Term::Record(record) => ordered_entries(record, key_order)?
+                    .into_iter()
+                    .map(|(id, t)| Ok((id.into_label(), Value::from_term(t, key_order)?)))
+                    .collect::<Result<_, String>>()
+                    .map(Value::Object),
+                Term::Array(elts, _) => elts
+                    .iter()
+                    .map(|elt| Value::from_term(elt, key_order))
+                    .collect::<Result<_, _>>()
+                    .map(Value::Array),
+                Term::Annotated(_, inner) => Value::from_term(inner, key_order),
+                // Scalars are converted as by the derived serializer of terms.
+                _ => serde_json::to_value(rt)
+                    .map(Value::from)
+                    .map_err(|err| err.to_string()),
+            }
+        }
+
+        pub fn is_null(&self) -> bool {
+            matches!(self, Value::Null)
+        }
+
+        pub fn is_array(&self) -> bool {
+            matches!(self, Value::Array(_))
+        }
+
+        pub fn is_object(&self) -> bool {
+            matches!(self, Value::Object(_))
+        }
+    }
+
+    impl From<serde_json::Value> for Value {
+        fn from(value: serde_json::Value) -> Self {
+            match value {
+                serde_json::Value::Null => Value::Null,
+                serde_json::Value::Bool(b) => Value::Bool(b),
+                serde_json::Value::Number(n) => Value::Number(n),
+                serde_json::Value::String(s) => Value::String(s),
+                serde_json::Value::Array(elts) => {
+                    Value::Array(elts.into_iter().map(Value::from).collect())
+                }
+                serde_json::Value::Object(fields) => Value::Object(
+                    fields
+                        .into_iter()
+                        .map(|(key, value)| (key, Value::from(value)))
+                        .collect(),
+                ),
+            }
+        }
+    }
+}
+
+/// We render YAML with our own emitter rather than with `serde_yaml`, which only quotes the
+/// strings that a YAML 1.2 parser would read as another type. Many consumers of YAML implement
+/// YAML 1.1, where for example `no`, `on` or `2024-01-01` aren't strings, so we quote every string
+/// which is ambiguous in either version. Multi-line strings are rendered as literal block scalars.
+/// The layout is otherwise the same as `serde_yaml`'s. Terms are converted to [value::Value]
+/// first.
+pub mod yaml_ser {
+    use super::value::{Map, Value};
+
+    /// The words which are booleans or null in YAML 1.1 or 1.2, and the special keys of YAML 1.1.
+    const RESERVED: &[&str] = &[
+        "~", "null", "Null", "NULL", "true", "True", "TRUE", "false", "False", "FALSE", "y", "Y",
+        "yes", "Yes", "YES", "n", "N", "no", "No", "NO", "on", "On", "ON", "off", "Off", "OFF",
+        "<<", "=",
+    ];
+
+    /// The indentation of nested mappings and of the content of block scalars.
+    const INDENT: usize = 2;
+
+    /// Render a value as a YAML document.
+    pub fn to_string(value: &Value) -> String {
+        let mut out = String::new();
+
+        match value {
+            Value::Object(map) if !map.is_empty() => mapping(&mut out, map, 0),
+            Value::Array(items) if !items.is_empty() => sequence(&mut out, items, 0),
+            Value::String(s) if is_block(s) => block(&mut out, s, INDENT),
+            scalar => {
+                inline(&mut out, scalar);
+                out.push('\n');
+            }
+        }
+
+        out
+    }
+
+    /// Render a singleton mapping. A block mapping being the concatenation of its entries, this
+    /// is used to render the fields of a record separately.
+    pub fn entry_to_string(key: &str, value: &Value) -> String {
+        let mut out = String::new();
+        entry(&mut out, key, value, 0);
+        out
+    }
+
+    /// Render the entries of a non-empty mapping. The first entry is rendered at the current
+    /// position, and the following ones are indented by `indent`.
+    fn mapping(out: &mut String, map: &Map<String, Value>, indent: usize) {
+        for (i, (key, value)) in map.iter().enumerate() {
+            if i > 0 {
+                pad(out, indent);
+            }
+
+            entry(out, key, value, indent);
+        }
+    }
+
+    fn entry(out: &mut String, key: &str, value: &Value, indent: usize) {
+        string(out, key);
+        out.push(':');
+
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                out.push('\n');
+                pad(out, indent + INDENT);
+                mapping(out, map, indent + INDENT);
+            }
+            // As with `serde_yaml`, sequences aren't indented relatively to their key.
+            Value::Array(items) if !items.is_empty() => {
+                out.push('\n');
+                pad(out, indent);
+                sequence(out, items, indent);
+            }
+            Value::String(s) if is_block(s) => {
+                out.push(' ');
+                block(out, s, indent + INDENT);
+            }
+            scalar => {
+                out.push(' ');
+                inline(out, scalar);
+                out.push('\n');
+            }
+        }
+    }
+
+    /// Render the items of a non-empty sequence. The first item is rendered at the current
+    /// position, and the following ones are indented by `indent`.
+    fn sequence(out: &mut String, items: &[Value], indent: usize) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                pad(out, indent);
+            }
+
+            out.push_str("- ");
+
+            match item {
+                Value::Object(map) if !map.is_empty() => mapping(out, map, indent + INDENT),
+                Value::Array(items) if !items.is_empty() => sequence(out, items, indent + INDENT),
+                Value::String(s) if is_block(s) => block(out, s, indent + INDENT),
+                scalar => {
+                    inline(out, scalar);
+                    out.push('\n');
+                }
+            }
+        }
+    }
+
+    /// Render a scalar or an empty collection on the current line.
+    fn inline(out: &mut String, value: &Value) {
+        match value {
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Value::Number(n) => out.push_str(&n.to_string()),
+            Value::String(s) => string(out, s),
+            Value::Array(_) => out.push_str("[]"),
+            Value::Object(_) => out.push_str("{}"),
+        }
+    }
+
+    fn pad(out: &mut String, indent: usize) {
+        out.extend(std::iter::repeat_n(' ', indent));
+    }
+
+    /// Render a string on the current line: plain if it can't be read as anything else, and
+    /// quoted otherwise.
+    fn string(out: &mut String, s: &str) {
+        if is_plain(s) {
+            out.push_str(s);
+        } else if s.chars().all(is_printable) {
+            out.push('\'');
+            out.push_str(&s.replace('\'', "''"));
+            out.push('\'');
+        } else {
+            out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    '\r' => out.push_str("\\r"),
+                    c if is_printable(c) => out.push(c),
+                    c if (c as u32) <= 0xff => out.push_str(&format!("\\x{:02x}", c as u32)),
+                    c => out.push_str(&format!("\\u{:04x}", c as u32)),
+                }
+            }
+            out.push('"');
+        }
+    }
+
+    /// Render a multi-line string as a literal block scalar, whose content is indented by
+    /// `indent`. The chomping indicator preserves the trailing newlines.
+    fn block(out: &mut String, s: &str, indent: usize) {
+        let content = s.trim_end_matches('\n');
+
+        let (chomping, content) = match s.len() - content.len() {
+            0 => ("-", content),
+            1 => ("", content),
+            // The trailing newlines but the last one are kept as empty lines.
+            _ => ("+", &s[..s.len() - 1]),
+        };
+
+        out.push('|');
+        out.push_str(chomping);
+        out.push('\n');
+
+        for line in content.split('\n') {
+            if !line.is_empty() {
+                pad(out, indent);
+                out.push_str(line);
+            }
+            out.push('\n');
+        }
+    }
+
+    /// Return `true` if a string is rendered as a literal block scalar. The indentation of a block
+    /// scalar is inferred from its first line, which thus can't be empty or start with a space.
+    fn is_block(s: &str) -> bool {
+        s.contains('\n')
+            && !s.starts_with(['\n', ' '])
+            && s.chars().all(|c| c == '\n' || is_printable(c))
+    }
+
+    fn is_printable(c: char) -> bool {
+        (c == '\t' || !c.is_control()) && c != '\u{feff}'
+    }
+
+    /// Return `true` if a string can be rendered without quotes.
+    fn is_plain(s: &str) -> bool {
+        let mut chars = s.chars();
+
+        let Some(first) = chars.next() else {
+            return false;
+        };
+
+        // `-`, `?` and `:` only start an indicator when they are followed by a space.
+        let indicator = match first {
+            '-' | '?' | ':' => chars.next().is_none_or(char::is_whitespace),
+            _ => ",[]{}#&*!|>'\"%@`".contains(first),
+        };
+
+        !indicator
+            && !RESERVED.contains(&s)
+            && !is_numeric(s)
+            && !s.starts_with("---")
+            && !s.starts_with("...")
+            && !s.starts_with(char::is_whitespace)
+            && !s.ends_with(char::is_whitespace)
+            && !s.ends_with(':')
+            && !s.contains(": ")
+            && !s.contains(" #")
+            && s.chars().all(|c| c != '\t' && is_printable(c))
+    }
+
+    /// Return `true` if a string could be read as a number, a date or a time by some YAML parser.
+    /// We err on the side of caution and quote every string starting like a number.
+    fn is_numeric(s: &str) -> bool {
+        let unsigned = s.strip_prefix(['-', '+']).unwrap_or(s);
+        let digits = unsigned.strip_prefix('.').unwrap_or(unsigned);
+
+        digits.starts_with(|c: char| c.is_ascii_digit())
+            || matches!(
+                unsigned.to_ascii_lowercase().as_str(),
+                ".inf" | ".nan" | "inf" | "nan"
+            )
+    }
+}
+
+/// XML doesn't have the data model of the other formats, so we export it following a fixed
+/// mapping, documented in the manual:
+///
+/// - the top-level value is a record with a single field, which is the root element;
+/// - a record is an element whose fields are its children, except for the fields whose name
+///   starts with `@`, which are attributes, and the field `$text`, which is its text content;
+/// - an array repeats the element of the field for each of its items;
+/// - strings, numbers, booleans and enum tags are text content, and `null` is an empty element.
+///
+/// Values are converted to JSON values first, which have the same data model as Nickel values.
+pub mod xml_ser {
+    use super::value::{Map, Value};
+
+    /// The prefix of the fields rendered as attributes.
+    pub const ATTRIBUTE_PREFIX: &str = "@";
+
+    /// The name of the field rendered as the text content of an element.
+    pub const TEXT_FIELD: &str = "$text";
+
+    const INDENT: &str = "  ";
+
+    /// Render a value as an XML document. Return the reason of the failure if the value doesn't
+    /// follow the mapping.
+    pub fn to_string(value: &Value) -> Result<String, String> {
+        let (name, value) = match value {
+            Value::Object(map) if map.len() == 1 => map.iter().next().expect("one field"),
+            _ => {
+                return Err(
+                    "XML export expects a record with a single field, the root element".to_owned(),
+                )
+            }
+        };
+
+        if value.is_array() {
+            return Err(format!("the root element `{name}` can't be an array"));
+        }
+
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        element(&mut out, name, value, 0)?;
+        Ok(out)
+    }
+
+    /// Render the elements of a field, which are repeated if the value is an array.
+    fn element(out: &mut String, name: &str, value: &Value, depth: usize) -> Result<(), String> {
+        match value {
+            Value::Array(items) => items.iter().try_for_each(|item| {
+                if item.is_array() {
+                    Err(format!(
+                        "`{name}` is an array of arrays, which can't be exported to XML"
+                    ))
+                } else {
+                    single_element(out, name, item, depth)
+                }
+            }),
+            value => single_element(out, name, value, depth),
+        }
+    }
+
+    fn single_element(
+        out: &mut String,
+        name: &str,
+        value: &Value,
+        depth: usize,
+    ) -> Result<(), String> {
+        check_name(name)?;
+
+        out.push_str(&INDENT.repeat(depth));
+        out.push('<');
+        out.push_str(name);
+
+        let (text, children) = match value {
+            Value::Object(map) => attributes(out, name, map)?,
+            Value::Null => (None, Vec::new()),
+            value => (
+                Some(text(value).ok_or_else(|| format!("`{name}` can't be exported to XML"))?),
+                Vec::new(),
+            ),
+        };
+
+        match (text, children.is_empty()) {
+            (None, true) => out.push_str("/>\n"),
+            (Some(text), true) => {
+                out.push('>');
+                escape(out, name, &text, false)?;
+                out.push_str(&format!("</{name}>\n"));
+            }
+            (None, false) => {
+                out.push_str(">\n");
+
+                for (child, value) in children {
+                    element(out, child, value, depth + 1)?;
+                }
+
+                out.push_str(&INDENT.repeat(depth));
+                out.push_str(&format!("</{name}>\n"));
+            }
+            (Some(_), false) => {
+                return Err(format!(
+                    "`{name}` has both child elements and a `{TEXT_FIELD}` field, but mixed \
+                    content can't be exported to XML"
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The child elements of an element, as pairs of a name and a value.
+    type Children<'a> = Vec<(&'a str, &'a Value)>;
+
+    /// Render the attributes of an element, and return its text content and its children.
+    fn attributes<'a>(
+        out: &mut String,
+        name: &str,
+        map: &'a Map<String, Value>,
+    ) -> Result<(Option<String>, Children<'a>), String> {
+        let mut content = None;
+        let mut children = Vec::new();
+
+        for (key, value) in map {
+            if let Some(attribute) = key.strip_prefix(ATTRIBUTE_PREFIX) {
+                check_name(attribute)?;
+
+                // A null attribute is omitted, which makes optional attributes easy to express.
+                if value.is_null() {
+                    continue;
+                }
+
+                let value = text(value).ok_or_else(|| {
+                    format!(
+                        "the attribute `{attribute}` of `{name}` must be a string, a number, a \
+                        boolean or an enum tag"
+                    )
+                })?;
+
+                out.push_str(&format!(" {attribute}=\""));
+                escape(out, name, &value, true)?;
+                out.push('"');
+            } else if key == TEXT_FIELD {
+                content = match value {
+                    Value::Null => None,
+                    value => Some(text(value).ok_or_else(|| {
+                        format!(
+                            "the field `{TEXT_FIELD}` of `{name}` must be a string, a number, a \
+                            boolean or an enum tag"
+                        )
+                    })?),
+                };
+            } else {
+                children.push((key.as_str(), value));
+            }
+        }
+
+        Ok((content, children))
+    }
+
+    fn text(value: &Value) -> Option<String> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Check that a name is a valid XML name. Namespace prefixes such as `xmlns:foo` are valid
+    /// names.
+    fn check_name(name: &str) -> Result<(), String> {
+        let mut chars = name.chars();
+
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+            && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'));
+
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("`{name}` isn't a valid XML name"))
+        }
+    }
+
+    /// Escape the text content or the value of an attribute. Line breaks and tabulations are
+    /// escaped in attributes, where they would otherwise be normalized to spaces by parsers.
+    fn escape(out: &mut String, name: &str, s: &str, attribute: bool) -> Result<(), String> {
+        for c in s.chars() {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' if attribute => out.push_str("&quot;"),
+                '\n' | '\t' if attribute => out.push_str(&format!("&#{};", c as u32)),
+                '\r' => out.push_str("&#13;"),
+                '\n' | '\t' => out.push(c),
+                c if c.is_control() || c == '\u{fffe}' || c == '\u{ffff}' => {
+                    return Err(format!(
+                        "`{name}` contains the character U+{:04X}, which isn't allowed in XML",
+                        c as u32
+                    ))
+                }
+                c => out.push(c),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Environment files are rendered as one `KEY=value` line for each field of a record of scalar
+/// values. Values are quoted following the conventions understood by Docker Compose and the
+/// common dotenv libraries:
+///
+/// - values made of letters, digits and a few punctuation characters are left unquoted;
+/// - other values are single-quoted, so that they are taken literally, without interpolation of
+///   variables;
+/// - values containing a single quote or a control character, such as a line break, are
+///   double-quoted with escape sequences, where `$` is escaped as well.
+///
+/// The same records can be rendered as shell scripts instead, with one `export KEY='value'` line
+/// for each field, to be sourced by a POSIX shell.
+pub mod dotenv_ser {
+    use super::value::Value;
+
+    /// Render a record of scalar values as an environment file. Return the reason of the failure
+    /// if the value isn't a record of scalar values, or if a field isn't a valid variable name.
+    pub fn to_string(value: &Value) -> Result<String, String> {
+        lines(value, "dotenv", |out, key, value| {
+            out.push_str(key);
+            out.push('=');
+            quote(out, value);
+        })
+    }
+
+    /// Render a record of scalar values as `export` commands of a POSIX shell. Values are always
+    /// single-quoted, so that the shell doesn't interpret them.
+    pub fn to_shell_string(value: &Value) -> Result<String, String> {
+        lines(value, "shell", |out, key, value| {
+            out.push_str("export ");
+            out.push_str(key);
+            out.push_str("='");
+            // A single quote can't be escaped inside single quotes: we close the quoted string,
+            // add an escaped quote and open a new one.
+            out.push_str(&value.replace('\'', "'\\''"));
+            out.push('\'');
+        })
+    }
+
+    /// Render one line for each field of a record of scalar values.
+    fn lines(
+        value: &Value,
+        format: &str,
+        line: impl Fn(&mut String, &str, &str),
+    ) -> Result<String, String> {
+        let Value::Object(map) = value else {
+            return Err(format!("{format} export expects a record"));
+        };
+
+        let mut out = String::new();
+
+        for (key, value) in map {
+            check_name(key)?;
+
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => {
+                    return Err(format!(
+                        "the value of `{key}` must be a string, a number, a boolean or an enum tag"
+                    ))
+                }
+            };
+
+            line(&mut out, key, &value);
+            out.push('\n');
+        }
+
+        Ok(out)
+    }
+
+    /// Check that a name is a portable name of environment variable.
+    fn check_name(name: &str) -> Result<(), String> {
+        let mut chars = name.chars();
+
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(format!(
+                "`{name}` isn't a valid name of environment variable"
+            ))
+        }
+    }
+
+    fn quote(out: &mut String, value: &str) {
+        let unquoted = !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-.,:/@%+=".contains(c));
+
+        if unquoted {
+            out.push_str(value);
+        } else if !value.contains('\'') && !value.contains(char::is_control) {
+            out.push('\'');
+            out.push_str(value);
+            out.push('\'');
+        } else {
+            out.push('"');
+            for c in value.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '$' => out.push_str("\\$"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+    }
+}
+
+/// INI files are rendered from a record: the fields with a scalar value are global keys, written
+/// first, and the fields with a record value are sections. A record inside a section is a
+/// subsection, written as `[section "subsection"]` as in git configuration files. An array of
+/// scalar values repeats its key for each item, as for the multi-valued settings of systemd units.
+/// Keys are written as `key=value`, and values are written as is, since INI dialects don't agree
+/// on quoting.
+pub mod ini_ser {
+    use super::value::{Map, Value};
+
+    /// Render a record as an INI file. Return the reason of the failure if the record doesn't
+    /// follow the mapping.
+    pub fn to_string(value: &Value) -> Result<String, String> {
+        let Value::Object(map) = value else {
+            return Err("INI export expects a record".to_owned());
+        };
+
+        let mut out = String::new();
+        section(&mut out, None, map)?;
+        Ok(out)
+    }
+
+    /// Render the keys of a section, followed by its subsections. `header` is the name of the
+    /// section and of its subsection, if any, or `None` for the global keys.
+    fn section(
+        out: &mut String,
+        header: Option<(&str, Option<&str>)>,
+        map: &Map<String, Value>,
+    ) -> Result<(), String> {
+        // A section holding only subsections doesn't need a header of its own.
+        let only_subsections = !map.is_empty() && map.values().all(Value::is_object);
+
+        if let Some((name, subsection)) = header.filter(|_| !only_subsections) {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+
+            check(name, "section name", &['[', ']', '"'])?;
+            out.push('[');
+            out.push_str(name);
+
+            if let Some(subsection) = subsection {
+                check(subsection, "subsection name", &[])?;
+                out.push_str(" \"");
+                out.push_str(&subsection.replace('\\', "\\\\").replace('"', "\\\""));
+                out.push('"');
+            }
+
+            out.push_str("]\n");
+        }
+
+        // The keys must come before the subsections, as they would otherwise belong to the last
+        // one.
+        for (key, value) in map {
+            if !value.is_object() {
+                entry(out, key, value)?;
+            }
+        }
+
+        for (key, value) in map {
+            if let Value::Object(map) = value {
+                match header {
+                    None => section(out, Some((key, None)), map)?,
+                    Some((name, None)) => section(out, Some((name, Some(key))), map)?,
+                    Some((name, Some(subsection))) => {
+                        return Err(format!(
+                            "`{name}.{subsection}.{key}` is a record, but INI sections can only \
+                            be nested once"
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render a key, which is repeated for each item if its value is an array.
+    fn entry(out: &mut String, key: &str, value: &Value) -> Result<(), String> {
+        check(key, "key", &['=', '[', ']', ';', '#'])?;
+
+        let values = match value {
+            Value::Array(items) => items.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+
+        for value in values {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => {
+                    return Err(format!(
+                        "the value of `{key}` must be a string, a number, a boolean, an enum tag \
+                        or an array of those"
+                    ))
+                }
+            };
+
+            if value.contains(char::is_control) {
+                return Err(format!(
+                    "the value of `{key}` contains a line break or a control character, which \
+                    can't be exported to INI"
+                ));
+            }
+
+            out.push_str(key);
+            out.push('=');
+            out.push_str(&value);
+            out.push('\n');
+        }
+
+        Ok(())
+    }
+
+    /// Check that a name is non-empty, doesn't start or end with a space, and doesn't contain
+    /// control characters nor the `forbidden` characters.
+    fn check(name: &str, what: &str, forbidden: &[char]) -> Result<(), String> {
+        if name.is_empty()
+            || name.starts_with(char::is_whitespace)
+            || name.ends_with(char::is_whitespace)
+            || name.contains(|c: char| c.is_control() || forbidden.contains(&c))
+        {
+            Err(format!("`{name}` isn't a valid INI {what}"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Java properties files are rendered by flattening a record into dotted keys: `{a.b = 1}` is
+/// written as `a.b=1`. The items of arrays are indexed as in Spring Boot, `{a = [1]}` being
+/// written as `a[0]=1`. Keys and values are escaped as by `java.util.Properties.store`, including
+/// the non-ASCII characters, so that the output can be read either as ISO-8859-1 or as UTF-8.
+pub mod properties_ser {
+    use super::value::Value;
+
+    /// Render a record as a properties file.
+    pub fn to_string(value: &Value) -> Result<String, String> {
+        let Value::Object(map) = value else {
+            return Err("properties export expects a record".to_owned());
+        };
+
+        let mut out = String::new();
+
+        for (key, value) in map {
+            let mut escaped_key = String::new();
+            escape(&mut escaped_key, key, true);
+            flatten(&mut out, &mut escaped_key, value)?;
+        }
+
+        Ok(out)
+    }
+
+    /// Render the properties of `value` under the prefix `key`, which is already escaped.
+    fn flatten(out: &mut String, key: &mut String, value: &Value) -> Result<(), String> {
+        let value = match value {
+            Value::Object(map) => {
+                for (field, value) in map {
+                    let len = key.len();
+                    key.push('.');
+                    escape(key, field, true);
+                    flatten(out, key, value)?;
+                    key.truncate(len);
+                }
+
+                return Ok(());
+            }
+            Value::Array(items) => {
+                for (index, value) in items.iter().enumerate() {
+                    let len = key.len();
+                    key.push_str(&format!("[{index}]"));
+                    flatten(out, key, value)?;
+                    key.truncate(len);
+                }
+
+                return Ok(());
+            }
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Null => return Err(format!("`{key}` is null, which can't be exported")),
+        };
+
+        out.push_str(key);
+        out.push('=');
+        escape(out, &value, false);
+        out.push('\n');
+
+        Ok(())
+    }
+
+    /// Escape a key or a value. Spaces are escaped everywhere in keys, but only at the start of
+    /// values, where they would otherwise be skipped.
+    fn escape(out: &mut String, s: &str, key: bool) {
+        for (i, c) in s.chars().enumerate() {
+            match c {
+                ' ' if key || i == 0 => out.push_str("\\ "),
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\u{c}' => out.push_str("\\f"),
+                '=' | ':' | '#' | '!' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                ' '..='~' => out.push(c),
+                c => {
+                    let mut buf = [0; 2];
+                    for unit in c.encode_utf16(&mut buf) {
+                        out.push_str(&format!("\\u{unit:04X}"));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// HCL files are rendered for Terraform and Packer, which need to know which fields are blocks
+/// rather than attributes. As this isn't apparent from the values, we follow a fixed mapping:
+///
+/// - the top-level fields named after a block type of Terraform or Packer, such as `resource` or
+///   `variable`, are blocks. Their labels are the names of the nested fields, as many as the block
+///   type takes: `{resource.aws_instance.web = {..}}` is rendered as `resource "aws_instance" "web"
+///   {..}`. The other top-level fields are attributes, as in `.tfvars` files;
+/// - in a block, a field whose value is a non-empty array of records is rendered as a sequence of
+///   nested blocks, such as `lifecycle` or `ingress`. The other fields are attributes.
+///
+/// Strings are rendered as HCL string literals, where template sequences such as `${var.name}`
+/// are kept, so that expressions can be written as strings.
+pub mod hcl_ser {
+    use super::value::{Map, Value};
+
+    /// The block types which can appear at the top level, with their number of labels, in the
+    /// order in which they are rendered.
+    const BLOCK_TYPES: &[(&str, usize)] = &[
+        ("terraform", 0),
+        ("packer", 0),
+        ("provider", 1),
+        ("variable", 1),
+        ("locals", 0),
+        ("data", 2),
+        ("source", 2),
+        ("resource", 2),
+        ("module", 1),
+        ("check", 1),
+        ("moved", 0),
+        ("import", 0),
+        ("removed", 0),
+        ("build", 0),
+        ("output", 1),
+    ];
+
+    const INDENT: &str = "  ";
+
+    /// Render a record as an HCL file. Return the reason of the failure if the record doesn't
+    /// follow the mapping.
+    pub fn to_string(value: &Value) -> Result<String, String> {
+        let Value::Object(map) = value else {
+            return Err("HCL export expects a record".to_owned());
+        };
+
+        let mut out = String::new();
+
+        for (key, value) in map {
+            if !BLOCK_TYPES.iter().any(|(name, _)| name == key) {
+                attribute(&mut out, key, value, 0);
+            }
+        }
+
+        for (block_type, labels) in BLOCK_TYPES {
+            if let Some(value) = map.get(*block_type) {
+                labelled_blocks(&mut out, block_type, &mut Vec::new(), *labels, value)?;
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Render the top-level blocks of `block_type` whose `labels` remaining labels are the names of
+    /// the nested fields of `value`.
+    fn labelled_blocks<'a>(
+        out: &mut String,
+        block_type: &str,
+        labels: &mut Vec<&'a str>,
+        remaining: usize,
+        value: &'a Value,
+    ) -> Result<(), String> {
+        if remaining == 0 {
+            return blocks(out, block_type, labels, value, 0);
+        }
+
+        let Value::Object(map) = value else {
+            return Err(format!(
+                "`{block_type}` blocks take {} label(s), given by the names of nested records",
+                labels.len() + remaining
+            ));
+        };
+
+        for (label, value) in map {
+            labels.push(label);
+            labelled_blocks(out, block_type, labels, remaining - 1, value)?;
+            labels.pop();
+        }
+
+        Ok(())
+    }
+
+    /// Render a block, or a sequence of blocks if `value` is an array.
+    fn blocks(
+        out: &mut String,
+        block_type: &str,
+        labels: &[&str],
+        value: &Value,
+        depth: usize,
+    ) -> Result<(), String> {
+        let bodies: Vec<&Value> = match value {
+            Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        };
+
+        for body in bodies {
+            let Value::Object(body) = body else {
+                return Err(format!(
+                    "the body of a `{block_type}` block must be a record"
+                ));
+            };
+
+            // Blocks are separated by an empty line, except at the start of their parent block.
+            if !out.is_empty() && !out.ends_with("{\n") {
+                out.push('\n');
+            }
+
+            out.push_str(&INDENT.repeat(depth));
+            out.push_str(block_type);
+            for label in labels {
+                out.push(' ');
+                string(out, label);
+            }
+            out.push_str(" {\n");
+            block_body(out, body, depth + 1)?;
+            out.push_str(&INDENT.repeat(depth));
+            out.push_str("}\n");
+        }
+
+        Ok(())
+    }
+
+    fn block_body(out: &mut String, body: &Map<String, Value>, depth: usize) -> Result<(), String> {
+        let is_block = |value: &Value| {
+            matches!(value, Value::Array(items)
+                if !items.is_empty() && items.iter().all(Value::is_object))
+        };
+
+        for (key, value) in body {
+            if !is_block(value) {
+                attribute(out, key, value, depth);
+            }
+        }
+
+        for (key, value) in body {
+            if is_block(value) {
+                blocks(out, key, &[], value, depth)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn attribute(out: &mut String, key: &str, value: &Value, depth: usize) {
+        out.push_str(&INDENT.repeat(depth));
+        name(out, key);
+        out.push_str(" = ");
+        expression(out, value, depth);
+        out.push('\n');
+    }
+
+    /// Render a value as an HCL expression. Collections of scalars are rendered on one line.
+    fn expression(out: &mut String, value: &Value, depth: usize) {
+        match value {
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Value::Number(n) => out.push_str(&n.to_string()),
+            Value::String(s) => string(out, s),
+            Value::Array(items) if items.iter().any(|v| v.is_array() || v.is_object()) => {
+                out.push_str("[\n");
+                for item in items {
+                    out.push_str(&INDENT.repeat(depth + 1));
+                    expression(out, item, depth + 1);
+                    out.push_str(",\n");
+                }
+                out.push_str(&INDENT.repeat(depth));
+                out.push(']');
+            }
+            Value::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    expression(out, item, depth);
+                }
+                out.push(']');
+            }
+            Value::Object(map) if map.is_empty() => out.push_str("{}"),
+            Value::Object(map) => {
+                out.push_str("{\n");
+                for (key, value) in map {
+                    attribute(out, key, value, depth + 1);
+                }
+                out.push_str(&INDENT.repeat(depth));
+                out.push('}');
+            }
+        }
+    }
+
+    /// Render the name of an attribute, which is quoted if it isn't an identifier. Quoted names
+    /// are only valid as object keys, which is where they can't be identifiers anyway.
+    fn name(out: &mut String, key: &str) {
+        let mut chars = key.chars();
+
+        let identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+
+        if identifier {
+            out.push_str(key);
+        } else {
+            string(out, key);
+        }
+    }
+
+    /// Render a string literal. Template sequences are kept, so that they are evaluated.
+    fn string(out: &mut String, s: &str) {
+        out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::resolvers::DummyResolver;
+    use crate::eval::cache::CacheImpl;
+    use crate::eval::VirtualMachine;
+    use crate::position::TermPos;
+    use crate::program::Program;
+    use crate::term::{make as mk_term, BinaryOp};
+    use serde_json::json;
+    use std::io::Cursor;
+
+    fn eval(s: &str) -> RichTerm {
+        let src = Cursor::new(s);
+        let mut prog =
+            Program::<CacheImpl>::new_from_source(src, "<test>", std::io::stderr()).unwrap();
+        prog.eval_full().expect("program eval should succeed")
+    }
+
+    #[test]
+    fn json_value_conversions() {
+        let value = json!({
+            "name": "nickel",
+            "version": 1,
+            "ratio": 0.25,
+            "big": u64::MAX,
+            "tags": ["config", null, true],
+            "nested": { "empty": {} }
+        });
+
+        let rt = RichTerm::from(value.clone());
+        assert_eq!(serde_json::Value::try_from(&rt).unwrap(), value);
+
+        let evaluated = eval("{ foo = [1 + 1, \"a\" ++ \"b\"], bar = { baz = null } }");
+        assert_eq!(
+            serde_json::Value::try_from(evaluated).unwrap(),
+            json!({ "foo": [2, "ab"], "bar": { "baz": null } })
+        );
+
+        assert!(serde_json::Value::try_from(eval("{ f = fun x => x }")).is_err());
+    }
+
+    #[test]
+    fn json_positions() {
+        let source = "{\n  \"name\": \"nickel\",\n  \"tags\": [1, \"a\\\"b\", {}]\n}\n";
+        let file_id = crate::files::Files::new().add("data.json", source);
+        let term = json_deser::from_str(source, file_id).unwrap();
+
+        assert_eq!(
+            serde_json::Value::try_from(&term).unwrap(),
+            json!({ "name": "nickel", "tags": [1, "a\"b", {}] })
+        );
+
+        let snippet = |pos: TermPos| {
+            let span = pos.unwrap();
+            &source[span.start.to_usize()..span.end.to_usize()]
+        };
+
+        let Term::Record(data) = term.as_ref() else {
+            panic!("expected a record")
+        };
+        let (key, tags) = data.fields.get_index(1).unwrap();
+        let tags = tags.value.as_ref().unwrap();
+
+        assert_eq!(snippet(key.pos), "\"tags\"");
+        assert_eq!(snippet(tags.pos), "[1, \"a\\\"b\", {}]");
+
+        let Term::Array(values, _) = tags.as_ref() else {
+            panic!("expected an array")
+        };
+        let snippets: Vec<_> = values.iter().map(|value| snippet(value.pos)).collect();
+        assert_eq!(snippets, ["1", "\"a\\\"b\"", "{}"]);
+
+        assert!(json_deser::from_str("{ \"a\": [1, }", file_id).is_err());
+    }
+
+    #[cfg(feature = "yaml-positions")]
+    #[test]
+    fn yaml_positions() {
+        let source = "name: nickel\nport: 0x1F90\ntags:\n  - 'it''s'\n  - \"a\\\"b\"\n  - 1.5\nbase: &base { on: true }\ncopy: *base\n";
+        let file_id = crate::files::Files::new().add("values.yaml", source);
+        let term = yaml_deser::from_str(source, file_id).unwrap();
+
+        assert_eq!(
+            serde_json::Value::try_from(&term).unwrap(),
+            json!({
+                "name": "nickel",
+                "port": 8080,
+                "tags": ["it's", "a\"b", 1.5],
+                "base": { "on": true },
+                "copy": { "on": true },
+            })
+        );
+
+        let snippet = |pos: TermPos| {
+            let span = pos.unwrap();
+            &source[span.start.to_usize()..span.end.to_usize()]
+        };
+
+        let Term::Record(data) = term.as_ref() else {
+            panic!("expected a record")
+        };
+        let field = |index: usize| {
+            let (key, field) = data.fields.get_index(index).unwrap();
+            (snippet(key.pos), snippet(field.value.as_ref().unwrap().pos))
+        };
+
+        assert_eq!(field(1), ("port", "0x1F90"));
+        assert_eq!(field(3), ("base", "{ on: true }"));
+
+        let (_, tags) = data.fields.get_index(2).unwrap();
+        let Term::Array(values, _) = tags.value.as_ref().unwrap().as_ref() else {
+            panic!("expected an array")
+        };
+        let snippets: Vec<_> = values.iter().map(|value| snippet(value.pos)).collect();
+        assert_eq!(snippets, ["'it''s'", "\"a\\\"b\"", "1.5"]);
+
+        assert_eq!(
+            serde_json::Value::try_from(&yaml_deser::from_str("", file_id).unwrap()).unwrap(),
+            serde_json::Value::Null
+        );
+        assert!(yaml_deser::from_str("a: [1,", file_id).is_err());
+        assert!(yaml_deser::from_str("a: .nan", file_id).is_err());
+    }
+
+    #[test]
+    fn yaml_export() {
+        let value = json!({
+            "ambiguous": ["yes", "off", "null", "~", "1.0", "0x1F", "2024-01-01", ".inf", "<<"],
+            "escaped": "tab\tbell\u{7}",
+            "indicators": ["- a", "*ref", "a: b", "a #b", "it's", "", " padded", "-flag"],
+            "multiline": ["a\nb", "a\n  b\n", "a\n\n", "\nleading"],
+            "nested": { "empty": {}, "list": [{ "a": 1, "b": [] }, [true, null]] },
+            "plain": "hello world",
+        });
+
+        let expected = "\
+ambiguous:
+- 'yes'
+- 'off'
+- 'null'
+- '~'
+- '1.0'
+- '0x1F'
+- '2024-01-01'
+- '.inf'
+- '<<'
+escaped: \"tab\\tbell\\x07\"
+indicators:
+- '- a'
+- '*ref'
+- 'a: b'
+- 'a #b'
+- it's
+- ''
+- ' padded'
+- -flag
+multiline:
+- |-
+  a
+  b
+- |
+  a
+    b
+- |+
+  a
+
+- \"\\nleading\"
+nested:
+  empty: {}
+  list:
+  - a: 1
+    b: []
+  - - true
+    - null
+plain: hello world
+";
+
+        let rendered = yaml_ser::to_string(&super::value::Value::from(value.clone()));
+        assert_eq!(rendered, expected);
+
+        #[cfg(feature = "yaml-positions")]
+        {
+            let file_id = crate::files::Files::new().add("export.yaml", rendered.as_str());
+            let parsed = yaml_deser::from_str(&rendered, file_id).unwrap();
+            assert_eq!(serde_json::Value::try_from(&parsed).unwrap(), value);
+        }
+        assert_eq!(
+            serde_yaml::from_str::<serde_json::Value>(&rendered).unwrap(),
+            value
+        );
+    }
+
+    #[track_caller]
+    fn assert_json_eq<T: Serialize>(term: &str, expected: T) {
+        assert_eq!(
+            serde_json::to_string(&eval(term)).unwrap(),
+            serde_json::to_string(&expected).unwrap()
+        )
+    }
+
+    #[track_caller]
+    fn assert_nickel_eq(term: RichTerm, expected: RichTerm) {
+        assert_eq!(
+            VirtualMachine::<_, CacheImpl>::new(DummyResolver {}, std::io::stderr())
+                .eval(mk_term::op2(BinaryOp::Eq, term, expected))
+                .map(Term::from),
+            Ok(Term::Bool(true))
         )
     }
 
@@ -639,6 +2980,34 @@ mod tests {
         assert_nickel_eq(from_toml, evaluated);
     }
 
+    #[test]
+    fn secrets() {
+        let evaluated = eval(
+            r#"{
+                user = "admin",
+                password | secret = "hunter2",
+                tokens = [{ value | secret = "abc" }, { value = "public" }],
+                internal | secret | not_exported = "hidden",
+            }"#,
+        );
+        let (public, secrets) = split_secrets(&evaluated);
+
+        assert_eq!(
+            serde_json::Value::try_from(public).unwrap(),
+            json!({
+                "user": "admin",
+                "password": REDACTED,
+                "tokens": [{ "value": REDACTED }, { "value": "public" }],
+            })
+        );
+        assert_eq!(
+            serde_json::Value::try_from(secrets.unwrap()).unwrap(),
+            json!({ "password": "hunter2", "tokens": [{ "value": "abc" }, null] })
+        );
+
+        assert!(split_secrets(&eval("{ foo = [1] }")).1.is_none());
+    }
+
     #[test]
     fn basic() {
         assert_json_eq("1 + 1", 2);
@@ -718,6 +3087,410 @@ mod tests {
         );
         assert_pass_validation(ExportFormat::Json, "{foo = null}");
         assert_fail_validation(ExportFormat::Toml, "{foo = null}");
+        assert_fail_validation(ExportFormat::Toml, "[{foo = 1}]");
+        assert_pass_validation(ExportFormat::Toml, "{foo = [1, \"a\", {bar = true}]}");
+    }
+
+    #[test]
+    fn json_layout() {
+        let evaluated = eval("{a = [1, {b = null}], c = \"d\"}");
+        let render = |layout| {
+            let mut buffer = Vec::new();
+            to_writer_json(&mut buffer, &evaluated, layout, KeyOrder::default()).unwrap();
+            String::from_utf8(buffer).unwrap()
+        };
+
+        assert_eq!(
+            render(JsonLayout::Compact),
+            r#"{"a":[1,{"b":null}],"c":"d"}"#
+        );
+        assert_eq!(
+            render(JsonLayout::Pretty { indent: 4 }),
+            "{\n    \"a\": [\n        1,\n        {\n            \"b\": null\n        }\n    ],\n    \"c\": \"d\"\n}"
+        );
+        assert_eq!(
+            render(JsonLayout::default()),
+            to_string(ExportFormat::Json, &evaluated).unwrap()
+        );
+    }
+
+    #[test]
+    fn exported_value() {
+        let value = ExportedValue::new(eval("{b = [1, null], a = {d = 'Tag, c = \"x\"}}")).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"a":{"c":"x","d":"Tag"},"b":[1,null]}"#
+        );
+        assert_eq!(
+            serde_yaml::to_string(&value.clone().with_key_order(KeyOrder::Declaration)).unwrap(),
+            "b:\n- 1\n- null\na:\n  d: Tag\n  c: x\n"
+        );
+
+        assert_matches::assert_matches!(
+            ExportedValue::try_from(eval("{a = [fun x => x]}")),
+            Err(ExportError {
+                data: ExportErrorData::NonSerializable(_),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn key_order() {
+        let evaluated = eval("{b = 1, a = {d = 2, c = [{f = 3, e = 4}]}}");
+
+        assert_eq!(
+            to_string(ExportFormat::Json, &evaluated).unwrap(),
+            to_string(
+                ExportFormat::Json,
+                &eval("{a = {c = [{e = 4, f = 3}], d = 2}, b = 1}")
+            )
+            .unwrap()
+        );
+
+        let render = |format, key_order| {
+            let mut buffer = Vec::new();
+            to_writer_with_key_order(&mut buffer, format, &evaluated, key_order).unwrap();
+            String::from_utf8(buffer).unwrap()
+        };
+
+        assert_eq!(
+            render(ExportFormat::Yaml, KeyOrder::Declaration),
+            "b: 1\na:\n  d: 2\n  c:\n  - f: 3\n    e: 4\n"
+        );
+        assert_eq!(
+            render(ExportFormat::Yaml, KeyOrder::Sorted),
+            "a:\n  c:\n  - e: 4\n    f: 3\n  d: 2\nb: 1\n"
+        );
+        assert_eq!(
+            render(ExportFormat::Json, KeyOrder::Declaration),
+            "{\n  \"b\": 1,\n  \"a\": {\n    \"d\": 2,\n    \"c\": [\n      {\n        \"f\": 3,\n        \"e\": 4\n      }\n    ]\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn binary_formats() {
+        let evaluated = eval("{a = 1, b = [-2, 0.5, \"c\", null], d = {e = true, f = 'Tag}}");
+        let expected = json!({"a": 1, "b": [-2, 0.5, "c", null], "d": {"e": true, "f": "Tag"}});
+
+        let mut buffer = Vec::new();
+        to_writer(&mut buffer, ExportFormat::Msgpack, &evaluated).unwrap();
+        assert_eq!(
+            rmp_serde::from_slice::<serde_json::Value>(&buffer).unwrap(),
+            expected
+        );
+
+        let mut buffer = Vec::new();
+        to_writer(&mut buffer, ExportFormat::Cbor, &evaluated).unwrap();
+        assert_eq!(
+            ciborium::from_reader::<serde_json::Value, _>(buffer.as_slice()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn xml_export() {
+        let evaluated = eval(
+            r#"{
+              project = {
+                "@xmlns" = "http://maven.apache.org/POM/4.0.0",
+                artifactId = "app",
+                dependencies.dependency = [
+                  { artifactId = "a & b", optional = true },
+                  { artifactId = "c", scope = null },
+                ],
+                name = { "@lang" = "en\n\"us\"", "$text" = "<App>" },
+              }
+            }"#,
+        );
+
+        assert_eq!(
+            to_string(ExportFormat::Xml, &evaluated).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<project xmlns="http://maven.apache.org/POM/4.0.0">
+  <artifactId>app</artifactId>
+  <dependencies>
+    <dependency>
+      <artifactId>a &amp; b</artifactId>
+      <optional>true</optional>
+    </dependency>
+    <dependency>
+      <artifactId>c</artifactId>
+      <scope/>
+    </dependency>
+  </dependencies>
+  <name lang="en&#10;&quot;us&quot;">&lt;App&gt;</name>
+</project>
+"#
+        );
+
+        for invalid in [
+            "{a = 1, b = 2}",
+            "{a = [1]}",
+            "{a.b = [[1]]}",
+            "{a = {\"$text\" = \"t\", b = 1}}",
+            "{a = {\"@b\" = [1]}}",
+            "{a = {\"1b\" = 1}}",
+        ] {
+            assert!(to_string(ExportFormat::Xml, &eval(invalid)).is_err());
+        }
+    }
+
+    #[test]
+    fn dotenv_export() {
+        let evaluated = eval(
+            r#"{
+              PORT = 8080,
+              DEBUG = false,
+              MODE = 'production,
+              URL = "postgres://db:5432/app",
+              GREETING = "hello $USER",
+              QUOTE = "it's",
+              MULTILINE = "a\nb",
+              EMPTY = "",
+            }"#,
+        );
+
+        assert_eq!(
+            to_string(ExportFormat::Dotenv, &evaluated).unwrap(),
+            r#"DEBUG=false
+EMPTY=''
+GREETING='hello $USER'
+MODE=production
+MULTILINE="a\nb"
+PORT=8080
+QUOTE="it's"
+URL=postgres://db:5432/app
+"#
+        );
+
+        assert_fail_validation(ExportFormat::Dotenv, "{A = {B = 1}}");
+        assert_fail_validation(ExportFormat::Dotenv, "{A = [1]}");
+        assert_fail_validation(ExportFormat::Dotenv, "{A = null}");
+        assert_fail_validation(ExportFormat::Dotenv, "\"A=1\"");
+        assert!(to_string(ExportFormat::Dotenv, &eval("{\"not-a-name\" = 1}")).is_err());
+    }
+
+    #[test]
+    fn shell_export() {
+        let evaluated = eval(r#"{PORT = 8080, QUOTE = "it's $HOME", LINES = "a\nb"}"#);
+
+        assert_eq!(
+            to_string(ExportFormat::Shell, &evaluated).unwrap(),
+            "export LINES='a\nb'\nexport PORT='8080'\nexport QUOTE='it'\\''s $HOME'\n"
+        );
+
+        assert_fail_validation(ExportFormat::Shell, "{A.B = 1}");
+        assert_fail_validation(ExportFormat::Shell, "{A = null}");
+        assert!(to_string(ExportFormat::Shell, &eval("{\"A B\" = 1}")).is_err());
+    }
+
+    #[test]
+    fn ini_export() {
+        let evaluated = eval(
+            r#"{
+              Service = { ExecStart = "/usr/bin/app --port 80", Environment = ["A=1", "B=2"] },
+              Unit.Description = "My app",
+              remote.origin = { url = "https://example.com/app.git", mirror = false },
+              version = 2,
+            }"#,
+        );
+
+        assert_eq!(
+            to_string(ExportFormat::Ini, &evaluated).unwrap(),
+            r#"version=2
+
+[Service]
+Environment=A=1
+Environment=B=2
+ExecStart=/usr/bin/app --port 80
+
+[Unit]
+Description=My app
+
+[remote "origin"]
+mirror=false
+url=https://example.com/app.git
+"#
+        );
+
+        assert_fail_validation(ExportFormat::Ini, "{a = null}");
+        assert_fail_validation(ExportFormat::Ini, "[1]");
+
+        for invalid in [
+            "{a.b.c.d = 1}",
+            "{a = [[1]]}",
+            "{a = [{b = 1}]}",
+            "{a = \"multi\\nline\"}",
+            "{\"a=b\" = 1}",
+        ] {
+            assert!(to_string(ExportFormat::Ini, &eval(invalid)).is_err());
+        }
+    }
+
+    #[test]
+    fn properties_export() {
+        let evaluated = eval(
+            r#"{
+              server.port = 8080,
+              spring.datasource = { url = "jdbc:h2:mem:db", "user name" = " admin" },
+              app.greeting = "Grüß dich 😀\n!",
+              app.hosts = ["a", "b"],
+              empty = {},
+            }"#,
+        );
+
+        assert_eq!(
+            to_string(ExportFormat::Properties, &evaluated).unwrap(),
+            r#"app.greeting=Gr\u00FC\u00DF dich \uD83D\uDE00\n\!
+app.hosts[0]=a
+app.hosts[1]=b
+server.port=8080
+spring.datasource.url=jdbc\:h2\:mem\:db
+spring.datasource.user\ name=\ admin
+"#
+        );
+
+        assert_fail_validation(ExportFormat::Properties, "{a.b = null}");
+        assert_fail_validation(ExportFormat::Properties, "[1]");
+    }
+
+    #[test]
+    fn hcl_export() {
+        let evaluated = eval(
+            r#"{
+              region = "eu-west-1",
+              resource.aws_instance.web = {
+                ami = "ami-123",
+                tags = { Name = "web", "kubernetes.io/role" = "node" },
+                subnet_id = "${aws_subnet.main.id}",
+                lifecycle = [{ prevent_destroy = true }],
+                ports = [80, 443],
+              },
+              variable.size = { default = null },
+              terraform.required_providers = [{ aws = { source = "hashicorp/aws" } }],
+            }"#,
+        );
+
+        assert_eq!(
+            to_string(ExportFormat::Hcl, &evaluated).unwrap(),
+            r#"region = "eu-west-1"
+
+terraform {
+  required_providers {
+    aws = {
+      source = "hashicorp/aws"
+    }
+  }
+}
+
+variable "size" {
+  default = null
+}
+
+resource "aws_instance" "web" {
+  ami = "ami-123"
+  ports = [80, 443]
+  subnet_id = "${aws_subnet.main.id}"
+  tags = {
+    Name = "web"
+    "kubernetes.io/role" = "node"
+  }
+
+  lifecycle {
+    prevent_destroy = true
+  }
+}
+"#
+        );
+
+        assert_fail_validation(ExportFormat::Hcl, "[1]");
+        assert!(to_string(ExportFormat::Hcl, &eval("{resource.aws_instance = 1}")).is_err());
+    }
+
+    #[test]
+    fn toml_layout() {
+        let evaluated = eval(
+            "{a = 1, server = {host = \"h\", port = 80}, users = [{name = \"x\"}, {name = \"y\"}]}",
+        );
+
+        assert_eq!(
+            to_string(ExportFormat::Toml, &evaluated).unwrap(),
+            "\
+a = 1
+
+[server]
+host = \"h\"
+port = 80
+
+[[users]]
+name = \"x\"
+
+[[users]]
+name = \"y\"
+"
+        );
+    }
+
+    #[test]
+    fn parallel() {
+        let evaluated =
+            eval("{b = {c = [1, 2, {d = \"multi\\nline\"}], e = {}}, a = null, f = [], g = \"x\"}");
+
+        for (format, key_order) in [ExportFormat::Json, ExportFormat::Yaml]
+            .into_iter()
+            .flat_map(|format| [(format, KeyOrder::Sorted), (format, KeyOrder::Declaration)])
+        {
+            let mut buffer: Vec<u8> = Vec::new();
+            to_writer_parallel(
+                &mut buffer,
+                format,
+                &evaluated,
+                NonZeroUsize::new(3).unwrap(),
+                key_order,
+            )
+            .unwrap();
+
+            let mut expected: Vec<u8> = Vec::new();
+            to_writer_with_key_order(&mut expected, format, &evaluated, key_order).unwrap();
+
+            assert_eq!(buffer, expected);
+
+            // An error on any field aborts the export, and is located at the faulty value.
+            let err = to_writer_parallel(
+                std::io::sink(),
+                format,
+                &eval("{a = 1, b = {c = [fun x => x]}, d = 3}"),
+                NonZeroUsize::new(2).unwrap(),
+                key_order,
+            )
+            .unwrap_err();
+            assert_eq!(err.path.to_string(), "b.c[0]");
+        }
+
+        // More fields than can be rendered ahead of the output.
+        let evaluated = eval(
+            "std.array.generate (fun i => { field = \"f%{std.to_string i}\", value = [i] }) 50
+            |> std.record.from_array",
+        );
+
+        for format in [ExportFormat::Json, ExportFormat::Yaml] {
+            let mut buffer: Vec<u8> = Vec::new();
+            to_writer_parallel(
+                &mut buffer,
+                format,
+                &evaluated,
+                NonZeroUsize::new(2).unwrap(),
+                KeyOrder::Sorted,
+            )
+            .unwrap();
+
+            assert_eq!(
+                String::from_utf8(buffer).unwrap(),
+                to_string(format, &evaluated).unwrap()
+            );
+        }
     }
 
     #[test]
@@ -0,0 +1,227 @@
+//! Extraction of Rust values from evaluated terms.
+//!
+//! These accessors are meant to be used on the result of a full evaluation, such as
+//! [Program::eval_full](crate::program::Program::eval_full): they don't evaluate anything, and
+//! report a type mismatch on unevaluated terms. Errors are [AccessError]s, which carry the position
+//! of the offending value and can be reported as any other Nickel error.
+//!
+//! ```no_run
+//! # use nickel_lang_core::{eval::cache::CacheImpl, program::Program};
+//! # let mut program: Program<CacheImpl> = Program::new_from_file("config.ncl", std::io::stderr()).unwrap();
+//! let config = program.eval_full().unwrap();
+//! let port = config.get(["server", "port"]).and_then(|port| port.as_i64());
+//! ```
+use malachite::{num::conversion::traits::RoundingFrom, rounding_modes::RoundingMode};
+
+use super::{Number, RichTerm, Term};
+use crate::{
+    error::{AccessError, AccessErrorData},
+    identifier::{Ident, LocIdent},
+    serialize::{NickelPointer, NickelPointerElem},
+};
+
+impl RichTerm {
+    fn type_mismatch(&self, expected: &'static str) -> AccessError {
+        AccessError::from(AccessErrorData::TypeMismatch {
+            expected,
+            value: self.clone(),
+        })
+    }
+
+    /// Return the content of a string.
+    pub fn as_str(&self) -> Result<&str, AccessError> {
+        match self.as_ref() {
+            Term::Str(s) => Ok(s.as_ref()),
+            _ => Err(self.type_mismatch("String")),
+        }
+    }
+
+    /// Return the content of a number.
+    pub fn as_number(&self) -> Result<&Number, AccessError> {
+        match self.as_ref() {
+            Term::Num(n) => Ok(n),
+            _ => Err(self.type_mismatch("Number")),
+        }
+    }
+
+    /// Return the content of a number, which must be an integer fitting in an `i64`.
+    pub fn as_i64(&self) -> Result<i64, AccessError> {
+        i64::try_from(self.as_number()?).map_err(|_| {
+            AccessError::from(AccessErrorData::InvalidNumber {
+                expected: "i64",
+                value: self.clone(),
+            })
+        })
+    }
+
+    /// Return the content of a number, rounded to the nearest `f64`.
+    pub fn as_f64(&self) -> Result<f64, AccessError> {
+        Ok(f64::rounding_from(self.as_number()?, RoundingMode::Nearest).0)
+    }
+
+    /// Return the content of a boolean.
+    pub fn as_bool(&self) -> Result<bool, AccessError> {
+        match self.as_ref() {
+            Term::Bool(b) => Ok(*b),
+            _ => Err(self.type_mismatch("Bool")),
+        }
+    }
+
+    /// Return `true` if this term is `null`.
+    pub fn is_null(&self) -> bool {
+        matches!(self.as_ref(), Term::Null)
+    }
+
+    /// Iterate over the fields of a record which have a value. The order of the fields is
+    /// unspecified.
+    pub fn record_fields(
+        &self,
+    ) -> Result<impl Iterator<Item = (&LocIdent, &RichTerm)>, AccessError> {
+        match self.as_ref() {
+            Term::Record(data) => Ok(data
+                .fields
+                .iter()
+                .filter_map(|(id, field)| field.value.as_ref().map(|value| (id, value)))),
+            _ => Err(self.type_mismatch("Record")),
+        }
+    }
+
+    /// Iterate over the elements of an array.
+    pub fn array_elems(&self) -> Result<impl Iterator<Item = &RichTerm>, AccessError> {
+        match self.as_ref() {
+            Term::Array(elems, _) => Ok(elems.iter()),
+            _ => Err(self.type_mismatch("Array")),
+        }
+    }
+
+    /// Return the value of the field of a record.
+    pub fn field(&self, name: impl AsRef<str>) -> Result<&RichTerm, AccessError> {
+        let Term::Record(data) = self.as_ref() else {
+            return Err(self.type_mismatch("Record"));
+        };
+
+        let id = Ident::new(name);
+
+        data.fields
+            .get(&id)
+            .and_then(|field| field.value.as_ref())
+            .ok_or_else(|| {
+                AccessError::from(AccessErrorData::MissingField {
+                    field: id,
+                    record: self.clone(),
+                })
+            })
+    }
+
+    /// Follow a path of field names from this record, as in `get(["server", "port"])`. On error,
+    /// the path to the offending value is recorded in the [AccessError].
+    pub fn get<I, S>(&self, path: I) -> Result<&RichTerm, AccessError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut current = self;
+        let mut pointer = NickelPointer::new();
+
+        for name in path {
+            current = current.field(name.as_ref()).map_err(|mut err| {
+                err.path = pointer.clone();
+                err
+            })?;
+            pointer
+                .0
+                .push(NickelPointerElem::Field(Ident::new(name.as_ref())));
+        }
+
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::IntoDiagnostics, files::Files, mk_array, mk_record, term::make as mk_term};
+
+    fn config() -> RichTerm {
+        mk_record!(
+            (
+                "server",
+                mk_record!(
+                    ("host", Term::Str("localhost".into())),
+                    ("port", mk_term::integer(8080)),
+                    ("tls", Term::Bool(false))
+                )
+            ),
+            ("ratio", Term::Num(Number::from(1) / Number::from(2))),
+            (
+                "users",
+                mk_array!(Term::Str("alice".into()), Term::Str("bob".into()))
+            )
+        )
+    }
+
+    #[test]
+    fn extract() {
+        let config = config();
+
+        assert_eq!(
+            config.get(["server", "host"]).unwrap().as_str(),
+            Ok("localhost")
+        );
+        assert_eq!(config.get(["server", "port"]).unwrap().as_i64(), Ok(8080));
+        assert_eq!(config.get(["server", "tls"]).unwrap().as_bool(), Ok(false));
+        assert_eq!(config.field("ratio").unwrap().as_f64(), Ok(0.5));
+
+        let users: Vec<_> = config
+            .field("users")
+            .unwrap()
+            .array_elems()
+            .unwrap()
+            .map(|user| user.as_str().unwrap())
+            .collect();
+        assert_eq!(users, ["alice", "bob"]);
+
+        let mut fields: Vec<_> = config
+            .field("server")
+            .unwrap()
+            .record_fields()
+            .unwrap()
+            .map(|(id, _)| id.label())
+            .collect();
+        fields.sort();
+        assert_eq!(fields, ["host", "port", "tls"]);
+    }
+
+    #[test]
+    fn errors() {
+        let config = config();
+
+        let err = config.get(["server", "user"]).unwrap_err();
+        assert_eq!(err.path.to_string(), "server");
+        assert!(matches!(
+            err.data,
+            AccessErrorData::MissingField { field, .. } if field.label() == "user"
+        ));
+
+        let err = config.get(["server", "prot"]).unwrap_err();
+        let diagnostics = err.into_diagnostics(&Files::new()).diagnostics;
+        assert!(diagnostics[0]
+            .notes
+            .contains(&String::from("Did you mean `port`?")));
+
+        let err = config.get(["server", "port", "number"]).unwrap_err();
+        assert_eq!(err.path.to_string(), "server.port");
+        assert!(matches!(
+            err.data,
+            AccessErrorData::TypeMismatch {
+                expected: "Record",
+                ..
+            }
+        ));
+
+        assert!(matches!(
+            config.field("ratio").unwrap().as_i64().unwrap_err().data,
+            AccessErrorData::InvalidNumber { .. }
+        ));
+    }
+}
@@ -80,6 +80,18 @@ impl<A> Field<A> {
         self
     }
 
+    /// Mark the field as deprecated, with a message explaining what to use instead
+    pub fn deprecated(mut self, message: impl AsRef<str>) -> Self {
+        self.metadata.deprecated = Some(message.as_ref().to_owned());
+        self
+    }
+
+    /// Mark the field as secret
+    pub fn secret(mut self) -> Self {
+        self.metadata.secret = true;
+        self
+    }
+
     /// Attach a contract to the field
     pub fn contract(mut self, contract: impl Into<Type>) -> Self {
         self.metadata.annotation.contracts.push(LabeledType {
@@ -655,6 +667,7 @@ mod tests {
                                 label: Default::default()
                             }],
                         },
+                        ..Default::default()
                     }),
                 )],
                 Default::default()
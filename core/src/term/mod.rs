@@ -9,6 +9,7 @@
 //!
 //! It also features types and type annotations, and other typechecking or contracts-related
 //! constructs (label, symbols, etc.).
+pub mod access;
 pub mod array;
 pub mod pattern;
 pub mod record;
@@ -29,6 +30,7 @@ use crate::{
     impl_display_from_pretty,
     label::{Label, MergeLabel},
     match_sharedterm,
+    native::NativeFunction,
     position::{RawSpan, TermPos},
     pretty::PrettyPrintCap,
     typ::{Type, UnboundTypeVariableError},
@@ -1231,6 +1233,12 @@ impl SharedTerm {
     pub fn ptr_eq(this: &SharedTerm, that: &SharedTerm) -> bool {
         Rc::ptr_eq(&this.shared, &that.shared)
     }
+
+    /// Return a unique identifier for this term. Two shared terms with the same UID are physically
+    /// equal. In practice, the UID is currently the underlying `Rc` pointer value.
+    pub fn uid(&self) -> usize {
+        Rc::as_ptr(&self.shared) as usize
+    }
 }
 
 impl AsRef<Term> for SharedTerm {
@@ -1575,6 +1583,12 @@ pub enum UnaryOp {
 
     /// The tangent function.
     NumberTan,
+
+    /// Call a function implemented in Rust by the embedder. See [crate::native].
+    ///
+    /// The function is behind a reference-counted pointer, so that this variant doesn't increase
+    /// the size of [UnaryOp].
+    NativeCall(Rc<NativeFunction>),
 }
 
 impl fmt::Display for UnaryOp {
@@ -1645,6 +1659,8 @@ impl fmt::Display for UnaryOp {
             NumberCos => write!(f, "number/cos"),
             NumberSin => write!(f, "number/sin"),
             NumberTan => write!(f, "number/tan"),
+
+            NativeCall(fun) => write!(f, "native/{}", fun.name()),
         }
     }
 }
@@ -2737,7 +2753,10 @@ pub mod make {
         ( $( $terms:expr ),* ) => {
             {
                 let ts = [$( $crate::term::RichTerm::from($terms) ),*].into_iter().collect();
-                $crate::term::RichTerm::from(Term::Array(ts, ArrayAttrs::default()))
+                $crate::term::RichTerm::from($crate::term::Term::Array(
+                    ts,
+                    $crate::term::array::ArrayAttrs::default(),
+                ))
             }
         };
     }
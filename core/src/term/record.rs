@@ -103,6 +103,9 @@ pub struct RecordDeps {
     pub dyn_fields: Vec<FieldDeps>,
 }
 
+/// The placeholder replacing the value of secret fields when they are redacted.
+pub const REDACTED: &str = "<redacted>";
+
 /// The metadata attached to record fields.
 #[derive(Debug, PartialEq, Clone, Default)]
 pub struct FieldMetadata {
@@ -113,6 +116,13 @@ pub struct FieldMetadata {
     /// If the field is serialized.
     pub not_exported: bool,
     pub priority: MergePriority,
+    /// The deprecation message, if the field is deprecated. Accessing or exporting a deprecated
+    /// field emits a warning.
+    pub deprecated: Option<String>,
+    /// If the value of the field is sensitive. The values of secret fields are redacted in
+    /// diagnostics, in the output of `std.trace` and by [crate::pretty::to_string_redacted], but
+    /// not by regular pretty-printing. They can also be redacted from exports.
+    pub secret: bool,
 }
 
 impl FieldMetadata {
@@ -126,6 +136,15 @@ impl FieldMetadata {
             && !self.opt
             && !self.not_exported
             && matches!(self.priority, MergePriority::Neutral)
+            && self.deprecated.is_none()
+            && !self.secret
+    }
+
+    /// Return the span of the main annotation of the field, if any. See [TypeAnnotation::first].
+    pub fn annotation_span(&self) -> Option<RawSpan> {
+        self.annotation
+            .first()
+            .map(|labeled_ty| labeled_ty.label.span)
     }
 }
 
@@ -289,7 +308,7 @@ impl MissingFieldDefError {
     pub fn into_eval_err(self, pos_record: TermPos, pos_access: TermPos) -> EvalError {
         EvalError::MissingFieldDef {
             id: self.id,
-            metadata: self.metadata,
+            metadata: Box::new(self.metadata),
             pos_record,
             pos_access,
         }
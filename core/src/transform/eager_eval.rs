@@ -0,0 +1,103 @@
+//! Eagerly evaluate expressions that are statically known to be total and cheap.
+//!
+//! Most leaf expressions of a configuration are literals, or simple operations on literals such
+//! as `8080 + 1` or `"nickel-" ++ "lang"`. Evaluating them lazily means allocating a thunk,
+//! pushing and popping the stack and updating the thunk afterwards, which is pure overhead for an
+//! expression that can't fail, can't loop and whose result is smaller than the expression itself.
+//!
+//! This transformation folds such expressions to their value ahead of evaluation. Because
+//! constants are never allocated in thunks by closurization (see [crate::term::Term::is_constant]),
+//! a folded expression is then evaluated for free.
+//!
+//! The transformation is applied bottom-up, so that nested expressions such as `1 + 2 * 3` are
+//! folded from the leaves. It only handles:
+//!
+//! - arithmetic on number literals, excepted division by zero, which is left for the evaluator to
+//!   report;
+//! - comparisons of number literals and equality of atomic literals of the same kind;
+//! - concatenation of string literals and boolean negation of a boolean literal;
+//! - recursive records whose fields are all defined as constants, without any annotation nor
+//!   dynamic field, which are turned into already closurized non-recursive records. Such records
+//!   can't refer to themselves, so computing their fixpoint is a no-op.
+//!
+//! Folded terms retain the position of the original expression as an inherited position, exactly
+//! as if the primitive operation had been applied at runtime.
+use crate::{
+    match_sharedterm,
+    term::{
+        record::{Field, RecordData},
+        BinaryOp, Number, RichTerm, Term, UnaryOp, Zero,
+    },
+};
+
+/// Eagerly evaluate the top-level node of the AST if it's a total and cheap operation on
+/// constants, or leave it unchanged otherwise.
+pub fn transform_one(rt: RichTerm) -> RichTerm {
+    let pos = rt.pos;
+
+    match_sharedterm!(match (rt.term) {
+        Term::Op1(op, t) => match eval_op1(&op, t.as_ref()) {
+            Some(result) => RichTerm::new(result, pos.into_inherited()),
+            None => RichTerm::new(Term::Op1(op, t), pos),
+        },
+        Term::Op2(op, t1, t2) => match eval_op2(&op, t1.as_ref(), t2.as_ref()) {
+            Some(result) => RichTerm::new(result, pos.into_inherited()),
+            None => RichTerm::new(Term::Op2(op, t1, t2), pos),
+        },
+        Term::RecRecord(record_data, dyn_fields, deps) => {
+            if dyn_fields.is_empty() && record_data.fields.values().all(is_constant_field) {
+                let record_data = RecordData {
+                    attrs: record_data.attrs.closurized(),
+                    ..record_data
+                };
+
+                RichTerm::new(Term::Record(record_data), pos)
+            } else {
+                RichTerm::new(Term::RecRecord(record_data, dyn_fields, deps), pos)
+            }
+        }
+        _ => rt,
+    })
+}
+
+/// Determine if a field is defined as a constant and doesn't have any annotation, in which case
+/// it doesn't need to be closurized nor patched with a recursive environment.
+fn is_constant_field(field: &Field) -> bool {
+    field.metadata.annotation.is_empty()
+        && field.pending_contracts.is_empty()
+        && field
+            .value
+            .as_ref()
+            .is_some_and(|value| value.as_ref().is_constant())
+}
+
+fn eval_op1(op: &UnaryOp, t: &Term) -> Option<Term> {
+    match (op, t) {
+        (UnaryOp::BoolNot, Term::Bool(b)) => Some(Term::Bool(!b)),
+        _ => None,
+    }
+}
+
+fn eval_op2(op: &BinaryOp, t1: &Term, t2: &Term) -> Option<Term> {
+    match (op, t1, t2) {
+        (BinaryOp::Plus, Term::Num(n1), Term::Num(n2)) => Some(Term::Num(n1 + n2)),
+        (BinaryOp::Sub, Term::Num(n1), Term::Num(n2)) => Some(Term::Num(n1 - n2)),
+        (BinaryOp::Mult, Term::Num(n1), Term::Num(n2)) => Some(Term::Num(n1 * n2)),
+        (BinaryOp::Div, Term::Num(n1), Term::Num(n2)) if *n2 != Number::ZERO => {
+            Some(Term::Num(n1 / n2))
+        }
+        (BinaryOp::LessThan, Term::Num(n1), Term::Num(n2)) => Some(Term::Bool(n1 < n2)),
+        (BinaryOp::LessOrEq, Term::Num(n1), Term::Num(n2)) => Some(Term::Bool(n1 <= n2)),
+        (BinaryOp::GreaterThan, Term::Num(n1), Term::Num(n2)) => Some(Term::Bool(n1 > n2)),
+        (BinaryOp::GreaterOrEq, Term::Num(n1), Term::Num(n2)) => Some(Term::Bool(n1 >= n2)),
+        (BinaryOp::Eq, Term::Num(n1), Term::Num(n2)) => Some(Term::Bool(n1 == n2)),
+        (BinaryOp::Eq, Term::Str(s1), Term::Str(s2)) => Some(Term::Bool(s1 == s2)),
+        (BinaryOp::Eq, Term::Bool(b1), Term::Bool(b2)) => Some(Term::Bool(b1 == b2)),
+        (BinaryOp::Eq, Term::Null, Term::Null) => Some(Term::Bool(true)),
+        (BinaryOp::StringConcat, Term::Str(s1), Term::Str(s2)) => {
+            let ss: [&str; 2] = [s1, s2];
+            Some(Term::Str(ss.concat().into()))
+        }
+        _ => None,
+    }
+}
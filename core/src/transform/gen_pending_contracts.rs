@@ -24,7 +24,14 @@ use crate::{
 pub fn transform_one(rt: RichTerm) -> Result<RichTerm, UnboundTypeVariableError> {
     fn attach_to_field(field: Field) -> Result<Field, UnboundTypeVariableError> {
         // We simply add the contracts to the pending contract fields
-        let pending_contracts = field.metadata.annotation.pending_contracts()?;
+        let mut pending_contracts = field.metadata.annotation.pending_contracts()?;
+
+        if field.metadata.secret {
+            for ctr in pending_contracts.iter_mut() {
+                ctr.label.secret = true;
+            }
+        }
+
         // Type annotations are different: the contract is generated statically, because as opposed
         // to contract annotations, type anntotations don't propagate.
         let value = field
@@ -32,7 +39,9 @@ pub fn transform_one(rt: RichTerm) -> Result<RichTerm, UnboundTypeVariableError>
             .map(|v| -> Result<RichTerm, UnboundTypeVariableError> {
                 if let Some(labeled_ty) = &field.metadata.annotation.typ {
                     let pos = v.pos;
-                    let contract = RuntimeContract::from_static_type(labeled_ty.clone())?;
+                    let mut labeled_ty = labeled_ty.clone();
+                    labeled_ty.label.secret = field.metadata.secret;
+                    let contract = RuntimeContract::from_static_type(labeled_ty)?;
                     Ok(contract.apply(v, pos))
                 } else {
                     Ok(v)
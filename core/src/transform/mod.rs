@@ -7,6 +7,7 @@ use crate::{
 };
 
 pub mod desugar_destructuring;
+pub mod eager_eval;
 pub mod free_vars;
 pub mod gen_pending_contracts;
 pub mod import_resolution;
@@ -56,6 +57,9 @@ pub fn transform_no_free_vars(
                 // of the AST. This was witnessed on Terraform-Nickel, causing examples using huge
                 // auto-generated contracts (several of MBs) to not terminate in reasonable time.
                 let rt = gen_pending_contracts::transform_one(rt)?;
+                // Eager evaluation must come after `gen_pending_contracts`, so that fields with
+                // pending contracts are never mistaken for constant fields.
+                let rt = eager_eval::transform_one(rt);
                 Ok(rt)
             },
             TraverseOrder::BottomUp,
@@ -718,7 +718,11 @@ impl From<UnboundTypeVariableError> for EvalError {
     fn from(err: UnboundTypeVariableError) -> Self {
         let UnboundTypeVariableError(id) = err;
         let pos = id.pos;
-        EvalError::UnboundIdentifier(id, pos)
+        EvalError::UnboundIdentifier {
+            id,
+            pos,
+            in_scope: Default::default(),
+        }
     }
 }
 
@@ -600,7 +600,9 @@ fn contract_eq_fields<E: TermEnvironment>(
     // Check that "scalar" metadata (simple values) are equals
     let scalar_metadata_eq = field1.metadata.opt == field2.metadata.opt
         && field1.metadata.not_exported == field2.metadata.not_exported
-        && field1.metadata.priority == field2.metadata.priority;
+        && field1.metadata.priority == field2.metadata.priority
+        && field1.metadata.deprecated == field2.metadata.deprecated
+        && field1.metadata.secret == field2.metadata.secret;
 
     let value_eq = match (&field1.value, &field2.value) {
         (Some(ref value1), Some(ref value2)) => {
@@ -57,7 +57,7 @@
 use crate::{
     cache::ImportResolver,
     environment::Environment as GenericEnvironment,
-    error::TypecheckError,
+    error::{suggest, TypecheckError},
     identifier::{Ident, LocIdent},
     mk_uty_arrow, mk_uty_enum, mk_uty_record, mk_uty_record_row, stdlib as nickel_stdlib,
     term::{
@@ -68,6 +68,7 @@ use crate::{
 };
 
 use std::{
+    cell::RefCell,
     cmp::max,
     collections::{HashMap, HashSet},
     convert::{Infallible, TryInto},
@@ -1416,6 +1417,102 @@ where
     })
 }
 
+/// Infer the type of a term in enforce mode, as if it was annotated with a wildcard, in the given
+/// typing context. The type variables which are left unconstrained by the term are generalized:
+/// the type of `fun x => x` is `forall a. a -> a`. Record and enum rows left unconstrained are
+/// closed.
+pub fn infer_type(
+    t: &RichTerm,
+    initial_ctxt: Context,
+    resolver: &impl ImportResolver,
+) -> Result<Type, TypecheckError> {
+    let (mut table, mut names) = (UnifTable::new(), HashMap::new());
+    let mut wildcard_vars = Vec::new();
+
+    let uty = {
+        let mut state: State = State {
+            resolver,
+            table: &mut table,
+            constr: &mut RowConstrs::new(),
+            names: &mut names,
+            wildcard_vars: &mut wildcard_vars,
+        };
+
+        let uty = state.table.fresh_type_uvar(initial_ctxt.var_level);
+        check(&mut state, initial_ctxt, &mut (), t, uty.clone())?;
+        uty
+    };
+
+    let vars = RefCell::new(Vec::new());
+    free_type_vars(uty.clone(), &table, &vars);
+
+    let vars: Vec<_> = vars
+        .into_inner()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, id)| {
+            let letter = char::from(b'a' + (idx % 26) as u8);
+            let name = match idx / 26 {
+                0 => LocIdent::from(letter.to_string()),
+                n => LocIdent::from(format!("{letter}{n}")),
+            };
+
+            table.assign_type(id, UnifType::concrete(TypeF::Var(name.ident())));
+            name
+        })
+        .collect();
+
+    Ok(vars
+        .into_iter()
+        .rev()
+        .fold(uty.into_type(&table), |body, var| {
+            Type::from(TypeF::Forall {
+                var,
+                var_kind: VarKind::Type,
+                body: Box::new(body),
+            })
+        }))
+}
+
+/// Collect the free type unification variables of a type, in order of appearance.
+fn free_type_vars(uty: UnifType, table: &UnifTable, vars: &RefCell<Vec<VarId>>) {
+    fn rrows_vars(urrows: UnifRecordRows, table: &UnifTable, vars: &RefCell<Vec<VarId>>) {
+        if let UnifRecordRows::Concrete { rrows, .. } = urrows.into_root(table) {
+            rrows.map(
+                |uty| free_type_vars(*uty, table, vars),
+                |urrows| rrows_vars(*urrows, table, vars),
+            );
+        }
+    }
+
+    fn erows_vars(uerows: UnifEnumRows, table: &UnifTable, vars: &RefCell<Vec<VarId>>) {
+        if let UnifEnumRows::Concrete { erows, .. } = uerows.into_root(table) {
+            erows.map(
+                |uty| free_type_vars(*uty, table, vars),
+                |uerows| erows_vars(*uerows, table, vars),
+            );
+        }
+    }
+
+    match uty.into_root(table) {
+        UnifType::UnifVar { id, .. } => {
+            let mut vars = vars.borrow_mut();
+            if !vars.contains(&id) {
+                vars.push(id);
+            }
+        }
+        UnifType::Constant(_) => (),
+        UnifType::Concrete { typ, .. } => {
+            typ.map(
+                |uty| free_type_vars(*uty, table, vars),
+                |urrows| rrows_vars(urrows, table, vars),
+                |uerows| erows_vars(uerows, table, vars),
+                |_| (),
+            );
+        }
+    }
+}
+
 /// Walk the AST of a term looking for statically typed block to check. Fill the linearization
 /// alongside and store the apparent type of variable inside the typing environment.
 fn walk<V: TypecheckVisitor>(
@@ -1450,7 +1547,11 @@ fn walk<V: TypecheckVisitor>(
         | Term::ResolvedImport(_) => Ok(()),
         Term::Var(x) => ctxt.type_env
             .get(&x.ident())
-            .ok_or(TypecheckError::UnboundIdentifier { id: *x, pos: *pos })
+            .ok_or_else(|| TypecheckError::UnboundIdentifier {
+                id: *x,
+                pos: *pos,
+                in_scope: suggest::InScope::new([ctxt.type_env.clone()]),
+            })
             .map(|_| ()),
         Term::StrChunks(chunks) => {
             chunks
@@ -2573,11 +2674,13 @@ fn infer<V: TypecheckVisitor>(
 
     match term.as_ref() {
         Term::Var(x) => {
-            let x_ty = ctxt
-                .type_env
-                .get(&x.ident())
-                .cloned()
-                .ok_or(TypecheckError::UnboundIdentifier { id: *x, pos: *pos })?;
+            let x_ty = ctxt.type_env.get(&x.ident()).cloned().ok_or_else(|| {
+                TypecheckError::UnboundIdentifier {
+                    id: *x,
+                    pos: *pos,
+                    in_scope: suggest::InScope::new([ctxt.type_env.clone()]),
+                }
+            })?;
 
             visitor.visit_term(rt, x_ty.clone());
 
@@ -285,6 +285,14 @@ pub fn get_uop_type(
         | UnaryOp::NumberArcCos
         | UnaryOp::NumberArcSin
         | UnaryOp::NumberArcTan => (mk_uniftype::num(), mk_uniftype::num()),
+        // The type declared by the native function
+        UnaryOp::NativeCall(fun) => {
+            let env = SimpleTermEnvironment::new();
+            (
+                UnifType::from_type(fun.domain().clone(), &env),
+                UnifType::from_type(fun.codomain().clone(), &env),
+            )
+        }
     })
 }
 
@@ -0,0 +1,307 @@
+//! A visitor over fully evaluated values.
+//!
+//! [walk] traverses the result of a full evaluation (see
+//! [Program::eval_full](crate::program::Program::eval_full)) and calls the methods of a
+//! [ValueVisitor] on each value, together with its position in the source and its path from the
+//! root. Record fields are visited in alphabetical order, together with their metadata. This is
+//! the supported way for downstream tools, such as exporters or policy checkers, to inspect
+//! evaluated values without depending on the internal representation of terms.
+//!
+//! All the methods of [ValueVisitor] have a default implementation which does nothing, so that
+//! a visitor only needs to implement the methods it's interested in. The `enter_*` methods return
+//! a boolean: `false` skips the content of the record, array or field being entered.
+use crate::{
+    position::RawSpan,
+    serialize::{NickelPointer, NickelPointerElem},
+    term::{record::FieldMetadata, MergePriority, Number, RichTerm, Term},
+};
+
+/// The context of a visited value.
+#[derive(Debug, Clone, Copy)]
+pub struct VisitContext<'a> {
+    /// The path of the value from the root of the walk.
+    pub path: &'a NickelPointer,
+    /// The position of the value in the source, if any.
+    pub span: Option<RawSpan>,
+}
+
+/// The metadata of a record field.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldInfo<'a>(&'a FieldMetadata);
+
+impl<'a> FieldInfo<'a> {
+    pub fn doc(&self) -> Option<&'a str> {
+        self.0.doc.as_deref()
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.0.opt
+    }
+
+    pub fn is_not_exported(&self) -> bool {
+        self.0.not_exported
+    }
+
+    pub fn is_secret(&self) -> bool {
+        self.0.secret
+    }
+
+    pub fn priority(&self) -> &'a MergePriority {
+        &self.0.priority
+    }
+
+    /// The type annotation and the contracts of the field, pretty-printed.
+    pub fn annotations(&self) -> impl Iterator<Item = String> + 'a {
+        self.0
+            .annotation
+            .iter()
+            .map(|labeled| labeled.typ.to_string())
+    }
+
+    /// The position of the main annotation of the field, if any.
+    pub fn annotation_span(&self) -> Option<RawSpan> {
+        self.0.annotation_span()
+    }
+}
+
+/// A visitor over fully evaluated values. See the [module documentation](self).
+#[allow(unused_variables)]
+pub trait ValueVisitor {
+    type Error;
+
+    fn visit_null(&mut self, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_number(&mut self, value: &Number, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_string(&mut self, value: &str, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_enum_tag(&mut self, tag: &str, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Enter an enum variant `'tag arg`. The argument is visited next, with the same path.
+    fn enter_enum_variant(
+        &mut self,
+        tag: &str,
+        ctx: VisitContext<'_>,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    fn leave_enum_variant(&mut self, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn enter_record(&mut self, ctx: VisitContext<'_>) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    fn leave_record(&mut self, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Enter a field of a record. The context is the one of the value of the field, which is
+    /// visited next. `has_value` is `false` for fields without a definition, such as optional
+    /// fields, which don't have any value to visit.
+    fn enter_field(
+        &mut self,
+        name: &str,
+        info: FieldInfo<'_>,
+        has_value: bool,
+        ctx: VisitContext<'_>,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    fn leave_field(&mut self, name: &str, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn enter_array(&mut self, len: usize, ctx: VisitContext<'_>) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    fn leave_array(&mut self, ctx: VisitContext<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Visit a value which isn't data, such as a function, or a value which hasn't been
+    /// evaluated. `type_name` is the Nickel type of the value, if known.
+    fn visit_other(
+        &mut self,
+        type_name: Option<&str>,
+        ctx: VisitContext<'_>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Walk a fully evaluated value, calling the methods of `visitor` along the way.
+pub fn walk<V: ValueVisitor>(value: &RichTerm, visitor: &mut V) -> Result<(), V::Error> {
+    walk_at(value, &mut NickelPointer::new(), visitor)
+}
+
+fn walk_at<V: ValueVisitor>(
+    value: &RichTerm,
+    path: &mut NickelPointer,
+    visitor: &mut V,
+) -> Result<(), V::Error> {
+    let span = value.pos.into_opt();
+
+    macro_rules! ctx {
+        () => {
+            VisitContext { path, span }
+        };
+    }
+
+    match value.as_ref() {
+        Term::Null => visitor.visit_null(ctx!()),
+        Term::Bool(b) => visitor.visit_bool(*b, ctx!()),
+        Term::Num(n) => visitor.visit_number(n, ctx!()),
+        Term::Str(s) => visitor.visit_string(s.as_ref(), ctx!()),
+        Term::Enum(tag) => visitor.visit_enum_tag(tag.label(), ctx!()),
+        Term::EnumVariant { tag, arg, .. } => {
+            if visitor.enter_enum_variant(tag.label(), ctx!())? {
+                walk_at(arg, path, visitor)?;
+            }
+            visitor.leave_enum_variant(ctx!())
+        }
+        Term::Array(elts, _) => {
+            if visitor.enter_array(elts.len(), ctx!())? {
+                for (index, elt) in elts.iter().enumerate() {
+                    path.0.push(NickelPointerElem::Index(index));
+                    walk_at(elt, path, visitor)?;
+                    path.0.pop();
+                }
+            }
+            visitor.leave_array(ctx!())
+        }
+        Term::Record(record) => {
+            if visitor.enter_record(ctx!())? {
+                let mut fields: Vec<_> = record.fields.iter().collect();
+                fields.sort_by(|(id1, _), (id2, _)| id1.label().cmp(id2.label()));
+
+                for (id, field) in fields {
+                    path.0.push(NickelPointerElem::Field(id.ident()));
+
+                    let field_span = field
+                        .value
+                        .as_ref()
+                        .and_then(|value| value.pos.into_opt())
+                        .or(id.pos.into_opt());
+
+                    if visitor.enter_field(
+                        id.label(),
+                        FieldInfo(&field.metadata),
+                        field.value.is_some(),
+                        VisitContext {
+                            path,
+                            span: field_span,
+                        },
+                    )? {
+                        if let Some(value) = &field.value {
+                            walk_at(value, path, visitor)?;
+                        }
+                    }
+
+                    visitor.leave_field(
+                        id.label(),
+                        VisitContext {
+                            path,
+                            span: field_span,
+                        },
+                    )?;
+                    path.0.pop();
+                }
+            }
+            visitor.leave_record(ctx!())
+        }
+        term => visitor.visit_other(term.type_of().as_deref(), ctx!()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::tests::program;
+
+    /// Collect the paths of the strings and the documentation of the fields.
+    #[derive(Default)]
+    struct Collector {
+        strings: Vec<String>,
+        docs: Vec<String>,
+    }
+
+    impl ValueVisitor for Collector {
+        type Error = String;
+
+        fn visit_string(&mut self, value: &str, ctx: VisitContext<'_>) -> Result<(), String> {
+            self.strings.push(format!("{}={value}", ctx.path));
+            Ok(())
+        }
+
+        fn enter_field(
+            &mut self,
+            name: &str,
+            info: FieldInfo<'_>,
+            _has_value: bool,
+            _ctx: VisitContext<'_>,
+        ) -> Result<bool, String> {
+            if let Some(doc) = info.doc() {
+                self.docs.push(format!("{name}: {doc}"));
+            }
+            Ok(name != "secret")
+        }
+
+        fn visit_other(
+            &mut self,
+            type_name: Option<&str>,
+            ctx: VisitContext<'_>,
+        ) -> Result<(), String> {
+            Err(format!("unexpected {type_name:?} at {}", ctx.path))
+        }
+    }
+
+    fn collect(source: &str) -> Result<Collector, String> {
+        let value = program(source).eval_full().unwrap();
+        let mut collector = Collector::default();
+        walk(&value, &mut collector)?;
+        Ok(collector)
+    }
+
+    #[test]
+    fn walk_values() {
+        let collector = collect(
+            r#"{
+                users | doc "The users" = ["alice", "bob"],
+                server = { host = "localhost", mode = 'Prod },
+                secret = "hunter2",
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            collector.strings,
+            ["server.host=localhost", "users[0]=alice", "users[1]=bob"]
+        );
+        assert_eq!(collector.docs, ["users: The users"]);
+    }
+
+    #[test]
+    fn non_data() {
+        assert_eq!(
+            collect("{ foo = [fun x => x] }").err().unwrap(),
+            "unexpected Some(\"Function\") at foo[0]"
+        );
+    }
+}
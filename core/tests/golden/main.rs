@@ -0,0 +1,85 @@
+//! Golden tests for Nickel fixtures. See `README.md`.
+use std::path::Path;
+
+use nickel_lang_core::{
+    cache::normalize_path,
+    error::{report::ColorOpt, Error},
+    eval::cache::CacheImpl,
+    program::Program,
+    serialize::{self, ExportFormat},
+};
+use nickel_lang_utils::project_root::project_root;
+use pretty_assertions::assert_eq;
+use test_generator::test_resources;
+
+/// When this environment variable is set, the expected outputs are overwritten with the actual
+/// outputs instead of being checked.
+const UPDATE_VAR: &str = "NICKEL_UPDATE_GOLDEN";
+
+/// The directory of the fixtures, relative to the project root.
+const INPUTS_DIR: &str = "core/tests/golden/inputs";
+
+#[test_resources("core/tests/golden/inputs/**/*.ncl")]
+fn golden(path: &str) {
+    let path = project_root().join(path);
+    let expected_path = path.with_extension("out");
+    let actual = normalize(&run(&path));
+
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        std::fs::write(&expected_path, format!("{actual}\n"))
+            .expect("Failed to write the expected output");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&expected_path).unwrap_or_else(|_| {
+        panic!(
+            "Missing expected output {}. Run the test with {UPDATE_VAR}=1 to create it",
+            expected_path.display()
+        )
+    });
+
+    assert_eq!(
+        normalize(&expected),
+        actual,
+        "Unexpected output for {}",
+        path.display()
+    );
+}
+
+/// Export the fixture at `path` to JSON, and return the result or the error diagnostics, followed
+/// by the warnings emitted during the evaluation. The paths of the fixtures are shown relatively
+/// to the inputs directory, so that the outputs don't depend on the location of the repository.
+fn run(path: &Path) -> String {
+    let mut program: Program<CacheImpl> =
+        Program::new_from_file(path, std::io::sink()).expect("Failed to load the fixture");
+    program.color_opt = ColorOpt::NEVER;
+
+    let result = program
+        .eval_full_for_export()
+        .and_then(|rt| serialize::to_string(ExportFormat::Json, &rt).map_err(Error::from));
+
+    let mut output = match result {
+        Ok(json) => json,
+        Err(error) => program.report_as_str(error),
+    };
+
+    for warning in program.take_warnings() {
+        output.push_str(&program.report_as_str(warning));
+    }
+
+    let inputs_dir = normalize_path(project_root().join(INPUTS_DIR))
+        .expect("Failed to normalize the inputs directory");
+    output.replace(&format!("{}/", inputs_dir.display()), "")
+}
+
+/// Trailing whitespace isn't significant: diagnostics may end lines with spaces, which editors
+/// tend to strip from the expected outputs.
+fn normalize(output: &str) -> String {
+    output
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end()
+        .to_owned()
+}
@@ -22,8 +22,8 @@ fn array_contracts_label_path_is_set_correctly() {
     }
     // Check that reporting doesn't panic. Provide a dummy file database, as we won't report
     // the error message but just check that it can be built.
-    let mut files = Files::new();
-    res.unwrap_err().into_diagnostics(&mut files);
+    let files = Files::new();
+    res.unwrap_err().into_diagnostics(&files);
 
     let res = eval(
         "(%array/at% (\
@@ -41,7 +41,7 @@ fn array_contracts_label_path_is_set_correctly() {
         ),
         err => panic!("expected blame error, got {err:?}"),
     }
-    res.unwrap_err().into_diagnostics(&mut files);
+    res.unwrap_err().into_diagnostics(&files);
 }
 
 #[test]
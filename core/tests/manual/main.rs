@@ -41,11 +41,11 @@ enum CodeBlockType {
     /// particular REPL inputs must be separated by an empty line.
     ///
     /// Any text following a REPL input directly, without containing an empty
-    /// line, is interpreted as an expected result. If it starts with `error:`,
-    /// we expect the evaluation to produce an error and match the error report
-    /// with the expected result. A final `[...]` means that the expected
-    /// result is merely a prefix of the error report. If it doesn't start with
-    /// `error:`, the evaluation is expected to succeed.
+    /// line, is interpreted as an expected result. If it starts with `error:`
+    /// or `error[` (an error with a code), we expect the evaluation to produce
+    /// an error and match the error report with the expected result. A final
+    /// `[...]` means that the expected result is merely a prefix of the error
+    /// report. Otherwise, the evaluation is expected to succeed.
     Repl,
 }
 
@@ -91,7 +91,7 @@ fn extract_repl_piece(piece: impl AsRef<str>) -> (String, ReplResult) {
     let result_string = result_lines.concat();
     let result = if result_string.is_empty() {
         ReplResult::Empty
-    } else if result_string.starts_with("error:") {
+    } else if result_string.starts_with("error:") || result_string.starts_with("error[") {
         if let Some((result_string, _)) = result_string.rsplit_once("[...]") {
             ReplResult::Error(MessageExpectation::Abridged(result_string.to_owned()))
         } else {
@@ -156,8 +156,7 @@ fn check_repl(content: String) {
                 (Ok(EvalResult::Bound(_)), ReplResult::Empty) => (),
                 (Err(e), ReplResult::Error(expected)) => {
                     let mut error = NoColor::new(Vec::<u8>::new());
-                    let mut files = repl.cache_mut().files().clone();
-                    report_with(&mut error, &mut files, e, ErrorFormat::Text);
+                    report_with(&mut error, repl.cache_mut().files(), e, ErrorFormat::Text);
 
                     check_error_report(String::from_utf8(error.into_inner()).unwrap(), expected);
                 }
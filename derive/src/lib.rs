@@ -0,0 +1,402 @@
+//! Derive macros for `nickel-lang-core`. They are re-exported by `nickel-lang-core` with the
+//! `derive` feature, which should be used instead of depending on this crate directly.
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    meta::ParseNestedMeta, parse_macro_input, parse_quote, Attribute, Data, DataEnum, DeriveInput,
+    Error, Expr, ExprLit, Fields, Lit, LitStr, Meta, Type,
+};
+
+/// Derive `nickel_lang_core::contract::NickelContract`, from the shape of the data which
+/// deserializes to the type with `serde`. See the documentation of `nickel_lang_core::contract`.
+#[proc_macro_derive(NickelContract, attributes(serde))]
+pub fn derive_nickel_contract(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = SerdeAttrs::parse(&input.attrs)?;
+
+    let body = match &input.data {
+        Data::Struct(data) => struct_contract(&data.fields, &attrs, input.ident.span())?,
+        Data::Enum(data) => enum_contract(data, &attrs)?,
+        Data::Union(_) => {
+            return Err(Error::new(
+                input.ident.span(),
+                "NickelContract can't be derived for unions",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::nickel_lang_core::contract::NickelContract));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::nickel_lang_core::contract::NickelContract
+            for #name #ty_generics #where_clause
+        {
+            fn contract() -> ::std::string::String {
+                #body
+            }
+        }
+    })
+}
+
+/// The `serde` attributes of a container, a field or a variant which change the shape of the
+/// deserialized data.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<RenameRule>,
+    default: bool,
+    skip: bool,
+    deny_unknown_fields: bool,
+    /// The value is deserialized by a custom function, so that its shape is unknown.
+    custom: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = SerdeAttrs::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    result.rename = Some(deserialize_name(&meta)?.value());
+                } else if meta.path.is_ident("rename_all") {
+                    let rule = deserialize_name(&meta)?;
+                    result.rename_all = Some(RenameRule::parse(&rule)?);
+                } else if meta.path.is_ident("default") {
+                    result.default = true;
+                    skip_value(&meta)?;
+                } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                    result.skip = true;
+                } else if meta.path.is_ident("deny_unknown_fields") {
+                    result.deny_unknown_fields = true;
+                } else if meta.path.is_ident("with") || meta.path.is_ident("deserialize_with") {
+                    result.custom = true;
+                    skip_value(&meta)?;
+                } else if ["tag", "content", "untagged", "flatten", "transparent"]
+                    .iter()
+                    .any(|name| meta.path.is_ident(name))
+                {
+                    return Err(
+                        meta.error("this serde attribute isn't supported by NickelContract")
+                    );
+                } else {
+                    skip_value(&meta)?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// Parse the value of `rename = "..."` or `rename(deserialize = "...")`, as well as
+/// `rename_all`. Return the name used for deserialization.
+fn deserialize_name(meta: &ParseNestedMeta) -> syn::Result<LitStr> {
+    if meta.input.peek(syn::Token![=]) {
+        return meta.value()?.parse();
+    }
+
+    let mut name = None;
+    meta.parse_nested_meta(|nested| {
+        if nested.path.is_ident("deserialize") {
+            name = Some(nested.value()?.parse()?);
+        } else {
+            skip_value(&nested)?;
+        }
+
+        Ok(())
+    })?;
+
+    name.ok_or_else(|| meta.error("expected a name for deserialization"))
+}
+
+/// Skip the value of an attribute we're not interested in.
+fn skip_value(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|nested| skip_value(&nested))?;
+    }
+
+    Ok(())
+}
+
+/// A value of `#[serde(rename_all = "...")]`.
+#[derive(Clone, Copy)]
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(rule: &LitStr) -> syn::Result<Self> {
+        Ok(match rule.value().as_str() {
+            "lowercase" => RenameRule::Lower,
+            "UPPERCASE" => RenameRule::Upper,
+            "PascalCase" => RenameRule::Pascal,
+            "camelCase" => RenameRule::Camel,
+            "snake_case" => RenameRule::Snake,
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+            "kebab-case" => RenameRule::Kebab,
+            "SCREAMING-KEBAB-CASE" => RenameRule::ScreamingKebab,
+            _ => return Err(Error::new(rule.span(), "unknown rename rule")),
+        })
+    }
+
+    /// Rename a field, written in snake case in Rust, as done by serde.
+    fn apply_to_field(self, field: &str) -> String {
+        match self {
+            RenameRule::Lower | RenameRule::Snake => field.to_owned(),
+            RenameRule::Upper | RenameRule::ScreamingSnake => field.to_ascii_uppercase(),
+            RenameRule::Pascal | RenameRule::Camel => {
+                let mut pascal = String::new();
+                let mut capitalize = matches!(self, RenameRule::Pascal);
+
+                for c in field.chars() {
+                    if c == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(c.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(c);
+                    }
+                }
+
+                pascal
+            }
+            RenameRule::Kebab => field.replace('_', "-"),
+            RenameRule::ScreamingKebab => field.replace('_', "-").to_ascii_uppercase(),
+        }
+    }
+
+    /// Rename a variant, written in Pascal case in Rust, as done by serde.
+    fn apply_to_variant(self, variant: &str) -> String {
+        let snake = || {
+            let mut snake = String::new();
+
+            for (i, c) in variant.char_indices() {
+                if c.is_uppercase() && i > 0 {
+                    snake.push('_');
+                }
+                snake.push(c.to_ascii_lowercase());
+            }
+
+            snake
+        };
+
+        match self {
+            RenameRule::Lower => variant.to_ascii_lowercase(),
+            RenameRule::Upper => variant.to_ascii_uppercase(),
+            RenameRule::Pascal => variant.to_owned(),
+            RenameRule::Camel => variant[..1].to_ascii_lowercase() + &variant[1..],
+            RenameRule::Snake => snake(),
+            RenameRule::ScreamingSnake => snake().to_ascii_uppercase(),
+            RenameRule::Kebab => snake().replace('_', "-"),
+            RenameRule::ScreamingKebab => snake().replace('_', "-").to_ascii_uppercase(),
+        }
+    }
+}
+
+/// Write a string as a Nickel string literal.
+fn nickel_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '%' if chars.peek() == Some(&'{') => result.push_str("\\%"),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+/// Write an enum tag, quoting it if it isn't a valid identifier.
+fn nickel_tag(tag: &str) -> String {
+    let mut chars = tag.chars();
+    let is_ident = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '\''));
+
+    if is_ident {
+        format!("'{tag}")
+    } else {
+        format!("'{}", nickel_string(tag))
+    }
+}
+
+/// Gather the doc comments of an item, without the space following `///`.
+fn doc(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(name_value) if name_value.path.is_ident("doc") => {
+                match &name_value.value {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(s), ..
+                    }) => Some(s.value()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).to_owned())
+        .collect();
+
+    let doc = lines.join("\n").trim().to_owned();
+    (!doc.is_empty()).then_some(doc)
+}
+
+/// Whether a type is an `Option`. As for serde, this is a syntactic check.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// The contract of the type of a field, as an expression evaluating to a `String`.
+fn type_contract(ty: &Type, attrs: &SerdeAttrs) -> TokenStream2 {
+    if attrs.custom {
+        quote!(::std::string::String::from("Dyn"))
+    } else {
+        quote!(<#ty as ::nickel_lang_core::contract::NickelContract>::contract())
+    }
+}
+
+/// The contract of a struct or of the content of an enum variant, as an expression evaluating to
+/// a `String`.
+fn struct_contract(fields: &Fields, attrs: &SerdeAttrs, span: Span) -> syn::Result<TokenStream2> {
+    match fields {
+        Fields::Named(named) => {
+            let mut parts = Vec::new();
+
+            for field in named.named.iter() {
+                let field_attrs = SerdeAttrs::parse(&field.attrs)?;
+
+                if field_attrs.skip {
+                    continue;
+                }
+
+                // unwrap(): named fields have a name.
+                let ident = field.ident.as_ref().unwrap().to_string();
+                let ident = ident.strip_prefix("r#").unwrap_or(&ident);
+                let name = match (&field_attrs.rename, attrs.rename_all) {
+                    (Some(rename), _) => rename.clone(),
+                    (None, Some(rule)) => rule.apply_to_field(ident),
+                    (None, None) => ident.to_owned(),
+                };
+
+                let mut prefix = nickel_string(&name);
+                if let Some(doc) = doc(&field.attrs) {
+                    prefix.push_str(&format!(" | doc {}", nickel_string(&doc)));
+                }
+                if is_option(&field.ty) || field_attrs.default || attrs.default {
+                    prefix.push_str(" | optional");
+                }
+
+                let contract = type_contract(&field.ty, &field_attrs);
+                parts.push(quote!(::std::format!("{} | ({})", #prefix, #contract)));
+            }
+
+            if !attrs.deny_unknown_fields {
+                parts.push(quote!(::std::string::String::from("..")));
+            }
+
+            Ok(quote! {
+                let fields: ::std::vec::Vec<::std::string::String> = ::std::vec![#(#parts),*];
+                ::std::format!("{{ {} }}", fields.join(", "))
+            })
+        }
+        Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+            let field = &unnamed.unnamed[0];
+            Ok(type_contract(&field.ty, &SerdeAttrs::parse(&field.attrs)?))
+        }
+        Fields::Unnamed(_) => Err(Error::new(
+            span,
+            "NickelContract can't be derived for tuples with several fields",
+        )),
+        Fields::Unit => Ok(quote!(
+            <() as ::nickel_lang_core::contract::NickelContract>::contract()
+        )),
+    }
+}
+
+/// The contract of an externally tagged enum, as an expression evaluating to a `String`. Unit
+/// variants are enum tags, and the other variants are records with a single field.
+fn enum_contract(data: &DataEnum, attrs: &SerdeAttrs) -> syn::Result<TokenStream2> {
+    let mut tags = Vec::new();
+    let mut records = Vec::new();
+
+    for variant in data.variants.iter() {
+        let variant_attrs = SerdeAttrs::parse(&variant.attrs)?;
+
+        if variant_attrs.skip {
+            continue;
+        }
+
+        let ident = variant.ident.to_string();
+        let name = match (&variant_attrs.rename, attrs.rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rule.apply_to_variant(&ident),
+            (None, None) => ident,
+        };
+
+        if let Fields::Unit = variant.fields {
+            tags.push(nickel_tag(&name));
+        } else {
+            let prefix = nickel_string(&name);
+            let contract = struct_contract(&variant.fields, &variant_attrs, variant.ident.span())?;
+            records.push(quote!(
+                ::std::format!("{{ {} | ({}) }}", #prefix, { #contract })
+            ));
+        }
+    }
+
+    let tags =
+        (!tags.is_empty() || records.is_empty()).then(|| format!("[| {} |]", tags.join(", ")));
+    let tags = tags.iter();
+
+    Ok(quote! {
+        let alternatives: ::std::vec::Vec<::std::string::String> =
+            ::std::vec![#(::std::string::String::from(#tags),)* #(#records),*];
+        if alternatives.len() == 1 {
+            alternatives.into_iter().next().unwrap()
+        } else {
+            ::std::format!("std.contract.any_of [{}]", alternatives.join(", "))
+        }
+    })
+}
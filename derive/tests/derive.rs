@@ -0,0 +1,103 @@
+use std::{collections::HashMap, io::Cursor};
+
+use nickel_lang_core::{
+    contract::NickelContract,
+    error::{Error, EvalError},
+    eval::cache::CacheImpl,
+    program::Program,
+};
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize, NickelContract)]
+#[serde(rename_all = "kebab-case")]
+enum Protocol {
+    Http,
+    Https,
+    Unix(String),
+}
+
+#[derive(Debug, PartialEq, Deserialize, NickelContract)]
+#[serde(deny_unknown_fields)]
+struct Server {
+    /// The host to listen on.
+    host: String,
+    #[serde(rename = "listen-port")]
+    port: u16,
+    protocol: Protocol,
+    #[serde(default)]
+    tags: Vec<String>,
+    limits: Option<HashMap<String, f64>>,
+    #[serde(skip)]
+    connections: usize,
+}
+
+#[derive(Debug, PartialEq, Deserialize, NickelContract)]
+struct Wrapper<T>(T);
+
+fn program(src: &str) -> Program<CacheImpl> {
+    Program::new_from_source(Cursor::new(src.to_owned()), "<test>", std::io::sink()).unwrap()
+}
+
+#[test]
+fn derived_contracts() {
+    assert_eq!(
+        Protocol::contract(),
+        "std.contract.any_of [[| 'http, 'https |], { \"unix\" | (String) }]"
+    );
+    assert_eq!(
+        Server::contract(),
+        "{ \"host\" | doc \"The host to listen on.\" | (String), \
+        \"listen-port\" | (std.number.Nat), \
+        \"protocol\" | (std.contract.any_of [[| 'http, 'https |], { \"unix\" | (String) }]), \
+        \"tags\" | optional | (Array (String)), \
+        \"limits\" | optional | (std.contract.custom (fun label value => \
+        if value == null then 'Ok value else std.contract.check ({ _ | (Number) }) label value)) }"
+    );
+    assert_eq!(Wrapper::<bool>::contract(), "Bool");
+}
+
+#[test]
+fn checked_deserialization() {
+    let server: Server = program(
+        r#"{ host = "localhost", listen-port = 8080, protocol = 'https, limits = { cpu = 0.5 } }"#,
+    )
+    .eval_deserialize_checked()
+    .unwrap();
+
+    assert_eq!(
+        server,
+        Server {
+            host: "localhost".to_owned(),
+            port: 8080,
+            protocol: Protocol::Https,
+            tags: Vec::new(),
+            limits: Some(HashMap::from([("cpu".to_owned(), 0.5)])),
+            connections: 0,
+        }
+    );
+
+    let server: Server =
+        program(r#"{ host = "localhost", listen-port = 8080, protocol = 'http, limits = null }"#)
+            .eval_deserialize_checked()
+            .unwrap();
+    assert_eq!(server.limits, None);
+
+    let unix: Protocol = program(r#"{ unix = "/run/app.sock" }"#)
+        .eval_deserialize_checked()
+        .unwrap();
+    assert_eq!(unix, Protocol::Unix("/run/app.sock".to_owned()));
+
+    let result = program(r#"{ host = "localhost", listen-port = -1, protocol = 'http }"#)
+        .eval_deserialize_checked::<Server>();
+    assert!(
+        matches!(result, Err(Error::EvalError(EvalError::BlameError { .. }))),
+        "{result:?}"
+    );
+
+    let result = program(r#"{ host = "localhost", listen-port = 80, protocol = 'ftp }"#)
+        .eval_deserialize_checked::<Server>();
+    assert!(
+        matches!(result, Err(Error::EvalError(EvalError::BlameError { .. }))),
+        "{result:?}"
+    );
+}
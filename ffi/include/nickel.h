@@ -0,0 +1,82 @@
+/*
+ * C bindings for the Nickel programming language.
+ *
+ * See the README of the `nickel-lang-ffi` crate for the ownership rules.
+ */
+#ifndef NICKEL_H
+#define NICKEL_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An opaque handle to a Nickel program. */
+typedef struct NickelProgram nickel_program;
+
+/* The status returned by fallible functions. */
+typedef enum {
+    /* The operation succeeded. */
+    NICKEL_OK = 0,
+    /* The operation failed. The error report is written to the output parameter, if any. */
+    NICKEL_ERROR = 1,
+    /* An argument was NULL or not valid UTF-8. */
+    NICKEL_INVALID_ARGUMENT = 2,
+} nickel_status;
+
+/* The format of error reports and warnings. */
+typedef enum {
+    /* Human-readable text, as printed by the command-line tool. */
+    NICKEL_ERROR_FORMAT_TEXT = 0,
+    /* JSON diagnostics, with the message, the notes and the source locations of each diagnostic. */
+    NICKEL_ERROR_FORMAT_JSON = 1,
+} nickel_error_format;
+
+/*
+ * Create a new program from a Nickel source. `name` is the name of the source used in error
+ * reports, and may be NULL. Return NULL if `source` is NULL, or if `source` or `name` isn't valid
+ * UTF-8. The program must be freed with `nickel_program_free`.
+ */
+nickel_program *nickel_program_new(const char *source, const char *name);
+
+/* Free a program. Does nothing if `program` is NULL. */
+void nickel_program_free(nickel_program *program);
+
+/*
+ * Select the field to evaluate, given as a dot-separated path such as `server.port`. An empty
+ * path selects the whole program. On error, the error report is written to `error`.
+ */
+nickel_status nickel_program_set_field(nickel_program *program, const char *path, char **error);
+
+/*
+ * Evaluate the program, or the field selected by `nickel_program_set_field`, and serialize the
+ * result to JSON. The JSON on success, or the error report on error, is written to `out`.
+ */
+nickel_status nickel_program_eval_json(nickel_program *program, char **out);
+
+/*
+ * Evaluate the program, or the field selected by `nickel_program_set_field`, which must be a
+ * string. The string on success, or the error report on error, is written to `out`.
+ */
+nickel_status nickel_program_eval_string(nickel_program *program, char **out);
+
+/*
+ * Select the format of the error reports and of the warnings written by the other functions.
+ * Reports are written as text by default.
+ */
+nickel_status nickel_program_set_error_format(nickel_program *program, nickel_error_format format);
+
+/*
+ * Write a report of the warnings emitted since the last call, such as accesses to deprecated
+ * fields, to `out`. The report is empty if there isn't any warning. If building the report
+ * fails, an error message is written instead and NICKEL_ERROR is returned.
+ */
+nickel_status nickel_program_warnings(nickel_program *program, char **out);
+
+/* Free a string returned by this library. Does nothing if `s` is NULL. */
+void nickel_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* NICKEL_H */
@@ -0,0 +1,488 @@
+//! C bindings for Nickel.
+//!
+//! This crate exposes a minimal C interface to build a Nickel program from a string, select a
+//! field to evaluate, and get back either the resulting JSON or string, or an error report. Error
+//! reports and the warnings of the evaluation can be produced as text or as JSON diagnostics. The
+//! corresponding C header is `include/nickel.h`.
+//!
+//! # Ownership
+//!
+//! - A [NickelProgram] returned by [nickel_program_new] is owned by the caller, and must be freed
+//!   with [nickel_program_free].
+//! - Input strings are borrowed for the duration of the call.
+//! - Strings written to output parameters are owned by the caller, and must be freed with
+//!   [nickel_string_free].
+//!
+//! No panic ever crosses the FFI boundary: a panic is turned into an error report.
+use std::{
+    ffi::{c_char, CStr, CString},
+    io::Cursor,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use nickel_lang_core::{
+    error::{
+        report::{Aggregated, ColorOpt, ErrorFormat},
+        Error, IntoDiagnostics,
+    },
+    eval::cache::CacheImpl,
+    program::{FieldPath, Program},
+    serialize::{self, ExportFormat},
+};
+
+/// An opaque handle to a Nickel program.
+pub struct NickelProgram {
+    program: Program<CacheImpl>,
+    error_format: ErrorFormat,
+}
+
+impl NickelProgram {
+    /// Build a report in the format selected by [nickel_program_set_error_format].
+    fn report(&mut self, error: impl IntoDiagnostics) -> String {
+        self.program
+            .report_as_str_with_format(error, self.error_format)
+    }
+}
+
+/// The status returned by fallible functions.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NickelStatus {
+    /// The operation succeeded.
+    Ok = 0,
+    /// The operation failed. The error report is written to the output parameter, if any.
+    Error = 1,
+    /// An argument was `NULL` or not valid UTF-8.
+    InvalidArgument = 2,
+}
+
+/// The format of error reports and warnings.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NickelErrorFormat {
+    /// Human-readable text, as printed by the command-line tool.
+    Text = 0,
+    /// JSON diagnostics, with the message, the notes and the source locations of each diagnostic.
+    Json = 1,
+}
+
+/// Borrow a C string as a Rust string. Return `None` if the pointer is null or if the string isn't
+/// valid UTF-8.
+///
+/// # Safety
+///
+/// `s` must be null or point to a valid null-terminated string.
+unsafe fn borrow_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok()
+    }
+}
+
+/// Write an owned copy of `s` to the output parameter `out`, if it's not null.
+///
+/// # Safety
+///
+/// `out` must be null or point to a valid, writable `char *`.
+unsafe fn write_out(out: *mut *mut c_char, s: String) {
+    if out.is_null() {
+        return;
+    }
+
+    // C strings can't contain null bytes. JSON never does, but error reports quoting the source
+    // might.
+    let s = CString::new(s).unwrap_or_else(|err| {
+        let mut bytes = err.into_vec();
+        bytes.retain(|b| *b != 0);
+        // unwrap(): we've just removed all the null bytes
+        CString::new(bytes).unwrap()
+    });
+
+    *out = s.into_raw();
+}
+
+/// Run `f` on `program` and render its error report, if any, catching any panic.
+fn catch_panic<T>(
+    program: &mut NickelProgram,
+    f: impl FnOnce(&mut Program<CacheImpl>) -> Result<T, Error>,
+) -> Result<T, String> {
+    // Rendering the report can panic as well, so it must happen inside the guarded closure.
+    guard_panic(|| f(&mut program.program).map_err(|error| program.report(error)))
+}
+
+/// Run `f`, turning a panic into an error message.
+fn guard_panic<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let msg = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+
+        Err(format!("internal error: Nickel panicked: {msg}"))
+    })
+}
+
+/// Create a new program from a Nickel source. `name` is the name of the source used in error
+/// reports, and may be null.
+///
+/// Return null if `source` is null, or if `source` or `name` isn't valid UTF-8.
+///
+/// # Safety
+///
+/// `source` and `name` must be null or point to valid null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_new(
+    source: *const c_char,
+    name: *const c_char,
+) -> *mut NickelProgram {
+    let Some(source) = borrow_str(source) else {
+        return ptr::null_mut();
+    };
+
+    let name = if name.is_null() {
+        "<ffi>"
+    } else {
+        match borrow_str(name) {
+            Some(name) => name,
+            None => return ptr::null_mut(),
+        }
+    };
+
+    let result = panic::catch_unwind(|| {
+        Program::new_from_source(Cursor::new(source.to_owned()), name, std::io::sink())
+    });
+
+    match result {
+        Ok(Ok(mut program)) => {
+            program.color_opt = ColorOpt::NEVER;
+            Box::into_raw(Box::new(NickelProgram {
+                program,
+                error_format: ErrorFormat::Text,
+            }))
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Free a program. Does nothing if `program` is null.
+///
+/// # Safety
+///
+/// `program` must be null or a pointer returned by [nickel_program_new] that hasn't been freed
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_free(program: *mut NickelProgram) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+/// Select the field to evaluate, given as a dot-separated path such as `server.port`. An empty
+/// path selects the whole program. On error, the error report is written to `error`.
+///
+/// # Safety
+///
+/// `program` must be a valid program, `path` must point to a valid null-terminated string and
+/// `error` must be null or point to a valid, writable `char *`.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_set_field(
+    program: *mut NickelProgram,
+    path: *const c_char,
+    error: *mut *mut c_char,
+) -> NickelStatus {
+    let (Some(program), Some(path)) = (program.as_mut(), borrow_str(path)) else {
+        return NickelStatus::InvalidArgument;
+    };
+
+    let result = catch_panic(program, |program| {
+        if path.is_empty() {
+            Ok(FieldPath::new())
+        } else {
+            Ok(program.parse_field_path(path.to_owned())?)
+        }
+    });
+
+    match result {
+        Ok(field) => {
+            program.program.field = field;
+            NickelStatus::Ok
+        }
+        Err(report) => {
+            write_out(error, report);
+            NickelStatus::Error
+        }
+    }
+}
+
+/// Evaluate the program, or the field selected by [nickel_program_set_field], and serialize the
+/// result to JSON. The JSON on success, or the error report on error, is written to `out`.
+///
+/// # Safety
+///
+/// `program` must be a valid program, and `out` must be null or point to a valid, writable
+/// `char *`.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_eval_json(
+    program: *mut NickelProgram,
+    out: *mut *mut c_char,
+) -> NickelStatus {
+    let Some(program) = program.as_mut() else {
+        return NickelStatus::InvalidArgument;
+    };
+
+    let result = catch_panic(program, |program| {
+        let rt = program.eval_full_for_export()?;
+        serialize::validate(ExportFormat::Json, &rt)?;
+        Ok(serialize::to_string(ExportFormat::Json, &rt)?)
+    });
+
+    match result {
+        Ok(json) => {
+            write_out(out, json);
+            NickelStatus::Ok
+        }
+        Err(report) => {
+            write_out(out, report);
+            NickelStatus::Error
+        }
+    }
+}
+
+/// Evaluate the program, or the field selected by [nickel_program_set_field], which must be a
+/// string. The string on success, or the error report on error, is written to `out`.
+///
+/// # Safety
+///
+/// `program` must be a valid program, and `out` must be null or point to a valid, writable
+/// `char *`.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_eval_string(
+    program: *mut NickelProgram,
+    out: *mut *mut c_char,
+) -> NickelStatus {
+    let Some(program) = program.as_mut() else {
+        return NickelStatus::InvalidArgument;
+    };
+
+    let result = catch_panic(program, |program| {
+        let rt = program.eval_full_for_export()?;
+        serialize::validate(ExportFormat::Text, &rt)?;
+        Ok(serialize::to_string(ExportFormat::Text, &rt)?)
+    });
+
+    match result {
+        Ok(s) => {
+            write_out(out, s);
+            NickelStatus::Ok
+        }
+        Err(report) => {
+            write_out(out, report);
+            NickelStatus::Error
+        }
+    }
+}
+
+/// Select the format of the error reports and of the warnings written by the other functions.
+/// Reports are written as text by default.
+///
+/// # Safety
+///
+/// `program` must be a valid program.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_set_error_format(
+    program: *mut NickelProgram,
+    format: NickelErrorFormat,
+) -> NickelStatus {
+    let Some(program) = program.as_mut() else {
+        return NickelStatus::InvalidArgument;
+    };
+
+    program.error_format = match format {
+        NickelErrorFormat::Text => ErrorFormat::Text,
+        NickelErrorFormat::Json => ErrorFormat::Json,
+    };
+
+    NickelStatus::Ok
+}
+
+/// Write a report of the warnings emitted since the last call, such as accesses to deprecated
+/// fields, to `out`. The report is empty if there isn't any warning. If building the report
+/// fails, an error message is written instead and `NickelStatus::Error` is returned.
+///
+/// # Safety
+///
+/// `program` must be a valid program, and `out` must be null or point to a valid, writable
+/// `char *`.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_warnings(
+    program: *mut NickelProgram,
+    out: *mut *mut c_char,
+) -> NickelStatus {
+    let Some(program) = program.as_mut() else {
+        return NickelStatus::InvalidArgument;
+    };
+
+    let result = guard_panic(|| {
+        let warnings = program.program.take_warnings();
+
+        if warnings.is_empty() {
+            Ok(String::new())
+        } else {
+            Ok(program.report(Aggregated(warnings)))
+        }
+    });
+
+    match result {
+        Ok(report) => {
+            write_out(out, report);
+            NickelStatus::Ok
+        }
+        Err(report) => {
+            write_out(out, report);
+            NickelStatus::Error
+        }
+    }
+}
+
+/// Free a string returned by this library. Does nothing if `s` is null.
+///
+/// # Safety
+///
+/// `s` must be null or a string written by this library that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_json(source: &str, field: &str) -> (NickelStatus, String) {
+        let source = CString::new(source).unwrap();
+        let field = CString::new(field).unwrap();
+
+        unsafe {
+            let program = nickel_program_new(source.as_ptr(), ptr::null());
+            assert!(!program.is_null());
+
+            let mut out = ptr::null_mut();
+            let mut status = nickel_program_set_field(program, field.as_ptr(), &mut out);
+
+            if status == NickelStatus::Ok {
+                status = nickel_program_eval_json(program, &mut out);
+            }
+
+            let output = CStr::from_ptr(out).to_str().unwrap().to_owned();
+            nickel_string_free(out);
+            nickel_program_free(program);
+
+            (status, output)
+        }
+    }
+
+    #[test]
+    fn eval_field() {
+        let (status, output) = eval_json("{ foo = { bar = 1 + 1 } }", "foo");
+        assert_eq!(status, NickelStatus::Ok);
+        assert_eq!(output, "{\n  \"bar\": 2\n}");
+    }
+
+    #[test]
+    fn eval_error() {
+        let (status, output) = eval_json("{ foo = 1 + \"a\" }", "");
+        assert_eq!(status, NickelStatus::Error);
+        assert!(output.contains("error"));
+    }
+
+    #[test]
+    fn eval_string() {
+        let source = CString::new("{ greeting = \"hello %{\"world\"}\" }").unwrap();
+        let field = CString::new("greeting").unwrap();
+
+        unsafe {
+            let program = nickel_program_new(source.as_ptr(), ptr::null());
+            let mut out = ptr::null_mut();
+
+            assert_eq!(
+                nickel_program_set_field(program, field.as_ptr(), &mut out),
+                NickelStatus::Ok
+            );
+            assert_eq!(
+                nickel_program_eval_string(program, &mut out),
+                NickelStatus::Ok
+            );
+            assert_eq!(CStr::from_ptr(out).to_str().unwrap(), "hello world");
+
+            nickel_string_free(out);
+            nickel_program_free(program);
+        }
+    }
+
+    #[test]
+    fn diagnostics() {
+        let source = CString::new("{ old | deprecated \"use new\" = 1, new = 2 }").unwrap();
+        let mut out = ptr::null_mut();
+
+        unsafe {
+            let program = nickel_program_new(source.as_ptr(), ptr::null());
+            assert_eq!(
+                nickel_program_set_error_format(program, NickelErrorFormat::Json),
+                NickelStatus::Ok
+            );
+
+            assert_eq!(
+                nickel_program_eval_json(program, &mut out),
+                NickelStatus::Ok
+            );
+            nickel_string_free(out);
+
+            assert_eq!(nickel_program_warnings(program, &mut out), NickelStatus::Ok);
+            let warnings = CStr::from_ptr(out).to_str().unwrap().to_owned();
+            nickel_string_free(out);
+            assert!(warnings.starts_with('{'), "{warnings}");
+            assert!(warnings.contains("field `old` is deprecated"), "{warnings}");
+            assert!(warnings.contains("use new"), "{warnings}");
+
+            // Warnings are only reported once.
+            assert_eq!(nickel_program_warnings(program, &mut out), NickelStatus::Ok);
+            assert_eq!(CStr::from_ptr(out).to_str().unwrap(), "");
+            nickel_string_free(out);
+
+            nickel_program_free(program);
+        }
+
+        let source = CString::new("{ bad = 1 + \"a\" }").unwrap();
+
+        unsafe {
+            let program = nickel_program_new(source.as_ptr(), ptr::null());
+            nickel_program_set_error_format(program, NickelErrorFormat::Json);
+
+            assert_eq!(
+                nickel_program_eval_json(program, &mut out),
+                NickelStatus::Error
+            );
+            let report = CStr::from_ptr(out).to_str().unwrap().to_owned();
+            nickel_string_free(out);
+            assert!(report.starts_with('{'), "{report}");
+
+            nickel_program_free(program);
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        unsafe {
+            assert!(nickel_program_new(ptr::null(), ptr::null()).is_null());
+            assert_eq!(
+                nickel_program_eval_json(ptr::null_mut(), ptr::null_mut()),
+                NickelStatus::InvalidArgument
+            );
+        }
+    }
+}
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nickel_lang_core::term::RichTerm;
+
+/// The maximum number of steps of the virtual machine per input.
+const FUEL: usize = 100_000;
+
+fuzz_target!(|term: RichTerm| nickel_lang_core::fuzz::eval(&term, FUEL));
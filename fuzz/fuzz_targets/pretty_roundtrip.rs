@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nickel_lang_core::term::RichTerm;
+
+fuzz_target!(|term: RichTerm| nickel_lang_core::fuzz::pretty_print_roundtrip(&term));
@@ -3,7 +3,7 @@ use std::ops::Range;
 use codespan_reporting::diagnostic::{self, Diagnostic, LabelStyle};
 use lsp_types::{DiagnosticRelatedInformation, NumberOrString};
 use nickel_lang_core::files::{FileId, Files};
-use nickel_lang_core::{error::UNKNOWN_SOURCE_NAME, position::RawSpan};
+use nickel_lang_core::{error::DiagnosticFileId, position::RawSpan};
 use serde::{Deserialize, Serialize};
 
 use crate::codespan_lsp::byte_span_to_range;
@@ -93,7 +93,11 @@ pub trait DiagnosticCompat: Sized {
     //
     // We do use the `related_information` field for cross-file diagnostics, because the main
     // diagnostics notification assumes all the diagnostics are for the same file.
-    fn from_codespan(file_id: FileId, diagnostic: Diagnostic<FileId>, files: &Files) -> Vec<Self>;
+    fn from_codespan(
+        file_id: FileId,
+        diagnostic: Diagnostic<DiagnosticFileId>,
+        files: &Files,
+    ) -> Vec<Self>;
 }
 
 /// Determine the position of a [codespan_reporting::diagnostic::Label] by looking it up
@@ -129,7 +133,11 @@ impl LocationCompat for lsp_types::Location {
 }
 
 impl DiagnosticCompat for SerializableDiagnostic {
-    fn from_codespan(file_id: FileId, diagnostic: Diagnostic<FileId>, files: &Files) -> Vec<Self> {
+    fn from_codespan(
+        file_id: FileId,
+        diagnostic: Diagnostic<DiagnosticFileId>,
+        files: &Files,
+    ) -> Vec<Self> {
         let severity = Some(match diagnostic.severity {
             diagnostic::Severity::Bug => lsp_types::DiagnosticSeverity::WARNING,
             diagnostic::Severity::Error => lsp_types::DiagnosticSeverity::ERROR,
@@ -145,15 +153,20 @@ impl DiagnosticCompat for SerializableDiagnostic {
         let within_file_labels = diagnostic
             .labels
             .iter()
-            .filter(|label| label.file_id == file_id);
+            .filter(|label| label.file_id == DiagnosticFileId::File(file_id));
 
-        let cross_file_labels = diagnostic.labels.iter().filter(|label| {
-            label.file_id != file_id
+        let cross_file_labels = diagnostic
+            .labels
+            .iter()
+            .filter_map(|label| match label.file_id {
+                DiagnosticFileId::File(label_file_id) if label_file_id != file_id => {
+                    Some((label_file_id, label))
+                }
                 // When errors point to generated code, the diagnostic-formatting machinery
-                // replaces it with a generated file. This is appropriate for command line errors,
-                // but not for us. It would be nice if we could filter this out at an earlier stage.
-                && files.name(label.file_id) != UNKNOWN_SOURCE_NAME
-        });
+                // replaces it with a generated snippet. This is appropriate for command line
+                // errors, but not for us.
+                _ => None,
+            });
 
         if !diagnostic.message.is_empty() {
             // What location should we use for the "overall" diagnostic? `Diagnostic` doesn't
@@ -165,7 +178,7 @@ impl DiagnosticCompat for SerializableDiagnostic {
                 .or_else(|| within_file_labels.clone().next());
 
             if let Some(label) = maybe_label {
-                let range = lsp_types::Range::from_codespan(&label.file_id, &label.range, files);
+                let range = lsp_types::Range::from_codespan(&file_id, &label.range, files);
                 let message = if diagnostic.notes.is_empty() {
                     diagnostic.message.clone()
                 } else {
@@ -178,10 +191,10 @@ impl DiagnosticCompat for SerializableDiagnostic {
                     message,
                     related_information: Some(
                         cross_file_labels
-                            .map(|label| {
+                            .map(|(label_file_id, label)| {
                                 OrdDiagnosticRelatedInformation(DiagnosticRelatedInformation {
                                     location: lsp_types::Location::from_codespan(
-                                        &label.file_id,
+                                        &label_file_id,
                                         &label.range,
                                         files,
                                     ),
@@ -195,7 +208,7 @@ impl DiagnosticCompat for SerializableDiagnostic {
         }
 
         diagnostics.extend(within_file_labels.map(|label| {
-            let range = lsp_types::Range::from_codespan(&label.file_id, &label.range, files);
+            let range = lsp_types::Range::from_codespan(&file_id, &label.range, files);
 
             SerializableDiagnostic {
                 range: OrdRange(range),
@@ -210,7 +223,11 @@ impl DiagnosticCompat for SerializableDiagnostic {
 }
 
 impl DiagnosticCompat for lsp_types::Diagnostic {
-    fn from_codespan(file_id: FileId, diagnostic: Diagnostic<FileId>, files: &Files) -> Vec<Self> {
+    fn from_codespan(
+        file_id: FileId,
+        diagnostic: Diagnostic<DiagnosticFileId>,
+        files: &Files,
+    ) -> Vec<Self> {
         SerializableDiagnostic::from_codespan(file_id, diagnostic, files)
             .into_iter()
             .map(From::from)
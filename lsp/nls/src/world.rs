@@ -138,10 +138,12 @@ impl World {
         file_id: FileId,
         err: impl IntoDiagnostics,
     ) -> Vec<SerializableDiagnostic> {
-        let mut files = self.cache.files().clone();
-        err.into_diagnostics(&mut files)
+        // The snippets of the diagnostics are dropped: only the labels pointing to files are
+        // reported.
+        err.into_diagnostics(self.cache.files())
+            .diagnostics
             .into_iter()
-            .flat_map(|d| SerializableDiagnostic::from_codespan(file_id, d, &files))
+            .flat_map(|d| SerializableDiagnostic::from_codespan(file_id, d, self.cache.files()))
             .collect()
     }
 
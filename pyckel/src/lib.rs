@@ -1,42 +1,212 @@
-use std::io::Cursor;
+use std::{io::Cursor, path::PathBuf};
 
 use nickel_lang_core::{
-    error::Error,
+    error::{report::ErrorFormat, Error},
     eval::cache::{Cache, CacheImpl},
     program::Program,
-    serialize,
+    serialize::{self, ExportFormat},
+    typecheck::TypecheckMode,
 };
 
-use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use serde_json::Value;
 
 create_exception!(pyckel, NickelException, PyException);
+create_exception!(pyckel, ParseError, NickelException);
+create_exception!(pyckel, TypecheckError, NickelException);
+create_exception!(pyckel, EvalError, NickelException);
+create_exception!(pyckel, ImportError, NickelException);
+create_exception!(pyckel, ExportError, NickelException);
+
+/// Turn an internal Nickel error into a PyErr with a fancy diagnostic message. The exception has a
+/// `diagnostics` attribute holding the structured diagnostics, as a list of dicts in the format of
+/// `nickel --error-format json`.
+fn error_to_exception<E: Into<Error>, EC: Cache>(
+    py: Python<'_>,
+    error: E,
+    program: &mut Program<EC>,
+) -> PyErr {
+    let error = error.into();
+    let json = program.report_as_str_with_format(error.clone(), ErrorFormat::Json);
+    let message = program.report_as_str(error.clone());
+
+    let exception = match error {
+        Error::ParseErrors(_) => ParseError::new_err(message),
+        Error::TypecheckError(_) => TypecheckError::new_err(message),
+        Error::EvalError(_) => EvalError::new_err(message),
+        Error::ImportError(_) => ImportError::new_err(message),
+        Error::ExportError(_) => ExportError::new_err(message),
+        Error::IOError(_) | Error::ReplError(_) => NickelException::new_err(message),
+    };
+
+    let diagnostics = serde_json::from_str::<Value>(&json)
+        .ok()
+        .and_then(|mut report| report.get_mut("diagnostics").map(Value::take))
+        .unwrap_or_else(|| Value::Array(Vec::new()));
 
-/// Turn an internal Nickel error into a PyErr with a fancy diagnostic message
-fn error_to_exception<E: Into<Error>, EC: Cache>(error: E, program: &mut Program<EC>) -> PyErr {
-    NickelException::new_err(program.report_as_str(error.into()))
+    if let Err(err) = exception
+        .value(py)
+        .setattr("diagnostics", to_python(py, &diagnostics))
+    {
+        return err;
+    }
+
+    exception
 }
 
-/// Evaluate from a Python str of a Nickel expression to a Python str of the resulting JSON.
-#[pyfunction]
-pub fn run(s: String) -> PyResult<String> {
-    let mut program: Program<CacheImpl> =
-        Program::new_from_source(Cursor::new(s), "python", std::io::sink())?;
+/// Convert a JSON value to the corresponding native Python value. Integers are kept as Python
+/// integers, and records are converted to dicts which preserve the order of the fields.
+fn to_python(py: Python<'_>, value: &Value) -> PyObject {
+    match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => b.to_object(py),
+        Value::Number(n) => {
+            if let Some(n) = n.as_i64() {
+                n.to_object(py)
+            } else if let Some(n) = n.as_u64() {
+                n.to_object(py)
+            } else {
+                n.as_f64().to_object(py)
+            }
+        }
+        Value::String(s) => s.to_object(py),
+        Value::Array(elts) => PyList::new(py, elts.iter().map(|elt| to_python(py, elt))).into(),
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, value) in fields {
+                // unwrap(): setting a string key can't fail
+                dict.set_item(key, to_python(py, value)).unwrap();
+            }
+            dict.into()
+        }
+    }
+}
+
+/// Parse the name of a textual export format.
+fn export_format(name: &str) -> PyResult<ExportFormat> {
+    match name {
+        "text" | "raw" => Ok(ExportFormat::Text),
+        "json" => Ok(ExportFormat::Json),
+        "yaml" => Ok(ExportFormat::Yaml),
+        "toml" => Ok(ExportFormat::Toml),
+        "xml" => Ok(ExportFormat::Xml),
+        "dotenv" => Ok(ExportFormat::Dotenv),
+        "shell" => Ok(ExportFormat::Shell),
+        "ini" => Ok(ExportFormat::Ini),
+        "properties" => Ok(ExportFormat::Properties),
+        "hcl" => Ok(ExportFormat::Hcl),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown or binary export format `{name}`"
+        ))),
+    }
+}
+
+/// A Nickel program, built from a source string or a file.
+///
+/// ```python
+/// program = pyckel.Program.from_file("config.ncl")
+/// program.add_import_paths(["lib"])
+/// config = program.eval()
+/// ```
+#[pyclass(unsendable, name = "Program")]
+pub struct PyProgram {
+    program: Program<CacheImpl>,
+}
+
+impl PyProgram {
+    fn eval_value(&mut self, py: Python<'_>) -> PyResult<Value> {
+        let value = self
+            .program
+            .eval_exported_value()
+            .map_err(|error| error_to_exception(py, error, &mut self.program))?;
+
+        serde_json::to_value(&value).map_err(|err| ExportError::new_err(err.to_string()))
+    }
+}
 
-    let term = program
-        .eval_full()
-        .map_err(|error| error_to_exception(error, &mut program))?;
+#[pymethods]
+impl PyProgram {
+    /// Create a program from a source string. `name` is the name of the source shown in error
+    /// messages.
+    #[new]
+    fn new(source: String, name: Option<String>) -> PyResult<Self> {
+        let name = name.unwrap_or_else(|| String::from("python"));
+        let program = Program::new_from_source(Cursor::new(source), name, std::io::sink())?;
 
-    serialize::validate(serialize::ExportFormat::Json, &term)
-        .map_err(|error| error_to_exception(error, &mut program))?;
+        Ok(Self { program })
+    }
 
-    let json_string = serialize::to_string(serialize::ExportFormat::Json, &term)
-        .map_err(|error| error_to_exception(error, &mut program))?;
+    /// Create a program from a file.
+    #[staticmethod]
+    fn from_file(path: PathBuf) -> PyResult<Self> {
+        let program = Program::new_from_file(path, std::io::sink())?;
 
-    Ok(json_string)
+        Ok(Self { program })
+    }
+
+    /// Add paths to the list of paths where imports are looked for.
+    fn add_import_paths(&mut self, paths: Vec<PathBuf>) {
+        self.program.add_import_paths(paths.into_iter());
+    }
+
+    /// Typecheck the program, without evaluating it.
+    fn typecheck(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.program
+            .typecheck(TypecheckMode::Walk)
+            .map_err(|error| error_to_exception(py, error, &mut self.program))
+    }
+
+    /// Evaluate the program to native Python values: records become dicts, arrays become lists,
+    /// and enum tags become strings.
+    fn eval(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        let value = self.eval_value(py)?;
+        Ok(to_python(py, &value))
+    }
+
+    /// Evaluate the program and serialize the result to a textual format, `json` by default.
+    fn export(&mut self, py: Python<'_>, format: Option<&str>) -> PyResult<String> {
+        let format = export_format(format.unwrap_or("json"))?;
+
+        let term = self
+            .program
+            .eval_full_for_export()
+            .map_err(|error| error_to_exception(py, error, &mut self.program))?;
+
+        serialize::validate(format, &term)
+            .map_err(|error| error_to_exception(py, error, &mut self.program))?;
+
+        serialize::to_string(format, &term)
+            .map_err(|error| error_to_exception(py, error, &mut self.program))
+    }
+}
+
+/// Evaluate from a Python str of a Nickel expression to a Python str of the resulting JSON.
+#[pyfunction]
+pub fn run(py: Python<'_>, s: String) -> PyResult<String> {
+    PyProgram::new(s, None)?.export(py, Some("json"))
+}
+
+/// Evaluate from a Python str of a Nickel expression to native Python values.
+#[pyfunction]
+pub fn eval(py: Python<'_>, s: String) -> PyResult<PyObject> {
+    PyProgram::new(s, None)?.eval(py)
 }
 
 #[pymodule]
-pub fn pyckel(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+pub fn pyckel(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyProgram>()?;
     m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add_function(wrap_pyfunction!(eval, m)?)?;
+    m.add("NickelException", py.get_type::<NickelException>())?;
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("TypecheckError", py.get_type::<TypecheckError>())?;
+    m.add("EvalError", py.get_type::<EvalError>())?;
+    m.add("ImportError", py.get_type::<ImportError>())?;
+    m.add("ExportError", py.get_type::<ExportError>())?;
     Ok(())
 }
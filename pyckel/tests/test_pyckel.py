@@ -0,0 +1,104 @@
+"""Tests of the Python API of pyckel.
+
+Run with `python -m unittest discover pyckel/tests` once pyckel is installed,
+for example with `pip install ./pyckel`.
+"""
+
+import json
+import os
+import tempfile
+import unittest
+
+import pyckel
+
+
+class RunTest(unittest.TestCase):
+    def test_run_returns_json(self):
+        result = pyckel.run("let x = 1 in { y = x + 2 }")
+        self.assertEqual(json.loads(result), {"y": 3})
+
+    def test_eval_returns_native_values(self):
+        config = pyckel.eval("{ port = 80, ratio = 0.5, hosts = ['a, 'b], debug = false, key = null }")
+        self.assertEqual(
+            config,
+            {"port": 80, "ratio": 0.5, "hosts": ["a", "b"], "debug": False, "key": None},
+        )
+        self.assertIsInstance(config["port"], int)
+
+    def test_eval_sorts_fields(self):
+        config = pyckel.eval("{ b = 1, a = 2 }")
+        self.assertEqual(list(config), ["a", "b"])
+
+
+class ProgramTest(unittest.TestCase):
+    def test_export_formats(self):
+        program = pyckel.Program("{ name = \"nickel\", version = 1 }")
+        self.assertEqual(json.loads(program.export()), {"name": "nickel", "version": 1})
+        self.assertEqual(program.export("yaml"), "name: nickel\nversion: 1\n")
+        self.assertEqual(pyckel.Program("\"hello\"").export("text"), "hello")
+
+    def test_unknown_export_format(self):
+        with self.assertRaises(ValueError):
+            pyckel.Program("1").export("msgpack")
+
+    def test_from_file_with_import_paths(self):
+        with tempfile.TemporaryDirectory() as root:
+            lib = os.path.join(root, "lib")
+            os.mkdir(lib)
+            with open(os.path.join(lib, "port.ncl"), "w") as f:
+                f.write("8080")
+            main = os.path.join(root, "main.ncl")
+            with open(main, "w") as f:
+                f.write("{ port = import \"port.ncl\" }")
+
+            program = pyckel.Program.from_file(main)
+            program.add_import_paths([lib])
+            self.assertEqual(program.eval(), {"port": 8080})
+
+    def test_typecheck(self):
+        pyckel.Program("(1 + 1 : Number)").typecheck()
+
+        with self.assertRaises(pyckel.TypecheckError):
+            pyckel.Program("(1 : String)").typecheck()
+
+
+class ErrorTest(unittest.TestCase):
+    def test_exception_hierarchy(self):
+        for exception in [
+            pyckel.ParseError,
+            pyckel.TypecheckError,
+            pyckel.EvalError,
+            pyckel.ImportError,
+            pyckel.ExportError,
+        ]:
+            self.assertTrue(issubclass(exception, pyckel.NickelException))
+
+    def test_parse_error(self):
+        with self.assertRaises(pyckel.ParseError):
+            pyckel.eval("{ port = }")
+
+    def test_import_error(self):
+        with self.assertRaises(pyckel.ImportError):
+            pyckel.eval("import \"does_not_exist.ncl\"")
+
+    def test_export_error(self):
+        with self.assertRaises(pyckel.ExportError):
+            pyckel.run("{ f = fun x => x }")
+
+    def test_eval_error_diagnostics(self):
+        with self.assertRaises(pyckel.EvalError) as context:
+            pyckel.eval("{ port | Number = \"80\" }")
+
+        err = context.exception
+        self.assertIn("contract broken", str(err))
+        self.assertGreater(len(err.diagnostics), 0)
+        diagnostic = err.diagnostics[0]
+        self.assertEqual(diagnostic["severity"], "error")
+        self.assertIn("code", diagnostic)
+        label = diagnostic["labels"][0]
+        self.assertEqual(label["file"], "python")
+        self.assertEqual(label["start"]["line"], 1)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,78 @@
+#!/usr/bin/env bash
+
+# Nickel benchmark comparison script
+#
+# Run the benchmarks of nickel-lang-core on a base git revision and on the
+# current working tree, and let criterion report the difference.
+#
+# Usage: scripts/compare-benches.sh <base-revision> [<bench>...]
+#
+# Benches default to the representative `configs` suite. Any other bench target
+# of `core/Cargo.toml` (e.g. `arrays`, `mantis`) can be given instead. Each bench
+# must exist both at the base revision and in the working tree.
+#
+# The base revision is checked out in a temporary git worktree, and both runs
+# share the same cargo target directory, so that criterion stores the baseline
+# measurements where the second run will look for them.
+
+set -euo pipefail
+
+if [[ $# -lt 1 ]]; then
+  echo "Usage: $0 <base-revision> [<bench>...]" >&2
+  exit 1
+fi
+
+BASE_REV="$1"
+shift
+BENCHES=("$@")
+if [[ ${#BENCHES[@]} -eq 0 ]]; then
+  BENCHES=("configs")
+fi
+
+BASELINE_NAME="compare-base"
+REPO_ROOT="$(git rev-parse --show-toplevel)"
+
+# List the bench targets declared in the manifest of nickel-lang-core, read from stdin.
+bench_targets() {
+  awk '/^\[\[bench\]\]/ { in_bench = 1; next }
+       /^\[/ { in_bench = 0 }
+       in_bench && /^name *=/ { gsub(/[" ]/, "", $0); sub(/^name=/, "", $0); print }'
+}
+
+# The benches are compared by name, so they must exist on both sides. The `configs` suite, for
+# example, doesn't exist on revisions older than the one which introduced this script.
+BASE_BENCHES="$(git -C "$REPO_ROOT" show "$BASE_REV:core/Cargo.toml" | bench_targets)"
+CURRENT_BENCHES="$(bench_targets < "$REPO_ROOT/core/Cargo.toml")"
+for bench in "${BENCHES[@]}"; do
+  if ! grep -qxF "$bench" <<< "$BASE_BENCHES"; then
+    echo "error: the bench \`$bench\` doesn't exist at $BASE_REV." >&2
+    echo "Benches available on both sides: $(grep -xF -f <(echo "$CURRENT_BENCHES") <<< "$BASE_BENCHES" | tr '\n' ' ')" >&2
+    exit 1
+  fi
+  if ! grep -qxF "$bench" <<< "$CURRENT_BENCHES"; then
+    echo "error: the bench \`$bench\` doesn't exist in the working tree." >&2
+    exit 1
+  fi
+done
+WORKTREE="$(mktemp -d -t nickel-bench-base.XXXXXX)"
+export CARGO_TARGET_DIR="${CARGO_TARGET_DIR:-$REPO_ROOT/target}"
+
+cleanup() {
+  git -C "$REPO_ROOT" worktree remove --force "$WORKTREE" || true
+}
+trap cleanup EXIT
+
+echo "++ Checking out $BASE_REV in $WORKTREE"
+git -C "$REPO_ROOT" worktree add --detach "$WORKTREE" "$BASE_REV" > /dev/null
+
+for bench in "${BENCHES[@]}"; do
+  echo "++ [$bench] Measuring base revision $BASE_REV"
+  (cd "$WORKTREE" && cargo bench -p nickel-lang-core --bench "$bench" -- \
+    --save-baseline "$BASELINE_NAME")
+
+  echo "++ [$bench] Measuring working tree against $BASE_REV"
+  (cd "$REPO_ROOT" && cargo bench -p nickel-lang-core --bench "$bench" -- \
+    --baseline "$BASELINE_NAME")
+done
+
+echo "++ Done. HTML reports are available in $CARGO_TARGET_DIR/criterion/report/index.html"
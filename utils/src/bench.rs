@@ -191,7 +191,7 @@ macro_rules! ncl_bench_group {
                 eval::{VirtualMachine, cache::{CacheImpl, Cache as EvalCache}},
                 transform::import_resolution::strict::resolve_imports,
                 typecheck::TypecheckMode,
-                error::report::{report, ColorOpt, ErrorFormat},
+                error::report::{report, ColorOpt, ErrorFormat, ReportStyle},
             };
 
             let mut c: criterion::Criterion<_> = $config
@@ -235,6 +235,7 @@ macro_rules! ncl_bench_group {
                                         e,
                                         ErrorFormat::Text,
                                         ColorOpt::default(),
+                                        ReportStyle::default(),
                                     );
                                     panic!("Error during bench evaluation");
                                 }